@@ -0,0 +1,49 @@
+//! Measures how long a `Stream` takes to unpack a large archive.
+//!
+//! Compare `cargo bench --bench decompress` against
+//! `cargo bench --bench decompress --features parallel`.
+
+use futures::StreamExt;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+const MEMBERS: usize = 3000;
+const ROUNDS: u32 = 5;
+
+fn archive() -> bytes::Bytes {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for i in 0..MEMBERS {
+        zip.start_file(format!("PRODUCT{:05}.TXT", i), Default::default())
+            .unwrap();
+        for line in 0..200 {
+            writeln!(
+                zip,
+                "product {} line {}: .SYNOPSIS...QUIET WEATHER",
+                i, line
+            )
+            .unwrap();
+        }
+    }
+    bytes::Bytes::from(zip.finish().unwrap().into_inner())
+}
+
+#[tokio::main]
+async fn main() {
+    let archive = archive();
+
+    let mut total = Duration::ZERO;
+    for _ in 0..ROUNDS {
+        let source = futures::stream::iter(vec![Ok(archive.clone())]).boxed();
+        let start = Instant::now();
+        let count = emwin_tg::Stream::from_source(source).count().await;
+        total += start.elapsed();
+        assert_eq!(count, MEMBERS);
+    }
+
+    println!(
+        "unpacked {} products ({} bytes compressed) in {:?} on average",
+        MEMBERS,
+        archive.len(),
+        total / ROUNDS
+    );
+}
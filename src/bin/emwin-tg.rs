@@ -0,0 +1,193 @@
+//! A command-line client for the EMWIN telecommunications gateway.
+//!
+//! ```text
+//! emwin-tg tail --awips TOR --awips SVR
+//! emwin-tg tail --format json --state MN > products.jsonl
+//! emwin-tg mirror /var/lib/emwin --feed all
+//! ```
+//!
+//! Requires the `cli` feature.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use emwin_tg::sink::{DirSink, JsonlWriter};
+use emwin_tg::{
+    CombinedSource, Error, ImageSource, Mirrors, Product, Source, SourceConfig, Stream, TextSource,
+};
+use futures::StreamExt;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Stream products from the NWS Emergency Managers Weather Information Network.
+#[derive(Debug, Parser)]
+#[command(name = "emwin-tg", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    #[command(flatten)]
+    selection: Selection,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print products as they arrive
+    #[command(alias = "filter")]
+    Tail {
+        /// How to print each product
+        #[arg(long, value_enum, default_value = "summary")]
+        format: Format,
+    },
+    /// Write each product to a file under DIR, in dated subdirectories
+    Mirror {
+        /// The root directory
+        dir: PathBuf,
+        /// Write every product directly into DIR
+        #[arg(long)]
+        flat: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, ValueEnum)]
+enum Format {
+    /// The filename and the start of the contents
+    Summary,
+    /// The filename and the whole contents
+    Full,
+    /// One JSON object per line
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Feed {
+    /// Text products
+    Text,
+    /// Image products
+    Image,
+    /// Text and image products
+    All,
+}
+
+/// Which products to receive, and from where.
+#[derive(Debug, Args)]
+struct Selection {
+    /// The archives to poll
+    #[arg(long, value_enum, default_value = "text", global = true)]
+    feed: Feed,
+    /// A gateway URL to poll instead of the operational one; repeat to fall back to others
+    #[arg(long = "gateway", value_name = "URL", global = true)]
+    gateways: Vec<String>,
+    /// Only products whose AWIPS identifier starts with PREFIX, e.g. TOR; may be repeated
+    #[arg(long, value_name = "PREFIX", global = true)]
+    awips: Vec<String>,
+    /// Only products issued by a forecast office, e.g. MPX
+    #[arg(long, global = true)]
+    wfo: Option<String>,
+    /// Only products affecting STATE, e.g. MN; may be repeated
+    #[arg(long = "state", value_name = "STATE", global = true)]
+    states: Vec<String>,
+    /// Only products whose WMO heading starts with PREFIX, e.g. WU
+    #[arg(long, value_name = "PREFIX", global = true)]
+    heading: Option<String>,
+    /// Only products whose filename matches PATTERN, where * and ? are wildcards
+    #[arg(long, value_name = "PATTERN", global = true)]
+    filename: Option<String>,
+}
+
+impl Selection {
+    /// Start a stream of the selected products, which ends on Ctrl-C.
+    fn stream<S: Source + From<(reqwest::Client, SourceConfig)>>(&self) -> Stream<S> {
+        let mut config = SourceConfig::new();
+        if !self.gateways.is_empty() {
+            config = config.mirrors(Mirrors::new(self.gateways.iter().cloned()));
+        }
+
+        let mut builder = Stream::<S>::builder().source_config(config);
+        if !self.awips.is_empty() {
+            builder = builder.filter_awips(self.awips.iter().cloned());
+        }
+        if let Some(wfo) = &self.wfo {
+            builder = builder.filter_wfo(wfo.clone());
+        }
+        if !self.states.is_empty() {
+            builder = builder.filter_states(self.states.iter().cloned());
+        }
+        if let Some(prefix) = &self.heading {
+            builder = builder.filter_heading(prefix.clone());
+        }
+        if let Some(pattern) = &self.filename {
+            builder = builder.filter_filename(pattern.clone());
+        }
+        builder.build().shutdown_on(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Warn)
+        .parse_default_env()
+        .init();
+
+    let cli = Cli::parse();
+    let result = match cli.selection.feed {
+        Feed::Text => run(cli.selection.stream::<TextSource>(), &cli.command).await,
+        Feed::Image => run(cli.selection.stream::<ImageSource>(), &cli.command).await,
+        Feed::All => run(cli.selection.stream::<CombinedSource>(), &cli.command).await,
+    };
+    if let Err(e) = result {
+        eprintln!("emwin-tg: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run<S: Source>(stream: Stream<S>, command: &Command) -> Result<(), Error> {
+    let mut stream = Box::pin(stream);
+    match command {
+        Command::Tail {
+            format: Format::Json,
+        } => {
+            JsonlWriter::new(std::io::stdout())
+                .write_stream(stream)
+                .await
+        }
+        Command::Tail { format } => {
+            while let Some(result) = stream.next().await {
+                match result {
+                    Ok(product) => print(&product, *format == Format::Full)?,
+                    Err(e) => log::warn!("{}", e),
+                }
+            }
+            Ok(())
+        }
+        Command::Mirror { dir, flat } => {
+            let mut sink = DirSink::new(dir);
+            if *flat {
+                sink = sink.flat();
+            }
+            sink.write_stream(stream).await
+        }
+    }
+}
+
+/// Print a product's filename, followed by its contents if `full` or else a preview.
+fn print(product: &Product, full: bool) -> std::io::Result<()> {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    if product.is_binary() {
+        let mime_type = product.mime_type().unwrap_or("binary");
+        writeln!(
+            out,
+            "{}: ({}, {} bytes)",
+            product.filename,
+            mime_type,
+            product.contents.len()
+        )?;
+    } else if full {
+        writeln!(out, "{}:\n{}\n", product.filename, product.decode_text())?;
+    } else {
+        let preview = format!("{:?}", product.string_contents());
+        writeln!(out, "{}:\n    {:.100}", product.filename, preview)?;
+    }
+    out.flush()
+}
@@ -0,0 +1,306 @@
+//! A blocking client, for programs which don't use an async runtime.
+//!
+//! [`TextStream`] polls the same archives as [`crate::TextStream`], using
+//! [`reqwest::blocking`], and yields products from an [`Iterator`]. Each call to `next()` sleeps
+//! until a product is available.
+//!
+//! Requires the `blocking` feature.
+
+use crate::fetch::schedule::{FetchPhase, Outcome, Schedule};
+use crate::state::NewMembers;
+use crate::stream::{Text20Min, Text2Min, Text3Hour, Text6Min};
+use crate::{Error, Fetchable, Mirrors, Product, SourceConfig, StreamState};
+use bytes::Bytes;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use std::collections::VecDeque;
+use std::io::Read;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The feed of text products from EMWIN TG, as an [`Iterator`].
+///
+/// This is the blocking counterpart of [`crate::TextStream`]. Archives are fetched in turn as they
+/// come due, with conditional requests, and each product is yielded once. A fetch which fails is
+/// retried according to the configured [`RetryPolicy`](crate::RetryPolicy), and the error is
+/// yielded. Mirrors fail over and recover as [`Mirrors`] describes, and a
+/// [dry run](Mirrors::dry_run) logs each fetch instead of making it. Rate limiters are ignored,
+/// since fetches are never concurrent.
+///
+/// The iterator never ends.
+///
+/// # Example
+///
+/// ```no_run
+/// let stream = emwin_tg::blocking::TextStream::new();
+/// for result in stream {
+///     match result {
+///         Ok(product) => println!("{}", product.filename),
+///         Err(error) => eprintln!("uh oh: {}", error),
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct TextStream {
+    client: reqwest::blocking::Client,
+    mirrors: Arc<Mirrors>,
+    archives: Vec<Archive>,
+    state: StreamState,
+    pending: Option<NewMembers>,
+    buffer: VecDeque<Result<Product, Error>>,
+}
+
+/// One of the archives being polled, and when to fetch it next.
+#[derive(Debug)]
+struct Archive {
+    filename: &'static str,
+    interval: Duration,
+    span: Duration,
+    enabled: bool,
+    due_at: Instant,
+    schedule: Schedule,
+    /// Validators from the last response, which only the mirror that sent them can check
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Archive {
+    fn new<F: Fetchable>(config: &SourceConfig, mirrors: &Mirrors) -> Self {
+        Self {
+            filename: F::FILENAME,
+            interval: config.interval_for(F::FILENAME, F::REFETCH_INTERVAL),
+            span: F::SPAN,
+            enabled: true,
+            due_at: Instant::now(),
+            schedule: Schedule::new(mirrors, config.retry_policy().clone()),
+            etag: None,
+            last_modified: None,
+        }
+    }
+
+    /// Forget the validators, e.g. after switching to a mirror which didn't send them.
+    fn reset_validators(&mut self) {
+        self.etag = None;
+        self.last_modified = None;
+    }
+}
+
+impl TextStream {
+    /// Poll the operational gateway on the usual schedule.
+    pub fn new() -> Self {
+        let client = reqwest::blocking::Client::builder()
+            .user_agent("https://github.com/willglynn/emwin")
+            .build()
+            .expect("build client");
+        Self::with_config(client, SourceConfig::new())
+    }
+
+    /// Poll using `client` and custom settings, like mirrors and polling intervals.
+    pub fn with_config(client: reqwest::blocking::Client, config: SourceConfig) -> Self {
+        let mirrors = config.shared_mirrors();
+        let archives = vec![
+            Archive::new::<Text2Min>(&config, &mirrors),
+            Archive::new::<Text6Min>(&config, &mirrors),
+            Archive::new::<Text20Min>(&config, &mirrors),
+            Archive::new::<Text3Hour>(&config, &mirrors),
+        ];
+        let mut stream = Self {
+            client,
+            mirrors,
+            archives,
+            state: StreamState::new(),
+            pending: None,
+            buffer: VecDeque::new(),
+        };
+        stream.update_retention();
+        stream
+    }
+
+    /// Skip the 20-minute and 3-hour archives, polling only the 2- and 6-minute ones.
+    ///
+    /// See [`TextSource::realtime_only`](crate::TextSource::realtime_only).
+    pub fn realtime_only(mut self) -> Self {
+        for archive in &mut self.archives[2..] {
+            archive.enabled = false;
+        }
+        self.update_retention();
+        self
+    }
+
+    /// Remember filenames for twice the span of the longest archive being polled.
+    fn update_retention(&mut self) {
+        let span = self
+            .archives
+            .iter()
+            .filter(|archive| archive.enabled)
+            .map(|archive| archive.span)
+            .max();
+        if let Some(span) = span {
+            self.state.set_retention(span * 2);
+        }
+    }
+
+    /// Fetch the archive which is due soonest, sleeping until it's due.
+    ///
+    /// Returns Ok(None) if the archive is not modified, or wasn't fetched because of a dry run.
+    fn fetch_next(&mut self) -> Result<Option<Bytes>, Error> {
+        let archive = self
+            .archives
+            .iter_mut()
+            .filter(|archive| archive.enabled)
+            .min_by_key(|archive| archive.due_at)
+            .expect("at least one archive is enabled");
+        let now = Instant::now();
+        if archive.due_at > now {
+            std::thread::sleep(archive.due_at - now);
+        }
+        archive.due_at = Instant::now() + archive.interval;
+
+        let now = tokio::time::Instant::now();
+        let attempt = match archive.schedule.due(now, false) {
+            Some(attempt) => attempt,
+            None => return Ok(None),
+        };
+        if attempt.reset_state {
+            log::info!("returning to {}", self.mirrors.urls[attempt.mirror]);
+            archive.reset_validators();
+        }
+        let url = self.mirrors.url(attempt.mirror, archive.filename);
+        if self.mirrors.dry_run {
+            log::info!("dry run: would GET {}", url);
+            archive.schedule.abandon();
+            return Ok(None);
+        }
+
+        let result = fetch(&self.client, &url, archive, self.mirrors.max_archive_size);
+        let outcome = match &result {
+            Ok(Some(_)) => Outcome::Fetched,
+            Ok(None) => Outcome::NotModified,
+            Err(e) => Outcome::Failed {
+                retryable: archive.schedule.retry().should_retry(e),
+            },
+        };
+        let finished =
+            archive
+                .schedule
+                .finished(tokio::time::Instant::now(), attempt.mirror, outcome);
+        if finished.reset_state {
+            log::warn!(
+                "failing over to {}",
+                self.mirrors.urls[archive.schedule.mirror()]
+            );
+            archive.reset_validators();
+        }
+        match archive.schedule.phase() {
+            FetchPhase::Due => archive.due_at = Instant::now(),
+            FetchPhase::Backoff { retry_at } => {
+                let retry_at = retry_at.into_std();
+                log::debug!(
+                    "retrying {} in {:?}",
+                    archive.filename,
+                    retry_at.saturating_duration_since(Instant::now())
+                );
+                archive.due_at = archive.due_at.min(retry_at);
+            }
+            _ => (),
+        }
+
+        result.map_err(|e| Error::Fetch {
+            url,
+            archive: archive.filename.to_string(),
+            cycle: attempt.cycle,
+            attempt: attempt.attempt,
+            source: Box::new(e),
+        })
+    }
+}
+
+impl Default for TextStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Iterator for TextStream {
+    type Item = Result<Product, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.buffer.pop_front() {
+                return Some(value);
+            }
+
+            // Decompress more of the last archive before fetching another
+            if let Some(members) = &mut self.pending {
+                if !members.is_empty() {
+                    let state = &mut self.state;
+                    self.buffer.extend(members.next_batch().into_iter().filter(
+                        |result| match result {
+                            Ok(product) => state.accepts(product),
+                            Err(_) => true,
+                        },
+                    ));
+                    continue;
+                }
+                self.pending = None;
+            }
+
+            match self.fetch_next() {
+                Ok(Some(bytes)) => match self.state.new_members_in(bytes, true) {
+                    Ok(members) => self.pending = Some(members),
+                    Err(e) => return Some(Err(e)),
+                },
+                Ok(None) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Fetch an archive, returning Ok(None) if it is not modified.
+fn fetch(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    archive: &mut Archive,
+    limit: u64,
+) -> Result<Option<Bytes>, Error> {
+    let mut req = client.get(url);
+    if let Some(value) = &archive.etag {
+        req = req.header(IF_NONE_MATCH, value);
+    }
+    if let Some(value) = &archive.last_modified {
+        req = req.header(IF_MODIFIED_SINCE, value);
+    }
+
+    log::debug!("GET {}", url);
+    let resp = req.send()?.error_for_status()?;
+    if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+        log::debug!("304 Not Modified: {}", url);
+        return Ok(None);
+    }
+
+    let too_large = |size| Error::ArchiveTooLarge {
+        url: url.to_string(),
+        size,
+        limit,
+    };
+    if let Some(size) = resp.content_length().filter(|size| *size > limit) {
+        return Err(too_large(size));
+    }
+    let header = |name| {
+        resp.headers()
+            .get(name)
+            .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+            .map(String::from)
+    };
+    let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+    // Stop reading once the body exceeds the limit, in case the length wasn't sent or was wrong
+    let mut body = Vec::with_capacity(resp.content_length().unwrap_or(0).min(limit) as usize);
+    resp.take(limit + 1).read_to_end(&mut body)?;
+    if body.len() as u64 > limit {
+        return Err(too_large(body.len() as u64));
+    }
+    log::debug!("200 OK {}", url);
+    archive.etag = etag;
+    archive.last_modified = last_modified;
+    Ok(Some(Bytes::from(body)))
+}
@@ -1,13 +1,253 @@
 /// An error which occurred while retrieving from an EMWIN TG stream.
+///
+/// Some variants only exist when a feature enables them, so matches need a wildcard arm.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// A failure occurred during an HTTP exchange
     #[error("HTTP error: {0}")]
     Http(#[from] reqwest::Error),
+    /// The server responded with an error status
+    #[error("HTTP error: status {status} from {url}")]
+    Status {
+        /// The URL requested
+        url: String,
+        /// The status code
+        status: u16,
+    },
+    /// An [`HttpTransport`](crate::HttpTransport) failed
+    #[error("HTTP error: {0}")]
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+    /// A local file could not be read
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
     /// The retrieved archive could not be processed
     #[error("archive format error: {0}")]
     ArchiveFormat(#[from] zip::result::ZipError),
     /// An entry within the archive could not be processed
     #[error("inner archive format error in {0:?}")]
     ArchiveMember(String),
+    /// A member of the archive was corrupt, e.g. failing its checksum, and was skipped
+    ///
+    /// The rest of the archive is still read.
+    #[error("corrupt archive member {name:?}: {source}")]
+    CorruptMember {
+        /// The member's filename, normalized like [`Product::filename`](crate::Product::filename)
+        name: String,
+        /// The underlying error
+        source: zip::result::ZipError,
+    },
+    /// The archive was damaged, e.g. truncated in transit, and only some of its members could be
+    /// recovered
+    ///
+    /// The recovered members are read as usual, and this error follows them.
+    #[error("damaged archive, recovered {recovered} members: {source}")]
+    DamagedArchive {
+        /// How many members were recovered
+        recovered: usize,
+        /// Why the archive couldn't be read whole
+        source: zip::result::ZipError,
+    },
+    /// The server sent an archive larger than [`Mirrors::max_archive_size`](crate::Mirrors::max_archive_size)
+    #[error("archive at {url} is too large: {size} bytes exceeds the limit of {limit}")]
+    ArchiveTooLarge {
+        /// The URL being fetched
+        url: String,
+        /// The size of the archive, or as much of it as was received before giving up
+        size: u64,
+        /// The configured limit
+        limit: u64,
+    },
+    /// A member of the archive decompressed to more than
+    /// [`MAX_MEMBER_SIZE`](crate::product::MAX_MEMBER_SIZE) bytes, counting any archives nested
+    /// within it, and was skipped
+    #[error("archive member {name:?} decompresses to more than {limit} bytes")]
+    MemberTooLarge {
+        /// The member's filename, normalized like [`Product::filename`](crate::Product::filename)
+        name: String,
+        /// The limit
+        limit: u64,
+    },
+    /// No products arrived within the window set by
+    /// [`Stream::with_idle_timeout`](crate::Stream::with_idle_timeout)
+    #[error("no products received in {idle:?}")]
+    Stalled {
+        /// The configured window
+        idle: std::time::Duration,
+    },
+    /// A [`SqliteSink`](crate::sink::SqliteSink) could not store a product
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    /// An image product could not be decoded
+    #[cfg(feature = "image")]
+    #[error("image error: {0}")]
+    Image(#[from] image::ImageError),
+    /// A product could not be parsed as a CAP alert
+    #[cfg(feature = "cap")]
+    #[error("CAP error: {0}")]
+    Cap(#[from] crate::product::cap::ParseCapError),
+    /// A product could not be parsed as JSON
+    #[cfg(feature = "serde")]
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    /// Retrieving an archive failed
+    #[error("fetch #{cycle} of {url} failed (attempt {attempt}): {source}")]
+    Fetch {
+        /// The URL being fetched
+        url: String,
+        /// The name of the archive being fetched, e.g. `txtmin02.zip`
+        archive: String,
+        /// Which fetch of this archive failed, counting from 1 when the stream started
+        cycle: u64,
+        /// How many times in a row this archive has now failed from the current mirror, counting
+        /// this one; see [`RetryPolicy`](crate::RetryPolicy)
+        attempt: u32,
+        /// The underlying error
+        source: Box<Error>,
+    },
+}
+
+/// The general category of an [`Error`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// See [`Error::Http`], [`Error::Status`], and [`Error::Transport`]
+    Http,
+    /// See [`Error::Io`]
+    Io,
+    /// See [`Error::ArchiveFormat`], [`Error::CorruptMember`], and [`Error::DamagedArchive`]
+    ArchiveFormat,
+    /// See [`Error::ArchiveMember`]
+    ArchiveMember,
+    /// See [`Error::ArchiveTooLarge`] and [`Error::MemberTooLarge`]
+    ArchiveTooLarge,
+    /// See [`Error::Stalled`]
+    Stalled,
+    /// See `Error::Sqlite`
+    Sqlite,
+    /// See `Error::Image`
+    Image,
+    /// See `Error::Json`
+    Json,
+    /// See `Error::Cap`
+    Cap,
+}
+
+/// A serializable description of an [`Error`], for logging or forwarding errors as data.
+///
+/// Requires the `serde` feature.
+///
+/// # Example
+///
+/// ```
+/// use emwin_tg::{Error, ErrorKind, ErrorReport};
+///
+/// let report = ErrorReport::from(&Error::ArchiveMember("NESTED.ZIP".into()));
+/// assert_eq!(report.kind, ErrorKind::ArchiveMember);
+/// assert_eq!(report.message, "inner archive format error in \"NESTED.ZIP\"");
+/// assert_eq!(
+///     serde_json::to_string(&report).unwrap(),
+///     r#"{"kind":"ArchiveMember","message":"inner archive format error in \"NESTED.ZIP\""}"#
+/// );
+/// ```
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ErrorReport {
+    /// The category of the error
+    pub kind: ErrorKind,
+    /// The error's message, as displayed
+    pub message: String,
+}
+
+#[cfg(feature = "serde")]
+impl From<&Error> for ErrorReport {
+    fn from(error: &Error) -> Self {
+        Self {
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl Error {
+    /// Whether a fetch which failed with this error is worth retrying soon.
+    ///
+    /// Client errors, like `404 Not Found`, and archives over the size limit aren't expected to
+    /// clear up on their own, so they aren't, except for `408 Request Timeout` and
+    /// `429 Too Many Requests`. Everything else, like connection failures, server errors, and
+    /// damaged archives, is. [`Error::Fetch`] reports on the error it wraps.
+    ///
+    /// This is how a [`RetryPolicy`](crate::RetryPolicy) classifies errors by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Error;
+    ///
+    /// let status = |status| Error::Status { url: "https://example.com/".into(), status };
+    /// assert!(status(503).is_retryable());
+    /// assert!(status(429).is_retryable());
+    /// assert!(!status(404).is_retryable());
+    /// ```
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::ArchiveTooLarge { .. } => false,
+            Error::Fetch { source, .. } => source.is_retryable(),
+            _ => match self.status() {
+                Some(status) => !(400..500).contains(&status) || status == 408 || status == 429,
+                None => true,
+            },
+        }
+    }
+
+    /// The HTTP status the server responded with, if this error is due to one.
+    ///
+    /// Error statuses arrive as [`Error::Status`] or, from `reqwest`, as [`Error::Http`].
+    /// [`Error::Fetch`] reports on the error it wraps.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Status { status, .. } => Some(*status),
+            Error::Http(e) => e.status().map(|status| status.as_u16()),
+            Error::Fetch { source, .. } => source.status(),
+            _ => None,
+        }
+    }
+
+    /// The category of this error, for matching without regard to the data it carries.
+    ///
+    /// [`Error::Fetch`] only adds context, so it reports the kind of the error it wraps.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::{Error, ErrorKind};
+    ///
+    /// let error = Error::ArchiveMember("NESTED.ZIP".into());
+    /// assert_eq!(error.kind(), ErrorKind::ArchiveMember);
+    /// ```
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Http(_) | Error::Status { .. } | Error::Transport(_) => ErrorKind::Http,
+            Error::Io(_) => ErrorKind::Io,
+            Error::ArchiveFormat(_)
+            | Error::CorruptMember { .. }
+            | Error::DamagedArchive { .. } => ErrorKind::ArchiveFormat,
+            Error::ArchiveMember(_) => ErrorKind::ArchiveMember,
+            Error::ArchiveTooLarge { .. } | Error::MemberTooLarge { .. } => {
+                ErrorKind::ArchiveTooLarge
+            }
+            Error::Stalled { .. } => ErrorKind::Stalled,
+            #[cfg(feature = "sqlite")]
+            Error::Sqlite(_) => ErrorKind::Sqlite,
+            #[cfg(feature = "image")]
+            Error::Image(_) => ErrorKind::Image,
+            #[cfg(feature = "serde")]
+            Error::Json(_) => ErrorKind::Json,
+            #[cfg(feature = "cap")]
+            Error::Cap(_) => ErrorKind::Cap,
+            Error::Fetch { source, .. } => source.kind(),
+        }
+    }
 }
@@ -2,149 +2,875 @@ use crate::time::Ticker;
 use crate::Error;
 use bytes::Bytes;
 use futures::future::BoxFuture;
+use futures::Stream;
 use pin_project_lite::pin_project;
-use std::marker::PhantomData;
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+/// The gateway directory which holds the operational EMWIN archives.
+/// The default for [`Mirrors::max_archive_size`].
+pub(crate) const DEFAULT_MAX_ARCHIVE_SIZE: u64 = 256 << 20;
+
+pub(crate) const OPS_URL: &str =
+    "https://tgftp.nws.noaa.gov/SL.us008001/CU.EMWIN/DF.xt/DC.gsatR/OPS/";
 
 pub trait Fetchable {
-    const URL: &'static str;
+    const FILENAME: &'static str;
     const REFETCH_INTERVAL: std::time::Duration;
+    /// How long a product stays in the archive
+    const SPAN: std::time::Duration;
+}
+
+/// A prioritized list of gateway directories serving the EMWIN archives.
+///
+/// Archives are fetched from the first directory. If fetches from the current directory fail
+/// repeatedly, the feed fails over to the next one, and returns to the first directory after a
+/// while.
+///
+/// # Example
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use std::time::Duration;
+///
+/// let mirrors = emwin_tg::Mirrors::new([
+///     "https://tgftp.nws.noaa.gov/SL.us008001/CU.EMWIN/DF.xt/DC.gsatR/OPS/",
+///     "https://mirror.example.com/emwin/",
+/// ])
+/// .failover_after(2)
+/// .recover_after(Duration::from_secs(30 * 60));
+///
+/// let client = reqwest::Client::new();
+/// let source = emwin_tg::TextSource::with_mirrors(client, mirrors);
+/// let stream = emwin_tg::Stream::from_source(source);
+/// # std::mem::drop(stream);
+/// # })
+/// ```
+#[derive(Debug, Clone)]
+pub struct Mirrors {
+    pub(crate) urls: Vec<String>,
+    failover_after: u32,
+    recover_after: Duration,
+    pub(crate) dry_run: bool,
+    pub(crate) max_archive_size: u64,
+    range_requests: bool,
+}
+
+impl Mirrors {
+    /// Fetch from these gateway directories, in order of preference.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `urls` is empty.
+    pub fn new<I: IntoIterator<Item = U>, U: Into<String>>(urls: I) -> Self {
+        let urls: Vec<String> = urls
+            .into_iter()
+            .map(|url| {
+                let mut url = url.into();
+                if !url.ends_with('/') {
+                    url.push('/');
+                }
+                url
+            })
+            .collect();
+        assert!(!urls.is_empty(), "at least one mirror is required");
+        Self::with_urls(urls)
+    }
+
+    /// A single URL prefix, used as is.
+    pub(crate) fn with_prefix(prefix: &str) -> Self {
+        Self::with_urls(vec![prefix.to_string()])
+    }
+
+    fn with_urls(urls: Vec<String>) -> Self {
+        Self {
+            urls,
+            failover_after: 3,
+            recover_after: Duration::from_secs(60 * 60),
+            dry_run: false,
+            max_archive_size: DEFAULT_MAX_ARCHIVE_SIZE,
+            range_requests: false,
+        }
+    }
+
+    /// Fail over to the next directory after this many consecutive failures.
+    ///
+    /// The default is 3.
+    pub fn failover_after(mut self, failures: u32) -> Self {
+        self.failover_after = failures.max(1);
+        self
+    }
+
+    /// Return to the first directory this long after failing over.
+    ///
+    /// The default is one hour.
+    pub fn recover_after(mut self, duration: Duration) -> Self {
+        self.recover_after = duration;
+        self
+    }
+
+    /// Log each request instead of making it.
+    ///
+    /// Fetches are scheduled as usual, but each one is logged at `info` level and skipped, so no
+    /// archives are retrieved. This is useful for checking a configuration without touching the
+    /// gateway.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Reject archives larger than this many bytes with [`Error::ArchiveTooLarge`].
+    ///
+    /// This guards against a misbehaving server sending an enormous body. The default is 256 MiB,
+    /// well above the size of any real archive.
+    pub fn max_archive_size(mut self, bytes: u64) -> Self {
+        self.max_archive_size = bytes;
+        self
+    }
+
+    /// Download only the members of each archive which weren't in it last time, using HTTP range
+    /// requests.
+    ///
+    /// The archives overlap from one fetch to the next, so most of each download repeats the
+    /// last one. With range requests, the first fetch of an archive downloads it whole, and later
+    /// fetches read its central directory from the end of the file, then download just the
+    /// members which are new. Those are passed on as a smaller archive. Servers which don't
+    /// support range requests get ordinary full downloads, as do archives which changed too much
+    /// to be worth fetching piecemeal.
+    ///
+    /// Products which were dropped from a stream's dedup memory, e.g. by
+    /// [`Stream::dedup_capacity`](crate::Stream::dedup_capacity), won't be fetched again while
+    /// they remain in the archive. This is off by default.
+    pub fn range_requests(mut self, enabled: bool) -> Self {
+        self.range_requests = enabled;
+        self
+    }
+
+    pub(crate) fn url(&self, mirror: usize, filename: &str) -> String {
+        format!("{}{}", self.urls[mirror], filename)
+    }
+}
+
+impl Default for Mirrors {
+    fn default() -> Self {
+        Self::new([OPS_URL])
+    }
+}
+
+/// Settings for a [`TextSource`](crate::TextSource) or [`ImageSource`](crate::ImageSource).
+///
+/// Each source polls several archives covering different spans of time, each on its own
+/// schedule. Polling the short archives more often lowers latency; polling everything less often
+/// saves bandwidth on a metered connection.
+///
+/// # Example
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use std::time::Duration;
+///
+/// let config = emwin_tg::SourceConfig::new()
+///     .refetch_interval("txtmin02.zip", Duration::from_secs(30))
+///     .min_refetch_interval(Duration::from_secs(20));
+///
+/// let source = emwin_tg::TextSource::with_config(reqwest::Client::new(), config);
+/// let stream = emwin_tg::Stream::from_source(source);
+/// # std::mem::drop(stream);
+/// # })
+/// ```
+#[derive(Debug, Clone)]
+pub struct SourceConfig {
+    mirrors: Mirrors,
+    intervals: HashMap<String, Duration>,
+    min_interval: Duration,
+    retry: RetryPolicy,
+    rate_limiter: Option<RateLimiter>,
+    max_buffered_archives: usize,
+    transport: Option<SharedTransport>,
+}
+
+impl Default for SourceConfig {
+    fn default() -> Self {
+        Self {
+            mirrors: Mirrors::default(),
+            intervals: HashMap::new(),
+            min_interval: Duration::ZERO,
+            retry: RetryPolicy::default(),
+            rate_limiter: None,
+            max_buffered_archives: 2,
+            transport: None,
+        }
+    }
+}
+
+impl SourceConfig {
+    /// The default settings: the operational gateway, polled on the usual schedule.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fetch from these gateway directories.
+    pub fn mirrors(mut self, mirrors: Mirrors) -> Self {
+        self.mirrors = mirrors;
+        self
+    }
+
+    /// Poll the archive named `archive`, e.g. `txtmin02.zip`, every `interval`.
+    ///
+    /// Names of archives the source doesn't poll are ignored. By default, each archive is polled
+    /// at a rate suited to how often it's regenerated, from every 47 seconds for `txtmin02.zip` to
+    /// hourly for `txthrs03.zip`.
+    pub fn refetch_interval<N: Into<String>>(mut self, archive: N, interval: Duration) -> Self {
+        self.intervals.insert(archive.into(), interval);
+        self
+    }
+
+    /// Poll no archive more often than every `interval`.
+    pub fn min_refetch_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    /// Retry failed fetches according to `policy`.
+    ///
+    /// See [`RetryPolicy`] for the default.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Wait for a token from `limiter` before each fetch.
+    ///
+    /// Give the same [`RateLimiter`] to every source in a process to coordinate their requests.
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Stop fetching an archive while this many of its downloads are waiting to be consumed.
+    ///
+    /// A consumer which stops polling the stream leaves downloads buffered in the source. Rather
+    /// than adding to them, each archive's scheduled fetches are put off until the consumer catches
+    /// up, and then made once, however many were missed. The default is 2.
+    pub fn max_buffered_archives(mut self, archives: usize) -> Self {
+        self.max_buffered_archives = archives.max(1);
+        self
+    }
+
+    /// Send requests through `transport`, instead of the source's `reqwest::Client`.
+    ///
+    /// See [`HttpTransport`].
+    pub fn transport<T: HttpTransport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(SharedTransport(Arc::new(transport)));
+        self
+    }
+
+    pub(crate) fn shared_mirrors(&self) -> Arc<Mirrors> {
+        Arc::new(self.mirrors.clone())
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    pub(crate) fn interval_for(&self, filename: &str, default: Duration) -> Duration {
+        let interval = self.intervals.get(filename).copied().unwrap_or(default);
+        interval.max(self.min_interval)
+    }
+}
+
+/// How soon to retry an archive after a failed fetch.
+///
+/// Without retries, a failed fetch waits for the archive's next scheduled fetch, which for the
+/// 3-hour archive is an hour away. Instead, failures are retried after a delay which starts at
+/// `initial` and doubles with each consecutive failure, up to `max`. Each delay is shortened by a
+/// random fraction of up to [`jitter`](Self::jitter), so that many clients which failed together
+/// don't retry together. A successful fetch resets the delay.
+///
+/// The default starts at 5 seconds, caps at 5 minutes, and has 25% jitter. Only errors which
+/// [`Error::is_retryable`] accepts are retried, unless a
+/// [`retry_classifier`](Self::retry_classifier) says otherwise.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let retry = emwin_tg::RetryPolicy::new(Duration::from_secs(1), Duration::from_secs(60))
+///     .jitter(0.5)
+///     .max_retries(10);
+/// let config = emwin_tg::SourceConfig::new().retry(retry);
+/// # std::mem::drop(config);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    initial: Duration,
+    max: Duration,
+    jitter: f64,
+    max_retries: u32,
+    classifier: Option<Classifier>,
+}
+
+/// A function deciding which errors to retry.
+#[derive(Clone)]
+struct Classifier(Arc<dyn Fn(&Error) -> bool + Send + Sync>);
+
+impl std::fmt::Debug for Classifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Classifier")
+    }
+}
+
+impl PartialEq for Classifier {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl RetryPolicy {
+    /// Retry after `initial`, doubling the delay after each further failure up to `max`.
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max: max.max(initial),
+            jitter: 0.25,
+            max_retries: u32::MAX,
+            classifier: None,
+        }
+    }
+
+    /// Never retry; wait for the next scheduled fetch instead.
+    pub fn none() -> Self {
+        Self::default().max_retries(0)
+    }
+
+    /// Shorten each delay by a random fraction of up to `fraction`, from 0 to 1.
+    pub fn jitter(mut self, fraction: f64) -> Self {
+        self.jitter = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Give up after this many consecutive retries, until the next scheduled fetch.
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    /// Decide which errors to retry with `classifier`, instead of [`Error::is_retryable`].
+    ///
+    /// The classifier is given the error which caused the fetch to fail, not the
+    /// [`Error::Fetch`] wrapping it. Errors it rejects wait for the archive's next scheduled fetch,
+    /// though they still count towards [`Mirrors::failover_after`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::{Error, RetryPolicy};
+    ///
+    /// // This mirror answers 403 while it's being updated
+    /// let retry = RetryPolicy::default().retry_classifier(|error: &Error| {
+    ///     error.status() == Some(403) || error.is_retryable()
+    /// });
+    /// # std::mem::drop(retry);
+    /// ```
+    pub fn retry_classifier<F>(mut self, classifier: F) -> Self
+    where
+        F: Fn(&Error) -> bool + Send + Sync + 'static,
+    {
+        self.classifier = Some(Classifier(Arc::new(classifier)));
+        self
+    }
+
+    /// Whether to retry after `error`, unwrapping [`Error::Fetch`].
+    pub(crate) fn should_retry(&self, error: &Error) -> bool {
+        let error = match error {
+            Error::Fetch { source, .. } => source,
+            other => other,
+        };
+        match &self.classifier {
+            Some(classifier) => (classifier.0)(error),
+            None => error.is_retryable(),
+        }
+    }
+
+    /// The delay before retrying after `failures` consecutive failures, if any.
+    pub(crate) fn delay(&self, failures: u32) -> Option<Duration> {
+        if failures == 0 || failures > self.max_retries {
+            return None;
+        }
+        let exponent = (failures - 1).min(31);
+        let delay = self
+            .initial
+            .checked_mul(1 << exponent)
+            .map_or(self.max, |delay| delay.min(self.max));
+        Some(delay.mul_f64(1.0 - self.jitter * random_fraction()))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5), Duration::from_secs(5 * 60))
+    }
+}
+
+/// A number in `0..1` which differs from call to call.
+fn random_fraction() -> f64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    // Each `RandomState` is seeded differently, which is plenty for jitter
+    let bits = RandomState::new().build_hasher().finish() >> 11;
+    bits as f64 / (1u64 << 53) as f64
+}
+
+/// An archive, exactly as it was retrieved from the gateway.
+///
+/// Yielded by an [`ArchiveStream`](crate::ArchiveStream).
+#[derive(Debug, Clone)]
+pub struct FetchedArchive {
+    /// The archive's filename, e.g. `txtmin02.zip`
+    pub name: String,
+    /// The URL it was retrieved from
+    pub url: String,
+    /// The archive itself
+    pub contents: Bytes,
+    /// When the download finished
+    pub fetched_at: SystemTime,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Default)]
 struct FetchState {
     etag: Option<String>,
     last_modified: Option<String>,
+    /// The names of the members in the archive, when using range requests
+    members: Arc<BTreeSet<String>>,
 }
 
-type FetchResult = Result<Option<(Bytes, FetchState)>, Error>;
+impl FetchState {
+    fn from_headers(resp: &HttpResponse) -> Self {
+        Self {
+            etag: resp.header("etag").map(String::from),
+            last_modified: resp.header("last-modified").map(String::from),
+            members: Arc::default(),
+        }
+    }
+}
+
+type FetchResult = Result<Option<(FetchedArchive, FetchState)>, Error>;
 
 pin_project! {
-pub struct FetchStream<F: Fetchable> {
-    client: reqwest::Client,
-    url: PhantomData<F>,
+pub struct FetchStream {
+    transport: Arc<dyn HttpTransport>,
+    filename: String,
+    span: Option<Duration>,
+    interval: Duration,
+    mirrors: Arc<Mirrors>,
+    schedule: Schedule,
+    enabled: bool,
     fetch_state: FetchState,
     #[pin]
     ticker: Ticker,
-    fetches: Vec<BoxFuture<'static, FetchResult>>,
-    fetch_results: Vec<Result<Bytes, Error>>,
+    retry_at: Option<Pin<Box<tokio::time::Sleep>>>,
+    rate_limiter: Option<RateLimiter>,
+    max_buffered: usize,
+    fetches: Vec<BoxFuture<'static, (usize, FetchResult)>>,
+    fetch_results: Vec<Result<FetchedArchive, Error>>,
 }
 }
 
-impl<F: Fetchable> From<reqwest::Client> for FetchStream<F> {
-    fn from(client: reqwest::Client) -> Self {
+impl FetchStream {
+    pub fn new<F: Fetchable>(
+        client: reqwest::Client,
+        mirrors: Arc<Mirrors>,
+        config: &SourceConfig,
+    ) -> Self {
+        let mut stream =
+            Self::with_filename(F::FILENAME, F::REFETCH_INTERVAL, client, mirrors, config);
+        stream.span = Some(F::SPAN);
+        stream
+    }
+
+    /// Fetch `filename` from the mirrors, every `interval` unless `config` says otherwise.
+    pub fn with_filename(
+        filename: &str,
+        interval: Duration,
+        client: reqwest::Client,
+        mirrors: Arc<Mirrors>,
+        config: &SourceConfig,
+    ) -> Self {
+        let interval = config.interval_for(filename, interval);
+        let transport = match &config.transport {
+            Some(transport) => transport.0.clone(),
+            None => Arc::new(client),
+        };
         Self {
-            client,
+            transport,
+            filename: filename.to_string(),
+            span: None,
+            interval,
+            schedule: Schedule::new(&mirrors, config.retry.clone()),
+            mirrors,
+            enabled: true,
             fetch_state: FetchState::default(),
-            url: PhantomData::default(),
-            ticker: Ticker::new(F::REFETCH_INTERVAL),
+            ticker: Ticker::new(interval.max(Duration::from_millis(1))),
+            retry_at: None,
+            rate_limiter: config.rate_limiter.clone(),
+            max_buffered: config.max_buffered_archives,
             fetches: Vec::with_capacity(2),
             fetch_results: Vec::with_capacity(2),
         }
     }
 }
 
-impl<F: Fetchable> futures::Stream for FetchStream<F> {
-    type Item = Result<Bytes, Error>;
+impl FetchStream {
+    /// Never fetch this archive.
+    pub fn disable(&mut self) {
+        self.enabled = false;
+    }
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    /// Stop starting fetches, including retries, while still advancing those in flight.
+    pub fn shut_down(self: Pin<&mut Self>) {
         let this = self.project();
+        this.schedule.shut_down();
+        *this.retry_at = None;
+    }
 
-        match this.ticker.poll_next(cx) {
-            Poll::Ready(_) => {
-                this.fetches.push(Box::pin(fetch(
-                    F::URL,
-                    this.client.clone(),
-                    this.fetch_state.clone(),
-                )));
-            }
-            Poll::Pending => (),
+    /// How long a product stays in this feed's archive, if known and the feed is enabled.
+    pub fn span(&self) -> Option<Duration> {
+        self.span.filter(|_| self.enabled)
+    }
+
+    /// How often this feed is fetched, if it's enabled.
+    pub fn refetch_interval(&self) -> Option<Duration> {
+        Some(self.interval).filter(|_| self.enabled)
+    }
+
+    /// Returns true if this feed is shut down or disabled, and has nothing in flight or buffered.
+    pub fn is_drained(&self) -> bool {
+        (self.schedule.is_shut_down() || !self.enabled)
+            && self.fetches.is_empty()
+            && self.fetch_results.is_empty()
+    }
+
+    /// Start any fetches which are due and advance those in flight, buffering their results.
+    pub fn poll_fetches(self: Pin<&mut Self>, cx: &mut Context<'_>) {
+        let mut this = self.project();
+        if !*this.enabled {
+            return;
         }
 
-        let mut to_remove = Vec::new();
-        for (i, fetch) in this.fetches.iter_mut().enumerate() {
-            match fetch.as_mut().poll(cx) {
-                Poll::Ready(result) => {
+        loop {
+            if let Some(retry_at) = this.retry_at {
+                // Only wakes us; the schedule decides whether the retry is still wanted
+                let _ = retry_at.as_mut().poll(cx);
+            }
+
+            let now = tokio::time::Instant::now();
+            let ticked = this.ticker.as_mut().poll_next(cx).is_ready();
+            let paused = this.fetch_results.len() >= *this.max_buffered;
+            let attempt = if ticked || this.schedule.wants_fetch(now) {
+                let fetching = this.schedule.phase() == FetchPhase::Fetching;
+                let attempt = this.schedule.due(now, paused);
+                if fetching {
+                    log::debug!("{} is still being fetched; skipping", this.filename);
+                } else if paused && !this.schedule.is_shut_down() {
+                    log::debug!("{} results are waiting; deferring fetch", this.filename);
+                }
+                attempt
+            } else {
+                None
+            };
+
+            if let Some(attempt) = attempt {
+                if attempt.reset_state {
+                    log::info!("returning to {}", this.mirrors.urls[attempt.mirror]);
+                    *this.fetch_state = FetchState::default();
+                }
+
+                let Attempt {
+                    mirror,
+                    cycle,
+                    attempt,
+                    ..
+                } = attempt;
+                let url = this.mirrors.url(mirror, this.filename);
+                let name = this.filename.clone();
+                if this.mirrors.dry_run {
+                    log::info!("dry run: would GET {}", url);
+                    this.schedule.abandon();
+                } else {
+                    let rate_limiter = this.rate_limiter.clone();
+                    let fetch = fetch(
+                        url.clone(),
+                        this.transport.clone(),
+                        this.fetch_state.clone(),
+                        this.mirrors.max_archive_size,
+                        this.mirrors.range_requests,
+                    );
+                    #[cfg(feature = "tracing")]
+                    let fetch = instrument(fetch, &url, &name, cycle, attempt);
+                    this.fetches.push(Box::pin(async move {
+                        if let Some(rate_limiter) = rate_limiter {
+                            rate_limiter.acquire().await;
+                        }
+                        #[cfg(feature = "metrics")]
+                        let started = std::time::Instant::now();
+                        let fetched = fetch.await;
+                        #[cfg(feature = "metrics")]
+                        record_metrics(&name, started.elapsed(), &fetched);
+                        let result = match fetched {
+                            Ok(fetched) => Ok(fetched.map(|(contents, fetch_state)| {
+                                let archive = FetchedArchive {
+                                    name,
+                                    url,
+                                    contents,
+                                    fetched_at: SystemTime::now(),
+                                };
+                                (archive, fetch_state)
+                            })),
+                            // Say which fetch failed
+                            Err(e) => Err(Error::Fetch {
+                                url,
+                                archive: name,
+                                cycle,
+                                attempt,
+                                source: Box::new(e),
+                            }),
+                        };
+                        (mirror, result)
+                    }));
+                }
+            }
+
+            let mut to_remove = Vec::new();
+            for (i, fetch) in this.fetches.iter_mut().enumerate() {
+                if let Poll::Ready((mirror, result)) = fetch.as_mut().poll(cx) {
                     to_remove.push(i);
+
+                    let now = tokio::time::Instant::now();
+                    let outcome = match &result {
+                        Ok(Some(_)) => Outcome::Fetched,
+                        Ok(None) => Outcome::NotModified,
+                        Err(e) => Outcome::Failed {
+                            retryable: this.schedule.retry().should_retry(e),
+                        },
+                    };
+                    let finished = this.schedule.finished(now, mirror, outcome);
+                    if finished.reset_state {
+                        log::warn!(
+                            "failing over to {}",
+                            this.mirrors.urls[this.schedule.mirror()]
+                        );
+                        *this.fetch_state = FetchState::default();
+                    }
                     match result {
-                        Ok(Some((bytes, fetch_state))) => {
-                            *this.fetch_state = fetch_state;
-                            this.fetch_results.push(Ok(bytes));
+                        Ok(Some((archive, fetch_state))) => {
+                            if finished.current {
+                                *this.fetch_state = fetch_state;
+                            }
+                            this.fetch_results.push(Ok(archive));
                         }
-                        Ok(None) => {}
+                        Ok(None) => (),
                         Err(e) => this.fetch_results.push(Err(e)),
                     }
                 }
-                Poll::Pending => (),
             }
-        }
 
-        for index in to_remove.into_iter().rev() {
-            this.fetches.remove(index);
+            for index in to_remove.into_iter().rev() {
+                drop(this.fetches.remove(index));
+            }
+
+            // Keep a timer running for the retry, if there is one
+            match this.schedule.phase() {
+                FetchPhase::Backoff { retry_at } => {
+                    let deadline = this.retry_at.as_ref().map(|sleep| sleep.deadline());
+                    if deadline != Some(retry_at) {
+                        log::debug!(
+                            "retrying {} in {:?}",
+                            this.filename,
+                            retry_at.saturating_duration_since(now)
+                        );
+                        let mut sleep = Box::pin(tokio::time::sleep_until(retry_at));
+                        let _ = sleep.as_mut().poll(cx);
+                        *this.retry_at = Some(sleep);
+                    }
+                }
+                _ => *this.retry_at = None,
+            }
+
+            if !this.schedule.wants_fetch(tokio::time::Instant::now())
+                || this.fetch_results.len() >= *this.max_buffered
+            {
+                break;
+            }
         }
+    }
 
-        match this.fetch_results.pop() {
+    /// Take a buffered result, if any.
+    pub fn take_result(self: Pin<&mut Self>) -> Option<Result<FetchedArchive, Error>> {
+        self.project().fetch_results.pop()
+    }
+}
+
+impl Stream for FetchStream {
+    type Item = Result<FetchedArchive, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.as_mut().poll_fetches(cx);
+
+        match self.as_mut().take_result() {
             Some(result) => Poll::Ready(Some(result)),
+            None if self.is_drained() => Poll::Ready(None),
             None => Poll::Pending,
         }
     }
 }
 
+/// Wrap a fetch in a span recording its outcome.
+#[cfg(feature = "tracing")]
+fn instrument<T>(
+    fetch: T,
+    url: &str,
+    archive: &str,
+    cycle: u64,
+    attempt: u32,
+) -> impl Future<Output = T::Output>
+where
+    T: Future<Output = Result<Option<(Bytes, FetchState)>, Error>>,
+{
+    use tracing::field::Empty;
+    use tracing::Instrument;
+
+    let span = tracing::info_span!(
+        "fetch",
+        url,
+        archive,
+        cycle,
+        attempt,
+        status = Empty,
+        bytes = Empty,
+        latency_ms = Empty,
+    );
+    let record = span.clone();
+    async move {
+        let started = std::time::Instant::now();
+        let result = fetch.await;
+        record.record("latency_ms", started.elapsed().as_millis() as u64);
+        match &result {
+            Ok(Some((body, _))) => {
+                record.record("bytes", body.len());
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!(error = %e, "fetch failed"),
+        }
+        result
+    }
+    .instrument(span)
+}
+
+/// Count a completed fetch, recording how long it took and how large the archive was.
+#[cfg(feature = "metrics")]
+fn record_metrics(
+    archive: &str,
+    elapsed: Duration,
+    result: &Result<Option<(Bytes, FetchState)>, Error>,
+) {
+    let archive = archive.to_string();
+    metrics::counter!("emwin_tg_fetches_total", "archive" => archive.clone()).increment(1);
+    metrics::histogram!("emwin_tg_fetch_duration_seconds", "archive" => archive.clone())
+        .record(elapsed);
+    match result {
+        Ok(Some((body, _))) => metrics::histogram!("emwin_tg_archive_bytes", "archive" => archive)
+            .record(body.len() as f64),
+        Ok(None) => {
+            metrics::counter!("emwin_tg_not_modified_total", "archive" => archive).increment(1)
+        }
+        Err(_) => {
+            metrics::counter!("emwin_tg_fetch_errors_total", "archive" => archive).increment(1)
+        }
+    }
+}
+
+/// Note the status of a response on the current fetch span.
+fn record_status(_status: u16) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("status", _status);
+}
+
 /// Returns Ok(None) if the resource is not modified
 async fn fetch(
-    url: &'static str,
-    client: reqwest::Client,
+    url: String,
+    transport: Arc<dyn HttpTransport>,
     fetch_state: FetchState,
+    limit: u64,
+    ranged: bool,
 ) -> Result<Option<(Bytes, FetchState)>, crate::Error> {
-    let req = client.get(url);
-    let req = if let Some(value) = &fetch_state.etag {
-        req.header(reqwest::header::IF_NONE_MATCH, value)
-    } else {
-        req
-    };
-    let req = if let Some(value) = &fetch_state.last_modified {
-        req.header(reqwest::header::IF_MODIFIED_SINCE, value)
-    } else {
-        req
-    };
-    let req = req.build()?;
+    if ranged && !fetch_state.members.is_empty() {
+        return range::fetch_new_members(url, &*transport, fetch_state, limit).await;
+    }
+
+    let result = fetch_whole(&url, &*transport, &fetch_state, limit).await?;
+    Ok(result.map(|(body, new_fetch_state)| {
+        if ranged {
+            // Note what's in it, so the next fetch can skip those members
+            range::with_members(body, new_fetch_state)
+        } else {
+            (body, new_fetch_state)
+        }
+    }))
+}
+
+/// Fetch an entire archive, returning Ok(None) if it is not modified.
+async fn fetch_whole(
+    url: &str,
+    transport: &dyn HttpTransport,
+    fetch_state: &FetchState,
+    limit: u64,
+) -> Result<Option<(Bytes, FetchState)>, crate::Error> {
+    let req = conditional(HttpRequest::new(url, limit), fetch_state);
 
     log::debug!("GET {}", url);
-    let mut resp = client.execute(req).await?.error_for_status().map_err(|e| {
-        log::debug!("{}: {}", e, url);
-        e
-    })?;
-    if (fetch_state.etag.is_some() || fetch_state.last_modified.is_some())
-        && resp.status() == reqwest::StatusCode::NOT_MODIFIED
-    {
+    let resp = send(transport, req).await?;
+    if (fetch_state.etag.is_some() || fetch_state.last_modified.is_some()) && resp.status == 304 {
         log::debug!("304 Not Modified: {}", url);
-        // Sink the body, if any, to make the connection reusable
-        // (Discard errors)
-        while let Ok(Some(_)) = resp.chunk().await {}
-
         Ok(None)
     } else {
         // Copy in the response headers, if any
-        let etag = resp
-            .headers()
-            .get(reqwest::header::ETAG)
-            .and_then(|v| v.to_str().ok())
-            .map(String::from);
-        let last_modified = resp
-            .headers()
-            .get(reqwest::header::LAST_MODIFIED)
-            .and_then(|v| v.to_str().ok())
-            .map(String::from);
-
-        let new_fetch_state = FetchState {
-            etag,
-            last_modified,
-        };
+        let new_fetch_state = FetchState::from_headers(&resp);
         log::debug!("200 OK {}", url);
-        let body = resp.bytes().await?;
 
         // Return the response
-        Ok(Some((body, new_fetch_state)))
+        Ok(Some((resp.body, new_fetch_state)))
+    }
+}
+
+/// Make a request conditional on the resource having changed since `fetch_state` was recorded.
+fn conditional(req: HttpRequest, fetch_state: &FetchState) -> HttpRequest {
+    let req = if let Some(value) = &fetch_state.etag {
+        req.header("if-none-match", value.as_str())
+    } else {
+        req
+    };
+    if let Some(value) = &fetch_state.last_modified {
+        req.header("if-modified-since", value.as_str())
+    } else {
+        req
     }
 }
+
+mod range;
+
+pub(crate) mod schedule;
+use schedule::{Attempt, FetchPhase, Outcome, Schedule};
+
+mod rate;
+pub use rate::RateLimiter;
+
+pub(crate) mod transport;
+use transport::{send, SharedTransport};
+pub use transport::{HttpRequest, HttpResponse, HttpTransport};
@@ -0,0 +1,341 @@
+//! Fetching only the new members of an archive, using HTTP range requests.
+//!
+//! A zip archive ends with a central directory listing every member and where its data starts.
+//! Fetching the end of the archive reveals which members are new. Their data is then fetched by
+//! range and reassembled, with the matching central directory records, into a smaller archive.
+
+use super::{conditional, fetch_whole, send, FetchState, HttpRequest, HttpTransport};
+use crate::Error;
+use bytes::Bytes;
+use std::sync::Arc;
+
+/// How much of the end of the archive to fetch at first, which usually covers the central
+/// directory.
+const TAIL_SIZE: u64 = 64 << 10;
+
+/// The most requests to make for member data before fetching the whole archive instead.
+const MAX_RANGES: usize = 8;
+
+const END_OF_DIRECTORY: &[u8] = b"PK\x05\x06";
+const DIRECTORY_RECORD: &[u8] = b"PK\x01\x02";
+
+/// Fetch the members of an archive which aren't in `fetch_state.members`, returning Ok(None) if
+/// the archive is not modified.
+///
+/// Falls back to fetching the whole archive if the server doesn't support range requests, or if
+/// that would be simpler.
+pub(super) async fn fetch_new_members(
+    url: String,
+    transport: &dyn HttpTransport,
+    fetch_state: FetchState,
+    limit: u64,
+) -> Result<Option<(Bytes, FetchState)>, Error> {
+    log::debug!("GET {} (last {} bytes)", url, TAIL_SIZE);
+    let req = conditional(HttpRequest::new(&url, limit), &fetch_state)
+        .header("range", format!("bytes=-{}", TAIL_SIZE));
+    let resp = send(transport, req).await?;
+    match resp.status {
+        304 => {
+            log::debug!("304 Not Modified: {}", url);
+            return Ok(None);
+        }
+        206 => {}
+        _ => {
+            // The server ignored the range and sent everything
+            log::debug!("{} {}: ranges unsupported", resp.status, url);
+            let new_fetch_state = FetchState::from_headers(&resp);
+            return Ok(Some(with_members(resp.body, new_fetch_state)));
+        }
+    }
+
+    // Later requests must see the same version of the archive
+    let new_fetch_state = FetchState::from_headers(&resp);
+    let validator = match new_fetch_state
+        .etag
+        .clone()
+        .or_else(|| new_fetch_state.last_modified.clone())
+    {
+        Some(validator) => validator,
+        None => return fetch_all(&url, transport, limit).await,
+    };
+    let tail_start = match resp.header("content-range").and_then(content_range_start) {
+        Some(start) => start,
+        None => return fetch_all(&url, transport, limit).await,
+    };
+    let tail = resp.body;
+
+    let end = match EndOfDirectory::find(&tail) {
+        Some(end) => end,
+        None => return fetch_all(&url, transport, limit).await,
+    };
+
+    // Fetch the rest of the central directory, if it didn't fit in the tail
+    let directory = if end.offset >= tail_start {
+        let start = (end.offset - tail_start) as usize;
+        let range = start..start.saturating_add(end.size as usize);
+        // A directory which claims to run past the tail is damaged
+        if tail.get(range.clone()).is_none() {
+            return fetch_all(&url, transport, limit).await;
+        }
+        tail.slice(range)
+    } else {
+        let range = end.offset..end.offset + end.size;
+        match fetch_range(&url, transport, &validator, range, limit).await? {
+            Ranged::Partial(directory) => directory,
+            Ranged::Whole(body, new_fetch_state) => {
+                return Ok(Some(with_members(body, new_fetch_state)))
+            }
+        }
+    };
+    let members = match parse_directory(&directory, end.offset, end.entries) {
+        Some(members) => members,
+        None => return fetch_all(&url, transport, limit).await,
+    };
+
+    let new: Vec<&Member> = members
+        .iter()
+        .filter(|member| !fetch_state.members.contains(&member.name))
+        .collect();
+    let new_bytes: u64 = new
+        .iter()
+        .map(|member| member.data.end - member.data.start)
+        .sum();
+    let runs = runs(&new);
+    log::debug!(
+        "{}: {} of {} members are new, in {} ranges",
+        url,
+        new.len(),
+        members.len(),
+        runs.len()
+    );
+    if runs.len() > MAX_RANGES || new_bytes * 2 > end.offset {
+        return fetch_all(&url, transport, limit).await;
+    }
+
+    // Fetch each run of new members, and copy out each member's data
+    let mut data = Vec::with_capacity(new.len());
+    for run in runs {
+        let body = match fetch_range(&url, transport, &validator, run.clone(), limit).await? {
+            Ranged::Partial(body) => body,
+            Ranged::Whole(body, new_fetch_state) => {
+                return Ok(Some(with_members(body, new_fetch_state)))
+            }
+        };
+        for member in new.iter().filter(|member| run.contains(&member.data.start)) {
+            let start = (member.data.start - run.start) as usize;
+            let end = (member.data.end - run.start) as usize;
+            match body.get(start..end) {
+                Some(bytes) => data.push((*member, bytes.to_vec())),
+                None => return fetch_all(&url, transport, limit).await,
+            }
+        }
+    }
+
+    let archive = assemble(&data);
+    let names = members.into_iter().map(|member| member.name).collect();
+    Ok(Some((
+        archive,
+        FetchState {
+            members: Arc::new(names),
+            ..new_fetch_state
+        },
+    )))
+}
+
+/// Record the names of the members of a whole archive in its fetch state.
+pub(super) fn with_members(body: Bytes, fetch_state: FetchState) -> (Bytes, FetchState) {
+    let names = EndOfDirectory::find(&body)
+        .and_then(|end| {
+            let start = end.offset as usize;
+            let directory = body.get(start..start + end.size as usize)?;
+            parse_directory(directory, end.offset, end.entries)
+        })
+        .map(|members| members.into_iter().map(|member| member.name).collect())
+        .unwrap_or_default();
+
+    (
+        body,
+        FetchState {
+            members: Arc::new(names),
+            ..fetch_state
+        },
+    )
+}
+
+/// Fetch the whole archive unconditionally.
+async fn fetch_all(
+    url: &str,
+    transport: &dyn HttpTransport,
+    limit: u64,
+) -> Result<Option<(Bytes, FetchState)>, Error> {
+    Ok(fetch_whole(url, transport, &FetchState::default(), limit)
+        .await?
+        .map(|(body, fetch_state)| with_members(body, fetch_state)))
+}
+
+/// The result of a range request.
+enum Ranged {
+    /// The requested range
+    Partial(Bytes),
+    /// The whole archive, because it changed since the validator was issued
+    Whole(Bytes, FetchState),
+}
+
+/// Fetch `range` of the archive, provided it still matches `validator`.
+async fn fetch_range(
+    url: &str,
+    transport: &dyn HttpTransport,
+    validator: &str,
+    range: std::ops::Range<u64>,
+    limit: u64,
+) -> Result<Ranged, Error> {
+    log::debug!("GET {} (bytes {}-{})", url, range.start, range.end - 1);
+    let req = HttpRequest::new(url, limit)
+        .header("range", format!("bytes={}-{}", range.start, range.end - 1))
+        .header("if-range", validator);
+    let resp = send(transport, req).await?;
+    if resp.status == 206 {
+        Ok(Ranged::Partial(resp.body))
+    } else {
+        log::debug!("{} {}: archive changed", resp.status, url);
+        let fetch_state = FetchState::from_headers(&resp);
+        Ok(Ranged::Whole(resp.body, fetch_state))
+    }
+}
+
+/// Parse the first byte position from a `Content-Range` header like `bytes 100-199/200`.
+fn content_range_start(value: &str) -> Option<u64> {
+    let range = value.strip_prefix("bytes ")?;
+    let (start, _) = range.split_once('-')?;
+    start.parse().ok()
+}
+
+/// The end of central directory record.
+#[derive(Debug)]
+struct EndOfDirectory {
+    entries: u16,
+    size: u64,
+    offset: u64,
+}
+
+impl EndOfDirectory {
+    /// Find the record at the end of `tail`.
+    ///
+    /// Returns `None` if there isn't one, or if the archive is in the zip64 format.
+    fn find(tail: &[u8]) -> Option<Self> {
+        // The record is 22 bytes plus a comment of up to 64 KiB
+        let earliest = tail.len().saturating_sub(22 + 0xffff);
+        let start = (earliest..=tail.len().checked_sub(22)?).rev().find(|&i| {
+            tail[i..].starts_with(END_OF_DIRECTORY)
+                && 22 + usize::from(u16_at(tail, i + 20)) == tail.len() - i
+        })?;
+
+        let entries = u16_at(tail, start + 10);
+        let size = u32_at(tail, start + 12);
+        let offset = u32_at(tail, start + 16);
+        if entries == 0xffff || size == 0xffff_ffff || offset == 0xffff_ffff {
+            return None;
+        }
+        Some(Self {
+            entries,
+            size: size.into(),
+            offset: offset.into(),
+        })
+    }
+}
+
+/// A member of an archive, as described by the central directory.
+#[derive(Debug)]
+struct Member<'a> {
+    name: String,
+    /// The central directory record
+    record: &'a [u8],
+    /// Where the local header and data lie in the archive
+    data: std::ops::Range<u64>,
+}
+
+/// Parse the central directory, which starts at `offset` in the archive.
+///
+/// Returns `None` if it's malformed or in the zip64 format.
+fn parse_directory(directory: &[u8], offset: u64, entries: u16) -> Option<Vec<Member<'_>>> {
+    let mut members = Vec::with_capacity(entries.into());
+    let mut rest = directory;
+    for _ in 0..entries {
+        if rest.len() < 46 || !rest.starts_with(DIRECTORY_RECORD) {
+            return None;
+        }
+        let name_len = usize::from(u16_at(rest, 28));
+        let len = 46 + name_len + usize::from(u16_at(rest, 30)) + usize::from(u16_at(rest, 32));
+        let record = rest.get(..len)?;
+        let start = u32_at(rest, 42);
+        if start == 0xffff_ffff || u64::from(start) >= offset {
+            return None;
+        }
+        members.push(Member {
+            name: String::from_utf8_lossy(&record[46..46 + name_len]).into_owned(),
+            record,
+            data: start.into()..offset,
+        });
+        rest = &rest[len..];
+    }
+
+    // Each member's data runs until the next member's local header, or the central directory
+    let mut starts: Vec<u64> = members.iter().map(|member| member.data.start).collect();
+    starts.sort_unstable();
+    for member in &mut members {
+        let next = starts.partition_point(|&start| start <= member.data.start);
+        if let Some(&end) = starts.get(next) {
+            member.data.end = end;
+        }
+    }
+    Some(members)
+}
+
+/// Group members into runs of adjacent data, to fetch with one request each.
+fn runs(members: &[&Member]) -> Vec<std::ops::Range<u64>> {
+    let mut ranges: Vec<_> = members.iter().map(|member| member.data.clone()).collect();
+    ranges.sort_by_key(|range| range.start);
+
+    let mut runs: Vec<std::ops::Range<u64>> = Vec::new();
+    for range in ranges {
+        match runs.last_mut() {
+            Some(run) if run.end == range.start => run.end = range.end,
+            _ => runs.push(range),
+        }
+    }
+    runs
+}
+
+/// Build an archive from members' data and central directory records.
+fn assemble(members: &[(&Member, Vec<u8>)]) -> Bytes {
+    let mut archive = Vec::new();
+    let mut directory = Vec::new();
+    for (member, data) in members {
+        let offset = archive.len() as u32;
+        archive.extend_from_slice(data);
+        directory.extend_from_slice(&member.record[..42]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(&member.record[46..]);
+    }
+
+    let offset = archive.len() as u32;
+    let size = directory.len() as u32;
+    let entries = members.len() as u16;
+    archive.extend_from_slice(&directory);
+    archive.extend_from_slice(END_OF_DIRECTORY);
+    archive.extend_from_slice(&[0; 4]);
+    archive.extend_from_slice(&entries.to_le_bytes());
+    archive.extend_from_slice(&entries.to_le_bytes());
+    archive.extend_from_slice(&size.to_le_bytes());
+    archive.extend_from_slice(&offset.to_le_bytes());
+    archive.extend_from_slice(&[0; 2]);
+    archive.into()
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
@@ -0,0 +1,104 @@
+//! Limiting the rate of requests across sources.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// A limit on the rate of requests, shared by every source it's given to.
+///
+/// Each source polls its archives on its own schedule, so a [`TextStream`](crate::TextStream)
+/// and an [`ImageStream`](crate::ImageStream) running in one process can send requests in
+/// bursts. Giving both the same `RateLimiter` spaces their requests out.
+///
+/// This is a token bucket: it holds up to `burst` tokens, refilled at one per `interval`, and
+/// each fetch of an archive waits for a token. Clones share the same bucket.
+///
+/// # Example
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use std::time::Duration;
+///
+/// let limiter = emwin_tg::RateLimiter::new(Duration::from_secs(2)).burst(3);
+///
+/// let text = emwin_tg::TextStream::builder()
+///     .rate_limiter(limiter.clone())
+///     .build();
+/// let images = emwin_tg::ImageStream::builder()
+///     .rate_limiter(limiter)
+///     .build();
+/// # std::mem::drop((text, images));
+/// # })
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    interval: Duration,
+    burst: u32,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// How many requests may start now, in units of `interval`
+    tokens: f64,
+    updated: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Allow one fetch per `interval`, with no bursts.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            burst: 1,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: 1.0,
+                updated: None,
+            })),
+        }
+    }
+
+    /// Allow up to `fetches` fetches at once, after a quiet period.
+    ///
+    /// The default is 1. This returns a limiter with a bucket of its own, leaving any clones of
+    /// `self` as they were, so set the burst before sharing the limiter.
+    pub fn burst(self, fetches: u32) -> Self {
+        let burst = fetches.max(1);
+        Self {
+            interval: self.interval,
+            burst,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: burst.into(),
+                updated: None,
+            })),
+        }
+    }
+
+    /// Take a token, or return how long to wait before trying again.
+    fn try_acquire(&self) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        if let Some(updated) = bucket.updated {
+            let refilled = match self.interval.as_secs_f64() {
+                interval if interval > 0.0 => (now - updated).as_secs_f64() / interval,
+                _ => f64::INFINITY,
+            };
+            bucket.tokens = (bucket.tokens + refilled).min(self.burst.into());
+        }
+        bucket.updated = Some(now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(self.interval.mul_f64(1.0 - bucket.tokens))
+        }
+    }
+
+    /// Wait until a fetch may start.
+    pub(crate) async fn acquire(&self) {
+        while let Err(delay) = self.try_acquire() {
+            log::debug!("rate limited for {:?}", delay);
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
@@ -0,0 +1,366 @@
+//! Deciding when to fetch an archive, and from which mirror.
+//!
+//! [`Schedule`] holds no timers: the caller tells it what happened and when, and it says what to
+//! do next. [`FetchStream`](super::FetchStream) drives it from its ticker and retry timer, and the
+//! blocking client from its own sleeps.
+
+use super::{Mirrors, RetryPolicy};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// What a feed is doing between fetches.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum FetchPhase {
+    /// Waiting for the next tick
+    Idle,
+    /// A fetch should start as soon as nothing prevents it
+    Due,
+    /// A fetch is in flight
+    Fetching,
+    /// The last fetch failed, and will be retried at `retry_at` unless a tick comes first
+    Backoff { retry_at: Instant },
+    /// No more fetches will start
+    ShutDown,
+}
+
+/// A fetch which should start now.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct Attempt {
+    /// The index of the mirror to fetch from
+    pub mirror: usize,
+    /// Counts every fetch of this archive, starting from 1
+    pub cycle: u64,
+    /// Counts fetches since the last success, starting from 1
+    pub attempt: u32,
+    /// True if what's known about the archive came from another mirror and should be forgotten
+    pub reset_state: bool,
+}
+
+/// How a fetch ended.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum Outcome {
+    /// The archive was retrieved
+    Fetched,
+    /// The archive hasn't changed
+    NotModified,
+    /// The fetch failed, and `retryable` says whether the retry policy accepts the error
+    Failed { retryable: bool },
+}
+
+/// What to do with the result of a fetch.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct Finished {
+    /// False if the fetch was from a mirror we've since left, and says nothing about this one
+    pub current: bool,
+    /// True if we failed over, and what's known about the archive should be forgotten
+    pub reset_state: bool,
+}
+
+/// The scheduling state of one archive: its [`FetchPhase`], its mirror, and its failure counts.
+#[derive(Debug)]
+pub(crate) struct Schedule {
+    phase: FetchPhase,
+    retry: RetryPolicy,
+    mirrors: usize,
+    failover_after: u32,
+    recover_after: Duration,
+    mirror: usize,
+    consecutive_failures: u32,
+    retry_failures: u32,
+    failed_over_at: Option<Instant>,
+    cycle: u64,
+}
+
+impl Schedule {
+    pub fn new(mirrors: &Mirrors, retry: RetryPolicy) -> Self {
+        Self {
+            phase: FetchPhase::Idle,
+            retry,
+            mirrors: mirrors.urls.len(),
+            failover_after: mirrors.failover_after,
+            recover_after: mirrors.recover_after,
+            mirror: 0,
+            consecutive_failures: 0,
+            retry_failures: 0,
+            failed_over_at: None,
+            cycle: 0,
+        }
+    }
+
+    pub fn phase(&self) -> FetchPhase {
+        self.phase
+    }
+
+    pub fn retry(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// The index of the mirror currently in use.
+    pub fn mirror(&self) -> usize {
+        self.mirror
+    }
+
+    /// Returns true if a fetch should start at `now`, without waiting for a tick.
+    pub fn wants_fetch(&self, now: Instant) -> bool {
+        match self.phase {
+            FetchPhase::Due => true,
+            FetchPhase::Backoff { retry_at } => now >= retry_at,
+            _ => false,
+        }
+    }
+
+    /// A fetch is due at `now`, either from a tick or because [`wants_fetch`](Self::wants_fetch)
+    /// said so. Returns the fetch to start, if any.
+    ///
+    /// `paused` means earlier results haven't been taken yet, in which case the fetch waits
+    /// until they are.
+    pub fn due(&mut self, now: Instant, paused: bool) -> Option<Attempt> {
+        match self.phase {
+            FetchPhase::ShutDown => return None,
+            FetchPhase::Fetching => {
+                // The fetch in flight will be as fresh as another, so let this one go rather than
+                // stacking up requests for the same URL
+                return None;
+            }
+            _ if paused => {
+                // Nobody is taking the results, so fetch once they do
+                self.phase = FetchPhase::Due;
+                return None;
+            }
+            _ => (),
+        }
+
+        // Go back to the preferred mirror once it's had time to recover
+        let mut reset_state = false;
+        if let Some(failed_over_at) = self.failed_over_at {
+            if now.saturating_duration_since(failed_over_at) >= self.recover_after {
+                self.mirror = 0;
+                self.consecutive_failures = 0;
+                self.failed_over_at = None;
+                reset_state = true;
+            }
+        }
+
+        // This fetch supersedes any pending retry
+        self.phase = FetchPhase::Fetching;
+        self.cycle += 1;
+        Some(Attempt {
+            mirror: self.mirror,
+            cycle: self.cycle,
+            attempt: self.retry_failures + 1,
+            reset_state,
+        })
+    }
+
+    /// The fetch started by [`due`](Self::due) was never sent, e.g. in a dry run.
+    pub fn abandon(&mut self) {
+        if self.phase == FetchPhase::Fetching {
+            self.phase = FetchPhase::Idle;
+        }
+    }
+
+    /// A fetch from `mirror` ended at `now`.
+    pub fn finished(&mut self, now: Instant, mirror: usize, outcome: Outcome) -> Finished {
+        if self.phase == FetchPhase::Fetching {
+            self.phase = FetchPhase::Idle;
+        }
+
+        // Results from a mirror we've since left can't tell us anything about the current one
+        if mirror != self.mirror {
+            return Finished {
+                current: false,
+                reset_state: false,
+            };
+        }
+
+        let retryable = match outcome {
+            Outcome::Fetched | Outcome::NotModified => {
+                self.consecutive_failures = 0;
+                self.retry_failures = 0;
+                return Finished {
+                    current: true,
+                    reset_state: false,
+                };
+            }
+            Outcome::Failed { retryable } => retryable,
+        };
+
+        self.retry_failures += 1;
+        if self.phase != FetchPhase::ShutDown {
+            if let Some(delay) = self.retry.delay(self.retry_failures).filter(|_| retryable) {
+                self.phase = FetchPhase::Backoff {
+                    retry_at: now + delay,
+                };
+            }
+        }
+
+        self.consecutive_failures += 1;
+        let fail_over = self.consecutive_failures >= self.failover_after && self.mirrors > 1;
+        if fail_over {
+            // Try the next mirror right away
+            self.mirror = (mirror + 1) % self.mirrors;
+            self.consecutive_failures = 0;
+            self.failed_over_at = match self.mirror {
+                0 => None,
+                _ => Some(now),
+            };
+            self.retry_failures = 0;
+            if self.phase != FetchPhase::ShutDown {
+                self.phase = FetchPhase::Due;
+            }
+        }
+        Finished {
+            current: true,
+            reset_state: fail_over,
+        }
+    }
+
+    /// Start no more fetches, including retries.
+    pub fn shut_down(&mut self) {
+        self.phase = FetchPhase::ShutDown;
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.phase == FetchPhase::ShutDown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(urls: &[&str], failover_after: u32) -> Schedule {
+        let mirrors = Mirrors::new(urls.iter().map(|url| url.to_string()))
+            .failover_after(failover_after)
+            .recover_after(Duration::from_secs(600));
+        let retry = RetryPolicy::new(Duration::from_secs(5), Duration::from_secs(60)).jitter(0.0);
+        Schedule::new(&mirrors, retry)
+    }
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    #[test]
+    fn success_returns_to_idle() {
+        let start = Instant::now();
+        let mut s = schedule(&["https://a/"], 3);
+        assert_eq!(s.phase(), FetchPhase::Idle);
+        assert!(!s.wants_fetch(start));
+
+        let attempt = s.due(start, false).unwrap();
+        assert_eq!((attempt.mirror, attempt.cycle, attempt.attempt), (0, 1, 1));
+        assert_eq!(s.phase(), FetchPhase::Fetching);
+
+        // A tick while fetching doesn't start another
+        assert_eq!(s.due(start + secs(1), false), None);
+
+        let finished = s.finished(start + secs(2), 0, Outcome::Fetched);
+        assert!(finished.current);
+        assert_eq!(s.phase(), FetchPhase::Idle);
+        assert_eq!(s.due(start + secs(60), false).unwrap().cycle, 2);
+    }
+
+    #[test]
+    fn paused_fetches_wait_until_unpaused() {
+        let start = Instant::now();
+        let mut s = schedule(&["https://a/"], 3);
+        assert_eq!(s.due(start, true), None);
+        assert_eq!(s.phase(), FetchPhase::Due);
+        assert!(s.wants_fetch(start));
+        assert!(s.due(start, false).is_some());
+    }
+
+    #[test]
+    fn failures_back_off_exponentially() {
+        let start = Instant::now();
+        let mut s = schedule(&["https://a/"], 10);
+
+        s.due(start, false).unwrap();
+        s.finished(start, 0, Outcome::Failed { retryable: true });
+        let retry_at = start + secs(5);
+        assert_eq!(s.phase(), FetchPhase::Backoff { retry_at });
+        assert!(!s.wants_fetch(start + secs(4)));
+        assert!(s.wants_fetch(retry_at));
+
+        let attempt = s.due(retry_at, false).unwrap();
+        assert_eq!(attempt.attempt, 2);
+        s.finished(retry_at, 0, Outcome::Failed { retryable: true });
+        assert_eq!(
+            s.phase(),
+            FetchPhase::Backoff {
+                retry_at: retry_at + secs(10)
+            }
+        );
+
+        // Success resets the delay
+        s.due(retry_at + secs(10), false).unwrap();
+        s.finished(retry_at + secs(10), 0, Outcome::NotModified);
+        assert_eq!(s.phase(), FetchPhase::Idle);
+        assert_eq!(s.due(retry_at + secs(60), false).unwrap().attempt, 1);
+    }
+
+    #[test]
+    fn unretryable_failures_wait_for_the_next_tick() {
+        let start = Instant::now();
+        let mut s = schedule(&["https://a/"], 10);
+        s.due(start, false).unwrap();
+        s.finished(start, 0, Outcome::Failed { retryable: false });
+        assert_eq!(s.phase(), FetchPhase::Idle);
+        assert!(!s.wants_fetch(start + secs(3600)));
+    }
+
+    #[test]
+    fn failures_fail_over_and_recover() {
+        let start = Instant::now();
+        let mut s = schedule(&["https://a/", "https://b/"], 2);
+
+        s.due(start, false).unwrap();
+        let finished = s.finished(start, 0, Outcome::Failed { retryable: true });
+        assert!(!finished.reset_state);
+        assert_eq!(s.mirror(), 0);
+
+        s.due(start + secs(5), false).unwrap();
+        let finished = s.finished(start + secs(5), 0, Outcome::Failed { retryable: true });
+        assert!(finished.reset_state);
+        assert_eq!(s.mirror(), 1);
+        assert_eq!(s.phase(), FetchPhase::Due);
+
+        let attempt = s.due(start + secs(5), false).unwrap();
+        assert_eq!((attempt.mirror, attempt.attempt), (1, 1));
+        s.finished(start + secs(6), 1, Outcome::Fetched);
+
+        // Stay on the backup until the preferred mirror has had time to recover
+        let attempt = s.due(start + secs(60), false).unwrap();
+        assert_eq!((attempt.mirror, attempt.reset_state), (1, false));
+        s.finished(start + secs(60), 1, Outcome::NotModified);
+
+        let attempt = s.due(start + secs(605), false).unwrap();
+        assert_eq!((attempt.mirror, attempt.reset_state), (0, true));
+    }
+
+    #[test]
+    fn results_from_other_mirrors_are_ignored() {
+        let start = Instant::now();
+        let mut s = schedule(&["https://a/", "https://b/"], 1);
+        s.due(start, false).unwrap();
+        s.finished(start, 0, Outcome::Failed { retryable: true });
+        assert_eq!(s.mirror(), 1);
+
+        let finished = s.finished(start, 0, Outcome::Failed { retryable: true });
+        assert!(!finished.current);
+        assert_eq!(s.mirror(), 1);
+    }
+
+    #[test]
+    fn shutting_down_stops_retries() {
+        let start = Instant::now();
+        let mut s = schedule(&["https://a/", "https://b/"], 1);
+        s.due(start, false).unwrap();
+        s.shut_down();
+        s.finished(start, 0, Outcome::Failed { retryable: true });
+        assert!(s.is_shut_down());
+        assert!(!s.wants_fetch(start + secs(3600)));
+        assert_eq!(s.due(start + secs(3600), false), None);
+    }
+}
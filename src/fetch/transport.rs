@@ -0,0 +1,217 @@
+//! The HTTP layer beneath the sources.
+
+use crate::Error;
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use std::sync::Arc;
+
+/// A GET request for an archive, or part of one.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct HttpRequest {
+    /// The URL to retrieve
+    pub url: String,
+    /// Headers to send, e.g. `If-None-Match` and `Range`, as lowercase names and values
+    pub headers: Vec<(String, String)>,
+    /// The largest response body to accept
+    ///
+    /// Transports should stop reading a larger body and return [`Error::ArchiveTooLarge`]. Larger
+    /// bodies which are returned anyway are rejected.
+    pub max_body_size: u64,
+}
+
+impl HttpRequest {
+    pub(crate) fn new(url: &str, max_body_size: u64) -> Self {
+        Self {
+            url: url.to_string(),
+            headers: Vec::new(),
+            max_body_size,
+        }
+    }
+
+    pub(crate) fn header<V: Into<String>>(mut self, name: &str, value: V) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+}
+
+/// The response to an [`HttpRequest`].
+///
+/// # Example
+///
+/// ```
+/// let response = emwin_tg::HttpResponse::new(200, b"PK\x05\x06".to_vec())
+///     .with_header("ETag", "\"abc\"");
+/// assert_eq!(response.header("etag"), Some("\"abc\""));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HttpResponse {
+    /// The status code
+    pub status: u16,
+    /// The response headers, as names and values
+    pub headers: Vec<(String, String)>,
+    /// The response body
+    pub body: Bytes,
+}
+
+impl HttpResponse {
+    /// A response with a status and body, and no headers.
+    pub fn new<B: Into<Bytes>>(status: u16, body: B) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    /// Add a header.
+    pub fn with_header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// The value of the first header named `name`, compared without regard to case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Sends the HTTP requests with which sources retrieve archives.
+///
+/// Sources use the `reqwest::Client` they're given by default. Implement this trait to retrieve
+/// archives some other way, e.g. with another HTTP client, through a proxy, or from canned
+/// responses in tests, and pass it to [`SourceConfig::transport`](crate::SourceConfig::transport).
+///
+/// Responses are returned whole. A response with an error status may be returned as is, which
+/// sources report as [`Error::Status`], or as an error of the transport's own. Errors which aren't
+/// already an [`Error`] can be wrapped in [`Error::Transport`].
+///
+/// # Example
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use emwin_tg::{Error, HttpRequest, HttpResponse, HttpTransport};
+/// use futures::future::BoxFuture;
+///
+/// /// Answers every request with the same archive.
+/// struct Canned(bytes::Bytes);
+///
+/// impl HttpTransport for Canned {
+///     fn get(&self, _request: HttpRequest) -> BoxFuture<'static, Result<HttpResponse, Error>> {
+///         let response = HttpResponse::new(200, self.0.clone());
+///         Box::pin(async move { Ok(response) })
+///     }
+/// }
+///
+/// let config = emwin_tg::SourceConfig::new().transport(Canned(bytes::Bytes::new()));
+/// let stream = emwin_tg::TextStream::builder().source_config(config).build();
+/// # std::mem::drop(stream);
+/// # })
+/// ```
+pub trait HttpTransport: Send + Sync {
+    /// Send a GET request, returning the response.
+    fn get(&self, request: HttpRequest) -> BoxFuture<'static, Result<HttpResponse, Error>>;
+}
+
+impl HttpTransport for reqwest::Client {
+    fn get(&self, request: HttpRequest) -> BoxFuture<'static, Result<HttpResponse, Error>> {
+        let client = self.clone();
+        Box::pin(async move {
+            let mut req = client.get(&request.url);
+            for (name, value) in &request.headers {
+                req = req.header(name.as_str(), value.as_str());
+            }
+            let resp = client.execute(req.build()?).await?;
+
+            // Report error statuses as reqwest does, noting the status before it's lost
+            let status = resp.status().as_u16();
+            if status >= 400 {
+                super::record_status(status);
+            }
+            let mut resp = resp.error_for_status()?;
+            let headers = resp
+                .headers()
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect();
+            if status == 304 {
+                // Sink the body, if any, to make the connection reusable
+                // (Discard errors)
+                while let Ok(Some(_)) = resp.chunk().await {}
+                return Ok(HttpResponse {
+                    status,
+                    headers,
+                    body: Bytes::new(),
+                });
+            }
+
+            let body = read_body(resp, &request.url, request.max_body_size).await?;
+            Ok(HttpResponse {
+                status,
+                headers,
+                body,
+            })
+        })
+    }
+}
+
+/// Read a response body, giving up if it's larger than `limit`.
+async fn read_body(mut resp: reqwest::Response, url: &str, limit: u64) -> Result<Bytes, Error> {
+    // Check the declared size up front, and the actual size as it arrives
+    if let Some(size) = resp.content_length().filter(|size| *size > limit) {
+        return Err(too_large(url, size, limit));
+    }
+    let mut body = bytes::BytesMut::new();
+    while let Some(chunk) = resp.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() as u64 > limit {
+            return Err(too_large(url, body.len() as u64, limit));
+        }
+    }
+    Ok(body.freeze())
+}
+
+fn too_large(url: &str, size: u64, limit: u64) -> Error {
+    Error::ArchiveTooLarge {
+        url: url.to_string(),
+        size,
+        limit,
+    }
+}
+
+/// A transport shared by the feeds of a source.
+#[derive(Clone)]
+pub(crate) struct SharedTransport(pub(crate) Arc<dyn HttpTransport>);
+
+impl std::fmt::Debug for SharedTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedTransport")
+    }
+}
+
+/// Send a request, turning error statuses and oversized bodies into errors.
+pub(crate) async fn send(
+    transport: &dyn HttpTransport,
+    request: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let url = request.url.clone();
+    let limit = request.max_body_size;
+    let resp = transport.get(request).await?;
+    super::record_status(resp.status);
+    if resp.status >= 400 {
+        log::debug!("{} {}", resp.status, url);
+        return Err(Error::Status {
+            url,
+            status: resp.status,
+        });
+    }
+    if resp.body.len() as u64 > limit {
+        return Err(too_large(&url, resp.body.len() as u64, limit));
+    }
+    Ok(resp)
+}
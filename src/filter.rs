@@ -0,0 +1,248 @@
+use crate::product::ugc::UgcCode;
+use crate::Product;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::fmt;
+
+type FilterFn = Box<dyn Fn(&Product) -> bool + Send + Sync>;
+
+/// The set of predicates a `Stream` applies before buffering a product.
+#[derive(Default)]
+pub(crate) struct Filters {
+    /// Filters which look only at the filename
+    names: Vec<FilterFn>,
+    /// Filters which may look at the contents
+    products: Vec<FilterFn>,
+}
+
+impl Filters {
+    pub fn push<F: Fn(&Product) -> bool + Send + Sync + 'static>(&mut self, filter: F) {
+        self.products.push(Box::new(filter));
+    }
+
+    /// Add a filter which looks only at the product's filename, so that it can run before the
+    /// product is decompressed.
+    pub fn push_by_name<F: Fn(&Product) -> bool + Send + Sync + 'static>(&mut self, filter: F) {
+        self.names.push(Box::new(filter));
+    }
+
+    /// Returns true if every filter accepts the product.
+    pub fn accepts(&self, product: &Product) -> bool {
+        self.names
+            .iter()
+            .chain(&self.products)
+            .all(|filter| filter(product))
+    }
+
+    /// Returns false if a filter would reject any product named `filename`, whatever its contents.
+    pub fn accepts_name(&self, filename: &str) -> bool {
+        if self.names.is_empty() {
+            return true;
+        }
+        let product = Product::new(crate::product::normalize_filename(filename), Bytes::new());
+        self.names.iter().all(|filter| filter(&product))
+    }
+}
+
+/// Accept products whose AWIPS identifier starts with any of `prefixes`.
+///
+/// The identifier is taken from the end of an EMWIN filename, e.g. `AFDDVNIA` in
+/// `A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT`, or from the stem of a bare
+/// filename like `AFDDVNIA.TXT`.
+pub(crate) fn awips(prefixes: Vec<String>) -> impl Fn(&Product) -> bool + Send + Sync {
+    let prefixes: Vec<String> = prefixes
+        .iter()
+        .map(|prefix| prefix.to_ascii_uppercase())
+        .collect();
+    move |product| {
+        let name = match product.filename_pil() {
+            Some(pil) => pil,
+            None => {
+                let filename = product.filename.as_str();
+                filename.rsplit_once('.').map_or(filename, |(stem, _)| stem)
+            }
+        };
+        prefixes
+            .iter()
+            .any(|prefix| name.starts_with(prefix.as_str()))
+    }
+}
+
+/// Accept products issued by `office`, per [`Product::originator`].
+pub(crate) fn office(office: String) -> impl Fn(&Product) -> bool + Send + Sync {
+    move |product| product.originator().as_deref() == Some(office.as_str())
+}
+
+/// Accept products issued by the weather forecast office `wfo`, e.g. `MPX`, per the AWIPS
+/// identifier in their contents or, failing that, [`Product::originator`].
+pub(crate) fn wfo(wfo: String) -> impl Fn(&Product) -> bool + Send + Sync {
+    let wfo = wfo.to_ascii_uppercase();
+    move |product| match product.awips_id() {
+        Some(id) if id.office().len() == 3 => id.office() == wfo || wfo.ends_with(id.office()),
+        _ => product.originator().is_some_and(|originator| {
+            originator == wfo || (wfo.len() == 3 && originator.get(1..) == Some(wfo.as_str()))
+        }),
+    }
+}
+
+/// Accept products whose UGC strings list a county or zone in any of `states`.
+pub(crate) fn states(states: Vec<String>) -> impl Fn(&Product) -> bool + Send + Sync {
+    let states: Vec<String> = states.iter().map(|s| s.to_ascii_uppercase()).collect();
+    move |product| {
+        product
+            .ugc()
+            .iter()
+            .flat_map(|ugc| ugc.codes.iter())
+            .any(|code| states.iter().any(|state| state == code.state()))
+    }
+}
+
+/// Accept products whose UGC strings list any of `codes`, or the whole state or area one of them
+/// is in.
+pub(crate) fn zones(codes: Vec<UgcCode>) -> impl Fn(&Product) -> bool + Send + Sync {
+    move |product| {
+        product
+            .ugc()
+            .iter()
+            .flat_map(|ugc| ugc.codes.iter())
+            .any(|listed| {
+                codes.iter().any(|code| {
+                    listed == code
+                        || (listed.number() == 0
+                            && listed.state() == code.state()
+                            && listed.kind() == code.kind())
+                })
+            })
+    }
+}
+
+/// Accept products whose WMO heading, as written with spaces, starts with `prefix`.
+pub(crate) fn heading(prefix: String) -> impl Fn(&Product) -> bool + Send + Sync {
+    move |product| {
+        product
+            .wmo_heading()
+            .is_some_and(|heading| heading.to_string().starts_with(&prefix))
+    }
+}
+
+/// Accept products whose filename matches `pattern`, where `*` matches any run of characters and
+/// `?` matches any one character. Matching ignores ASCII case.
+pub(crate) fn filename(pattern: String) -> impl Fn(&Product) -> bool + Send + Sync {
+    let pattern = pattern.to_ascii_uppercase();
+    move |product| glob_matches(pattern.as_bytes(), product.normalize_filename().as_bytes())
+}
+
+fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the most recent `*`, if the rest fails to match
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    // Let the `*` swallow one more character
+                    p = star_p;
+                    n = star_n + 1;
+                    backtrack = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+impl fmt::Debug for Filters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Filters")
+            .field(&(self.names.len() + self.products.len()))
+            .finish()
+    }
+}
+
+/// Resolves a county or zone to a representative point.
+///
+/// `emwin-tg` does not bundle a zone table. Consumers can supply their own, e.g. from the NWS
+/// [county and zone shapefiles](https://www.weather.gov/gis/AWIPSShapefiles), either as a
+/// `HashMap<UgcCode, (f64, f64)>` or as any `Fn(&UgcCode) -> Option<(f64, f64)>`.
+pub trait ZoneResolver: Send + Sync {
+    /// Return the `(latitude, longitude)` centroid of a county or zone, if known.
+    fn centroid(&self, code: &UgcCode) -> Option<(f64, f64)>;
+}
+
+impl<F: Fn(&UgcCode) -> Option<(f64, f64)> + Send + Sync> ZoneResolver for F {
+    fn centroid(&self, code: &UgcCode) -> Option<(f64, f64)> {
+        self(code)
+    }
+}
+
+impl ZoneResolver for HashMap<UgcCode, (f64, f64)> {
+    fn centroid(&self, code: &UgcCode) -> Option<(f64, f64)> {
+        self.get(code).copied()
+    }
+}
+
+/// A latitude/longitude rectangle, in degrees north and degrees east.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    /// The southern edge, in degrees latitude.
+    pub south: f64,
+    /// The western edge, in degrees longitude.
+    pub west: f64,
+    /// The northern edge, in degrees latitude.
+    pub north: f64,
+    /// The eastern edge, in degrees longitude.
+    pub east: f64,
+}
+
+impl BoundingBox {
+    /// Returns true if the point lies within this box, inclusive of its edges.
+    pub fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        (self.south..=self.north).contains(&latitude)
+            && (self.west..=self.east).contains(&longitude)
+    }
+
+    /// Returns true if any county or zone listed in the product's UGC strings has a centroid
+    /// within this box.
+    ///
+    /// Products without UGC strings, or whose codes the resolver does not know, are not considered
+    /// to affect the box.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::{BoundingBox, Product};
+    /// use emwin_tg::product::ugc::UgcCode;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut zones = HashMap::new();
+    /// zones.insert("MNZ060".parse::<UgcCode>().unwrap(), (44.98, -93.27));
+    /// zones.insert("FLZ072".parse::<UgcCode>().unwrap(), (25.76, -80.19));
+    ///
+    /// let minnesota = BoundingBox { south: 43.5, west: -97.2, north: 49.4, east: -89.5 };
+    ///
+    /// let inside = Product::new("A.TXT", &b"WWUS53 KMPX 202245\r\r\nMNZ060-210300-\r\r\n"[..]);
+    /// let outside = Product::new("B.TXT", &b"WWUS52 KMFL 202245\r\r\nFLZ072-210300-\r\r\n"[..]);
+    ///
+    /// assert!(minnesota.affects(&inside, &zones));
+    /// assert!(!minnesota.affects(&outside, &zones));
+    /// ```
+    pub fn affects<R: ZoneResolver + ?Sized>(&self, product: &Product, resolver: &R) -> bool {
+        product
+            .ugc()
+            .iter()
+            .flat_map(|ugc| ugc.codes.iter())
+            .filter_map(|code| resolver.centroid(code))
+            .any(|(latitude, longitude)| self.contains(latitude, longitude))
+    }
+}
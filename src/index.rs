@@ -0,0 +1,191 @@
+use crate::fetch::transport::send;
+use crate::fetch::{DEFAULT_MAX_ARCHIVE_SIZE, OPS_URL};
+use crate::{Error, Product, StreamState};
+use crate::{HttpRequest, HttpTransport};
+use time::{Date, Month, OffsetDateTime, Time};
+
+/// An archive listed in a telecommunications gateway directory.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ArchiveListing {
+    /// The archive's filename, e.g. `txtmin02.zip`.
+    pub filename: String,
+    /// The approximate size of the archive in bytes, if listed.
+    ///
+    /// Listings abbreviate sizes (e.g. `1.2M`), so this is only accurate to the displayed
+    /// precision.
+    pub size: Option<u64>,
+    /// The archive's modification time, if listed, interpreted as UTC.
+    pub modified: Option<OffsetDateTime>,
+}
+
+impl ArchiveListing {
+    /// Parse the `.zip` archives out of an HTML directory listing.
+    ///
+    /// Both the plain (`<pre>`) and tabular Apache listing formats are understood. Entries which
+    /// aren't `.zip` files are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::ArchiveListing;
+    ///
+    /// let html = r#"<html><body><h1>Index of /SL.us008001/CU.EMWIN/DF.xt/DC.gsatR/OPS</h1>
+    /// <pre><img src="/icons/blank.gif" alt="Icon "> <a href="?C=N;O=D">Name</a>                    <a href="?C=M;O=A">Last modified</a>      <a href="?C=S;O=A">Size</a>
+    /// <hr><img src="/icons/back.gif" alt="[PARENTDIR]"> <a href="/SL.us008001/CU.EMWIN/DF.xt/DC.gsatR/">Parent Directory</a>                             -
+    /// <img src="/icons/compressed.gif" alt="[   ]"> <a href="imghrs03.zip">imghrs03.zip</a>            15-Feb-2022 11:54   16M
+    /// <img src="/icons/compressed.gif" alt="[   ]"> <a href="txtmin02.zip">txtmin02.zip</a>            15-Feb-2022 11:55  1.5K
+    /// <img src="/icons/text.gif" alt="[TXT]"> <a href="README.txt">README.txt</a>              01-Jan-2020 00:00  120
+    /// <hr></pre>
+    /// </body></html>"#;
+    ///
+    /// let listings = ArchiveListing::parse_html(html);
+    /// assert_eq!(listings.len(), 2);
+    ///
+    /// assert_eq!(listings[0].filename, "imghrs03.zip");
+    /// assert_eq!(listings[0].size, Some(16 << 20));
+    ///
+    /// assert_eq!(listings[1].filename, "txtmin02.zip");
+    /// assert_eq!(listings[1].size, Some(1536));
+    /// let modified = listings[1].modified.unwrap();
+    /// assert_eq!((modified.year(), modified.day(), modified.hour(), modified.minute()), (2022, 15, 11, 55));
+    /// ```
+    pub fn parse_html(html: &str) -> Vec<Self> {
+        let mut out = Vec::new();
+
+        for line in html.lines() {
+            // Each entry is one line, whether it's a <pre> line or a <tr>
+            let mut rest = line;
+            while let Some(start) = rest.find("<a href=\"") {
+                rest = &rest[start + 9..];
+                let href = match rest.find('"') {
+                    Some(end) => &rest[..end],
+                    None => break,
+                };
+                if !href.to_ascii_lowercase().ends_with(".zip") || href.contains('/') {
+                    continue;
+                }
+
+                // Everything after the link is the modification time and size
+                let after = match rest.find("</a>") {
+                    Some(end) => strip_tags(&rest[end + 4..]),
+                    None => String::new(),
+                };
+                let fields: Vec<&str> = after.split_whitespace().collect();
+
+                out.push(ArchiveListing {
+                    filename: href.to_string(),
+                    modified: fields
+                        .get(0..2)
+                        .and_then(|fields| parse_modified(fields[0], fields[1])),
+                    size: fields.get(2).and_then(|size| parse_size(size)),
+                });
+            }
+        }
+
+        out
+    }
+}
+
+/// List the archives currently available in the operational EMWIN gateway directory.
+///
+/// This can be used to discover archive intervals, or to check that the gateway is up and the
+/// archives are being refreshed.
+///
+/// The request is sent with `transport`, which may be a `reqwest::Client` or any other
+/// [`HttpTransport`].
+pub async fn list_archives(transport: &dyn HttpTransport) -> Result<Vec<ArchiveListing>, Error> {
+    log::debug!("GET {}", OPS_URL);
+    let request = HttpRequest::new(OPS_URL, DEFAULT_MAX_ARCHIVE_SIZE);
+    let resp = send(transport, request).await?;
+    Ok(ArchiveListing::parse_html(&String::from_utf8_lossy(
+        &resp.body,
+    )))
+}
+
+/// Retrieve a single archive and unpack every product in it.
+///
+/// This is meant for diagnosing a particular archive, e.g. one listed by [`list_archives`] or
+/// reported to contain a bad product. Nested archives are unpacked as they would be by
+/// [`Stream`](crate::Stream). Errors retrieving or opening the archive are returned as a whole;
+/// errors unpacking individual products are returned in their place.
+///
+/// The request is sent with `transport`, which may be a `reqwest::Client` or any other
+/// [`HttpTransport`]. Archives larger than the default
+/// [`max_archive_size`](crate::Mirrors::max_archive_size) are rejected with
+/// [`Error::ArchiveTooLarge`].
+///
+/// # Example
+///
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// let client = reqwest::Client::new();
+/// let url = "https://tgftp.nws.noaa.gov/SL.us008001/CU.EMWIN/DF.xt/DC.gsatR/OPS/txtmin02.zip";
+/// for result in emwin_tg::fetch_archive(&client, url).await.unwrap() {
+///     match result {
+///         Ok(product) => println!("{}: {} bytes", product.filename, product.contents.len()),
+///         Err(e) => println!("bad product: {}", e),
+///     }
+/// }
+/// # })
+/// ```
+pub async fn fetch_archive(
+    transport: &dyn HttpTransport,
+    url: &str,
+) -> Result<Vec<Result<Product, Error>>, Error> {
+    log::debug!("GET {}", url);
+    let resp = send(transport, HttpRequest::new(url, DEFAULT_MAX_ARCHIVE_SIZE)).await?;
+    StreamState::new().new_products_in(resp.body, true)
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+                out.push(' ');
+            }
+            '>' => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out.replace("&nbsp;", " ")
+}
+
+/// Parse `15-Feb-2022 11:54` or `2022-02-15 11:54`.
+fn parse_modified(date: &str, time: &str) -> Option<OffsetDateTime> {
+    let date: Vec<&str> = date.split('-').collect();
+    let (year, month, day) = match date.as_slice() {
+        [day, month, year] if month.len() == 3 => (year, parse_month(month)?, day),
+        [year, month, day] => (year, Month::try_from(month.parse::<u8>().ok()?).ok()?, day),
+        _ => return None,
+    };
+    let date = Date::from_calendar_date(year.parse().ok()?, month, day.parse().ok()?).ok()?;
+
+    let (hour, minute) = time.split_once(':')?;
+    let time = Time::from_hms(hour.parse().ok()?, minute.parse().ok()?, 0).ok()?;
+
+    Some(date.with_time(time).assume_utc())
+}
+
+fn parse_month(month: &str) -> Option<Month> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let index = MONTHS.iter().position(|m| m.eq_ignore_ascii_case(month))?;
+    Month::try_from(index as u8 + 1).ok()
+}
+
+/// Parse `123`, `1.5K`, `16M`, or `2G`.
+fn parse_size(size: &str) -> Option<u64> {
+    let (number, multiplier) = match size.as_bytes().last()? {
+        b'K' => (&size[..size.len() - 1], 1u64 << 10),
+        b'M' => (&size[..size.len() - 1], 1 << 20),
+        b'G' => (&size[..size.len() - 1], 1 << 30),
+        _ => (size, 1),
+    };
+    let number: f64 = number.parse().ok()?;
+    Some((number * multiplier as f64).round() as u64)
+}
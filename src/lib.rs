@@ -36,22 +36,54 @@
 //! }
 //! # })
 //! ```
+//!
+//! # Metrics
+//!
+//! With the `metrics` feature, streams report through the [`metrics`](https://docs.rs/metrics)
+//! facade to whichever recorder is installed:
+//!
+//! * `emwin_tg_fetches_total`, `emwin_tg_not_modified_total`, and `emwin_tg_fetch_errors_total`
+//!   count completed fetches, labeled by `archive`
+//! * `emwin_tg_fetch_duration_seconds` and `emwin_tg_archive_bytes` are histograms of fetch
+//!   latency and archive size, labeled by `archive`
+//! * `emwin_tg_dedup_filenames` and `emwin_tg_buffered_products` are gauges of how many
+//!   filenames a stream is tracking and how many products it holds waiting to be yielded
 
 #![forbid(unsafe_code)]
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
 mod error;
 mod fetch;
-mod product;
+mod filter;
+mod index;
+pub mod product;
+pub mod sink;
 mod state;
 mod stream;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod time;
 
-pub use error::Error;
+#[cfg(feature = "serde")]
+pub use error::ErrorReport;
+pub use error::{Error, ErrorKind};
+pub use fetch::{
+    FetchedArchive, HttpRequest, HttpResponse, HttpTransport, Mirrors, RateLimiter, RetryPolicy,
+    SourceConfig,
+};
+pub use filter::{BoundingBox, ZoneResolver};
+pub use index::{fetch_archive, list_archives, ArchiveListing};
 pub use product::Product;
-pub use stream::{ImageSource, ImageStream, Source, Stream, TextSource, TextStream};
+pub use state::{ArchiveStats, Gap, StateSnapshot, StreamState};
+pub use stream::{
+    ArchiveStream, BufferedStream, CombinedSource, CombinedStream, DirectorySource, EventStream,
+    FilterMapProduct, ImageSource, ImageStream, Observer, OverflowPolicy, SortedStream, Source,
+    Stream, StreamBuilder, StreamEvent, TextSource, TextStream, UrlSource,
+};
 
 pub(crate) use fetch::*;
-pub(crate) use state::*;
+pub(crate) use filter::Filters;
 
 pub(crate) fn default_client() -> reqwest::Client {
     reqwest::Client::builder()
@@ -1,35 +1,327 @@
 use crate::Error;
+use bytes::Bytes;
 use std::borrow::Cow;
 use std::io::Read;
+use std::time::{Duration, SystemTime};
+use time::{Date, Month, OffsetDateTime};
+
+/// The general kind of a [`Format`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Category {
+    /// Text bulletins
+    Text,
+    /// Satellite imagery, radar mosaics, and graphical forecasts
+    Image,
+    /// Archives bundling other products
+    Archive,
+}
+
+/// A file format carried by EMWIN.
+#[derive(Debug, Eq, PartialEq, Hash)]
+pub struct Format {
+    /// The filename extension, in uppercase as EMWIN spells it
+    pub extension: &'static str,
+    /// The MIME type
+    pub mime_type: &'static str,
+    /// The general kind of format
+    pub category: Category,
+    /// Leading bytes which identify this format, if any
+    pub magic: &'static [&'static [u8]],
+}
+
+/// What a product is, as determined by [`Product::kind`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum ProductKind {
+    /// A text bulletin
+    Text,
+    /// A Common Alerting Protocol XML document; see [`Product::is_cap`]
+    CapXml,
+    /// An image
+    Image {
+        /// The image's format
+        format: &'static Format,
+    },
+    /// Anything else, including archives and unrecognized formats
+    Unknown,
+}
+
+/// An EMWIN feed, as determined by [`Product::feed`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Feed {
+    /// The text feed, carried by [`TextSource`](crate::TextSource)
+    Text,
+    /// The image feed, carried by [`ImageSource`](crate::ImageSource)
+    Image,
+}
+
+/// The most bytes an archive member may decompress to, including any archives nested within it.
+///
+/// This bounds the memory a small but highly compressed member can claim. The largest EMWIN
+/// products, image bundles, are a few megabytes.
+pub const MAX_MEMBER_SIZE: u64 = 64 << 20;
+
+/// How many levels of nested archives are unpacked.
+///
+/// EMWIN nests one level, e.g. a bundle of image tiles. Archives nested deeper are yielded as
+/// products, so that a member which contains itself can't recurse without end.
+const MAX_NESTING: u8 = 2;
+
+/// Every format `Product` recognizes, which drives [`Product::format`] and the accessors built on
+/// it.
+///
+/// Extensions are checked for uniqueness at compile time.
+pub const FORMATS: &[Format] = &[
+    Format {
+        extension: "TXT",
+        mime_type: "text/plain",
+        category: Category::Text,
+        magic: &[],
+    },
+    Format {
+        extension: "GIF",
+        mime_type: "image/gif",
+        category: Category::Image,
+        magic: &[b"GIF87a", b"GIF89a"],
+    },
+    Format {
+        extension: "JPG",
+        mime_type: "image/jpeg",
+        category: Category::Image,
+        magic: &[b"\xff\xd8\xff"],
+    },
+    Format {
+        extension: "PNG",
+        mime_type: "image/png",
+        category: Category::Image,
+        magic: &[b"\x89PNG\r\n\x1a\n"],
+    },
+    Format {
+        extension: "ZIP",
+        mime_type: "application/zip",
+        category: Category::Archive,
+        magic: &[b"PK\x03\x04"],
+    },
+];
+
+// Fails to compile if two formats share an extension
+const _: () = [()][!extensions_are_unique(FORMATS) as usize];
+
+const fn extensions_are_unique(formats: &[Format]) -> bool {
+    let mut i = 0;
+    while i < formats.len() {
+        let mut j = i + 1;
+        while j < formats.len() {
+            if str_eq(formats[i].extension, formats[j].extension) {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
 
 /// A data product from an EMWIN archive.
-#[derive(Debug)]
+///
+/// With the `serde` feature, this implements `Serialize` and `Deserialize`. The contents are
+/// serialized as bytes, which formats without a byte type, like JSON, write as an array of numbers.
+///
+/// Cloning is cheap: the clone shares the original's contents.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Product {
     /// The filename of the data product.
     pub filename: String,
     /// The binary contents of the data product.
-    pub contents: Vec<u8>,
+    ///
+    /// Products read from uncompressed archive members share the archive's buffer, and clones
+    /// share the same buffer, so a product can be fanned out to several consumers without copying
+    /// its contents.
+    pub contents: Bytes,
+    /// When EMWIN received the data product.
+    ///
+    /// This is the receipt time embedded in the filename if there is one, and otherwise the
+    /// modification time of the archive entry. Products built with [`Product::new`] which have no
+    /// receipt time in their filename use the time they were built. Comparing this to the current
+    /// time measures the latency of the feed; see also [`issued_at`](Self::issued_at).
+    pub received_at: SystemTime,
 }
 
 impl Product {
+    /// Construct a product from a filename and its contents.
+    pub fn new<N: Into<String>, C: Into<Bytes>>(filename: N, contents: C) -> Self {
+        let filename = filename.into();
+        let received_at = received_from_filename(&filename).unwrap_or_else(SystemTime::now);
+        Product {
+            filename,
+            contents: contents.into(),
+            received_at,
+        }
+    }
+
+    /// The format of this product, if known.
+    ///
+    /// The format is determined by the filename's extension, per [`FORMATS`]. Products with other
+    /// extensions, like `.DAT`, are identified by their contents if possible.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::product::Category;
+    /// use emwin_tg::Product;
+    ///
+    /// let format = Product::new("SATPAC.DAT", &b"GIF89a"[..]).format().unwrap();
+    /// assert_eq!(format.extension, "GIF");
+    /// assert_eq!(format.category, Category::Image);
+    ///
+    /// assert_eq!(Product::new("SATPAC.DAT", &b"hello"[..]).format(), None);
+    /// ```
+    pub fn format(&self) -> Option<&'static Format> {
+        let (_, extension) = self.filename.rsplit_once('.')?;
+        FORMATS
+            .iter()
+            .find(|format| format.extension == extension)
+            .or_else(|| sniff_format(&self.contents))
+    }
+
     /// The expected MIME type of this product, if known.
+    ///
+    /// The type is determined by the [`format`](Self::format). Products with an extension but no
+    /// known format are `application/octet-stream`. Products with no extension have no known type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// assert_eq!(Product::new("AFDOKXNY.TXT", Vec::new()).mime_type(), Some("text/plain"));
+    /// assert_eq!(Product::new("RADALLUS.GIF", Vec::new()).mime_type(), Some("image/gif"));
+    /// assert_eq!(Product::new("SATPAC.DAT", &b"GIF89a"[..]).mime_type(), Some("image/gif"));
+    /// assert_eq!(
+    ///     Product::new("SATPAC.DAT", &b"hello"[..]).mime_type(),
+    ///     Some("application/octet-stream")
+    /// );
+    /// assert_eq!(Product::new("README", Vec::new()).mime_type(), None);
+    /// ```
     pub fn mime_type(&self) -> Option<&'static str> {
-        Some(match self.filename.rsplit('.').next().unwrap() {
-            ".TXT" => "text/plain",
-            ".GIF" => "image/gif",
-            ".JPG" => "image/jpeg",
-            ".PNG" => "image/png",
-            _ => return None,
-        })
+        self.filename.rsplit_once('.')?;
+        Some(
+            self.format()
+                .map_or("application/octet-stream", |format| format.mime_type),
+        )
+    }
+
+    /// The general kind of this product, per its [`format`](Self::format).
+    pub fn category(&self) -> Option<Category> {
+        self.format().map(|format| format.category)
+    }
+
+    /// What this product is, based on its [`format`](Self::format) and a look at its contents.
+    ///
+    /// CAP documents are recognized by their contents regardless of filename.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::product::ProductKind;
+    /// use emwin_tg::Product;
+    ///
+    /// assert_eq!(Product::new("AFDOKXNY.TXT", Vec::new()).kind(), ProductKind::Text);
+    /// assert!(matches!(
+    ///     Product::new("SATPAC.DAT", &b"GIF89a"[..]).kind(),
+    ///     ProductKind::Image { format } if format.extension == "GIF"
+    /// ));
+    /// assert_eq!(Product::new("SATPAC.DAT", &b"hello"[..]).kind(), ProductKind::Unknown);
+    /// ```
+    pub fn kind(&self) -> ProductKind {
+        if self.is_cap() {
+            return ProductKind::CapXml;
+        }
+        match self.format() {
+            Some(format) => match format.category {
+                Category::Text => ProductKind::Text,
+                Category::Image => ProductKind::Image { format },
+                _ => ProductKind::Unknown,
+            },
+            None => ProductKind::Unknown,
+        }
     }
 
-    pub fn string_contents(&self) -> Cow<str> {
+    /// The image format of this product, identified by its contents alone.
+    ///
+    /// Many EMWIN image products carry an extension which doesn't match their contents, e.g. GIFs
+    /// named `.JPG`. This ignores the filename entirely, returning `None` for anything which
+    /// doesn't start with a known image signature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let product = Product::new("G16CONUS.JPG", &b"GIF89a"[..]);
+    /// assert_eq!(product.format().unwrap().extension, "JPG");
+    /// assert_eq!(product.image_format().unwrap().extension, "GIF");
+    ///
+    /// assert_eq!(Product::new("RADALLUS.GIF", &b"hello"[..]).image_format(), None);
+    /// ```
+    pub fn image_format(&self) -> Option<&'static Format> {
+        sniff_format(&self.contents).filter(|format| format.category == Category::Image)
+    }
+
+    /// Which EMWIN feed carries products like this one, per its [`kind`](Self::kind).
+    ///
+    /// Each feed carries only its own kind of product, so this tells which feed a product from a
+    /// [`CombinedStream`](crate::CombinedStream) came from.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::product::Feed;
+    /// use emwin_tg::Product;
+    ///
+    /// assert_eq!(Product::new("AFDOKXNY.TXT", Vec::new()).feed(), Some(Feed::Text));
+    /// assert_eq!(Product::new("SATPAC.DAT", &b"GIF89a"[..]).feed(), Some(Feed::Image));
+    /// assert_eq!(Product::new("SATPAC.DAT", &b"hello"[..]).feed(), None);
+    /// ```
+    pub fn feed(&self) -> Option<Feed> {
+        match self.kind() {
+            ProductKind::Text | ProductKind::CapXml => Some(Feed::Text),
+            ProductKind::Image { .. } => Some(Feed::Image),
+            ProductKind::Unknown => None,
+        }
+    }
+
+    /// The canonical extension for this product's [`format`](Self::format).
+    ///
+    /// This differs from the filename's extension for products identified by their contents.
+    pub fn extension(&self) -> Option<&'static str> {
+        self.format().map(|format| format.extension)
+    }
+
+    pub fn string_contents(&self) -> Cow<'_, str> {
         String::from_utf8_lossy(&self.contents)
     }
 
     pub fn into_string_lossy(self) -> String {
         // Assume it's valid UTF-8
-        match String::from_utf8(self.contents) {
+        match String::from_utf8(Vec::from(self.contents)) {
             Ok(string) => string,
             Err(e) => {
                 // That's surprising
@@ -42,25 +334,1030 @@ impl Product {
         }
     }
 
-    pub(crate) fn new(file: zip::result::ZipResult<zip::read::ZipFile>) -> Result<Self, Error> {
+    /// The character encoding of this product's contents, as guessed by
+    /// [`TextEncoding::detect`](text::TextEncoding::detect).
+    pub fn text_encoding(&self) -> text::TextEncoding {
+        text::TextEncoding::detect(&self.contents)
+    }
+
+    /// Decode this product's contents as text, in the encoding given by
+    /// [`text_encoding`](Self::text_encoding).
+    ///
+    /// Unlike [`into_string_lossy`](Self::into_string_lossy), which replaces anything that isn't
+    /// UTF-8, this keeps the occasional ISO-8859-1 or CP437 character intact.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let product = emwin_tg::Product::new("SPSMTRQC.TXT", &b"Montr\xe9al"[..]);
+    /// assert_eq!(product.decode_text(), "Montréal");
+    /// ```
+    pub fn decode_text(&self) -> String {
+        self.text_encoding().decode(&self.contents)
+    }
+
+    /// Consume the product, returning its contents.
+    ///
+    /// This copies the contents only if they share a buffer with something else, e.g. the
+    /// archive they were read from or a clone of this product.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let product = emwin_tg::Product::new("RADALLUS.GIF", &b"GIF89a"[..]);
+    /// assert_eq!(product.into_bytes(), b"GIF89a");
+    /// ```
+    pub fn into_bytes(self) -> Vec<u8> {
+        Vec::from(self.contents)
+    }
+
+    /// The header of a text product, exactly as received: every byte before the first blank line.
+    ///
+    /// This includes control characters like the leading SOH and the `\r\r\n` line endings. If
+    /// there's no blank line, this is the entire contents. See [`header`](Self::header) for a
+    /// normalized version.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let product = Product::new(
+    ///     "AFDDVNIA.TXT",
+    ///     &b"\x01\r\r\n000 \r\r\nFXUS63 KDVN 151152\r\r\nAFDDVN\r\r\n\r\r\nArea Forecast"[..],
+    /// );
+    /// assert_eq!(
+    ///     product.raw_header_bytes(),
+    ///     b"\x01\r\r\n000 \r\r\nFXUS63 KDVN 151152\r\r\nAFDDVN\r\r\n"
+    /// );
+    /// ```
+    pub fn raw_header_bytes(&self) -> &[u8] {
+        let mut start = 0;
+        for line in self.contents.split_inclusive(|b| *b == b'\n') {
+            if line.iter().all(|b| matches!(b, b'\r' | b'\n' | b' ')) {
+                break;
+            }
+            start += line.len();
+        }
+        &self.contents[..start]
+    }
+
+    /// The header of a text product, normalized for display.
+    ///
+    /// This is [`raw_header_bytes`](Self::raw_header_bytes) with control characters removed,
+    /// trailing whitespace trimmed from each line, empty lines dropped, and lines separated by
+    /// `\n`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let product = Product::new(
+    ///     "AFDDVNIA.TXT",
+    ///     &b"\x01\r\r\n000 \r\r\nFXUS63 KDVN 151152\r\r\nAFDDVN\r\r\n\r\r\nArea Forecast"[..],
+    /// );
+    /// assert_eq!(product.header(), "000\nFXUS63 KDVN 151152\nAFDDVN");
+    /// ```
+    pub fn header(&self) -> String {
+        String::from_utf8_lossy(self.raw_header_bytes())
+            .lines()
+            .map(|line| {
+                line.chars()
+                    .filter(|c| !c.is_control())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Whether the contents look like binary data rather than text, regardless of the extension.
+    ///
+    /// This examines the first 8 KiB for NUL bytes and for control characters other than those
+    /// found in text products: whitespace, and the SOH and ETX bytes framing WMO bulletins. Bytes
+    /// above 0x7F are allowed, since text products sometimes contain Latin-1 characters.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let text = Product::new("AFDOKXNY.TXT", &b"\x01\r\r\n000 \r\r\nTEMP 72\xb0F\r\r\n\x03"[..]);
+    /// assert!(!text.is_binary());
+    ///
+    /// let gif = Product::new("RADALLUS.GIF", &b"GIF89a\x01\x00\x01\x00\x80\x00\x00"[..]);
+    /// assert!(gif.is_binary());
+    /// ```
+    pub fn is_binary(&self) -> bool {
+        let sample = &self.contents[..self.contents.len().min(8 << 10)];
+        if sample.contains(&0) {
+            return true;
+        }
+
+        let unexpected = sample
+            .iter()
+            .filter(|&&b| {
+                (b < 0x20 || b == 0x7f) && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x01 | 0x03)
+            })
+            .count();
+        unexpected * 32 > sample.len()
+    }
+
+    /// Borrow the contents as a string, if they're valid UTF-8.
+    ///
+    /// On failure, returns the offset and value of the first byte which isn't part of a valid
+    /// UTF-8 sequence.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let product = Product::new("A.TXT", &b"ZCZC"[..]);
+    /// assert_eq!(product.as_str_checked(), Ok("ZCZC"));
+    ///
+    /// let product = Product::new("B.TXT", &b"TEMP 72\xb0F"[..]);
+    /// assert_eq!(product.as_str_checked(), Err((7, 0xb0)));
+    /// ```
+    pub fn as_str_checked(&self) -> Result<&str, (usize, u8)> {
+        std::str::from_utf8(&self.contents).map_err(|e| {
+            let offset = e.valid_up_to();
+            (offset, self.contents[offset])
+        })
+    }
+
+    /// Returns false if the product is missing its `NNNN` end-of-product marker.
+    ///
+    /// Products framed with a `ZCZC` start-of-product marker are expected to end with `NNNN`,
+    /// possibly followed by whitespace or an ETX control character. If such a product lacks the
+    /// marker, it was likely truncated. Products without `ZCZC` framing don't use the marker and
+    /// are always considered complete.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let complete = Product::new("A.TXT", &b"ZCZC MKEAFDMKX\r\r\nDISCUSSION...\r\r\nNNNN\r\r\n\x03"[..]);
+    /// assert!(complete.is_complete());
+    ///
+    /// let truncated = Product::new("B.TXT", &b"ZCZC MKEAFDMKX\r\r\nDISCUSS"[..]);
+    /// assert!(!truncated.is_complete());
+    ///
+    /// let unframed = Product::new("C.TXT", &b"FXUS63 KMKX 151152\r\r\nAFDMKX\r\r\n"[..]);
+    /// assert!(unframed.is_complete());
+    /// ```
+    pub fn is_complete(&self) -> bool {
+        let is_padding = |b: &u8| b.is_ascii_whitespace() || *b == 0x01 || *b == 0x03;
+        let start = self.contents.iter().position(|b| !is_padding(b));
+        let end = self.contents.iter().rposition(|b| !is_padding(b));
+        let trimmed = match (start, end) {
+            (Some(start), Some(end)) => &self.contents[start..=end],
+            _ => return true,
+        };
+
+        !trimmed.starts_with(b"ZCZC") || trimmed.ends_with(b"NNNN")
+    }
+
+    /// Returns true if the WMO heading marks this product as a retransmission: a delayed or
+    /// repeated bulletin, with a `BBB` indicator of `RRx`.
+    ///
+    /// Corrections (`CCx`) and amendments (`AAx`) carry new information, so they aren't
+    /// retransmissions. Products without a heading in their filename aren't either.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let rr = "A_WFUS53KLOT202245RRA_C_KWIN_20220220224512_061002-1-TORLOTIL.TXT";
+    /// assert!(Product::new(rr, Vec::new()).is_retransmission());
+    ///
+    /// let cc = "A_WFUS53KLOT202245CCA_C_KWIN_20220220224512_061002-1-TORLOTIL.TXT";
+    /// assert!(!Product::new(cc, Vec::new()).is_retransmission());
+    /// ```
+    pub fn is_retransmission(&self) -> bool {
+        self.wmo_heading()
+            .is_some_and(|heading| heading.bbb_kind() == Some(wmo::BbbKind::Delayed))
+    }
+
+    /// Returns true if the contents look like a Common Alerting Protocol (CAP) XML document.
+    ///
+    /// This is a cheap check for the CAP namespace and `<alert>` root element, not a validation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let cap = Product::new(
+    ///     "A.XML",
+    ///     &br#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <alert xmlns="urn:oasis:names:tc:emergency:cap:1.2"><identifier>x</identifier></alert>"#[..],
+    /// );
+    /// assert!(cap.is_cap());
+    ///
+    /// let text = Product::new("B.TXT", &b"WWUS53 KMPX 202245\r\r\nSVSMPX\r\r\n"[..]);
+    /// assert!(!text.is_cap());
+    /// ```
+    pub fn is_cap(&self) -> bool {
+        // CAP documents start with an XML declaration or the root element
+        let trimmed = match self.contents.iter().position(|b| !b.is_ascii_whitespace()) {
+            Some(start) => &self.contents[start..],
+            None => return false,
+        };
+        if !trimmed.starts_with(b"<") {
+            return false;
+        }
+
+        // The namespace belongs on the root element, so it appears near the start
+        let head = &trimmed[..trimmed.len().min(1024)];
+        contains(head, b"<alert") && contains(head, b"urn:oasis:names:tc:emergency:cap:")
+    }
+
+    /// The `LAT...LON` polygon describing the area this product covers, as `(latitude, longitude)`
+    /// pairs.
+    ///
+    /// See [`text::parse_polygon`].
+    pub fn polygon(&self) -> Option<Vec<(f64, f64)>> {
+        text::parse_polygon(&self.string_contents())
+    }
+
+    /// The UGC strings in this product's text, listing the counties and zones it affects.
+    ///
+    /// Segmented products have one per segment. See [`ugc::parse`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let product = Product::new(
+    ///     "A_WWUS53KLOT202245_C_KWIN_20220220225001_061010-1-SVSLOTIL.TXT",
+    ///     "WWUS53 KLOT 202245\r\r\nSVSLOT\r\r\n\r\r\nILC031-043-\r\r\n197-210300-\r\r\n",
+    /// );
+    /// let ugcs = product.ugc();
+    /// let codes: Vec<String> = ugcs[0].codes.iter().map(|code| code.to_string()).collect();
+    /// assert_eq!(codes, ["ILC031", "ILC043", "ILC197"]);
+    ///
+    /// let expires = product.expires_at().unwrap();
+    /// assert_eq!((expires.day(), expires.hour(), expires.minute()), (21, 3, 0));
+    /// ```
+    pub fn ugc(&self) -> Vec<ugc::Ugc> {
+        ugc::parse(&self.string_contents())
+    }
+
+    /// When this product expires, per the latest expiration time in its UGC strings.
+    ///
+    /// Expiration times give only the day of month, which is resolved relative to
+    /// [`issued_at`](Self::issued_at), or to [`received_at`](Self::received_at) if the issuance
+    /// time is unknown.
+    pub fn expires_at(&self) -> Option<OffsetDateTime> {
+        let reference = self
+            .issued_at()
+            .unwrap_or_else(|| OffsetDateTime::from(self.received_at));
+        self.ugc()
+            .iter()
+            .filter_map(|ugc| ugc.expires_at(reference))
+            .max()
+    }
+
+    /// The P-VTEC strings in this product's text, with any H-VTEC strings following them.
+    ///
+    /// See [`vtec::parse`].
+    pub fn vtec(&self) -> Vec<vtec::PVtec> {
+        vtec::parse(&self.string_contents())
+    }
+
+    /// The metadata embedded in this product's filename, if it's a well-formed EMWIN filename.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let product = Product::new(
+    ///     "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+    ///     Vec::new(),
+    /// );
+    /// let id = product.id().unwrap();
+    /// assert_eq!(id.heading().cccc(), Some("KDVN"));
+    /// assert_eq!(id.awips_id(), "AFDDVN");
+    /// assert_eq!(id.issued_at(), product.issued_at());
+    ///
+    /// assert_eq!(Product::new("RADALLUS.GIF", Vec::new()).id(), None);
+    /// ```
+    pub fn id(&self) -> Option<id::ProductId> {
+        self.filename.parse().ok()
+    }
+
+    /// The WMO abbreviated heading embedded in this product's filename, if any.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let product = Product::new(
+    ///     "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+    ///     Vec::new(),
+    /// );
+    /// let heading = product.wmo_heading().unwrap();
+    /// assert_eq!(heading.to_string(), "FXUS63 KDVN 151152");
+    ///
+    /// assert_eq!(Product::new("RADALLUS.GIF", Vec::new()).wmo_heading(), None);
+    /// ```
+    pub fn wmo_heading(&self) -> Option<wmo::WmoHeading> {
+        wmo::WmoHeading::from_filename(&self.filename)
+    }
+
+    /// The WMO abbreviated heading at the start of this product's contents, if any.
+    ///
+    /// This is the heading as issued, which can differ from the one in the filename if the
+    /// filename was damaged. Binary products have no heading in their contents. See
+    /// [`WmoHeading::from_contents`](wmo::WmoHeading::from_contents).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let product = Product::new(
+    ///     "A_WFUS53KLOT202245_C_KWIN_20220220225001_061010-1-TORLOTIL.TXT",
+    ///     "\x01\r\r\n123 \r\r\nWFUS53 KLOT 202245 CCA\r\r\nTORLOT\r\r\n",
+    /// );
+    /// let heading = product.wmo_heading_in_contents().unwrap();
+    /// assert_eq!(heading.to_string(), "WFUS53 KLOT 202245 CCA");
+    /// assert_eq!(product.wmo_heading().unwrap().bbb(), None);
+    /// ```
+    pub fn wmo_heading_in_contents(&self) -> Option<wmo::WmoHeading> {
+        wmo::WmoHeading::from_contents(&self.contents)
+    }
+
+    /// The AWIPS identifier on the line after the WMO heading in this product's contents, if any.
+    ///
+    /// This is more reliable than the product name in the filename, which is padded or
+    /// truncated. See [`AwipsId::from_contents`](awips::AwipsId::from_contents).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let product = Product::new(
+    ///     "A_WFUS53KILX202245_C_KWIN_20220220225001_061010-1-TORILXIL.TXT",
+    ///     "\x01\r\r\n123 \r\r\nWFUS53 KILX 202245\r\r\nTORILX\r\r\n",
+    /// );
+    /// let id = product.awips_id().unwrap();
+    /// assert_eq!(id.to_string(), "TORILX");
+    /// assert_eq!((id.category(), id.office()), ("TOR", "ILX"));
+    /// ```
+    pub fn awips_id(&self) -> Option<awips::AwipsId> {
+        awips::AwipsId::from_contents(&self.contents)
+    }
+
+    /// The `T1` data type designator of the WMO heading, e.g. `F` for forecasts, `W` for warnings,
+    /// or `S` for surface observations.
+    ///
+    /// See [`wmo_heading`](Self::wmo_heading).
+    pub fn wmo_t1(&self) -> Option<char> {
+        self.wmo_heading().map(|heading| heading.t1())
+    }
+
+    /// The `T2` data type designator of the WMO heading, which refines `T1`.
+    ///
+    /// See [`wmo_heading`](Self::wmo_heading).
+    pub fn wmo_t2(&self) -> Option<char> {
+        self.wmo_heading().map(|heading| heading.t2())
+    }
+
+    /// The ICAO identifier of the center which issued this product, e.g. `KDVN`.
+    ///
+    /// This comes from the WMO heading at the start of the product's contents, falling back to
+    /// [`guess_issuing_office`](Self::guess_issuing_office) for products without one, like images.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let product = Product::new(
+    ///     "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+    ///     "\x01\r\r\n000 \r\r\nFXUS63 KDVN 151152\r\r\nAFDDVN\r\r\n",
+    /// );
+    /// assert_eq!(product.originator().as_deref(), Some("KDVN"));
+    /// ```
+    pub fn originator(&self) -> Option<String> {
+        match self
+            .wmo_heading_in_contents()
+            .as_ref()
+            .and_then(wmo::WmoHeading::cccc)
+        {
+            Some(cccc) => Some(cccc.to_string()),
+            None => self.guess_issuing_office(),
+        }
+    }
+
+    /// The ICAO identifier of the issuing center, as embedded in the filename.
+    ///
+    /// EMWIN filenames embed the WMO heading even for binary products, so this works for images
+    /// whose contents carry no heading of their own. If the `TTAAii` field of the embedded heading
+    /// is malformed, the `CCCC` field is still used if it looks valid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let product = Product::new(
+    ///     "A_TIGQ53KNES151200_C_KWIN_20220215120512_060744-4-G16CIRUS.GIF",
+    ///     Vec::new(),
+    /// );
+    /// assert_eq!(product.guess_issuing_office().as_deref(), Some("KNES"));
+    ///
+    /// assert_eq!(Product::new("RADALLUS.GIF", Vec::new()).guess_issuing_office(), None);
+    /// ```
+    pub fn guess_issuing_office(&self) -> Option<String> {
+        if let Some(cccc) = self.wmo_heading().as_ref().and_then(wmo::WmoHeading::cccc) {
+            return Some(cccc.to_string());
+        }
+
+        let mut parts = self.filename.split('_');
+        if parts.next()? != "A" {
+            return None;
+        }
+        let cccc = parts.next()?.get(6..10)?;
+        if cccc
+            .bytes()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+        {
+            Some(cccc.to_string())
+        } else {
+            None
+        }
+    }
+
+    /// The time this product was issued, if it can be determined from the filename.
+    ///
+    /// EMWIN filenames like `A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT`
+    /// embed the WMO heading, whose `DDHHMM` field is the issuance time, and the full date and time
+    /// at which EMWIN received the product. The issuance day and time are resolved against the
+    /// receipt date.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    /// use time::{Date, Month};
+    ///
+    /// let product = Product::new(
+    ///     "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+    ///     Vec::new(),
+    /// );
+    /// let expected = Date::from_calendar_date(2022, Month::February, 15)
+    ///     .unwrap()
+    ///     .with_hms(11, 52, 0)
+    ///     .unwrap()
+    ///     .assume_utc();
+    /// assert_eq!(product.issued_at(), Some(expected));
+    ///
+    /// assert_eq!(Product::new("RADALLUS.GIF", Vec::new()).issued_at(), None);
+    /// ```
+    pub fn issued_at(&self) -> Option<OffsetDateTime> {
+        let (heading, received) = self.filename_times()?;
+        let received = parse_received(received)?;
+        crate::time::resolve_day_time(
+            digits(heading, 10..12)? as u8,
+            digits(heading, 12..14)? as u8,
+            digits(heading, 14..16)? as u8,
+            received,
+        )
+    }
+
+    /// The best available time for this product from its filename alone.
+    ///
+    /// This is the issuance time per [`issued_at`](Self::issued_at) if the heading's `DDHHMM`
+    /// field is intact, and otherwise the time at which EMWIN received the product.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    /// use time::{Date, Month};
+    ///
+    /// let date = Date::from_calendar_date(2022, Month::February, 15).unwrap();
+    ///
+    /// let image = Product::new(
+    ///     "A_TIGQ53KNES151200_C_KWIN_20220215120512_060744-4-G16CIRUS.GIF",
+    ///     Vec::new(),
+    /// );
+    /// let issued = date.with_hms(12, 0, 0).unwrap().assume_utc();
+    /// assert_eq!(image.time_from_filename(), Some(issued));
+    ///
+    /// // A damaged issuance time falls back to the receipt time
+    /// let damaged = Product::new(
+    ///     "A_TIGQ53KNES15#200_C_KWIN_20220215120512_060744-4-G16CIRUS.GIF",
+    ///     Vec::new(),
+    /// );
+    /// let received = date.with_hms(12, 5, 12).unwrap().assume_utc();
+    /// assert_eq!(damaged.time_from_filename(), Some(received));
+    ///
+    /// assert_eq!(Product::new("RADALLUS.GIF", Vec::new()).time_from_filename(), None);
+    /// ```
+    pub fn time_from_filename(&self) -> Option<OffsetDateTime> {
+        self.issued_at()
+            .or_else(|| parse_received(self.filename_times()?.1))
+    }
+
+    /// The time this product was issued, preferring the WMO heading in its contents.
+    ///
+    /// The `YYGGgg` field of the heading within the contents gives the day and time of issuance,
+    /// which is resolved to the nearest matching date around `reference`, typically the current
+    /// time. Products without a usable heading, like images, fall back to
+    /// [`time_from_filename`](Self::time_from_filename).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    /// use time::{Date, Month};
+    ///
+    /// let date = Date::from_calendar_date(2022, Month::February, 15).unwrap();
+    /// let now = date.with_hms(12, 30, 0).unwrap().assume_utc();
+    ///
+    /// let text = Product::new(
+    ///     "AFDDVNIA.TXT",
+    ///     &b"\x01\r\r\n000 \r\r\nFXUS63 KDVN 151152\r\r\nAFDDVN\r\r\n"[..],
+    /// );
+    /// let issued = date.with_hms(11, 52, 0).unwrap().assume_utc();
+    /// assert_eq!(text.timestamp(now), Some(issued));
+    ///
+    /// let image = Product::new(
+    ///     "A_TIGQ53KNES151200_C_KWIN_20220215120512_060744-4-G16CIRUS.GIF",
+    ///     &b"GIF89a"[..],
+    /// );
+    /// let issued = date.with_hms(12, 0, 0).unwrap().assume_utc();
+    /// assert_eq!(image.timestamp(now), Some(issued));
+    /// ```
+    pub fn timestamp(&self, reference: OffsetDateTime) -> Option<OffsetDateTime> {
+        self.wmo_heading_in_contents()
+            .and_then(|heading| heading.issued_at(reference))
+            .or_else(|| self.time_from_filename())
+    }
+
+    /// The heading and receipt time fields of an EMWIN filename.
+    fn filename_times(&self) -> Option<(&str, &str)> {
+        let mut parts = self.filename.split('_');
+        let (prefix, heading, _, _, received) = (
+            parts.next()?,
+            parts.next()?,
+            parts.next()?,
+            parts.next()?,
+            parts.next()?,
+        );
+        if prefix != "A" || heading.len() < 16 || received.len() != 14 {
+            return None;
+        }
+        Some((heading, received))
+    }
+
+    /// How long before `now` this product was issued, per [`issued_at`](Self::issued_at).
+    ///
+    /// Returns `None` if the issuance time can't be determined. Products issued after `now` have
+    /// an age of zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    /// use std::time::Duration;
+    /// use time::{Date, Month};
+    ///
+    /// let product = Product::new(
+    ///     "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+    ///     Vec::new(),
+    /// );
+    /// let now = Date::from_calendar_date(2022, Month::February, 15)
+    ///     .unwrap()
+    ///     .with_hms(12, 7, 30)
+    ///     .unwrap()
+    ///     .assume_utc();
+    /// assert_eq!(product.age(now), Some(Duration::from_secs(15 * 60 + 30)));
+    ///
+    /// assert_eq!(Product::new("RADALLUS.GIF", Vec::new()).age(now), None);
+    /// ```
+    pub fn age(&self, now: OffsetDateTime) -> Option<Duration> {
+        let age = now - self.issued_at()?;
+        Some(Duration::try_from(age).unwrap_or(Duration::ZERO))
+    }
+
+    /// The filename in canonical EMWIN form, for comparing products across sources.
+    ///
+    /// Archive members are already canonical, but filenames obtained elsewhere may include
+    /// directory components, either path separator, surrounding whitespace, or lowercase letters.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::Product;
+    ///
+    /// let canonical = "A_FXUS63KDVN151152_C_KWIN_20220215115257_060736-2-AFDDVNIA.TXT";
+    /// for filename in [
+    ///     canonical,
+    ///     "a_fxus63kdvn151152_c_kwin_20220215115257_060736-2-afddvnia.txt",
+    ///     "mirror/2022/02/15/A_FXUS63KDVN151152_C_KWIN_20220215115257_060736-2-AFDDVNIA.txt",
+    ///     "C:\\emwin\\a_FXUS63KDVN151152_C_KWIN_20220215115257_060736-2-AFDDVNIA.TXT ",
+    /// ] {
+    ///     assert_eq!(Product::new(filename, Vec::new()).normalize_filename(), canonical);
+    /// }
+    /// ```
+    pub fn normalize_filename(&self) -> String {
+        normalize_filename(&self.filename)
+    }
+
+    /// Decode every frame of a GIF, including animated loops.
+    ///
+    /// Each frame carries its delay and its offset within the full image. Products which aren't
+    /// GIFs return [`Error::Image`].
+    ///
+    /// Requires the `image` feature.
+    #[cfg(feature = "image")]
+    pub fn decode_gif_frames(&self) -> Result<Vec<image::Frame>, Error> {
+        use image::error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+        use image::AnimationDecoder;
+
+        if !self.contents.starts_with(b"GIF8") {
+            return Err(
+                image::ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+                    ImageFormatHint::Name(self.filename.clone()),
+                    UnsupportedErrorKind::Format(ImageFormatHint::Exact(image::ImageFormat::Gif)),
+                ))
+                .into(),
+            );
+        }
+
+        let decoder = image::codecs::gif::GifDecoder::new(std::io::Cursor::new(&self.contents))?;
+        Ok(decoder.into_frames().collect_frames()?)
+    }
+
+    /// Decode an image product, using the [`image_format`](Self::image_format) identified by its
+    /// contents rather than its extension.
+    ///
+    /// Products which aren't recognized as images return [`Error::Image`].
+    ///
+    /// Requires the `image` feature.
+    #[cfg(feature = "image")]
+    pub fn decode_image(&self) -> Result<image::DynamicImage, Error> {
+        use image::error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+
+        let format = self
+            .image_format()
+            .and_then(|format| image::ImageFormat::from_extension(format.extension));
+        let format = match format {
+            Some(format) => format,
+            None => {
+                return Err(
+                    image::ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+                        ImageFormatHint::Name(self.filename.clone()),
+                        UnsupportedErrorKind::Format(ImageFormatHint::Unknown),
+                    ))
+                    .into(),
+                )
+            }
+        };
+        Ok(image::load_from_memory_with_format(&self.contents, format)?)
+    }
+
+    /// Parse this product as a Common Alerting Protocol alert.
+    ///
+    /// See [`cap::parse`]. Products which aren't CAP alerts return [`Error::Cap`].
+    ///
+    /// Requires the `cap` feature.
+    #[cfg(feature = "cap")]
+    pub fn parse_cap(&self) -> Result<cap::Alert, Error> {
+        Ok(cap::parse(&self.contents)?)
+    }
+
+    /// The METAR and SPECI reports in this product's text.
+    ///
+    /// See [`aviation::parse_metars`].
+    ///
+    /// Requires the `aviation` feature.
+    #[cfg(feature = "aviation")]
+    pub fn metars(&self) -> Vec<aviation::Metar> {
+        aviation::parse_metars(&self.string_contents())
+    }
+
+    /// The terminal aerodrome forecasts in this product's text.
+    ///
+    /// See [`aviation::parse_tafs`].
+    ///
+    /// Requires the `aviation` feature.
+    #[cfg(feature = "aviation")]
+    pub fn tafs(&self) -> Vec<aviation::Taf> {
+        aviation::parse_tafs(&self.string_contents())
+    }
+
+    /// The SIGMETs in this product's text.
+    ///
+    /// See [`aviation::parse_sigmets`].
+    ///
+    /// Requires the `aviation` feature.
+    #[cfg(feature = "aviation")]
+    pub fn sigmets(&self) -> Vec<aviation::Sigmet> {
+        aviation::parse_sigmets(&self.string_contents())
+    }
+
+    /// Split the product into its filename and contents.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let product = emwin_tg::Product::new("AFDOKXNY.TXT", &b"hello"[..]);
+    /// let (filename, contents) = product.into_parts();
+    /// assert_eq!(filename, "AFDOKXNY.TXT");
+    /// assert_eq!(contents, &b"hello"[..]);
+    /// ```
+    pub fn into_parts(self) -> (String, Bytes) {
+        (self.filename, self.contents)
+    }
+
+    /// The AWIPS identifier at the end of an EMWIN filename.
+    pub(crate) fn filename_pil(&self) -> Option<&str> {
+        if !self.filename.starts_with("A_") {
+            return None;
+        }
+        let stem = self.filename.rsplit('_').next()?;
+        let stem = stem.rsplit_once('.').map_or(stem, |(stem, _)| stem);
+        let pil = stem.rsplit('-').next()?;
+        if pil.is_empty() || pil == stem {
+            None
+        } else {
+            Some(pil)
+        }
+    }
+
+    /// A key identifying the bulletin regardless of which archive or satellite delivered it: the
+    /// WMO heading and, if present, the AWIPS identifier.
+    pub(crate) fn bulletin_key(&self) -> Option<String> {
+        let heading = self.wmo_heading()?;
+        Some(match self.filename_pil() {
+            Some(pil) => format!("{} {}", heading, pil),
+            None => heading.to_string(),
+        })
+    }
+
+    /// A key identifying the original bulletin which this product is, corrects, or retransmits:
+    /// the [`bulletin_key`](Self::bulletin_key) without the `BBB` indicator.
+    pub(crate) fn base_bulletin_key(&self) -> Option<String> {
+        let heading = self.wmo_heading()?.without_bbb();
+        Some(match self.filename_pil() {
+            Some(pil) => format!("{} {}", heading, pil),
+            None => heading.to_string(),
+        })
+    }
+
+    /// A hash of the contents following the WMO heading line, which is the same for a bulletin
+    /// and its retransmissions.
+    pub(crate) fn body_hash(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+
+        let body = wmo::WmoHeading::split_contents(&self.contents)
+            .map_or(&self.contents[..], |(_, body)| body);
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        body.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// A hash of the entire contents.
+    pub(crate) fn content_hash(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.contents.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Read a product from an archive member.
+    ///
+    /// If `unpack_nested` is set, a member which is itself an archive is replaced by the products
+    /// it contains, e.g. a bundle of image tiles, in archive order.
+    ///
+    /// `buffer` is the archive the member was read from, from which uncompressed members are
+    /// sliced rather than copied.
+    ///
+    /// A member which decompresses to more than [`MAX_MEMBER_SIZE`] bytes, including any archives
+    /// nested within it, is rejected with [`Error::MemberTooLarge`]. Archives nested more than
+    /// [`MAX_NESTING`] deep are yielded as-is.
+    pub(crate) fn from_zip(
+        file: zip::result::ZipResult<zip::read::ZipFile>,
+        buffer: &Bytes,
+        unpack_nested: bool,
+    ) -> Result<Vec<Self>, Error> {
+        let mut budget = MAX_MEMBER_SIZE;
+        Self::from_zip_within(file, buffer, unpack_nested, 0, &mut budget)
+    }
+
+    /// Read a product from an archive member `depth` archives deep, decompressing at most
+    /// `budget` more bytes.
+    fn from_zip_within(
+        file: zip::result::ZipResult<zip::read::ZipFile>,
+        buffer: &Bytes,
+        unpack_nested: bool,
+        depth: u8,
+        budget: &mut u64,
+    ) -> Result<Vec<Self>, Error> {
         let mut file = file?;
+        let filename = normalize_filename(file.name());
 
-        let mut contents = vec![0u8; file.size().clamp(0, 8 << 20) as usize];
-        file.read_exact(&mut contents)
-            .map_err(zip::result::ZipError::Io)?;
+        let contents = match stored_contents(&file, buffer) {
+            Some(contents) => contents,
+            None => {
+                // Entries written in streaming mode may not record their size, so read until the
+                // end instead of trusting it, but no further than the budget allows
+                let mut contents =
+                    Vec::with_capacity(file.size().clamp(4 << 10, 8 << 20).min(*budget) as usize);
+                (&mut file)
+                    .take(*budget + 1)
+                    .read_to_end(&mut contents)
+                    .map_err(zip::result::ZipError::Io)?;
+                if contents.len() as u64 > *budget {
+                    return Err(Error::MemberTooLarge {
+                        name: filename,
+                        limit: MAX_MEMBER_SIZE,
+                    });
+                }
+                *budget -= contents.len() as u64;
+                Bytes::from(contents)
+            }
+        };
 
-        let filename = file.name().to_uppercase();
+        let modified = file.last_modified();
 
-        if filename.ends_with(".ZIP") {
-            // Recurse
-            let mut archive = zip::ZipArchive::new(std::io::Cursor::new(&contents))?;
-            if archive.len() != 1 {
+        if unpack_nested && depth < MAX_NESTING && filename.ends_with(".ZIP") {
+            // Recurse, skipping directory entries
+            let mut archive = zip::ZipArchive::new(std::io::Cursor::new(contents.clone()))?;
+            let mut products = Vec::new();
+            for index in 0..archive.len() {
+                if archive.by_index(index)?.is_dir() {
+                    continue;
+                }
+                products.extend(Product::from_zip_within(
+                    archive.by_index(index),
+                    &contents,
+                    unpack_nested,
+                    depth + 1,
+                    budget,
+                )?);
+            }
+            if products.is_empty() {
                 Err(Error::ArchiveMember(filename))
             } else {
-                Product::new(archive.by_index(0))
+                Ok(products)
             }
         } else {
-            Ok(Product { filename, contents })
+            // Prefer the filename's timestamp, which has no time zone ambiguity
+            let received_at = received_from_filename(&filename)
+                .or_else(|| zip_time(modified))
+                .unwrap_or_else(SystemTime::now);
+            Ok(vec![Product {
+                filename,
+                contents,
+                received_at,
+            }])
         }
     }
 }
+
+#[cfg(feature = "image")]
+impl TryFrom<Product> for image::DynamicImage {
+    type Error = Error;
+
+    /// Decode an image product, detecting its format from its contents.
+    ///
+    /// See [`Product::decode_image`]. Requires the `image` feature.
+    fn try_from(product: Product) -> Result<Self, Self::Error> {
+        product.decode_image()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<Product> for serde_json::Value {
+    type Error = Error;
+
+    /// Parse a JSON product.
+    ///
+    /// Requires the `serde` feature.
+    fn try_from(product: Product) -> Result<Self, Self::Error> {
+        Ok(serde_json::from_slice(&product.contents)?)
+    }
+}
+
+pub(crate) fn normalize_filename(filename: &str) -> String {
+    let basename = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+    basename.trim().to_uppercase()
+}
+
+/// The contents of an uncompressed archive member, sliced from the archive's buffer without
+/// copying, or `None` if the member is compressed or doesn't check out.
+fn stored_contents(file: &zip::read::ZipFile, buffer: &Bytes) -> Option<Bytes> {
+    if file.compression() != zip::CompressionMethod::Stored {
+        return None;
+    }
+    let start = usize::try_from(file.data_start()).ok()?;
+    let end = start.checked_add(usize::try_from(file.size()).ok()?)?;
+    let contents = buffer.get(start..end)?;
+
+    // Reading the member would have checked its CRC, so check it here too; on a mismatch the
+    // caller reads it anyway, which reports the error
+    if crc32fast::hash(contents) == file.crc32() {
+        Some(buffer.slice(start..end))
+    } else {
+        None
+    }
+}
+
+/// Identify binary formats by their leading magic bytes.
+fn sniff_format(contents: &[u8]) -> Option<&'static Format> {
+    FORMATS
+        .iter()
+        .find(|format| format.magic.iter().any(|magic| contents.starts_with(magic)))
+}
+
+/// The receipt time embedded in an EMWIN filename, if any.
+pub(crate) fn received_from_filename(filename: &str) -> Option<SystemTime> {
+    let received = filename
+        .split('_')
+        .nth(4)
+        .filter(|_| filename.starts_with("A_"))?;
+    parse_received(received).map(SystemTime::from)
+}
+
+/// Convert an archive entry's modification time, which the gateway records in UTC.
+fn zip_time(modified: zip::DateTime) -> Option<SystemTime> {
+    // Writers which don't record a time use the earliest one the format can represent
+    let fields = (modified.year(), modified.month(), modified.day());
+    if fields == (1980, 1, 1) && modified.timepart() == 0 {
+        return None;
+    }
+
+    let time = Date::from_calendar_date(
+        i32::from(modified.year()),
+        Month::try_from(modified.month()).ok()?,
+        modified.day(),
+    )
+    .ok()?
+    .with_hms(modified.hour(), modified.minute(), modified.second())
+    .ok()?
+    .assume_utc();
+    Some(time.into())
+}
+
+/// Parse a run of ASCII digits within `s`.
+fn digits(s: &str, range: std::ops::Range<usize>) -> Option<u32> {
+    let field = s.get(range)?;
+    if field.bytes().all(|b| b.is_ascii_digit()) {
+        field.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Parse the `YYYYMMDDhhmmss` receipt time from an EMWIN filename.
+fn parse_received(received: &str) -> Option<OffsetDateTime> {
+    Some(
+        Date::from_calendar_date(
+            digits(received, 0..4)? as i32,
+            Month::try_from(digits(received, 4..6)? as u8).ok()?,
+            digits(received, 6..8)? as u8,
+        )
+        .ok()?
+        .with_hms(
+            digits(received, 8..10)? as u8,
+            digits(received, 10..12)? as u8,
+            digits(received, 12..14)? as u8,
+        )
+        .ok()?
+        .assume_utc(),
+    )
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+#[cfg(feature = "aviation")]
+pub mod aviation;
+pub mod awips;
+#[cfg(feature = "cap")]
+pub mod cap;
+pub mod id;
+pub mod text;
+pub mod ugc;
+pub mod vtec;
+pub mod wmo;
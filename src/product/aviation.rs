@@ -0,0 +1,1044 @@
+//! Aviation weather report decoding: METARs, TAFs, and SIGMETs.
+//!
+//! Aviation products arrive as bulletins collecting many reports, each ending with `=`:
+//!
+//! * METAR and SPECI surface observations, like
+//!   `METAR KORD 201551Z 27012G20KT 10SM -SN BKN025 M02/M11 A3012 RMK AO2`
+//! * Terminal aerodrome forecasts (TAFs), like
+//!   `TAF KORD 201720Z 2018/2124 27012KT P6SM BKN025 FM210000 29008KT P6SM SKC`
+//! * SIGMETs, warning of hazards to aircraft, in the U.S. convective and non-convective formats
+//!   and the ICAO international format
+//!
+//! Groups are decoded into fields where they follow the common patterns. Groups which aren't
+//! recognized, like runway visual range or wind shear, are skipped, and every report keeps its
+//! original text. See [`Product::metars`](crate::Product::metars),
+//! [`Product::tafs`](crate::Product::tafs), and [`Product::sigmets`](crate::Product::sigmets).
+
+use std::str::FromStr;
+use time::{Duration, OffsetDateTime};
+
+/// A day of month, hour, and minute (UTC), as aviation reports write times.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct DayTime {
+    /// The day of month
+    pub day: u8,
+    /// The hour, where `24` means midnight at the end of `day`
+    pub hour: u8,
+    /// The minute
+    pub minute: u8,
+}
+
+impl DayTime {
+    /// Resolve this time to the matching instant nearest `reference`, usually when the product
+    /// was issued.
+    pub fn resolve(&self, reference: OffsetDateTime) -> Option<OffsetDateTime> {
+        if self.hour == 24 {
+            crate::time::resolve_day_time(self.day, 0, self.minute, reference)
+                .map(|midnight| midnight + Duration::DAY)
+        } else {
+            crate::time::resolve_day_time(self.day, self.hour, self.minute, reference)
+        }
+    }
+
+    /// Parse `DDHHMMZ`, or `DDHHMM` if `zulu` is false.
+    fn parse_ddhhmm(s: &str, zulu: bool) -> Option<Self> {
+        let digits = if zulu { s.strip_suffix('Z')? } else { s };
+        if digits.len() != 6 {
+            return None;
+        }
+        Some(Self {
+            day: two_digits(&digits[0..2])?,
+            hour: two_digits(&digits[2..4])?,
+            minute: two_digits(&digits[4..6])?,
+        })
+    }
+
+    /// Parse `DDHH`.
+    fn parse_ddhh(s: &str) -> Option<Self> {
+        if s.len() != 4 {
+            return None;
+        }
+        Some(Self {
+            day: two_digits(&s[0..2])?,
+            hour: two_digits(&s[2..4])?,
+            minute: 0,
+        })
+    }
+
+    /// Parse a `DDHH/DDHH` period.
+    fn parse_period(s: &str) -> Option<(Self, Self)> {
+        let (from, to) = s.split_once('/')?;
+        Some((Self::parse_ddhh(from)?, Self::parse_ddhh(to)?))
+    }
+}
+
+fn two_digits(s: &str) -> Option<u8> {
+    if s.len() == 2 && s.bytes().all(|b| b.is_ascii_digit()) {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// The units of a wind speed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum SpeedUnit {
+    /// `KT`
+    Knots,
+    /// `MPS`
+    MetersPerSecond,
+}
+
+/// A wind group, like `27012G20KT`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Wind {
+    /// The direction the wind blows from, in degrees true, or `None` if variable (`VRB`)
+    pub direction: Option<u16>,
+    /// The sustained speed
+    pub speed: u16,
+    /// The gust speed, if any
+    pub gust: Option<u16>,
+    /// The units of `speed` and `gust`
+    pub unit: SpeedUnit,
+}
+
+impl Wind {
+    fn parse(s: &str) -> Option<Self> {
+        let (s, unit) = if let Some(s) = s.strip_suffix("KT") {
+            (s, SpeedUnit::Knots)
+        } else {
+            (s.strip_suffix("MPS")?, SpeedUnit::MetersPerSecond)
+        };
+        let (s, gust) = match s.split_once('G') {
+            Some((s, gust)) => (s, Some(speed(gust)?)),
+            None => (s, None),
+        };
+        if s.len() < 5 {
+            return None;
+        }
+        let (direction, speed_digits) = s.split_at(3);
+        let direction = match direction {
+            "VRB" => None,
+            digits if digits.bytes().all(|b| b.is_ascii_digit()) => Some(digits.parse().ok()?),
+            _ => return None,
+        };
+        Some(Self {
+            direction,
+            speed: speed(speed_digits)?,
+            gust,
+            unit,
+        })
+    }
+}
+
+fn speed(s: &str) -> Option<u16> {
+    if (2..=3).contains(&s.len()) && s.bytes().all(|b| b.is_ascii_digit()) {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// A prevailing visibility group.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    /// Statute miles, like `10SM` or `1 1/2SM`, as used in the U.S.
+    ///
+    /// Bounds are reported as the bound itself, e.g. `M1/4SM` (less than a quarter mile) as `0.25`
+    /// and `P6SM` (more than six miles) as `6.0`.
+    StatuteMiles(f64),
+    /// Meters, like `0800`, where `9999` means 10 km or more
+    Meters(u16),
+    /// `CAVOK`: visibility 10 km or more, with no significant cloud or weather
+    Cavok,
+}
+
+impl Visibility {
+    /// Parse a visibility group, consuming a second token for split fractions like `1 1/2SM`.
+    ///
+    /// Returns the visibility and whether `next` was consumed.
+    fn parse(token: &str, next: Option<&str>) -> Option<(Self, bool)> {
+        if token == "CAVOK" {
+            return Some((Self::Cavok, false));
+        }
+        if token.len() == 4 && token.bytes().all(|b| b.is_ascii_digit()) {
+            return Some((Self::Meters(token.parse().ok()?), false));
+        }
+        if let Some(miles) = token.strip_suffix("SM") {
+            let miles = miles.trim_start_matches(['M', 'P']);
+            return Some((Self::StatuteMiles(miles_value(miles)?), false));
+        }
+        // A whole number of miles, followed by a fraction
+        if (1..=2).contains(&token.len()) && token.bytes().all(|b| b.is_ascii_digit()) {
+            let fraction = next?.strip_suffix("SM")?;
+            if fraction.contains('/') {
+                let miles = token.parse::<f64>().ok()? + miles_value(fraction)?;
+                return Some((Self::StatuteMiles(miles), true));
+            }
+        }
+        None
+    }
+}
+
+fn miles_value(s: &str) -> Option<f64> {
+    let number = |s: &str| -> Option<f64> {
+        if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+            s.parse().ok()
+        } else {
+            None
+        }
+    };
+    match s.split_once('/') {
+        Some((numerator, denominator)) => {
+            let denominator = number(denominator)?;
+            if denominator == 0.0 {
+                return None;
+            }
+            Some(number(numerator)? / denominator)
+        }
+        None => number(s),
+    }
+}
+
+/// How much of the sky a cloud layer covers.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Cover {
+    /// `SKC`, `CLR`, `NSC`, or `NCD`: no clouds reported
+    Clear,
+    /// `FEW`: 1–2 oktas
+    Few,
+    /// `SCT`: 3–4 oktas
+    Scattered,
+    /// `BKN`: 5–7 oktas
+    Broken,
+    /// `OVC`: 8 oktas
+    Overcast,
+    /// `VV`: the sky is obscured, and the height is the vertical visibility
+    VerticalVisibility,
+}
+
+/// A sky condition group, like `BKN025CB`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Cloud {
+    /// The coverage
+    pub cover: Cover,
+    /// The height of the base above ground level, in feet, if given
+    pub base_ft: Option<u32>,
+    /// The cloud type, `CB` or `TCU`, if given
+    pub kind: Option<String>,
+}
+
+impl Cloud {
+    fn parse(s: &str) -> Option<Self> {
+        if matches!(s, "SKC" | "CLR" | "NSC" | "NCD") {
+            return Some(Self {
+                cover: Cover::Clear,
+                base_ft: None,
+                kind: None,
+            });
+        }
+        let (cover, rest) = [
+            ("FEW", Cover::Few),
+            ("SCT", Cover::Scattered),
+            ("BKN", Cover::Broken),
+            ("OVC", Cover::Overcast),
+            ("VV", Cover::VerticalVisibility),
+        ]
+        .iter()
+        .find_map(|(prefix, cover)| s.strip_prefix(prefix).map(|rest| (*cover, rest)))?;
+        if rest.len() < 3 {
+            return None;
+        }
+        let (height, kind) = rest.split_at(3);
+        let base_ft = match height {
+            "///" => None,
+            digits if digits.bytes().all(|b| b.is_ascii_digit()) => {
+                Some(digits.parse::<u32>().ok()? * 100)
+            }
+            _ => return None,
+        };
+        let kind = match kind {
+            "" | "///" => None,
+            "CB" | "TCU" => Some(kind.to_string()),
+            _ => return None,
+        };
+        Some(Self {
+            cover,
+            base_ft,
+            kind,
+        })
+    }
+}
+
+const WEATHER_CODES: &[&str] = &[
+    "MI", "PR", "BC", "DR", "BL", "SH", "TS", "FZ", "DZ", "RA", "SN", "SG", "IC", "PL", "GR", "GS",
+    "UP", "BR", "FG", "FU", "VA", "DU", "SA", "HZ", "PY", "PO", "SQ", "FC", "SS", "DS",
+];
+
+/// Returns true if `s` is a present weather group, like `-RA`, `+TSRA`, or `VCSH`.
+fn is_weather(s: &str) -> bool {
+    if s == "NSW" {
+        return true;
+    }
+    let s = s.trim_start_matches(['-', '+']);
+    let s = s.strip_prefix("VC").unwrap_or(s);
+    !s.is_empty()
+        && s.len().is_multiple_of(2)
+        && s.as_bytes()
+            .chunks(2)
+            .all(|code| WEATHER_CODES.iter().any(|known| known.as_bytes() == code))
+}
+
+/// Returns true if `s` looks like an ICAO location indicator, like `KORD`.
+fn is_station(s: &str) -> bool {
+    s.len() == 4
+        && s.as_bytes()[0].is_ascii_uppercase()
+        && s.bytes()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+}
+
+/// The wind, visibility, weather, and sky groups shared by METARs and TAFs.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Conditions {
+    /// The wind, if given
+    pub wind: Option<Wind>,
+    /// The prevailing visibility, if given
+    pub visibility: Option<Visibility>,
+    /// Present or forecast weather groups, like `-RA` or `+TSRA`
+    pub weather: Vec<String>,
+    /// Sky condition groups, from lowest to highest
+    pub clouds: Vec<Cloud>,
+}
+
+impl Conditions {
+    /// Decode the group at the start of `tokens`, returning how many tokens it used, or `0` if it
+    /// isn't a condition group.
+    fn take(&mut self, tokens: &[&str]) -> usize {
+        let token = tokens[0];
+        if let Some(wind) = Wind::parse(token) {
+            self.wind = Some(wind);
+            1
+        } else if let Some((visibility, consumed_next)) =
+            Visibility::parse(token, tokens.get(1).copied())
+        {
+            self.visibility = Some(visibility);
+            if consumed_next {
+                2
+            } else {
+                1
+            }
+        } else if is_weather(token) {
+            self.weather.push(token.to_string());
+            1
+        } else if let Some(cloud) = Cloud::parse(token) {
+            self.clouds.push(cloud);
+            1
+        } else {
+            0
+        }
+    }
+}
+
+/// Whether a surface observation is routine or special.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum MetarKind {
+    /// `METAR`: a routine observation
+    Metar,
+    /// `SPECI`: a special observation, made when conditions change significantly
+    Speci,
+}
+
+/// An altimeter setting or sea-level pressure group.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pressure {
+    /// Inches of mercury, like `A2992`
+    InchesOfMercury(f64),
+    /// Hectopascals, like `Q1013`
+    Hectopascals(u16),
+}
+
+impl Pressure {
+    fn parse(s: &str) -> Option<Self> {
+        let digits = s
+            .get(1..)
+            .filter(|digits| digits.len() == 4 && digits.bytes().all(|b| b.is_ascii_digit()))?;
+        match s.as_bytes()[0] {
+            b'A' => Some(Self::InchesOfMercury(digits.parse::<f64>().ok()? / 100.0)),
+            b'Q' => Some(Self::Hectopascals(digits.parse().ok()?)),
+            _ => None,
+        }
+    }
+}
+
+/// A METAR or SPECI surface observation.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Metar {
+    /// Whether this is a routine or special observation
+    pub kind: MetarKind,
+    /// The ICAO location indicator of the station, e.g. `KORD`
+    pub station: String,
+    /// When the observation was made
+    pub observed: DayTime,
+    /// Whether the observation was made without human intervention (`AUTO`)
+    pub automated: bool,
+    /// Whether this report corrects an earlier one (`COR`)
+    pub corrected: bool,
+    /// The observed wind, visibility, weather, and sky
+    pub conditions: Conditions,
+    /// The temperature in whole degrees Celsius
+    pub temperature: Option<i8>,
+    /// The dew point in whole degrees Celsius
+    pub dew_point: Option<i8>,
+    /// The altimeter setting
+    pub altimeter: Option<Pressure>,
+    /// Everything after `RMK`, if present
+    pub remarks: Option<String>,
+    /// The report as it appeared in the product, from the station identifier on, with whitespace
+    /// normalized and without the trailing `=`
+    pub raw: String,
+}
+
+impl Metar {
+    /// When the observation was made, resolving the day of month to the nearest matching date
+    /// around `reference`.
+    pub fn observed_at(&self, reference: OffsetDateTime) -> Option<OffsetDateTime> {
+        self.observed.resolve(reference)
+    }
+
+    fn from_tokens(kind: MetarKind, tokens: &[&str]) -> Option<Self> {
+        let (kind, tokens) = match tokens.first() {
+            Some(&"METAR") => (MetarKind::Metar, &tokens[1..]),
+            Some(&"SPECI") => (MetarKind::Speci, &tokens[1..]),
+            _ => (kind, tokens),
+        };
+        let raw = tokens.join(" ");
+        let (corrected, tokens) = match tokens.first() {
+            Some(&"COR") => (true, &tokens[1..]),
+            _ => (false, tokens),
+        };
+        if tokens.len() < 2 || !is_station(tokens[0]) {
+            return None;
+        }
+        let observed = DayTime::parse_ddhhmm(tokens[1], true)?;
+        if tokens.get(2) == Some(&"NIL") {
+            return None;
+        }
+
+        let mut metar = Self {
+            kind,
+            station: tokens[0].to_string(),
+            observed,
+            automated: false,
+            corrected,
+            conditions: Conditions::default(),
+            temperature: None,
+            dew_point: None,
+            altimeter: None,
+            remarks: None,
+            raw,
+        };
+
+        let mut i = 2;
+        while i < tokens.len() {
+            let token = tokens[i];
+            match token {
+                "AUTO" => metar.automated = true,
+                "COR" => metar.corrected = true,
+                "RMK" => {
+                    metar.remarks = Some(tokens[i + 1..].join(" "));
+                    break;
+                }
+                _ => {
+                    let used = metar.conditions.take(&tokens[i..]);
+                    if used > 1 {
+                        i += used - 1;
+                    } else if used == 0 {
+                        if let Some((temperature, dew_point)) = temperatures(token) {
+                            metar.temperature = Some(temperature);
+                            metar.dew_point = dew_point;
+                        } else if let Some(altimeter) = Pressure::parse(token) {
+                            metar.altimeter = Some(altimeter);
+                        }
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        Some(metar)
+    }
+}
+
+/// Parse a temperature group like `M02/M11` or `15/`.
+fn temperatures(s: &str) -> Option<(i8, Option<i8>)> {
+    let (temperature, dew_point) = s.split_once('/')?;
+    let degrees = |s: &str| -> Option<i8> {
+        let (negative, digits) = match s.strip_prefix('M') {
+            Some(digits) => (true, digits),
+            None => (false, s),
+        };
+        let value = two_digits(digits)? as i8;
+        Some(if negative { -value } else { value })
+    };
+    let dew_point = match dew_point {
+        "" | "//" => None,
+        dew_point => Some(degrees(dew_point)?),
+    };
+    Some((degrees(temperature)?, dew_point))
+}
+
+/// The error returned when a string is not a valid [`Metar`].
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error("invalid METAR")]
+pub struct ParseMetarError;
+
+impl FromStr for Metar {
+    type Err = ParseMetarError;
+
+    /// Parse a single report, like `METAR KORD 201551Z 27012KT 10SM BKN025 M02/M11 A3012`.
+    ///
+    /// The leading `METAR` or `SPECI` is optional, and a trailing `=` is ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<&str> = s
+            .trim_end()
+            .trim_end_matches('=')
+            .split_whitespace()
+            .collect();
+        Self::from_tokens(MetarKind::Metar, &tokens).ok_or(ParseMetarError)
+    }
+}
+
+/// Split a bulletin into reports, each a list of tokens.
+///
+/// Reports end with `=`, or where `starts` returns true for a token beginning a new report. The
+/// starting token is kept at the front of the report.
+fn reports(text: &str, starts: impl Fn(&str) -> bool) -> Vec<Vec<&str>> {
+    let mut reports = Vec::new();
+    let mut current = Vec::new();
+    for chunk in text.split('=') {
+        for token in chunk.split_whitespace() {
+            if starts(token) && !current.is_empty() {
+                reports.push(std::mem::take(&mut current));
+            }
+            current.push(token);
+        }
+        if !current.is_empty() {
+            reports.push(std::mem::take(&mut current));
+        }
+    }
+    reports
+}
+
+/// Find and parse every METAR and SPECI report in a product's text.
+///
+/// Bulletins may prefix each report with `METAR` or `SPECI`, or give it once on a line of its
+/// own. Reports which can't be parsed, including `NIL` reports, are skipped.
+///
+/// # Example
+///
+/// ```
+/// use emwin_tg::product::aviation::{self, Cover, MetarKind, Pressure, Visibility};
+///
+/// let metars = aviation::parse_metars(
+///     "SAUS43 KLOT 201600\r\r\nMTRORD\r\r\n\
+///      METAR KORD 201551Z 27012G20KT 1 1/2SM -SN BKN025 OVC040 M02/M11 A3012 RMK AO2=\r\r\n\
+///      SPECI KMDW 201608Z AUTO VRB04KT 10SM CLR 01/M08 A3010=\r\r\n",
+/// );
+/// assert_eq!(metars.len(), 2);
+///
+/// let ord = &metars[0];
+/// assert_eq!(ord.station, "KORD");
+/// assert_eq!((ord.observed.day, ord.observed.hour, ord.observed.minute), (20, 15, 51));
+/// let wind = ord.conditions.wind.unwrap();
+/// assert_eq!((wind.direction, wind.speed, wind.gust), (Some(270), 12, Some(20)));
+/// assert_eq!(ord.conditions.visibility, Some(Visibility::StatuteMiles(1.5)));
+/// assert_eq!(ord.conditions.weather, ["-SN"]);
+/// assert_eq!(ord.conditions.clouds[0].cover, Cover::Broken);
+/// assert_eq!(ord.conditions.clouds[0].base_ft, Some(2500));
+/// assert_eq!((ord.temperature, ord.dew_point), (Some(-2), Some(-11)));
+/// assert_eq!(ord.altimeter, Some(Pressure::InchesOfMercury(30.12)));
+/// assert_eq!(ord.remarks.as_deref(), Some("AO2"));
+///
+/// let mdw = &metars[1];
+/// assert_eq!(mdw.kind, MetarKind::Speci);
+/// assert!(mdw.automated);
+/// assert_eq!(mdw.conditions.wind.unwrap().direction, None);
+/// ```
+pub fn parse_metars(text: &str) -> Vec<Metar> {
+    let mut kind = MetarKind::Metar;
+    reports(text, |token| token == "METAR" || token == "SPECI")
+        .into_iter()
+        .filter_map(|tokens| {
+            let start = match tokens.first() {
+                Some(&"METAR") => {
+                    kind = MetarKind::Metar;
+                    1
+                }
+                Some(&"SPECI") => {
+                    kind = MetarKind::Speci;
+                    1
+                }
+                _ => 0,
+            };
+            // Skip any heading lines before the station and observation time
+            let tokens = &tokens[start..];
+            let offset = (0..tokens.len()).find(|&i| {
+                is_station(tokens[i])
+                    && tokens
+                        .get(i + 1)
+                        .is_some_and(|t| DayTime::parse_ddhhmm(t, true).is_some())
+            })?;
+            let offset = if offset > 0 && tokens[offset - 1] == "COR" {
+                offset - 1
+            } else {
+                offset
+            };
+            Metar::from_tokens(kind, &tokens[offset..])
+        })
+        .collect()
+}
+
+/// What a TAF change group says about the forecast conditions it carries.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Change {
+    /// The initial forecast, at the start of the TAF
+    Base,
+    /// `FMDDHHMM`: conditions change rapidly at `from`, replacing everything before
+    From,
+    /// `BECMG`: conditions change gradually over the period
+    Becoming,
+    /// `TEMPO`: conditions fluctuate temporarily during the period
+    Temporary,
+    /// `PROBnn`: conditions have the given percent chance of occurring during the period, or of
+    /// occurring temporarily if followed by `TEMPO`
+    Probability {
+        /// The probability, usually `30` or `40`
+        percent: u8,
+        /// Whether `TEMPO` followed
+        temporary: bool,
+    },
+}
+
+/// One group of a TAF: the base forecast, or a change to it.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct TafGroup {
+    /// What kind of change this group describes
+    pub change: Change,
+    /// When the group's conditions begin
+    pub from: DayTime,
+    /// When the group's conditions end, if the group gives a period
+    pub to: Option<DayTime>,
+    /// The forecast wind, visibility, weather, and sky
+    pub conditions: Conditions,
+}
+
+/// A terminal aerodrome forecast.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Taf {
+    /// The ICAO location indicator of the aerodrome, e.g. `KORD`
+    pub station: String,
+    /// When the forecast was issued
+    pub issued: DayTime,
+    /// When the forecast period begins
+    pub valid_from: DayTime,
+    /// When the forecast period ends
+    pub valid_to: DayTime,
+    /// Whether this forecast amends an earlier one (`AMD`)
+    pub amended: bool,
+    /// Whether this forecast corrects an earlier one (`COR`)
+    pub corrected: bool,
+    /// The base forecast followed by each change group, in order
+    pub groups: Vec<TafGroup>,
+    /// The forecast as it appeared in the product, from the station identifier on, with whitespace
+    /// normalized and without the trailing `=`
+    pub raw: String,
+}
+
+impl Taf {
+    /// When the forecast was issued, resolving the day of month to the nearest matching date
+    /// around `reference`.
+    pub fn issued_at(&self, reference: OffsetDateTime) -> Option<OffsetDateTime> {
+        self.issued.resolve(reference)
+    }
+
+    fn from_tokens(tokens: &[&str]) -> Option<Self> {
+        let tokens = if tokens.first() == Some(&"TAF") {
+            &tokens[1..]
+        } else {
+            tokens
+        };
+        let modifiers = tokens.iter().take_while(|t| matches!(**t, "AMD" | "COR"));
+        let (mut amended, mut corrected) = (false, false);
+        for modifier in modifiers.clone() {
+            match *modifier {
+                "AMD" => amended = true,
+                _ => corrected = true,
+            }
+        }
+        let tokens = &tokens[modifiers.count()..];
+        if tokens.len() < 3 || !is_station(tokens[0]) {
+            return None;
+        }
+        let issued = DayTime::parse_ddhhmm(tokens[1], true)?;
+        let (valid_from, valid_to) = DayTime::parse_period(tokens[2])?;
+        if tokens.get(3) == Some(&"NIL") {
+            return None;
+        }
+
+        let mut groups = vec![TafGroup {
+            change: Change::Base,
+            from: valid_from,
+            to: Some(valid_to),
+            conditions: Conditions::default(),
+        }];
+        let mut i = 3;
+        while i < tokens.len() {
+            match change_group(&tokens[i..]) {
+                Some((change, from, to, used)) => {
+                    groups.push(TafGroup {
+                        change,
+                        from,
+                        to,
+                        conditions: Conditions::default(),
+                    });
+                    i += used;
+                }
+                None => {
+                    let group = groups.last_mut().expect("groups always has a base");
+                    i += group.conditions.take(&tokens[i..]).max(1);
+                }
+            }
+        }
+
+        Some(Self {
+            station: tokens[0].to_string(),
+            issued,
+            valid_from,
+            valid_to,
+            amended,
+            corrected,
+            groups,
+            raw: tokens.join(" "),
+        })
+    }
+}
+
+/// Parse the change group at the start of `tokens`, returning the change, its period, and how
+/// many tokens it used.
+fn change_group(tokens: &[&str]) -> Option<(Change, DayTime, Option<DayTime>, usize)> {
+    let period = |i: usize| tokens.get(i).and_then(|t| DayTime::parse_period(t));
+    match tokens[0] {
+        "BECMG" => period(1).map(|(from, to)| (Change::Becoming, from, Some(to), 2)),
+        "TEMPO" => period(1).map(|(from, to)| (Change::Temporary, from, Some(to), 2)),
+        token => {
+            if let Some(from) = token.strip_prefix("FM") {
+                let from = DayTime::parse_ddhhmm(from, false)?;
+                return Some((Change::From, from, None, 1));
+            }
+            let percent = two_digits(token.strip_prefix("PROB")?)?;
+            let temporary = tokens.get(1) == Some(&"TEMPO");
+            let used = if temporary { 3 } else { 2 };
+            let (from, to) = period(used - 1)?;
+            let change = Change::Probability { percent, temporary };
+            Some((change, from, Some(to), used))
+        }
+    }
+}
+
+/// The error returned when a string is not a valid [`Taf`].
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error("invalid TAF")]
+pub struct ParseTafError;
+
+impl FromStr for Taf {
+    type Err = ParseTafError;
+
+    /// Parse a single forecast, like `TAF KORD 201720Z 2018/2124 27012KT P6SM BKN025`.
+    ///
+    /// The leading `TAF` is optional, and a trailing `=` is ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<&str> = s
+            .trim_end()
+            .trim_end_matches('=')
+            .split_whitespace()
+            .collect();
+        Self::from_tokens(&tokens).ok_or(ParseTafError)
+    }
+}
+
+/// Find and parse every TAF in a product's text.
+///
+/// Forecasts which can't be parsed, including `NIL` forecasts, are skipped.
+///
+/// # Example
+///
+/// ```
+/// use emwin_tg::product::aviation::{self, Change, Visibility};
+///
+/// let tafs = aviation::parse_tafs(
+///     "FTUS43 KLOT 201720\r\r\nTAFORD\r\r\n\
+///      TAF AMD KORD 201720Z 2018/2124 27012G22KT P6SM BKN025\r\r\n\
+///      \x20    TEMPO 2018/2020 3SM -SN OVC015\r\r\n\
+///      \x20    FM210000 29008KT P6SM SKC=\r\r\n",
+/// );
+/// let taf = &tafs[0];
+/// assert_eq!(taf.station, "KORD");
+/// assert!(taf.amended);
+/// assert_eq!((taf.valid_to.day, taf.valid_to.hour), (21, 24));
+///
+/// let changes: Vec<Change> = taf.groups.iter().map(|group| group.change).collect();
+/// assert_eq!(changes, [Change::Base, Change::Temporary, Change::From]);
+/// assert_eq!(taf.groups[1].conditions.visibility, Some(Visibility::StatuteMiles(3.0)));
+/// assert_eq!(taf.groups[2].from.day, 21);
+/// assert_eq!(taf.groups[2].to, None);
+/// ```
+pub fn parse_tafs(text: &str) -> Vec<Taf> {
+    reports(text, |token| token == "TAF")
+        .into_iter()
+        .filter_map(|tokens| {
+            // Skip any heading lines before the station, issuance time, and valid period
+            let offset = (0..tokens.len()).find(|&i| {
+                is_station(tokens[i])
+                    && tokens
+                        .get(i + 1)
+                        .is_some_and(|t| DayTime::parse_ddhhmm(t, true).is_some())
+            })?;
+            let modifiers = tokens[..offset]
+                .iter()
+                .rev()
+                .take_while(|t| matches!(**t, "AMD" | "COR"))
+                .count();
+            Taf::from_tokens(&tokens[offset - modifiers..])
+        })
+        .collect()
+}
+
+/// The format of a SIGMET.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum SigmetKind {
+    /// A U.S. convective SIGMET, like `CONVECTIVE SIGMET 45C`, for thunderstorms
+    Convective,
+    /// A U.S. domestic non-convective SIGMET, like `SIGMET NOVEMBER 2`, for turbulence, icing,
+    /// dust, or volcanic ash
+    NonConvective,
+    /// An ICAO-format SIGMET, like `KZNY SIGMET ALFA 3`, for an oceanic or foreign flight
+    /// information region
+    International,
+}
+
+/// A SIGMET: a warning of weather hazardous to all aircraft.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Sigmet {
+    /// The format this SIGMET was written in
+    pub kind: SigmetKind,
+    /// The SIGMET's name within its series, like `45C`, `NOVEMBER 2`, or `ALFA 3`
+    pub id: String,
+    /// For international SIGMETs, the ICAO location indicator of the flight information region
+    /// or air traffic services unit, like `KZNY`
+    pub region: Option<String>,
+    /// When the SIGMET takes effect, if given
+    pub valid_from: Option<DayTime>,
+    /// The day of month the SIGMET expires, if given; convective SIGMETs give only the time
+    pub valid_until_day: Option<u8>,
+    /// The hour the SIGMET expires
+    pub valid_until_hour: u8,
+    /// The minute the SIGMET expires
+    pub valid_until_minute: u8,
+    /// The lines following the SIGMET's header, describing the hazard and area, trimmed and
+    /// joined with `\n`
+    pub text: String,
+}
+
+impl Sigmet {
+    /// When the SIGMET expires.
+    ///
+    /// If the day of month is given, it's resolved to the nearest matching date around
+    /// `reference`. Otherwise, the expiration is the first time of day after `reference`, which
+    /// should be when the SIGMET was issued.
+    pub fn valid_until(&self, reference: OffsetDateTime) -> Option<OffsetDateTime> {
+        match self.valid_until_day {
+            Some(day) => crate::time::resolve_day_time(
+                day,
+                self.valid_until_hour,
+                self.valid_until_minute,
+                reference,
+            ),
+            None => {
+                let time =
+                    time::Time::from_hms(self.valid_until_hour, self.valid_until_minute, 0).ok()?;
+                let until = reference.replace_time(time);
+                Some(if until < reference {
+                    until + Duration::DAY
+                } else {
+                    until
+                })
+            }
+        }
+    }
+
+    /// Parse the header line of a SIGMET, leaving `text` empty.
+    fn from_header(line: &str) -> Option<Self> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let sigmet = |kind, id: String, region, valid_from, until: (Option<u8>, u8, u8)| Self {
+            kind,
+            id,
+            region,
+            valid_from,
+            valid_until_day: until.0,
+            valid_until_hour: until.1,
+            valid_until_minute: until.2,
+            text: String::new(),
+        };
+        match words.as_slice() {
+            ["CONVECTIVE", "SIGMET", id] if !id.starts_with("...") => Some(sigmet(
+                SigmetKind::Convective,
+                id.to_string(),
+                None,
+                None,
+                (None, 0, 0),
+            )),
+            ["SIGMET", name, number, "VALID", "UNTIL", until, ..] => {
+                let until = DayTime::parse_ddhhmm(until, false)?;
+                Some(sigmet(
+                    SigmetKind::NonConvective,
+                    format!("{} {}", name, number),
+                    None,
+                    None,
+                    (Some(until.day), until.hour, until.minute),
+                ))
+            }
+            [region, "SIGMET", name, number, "VALID", period, ..] if is_station(region) => {
+                let (from, until) = period.split_once('/')?;
+                let from = DayTime::parse_ddhhmm(from, false)?;
+                let until = DayTime::parse_ddhhmm(until, false)?;
+                Some(sigmet(
+                    SigmetKind::International,
+                    format!("{} {}", name, number),
+                    Some(region.to_string()),
+                    Some(from),
+                    (Some(until.day), until.hour, until.minute),
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The error returned when a string is not a valid [`Sigmet`].
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error("invalid SIGMET")]
+pub struct ParseSigmetError;
+
+impl FromStr for Sigmet {
+    type Err = ParseSigmetError;
+
+    /// Parse a single SIGMET, starting with its header line.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let header = s.lines().map(str::trim).find(|line| !line.is_empty());
+        if header.and_then(Sigmet::from_header).is_none() {
+            return Err(ParseSigmetError);
+        }
+        parse_sigmets(s).into_iter().next().ok_or(ParseSigmetError)
+    }
+}
+
+/// Find and parse every SIGMET in a product's text.
+///
+/// Each SIGMET runs from its header to a blank line, a `=`, a convective outlook, or the next
+/// SIGMET. `CONVECTIVE SIGMET...NONE` placeholders are skipped.
+///
+/// # Example
+///
+/// ```
+/// use emwin_tg::product::aviation::{self, SigmetKind};
+///
+/// let sigmets = aviation::parse_sigmets(
+///     "WSUS32 KKCI 201855\r\r\nSIGC\r\r\n\
+///      CONVECTIVE SIGMET 45C\r\r\n\
+///      VALID UNTIL 2055Z\r\r\n\
+///      KS OK\r\r\n\
+///      FROM 30NW ICT-40SW END-60W OKC\r\r\n\
+///      LINE TS 20 NM WIDE MOV FROM 24030KT. TOPS ABV FL450.\r\r\n\
+///      \r\r\n\
+///      CONVECTIVE SIGMET 46C\r\r\n\
+///      VALID UNTIL 2055Z\r\r\n\
+///      TX\r\r\n\
+///      FROM 20E ABI\r\r\n\
+///      ISOL EMBD TS D20 MOV FROM 25025KT. TOPS TO FL400.\r\r\n\
+///      \r\r\n\
+///      OUTLOOK VALID 202055-210055\r\r\n\
+///      FROM ICT-TUL-DFW\r\r\n",
+/// );
+/// assert_eq!(sigmets.len(), 2);
+/// assert_eq!(sigmets[0].kind, SigmetKind::Convective);
+/// assert_eq!(sigmets[0].id, "45C");
+/// assert_eq!((sigmets[0].valid_until_hour, sigmets[0].valid_until_minute), (20, 55));
+/// assert!(sigmets[1].text.starts_with("TX\nFROM 20E ABI\n"));
+/// ```
+pub fn parse_sigmets(text: &str) -> Vec<Sigmet> {
+    let mut sigmets = Vec::new();
+    let mut current: Option<Sigmet> = None;
+    let mut finish = |current: &mut Option<Sigmet>| {
+        if let Some(mut sigmet) = current.take() {
+            sigmet.text = sigmet.text.trim_end().to_string();
+            sigmets.push(sigmet);
+        }
+    };
+
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(sigmet) = Sigmet::from_header(line) {
+            finish(&mut current);
+            current = Some(sigmet);
+            continue;
+        }
+        let sigmet = match current.as_mut() {
+            Some(sigmet) => sigmet,
+            None => continue,
+        };
+        if line.is_empty()
+            || line.starts_with("OUTLOOK VALID")
+            || line.starts_with("CONVECTIVE SIGMET")
+        {
+            finish(&mut current);
+            continue;
+        }
+
+        // Convective SIGMETs give their expiration on the line after the header
+        if sigmet.kind == SigmetKind::Convective && sigmet.text.is_empty() {
+            if let Some(until) = line
+                .strip_prefix("VALID UNTIL ")
+                .and_then(|until| until.strip_suffix('Z'))
+                .filter(|until| until.len() == 4)
+            {
+                if let (Some(hour), Some(minute)) =
+                    (two_digits(&until[..2]), two_digits(&until[2..]))
+                {
+                    sigmet.valid_until_hour = hour;
+                    sigmet.valid_until_minute = minute;
+                    continue;
+                }
+            }
+        }
+
+        let (line, ends) = match line.split_once('=') {
+            Some((line, _)) => (line.trim_end(), true),
+            None => (line, false),
+        };
+        if !line.is_empty() {
+            sigmet.text.push_str(line);
+            sigmet.text.push('\n');
+        }
+        if ends {
+            finish(&mut current);
+        }
+    }
+    finish(&mut current);
+
+    sigmets
+}
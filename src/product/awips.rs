@@ -0,0 +1,109 @@
+//! AWIPS identifier parsing.
+//!
+//! NWS text products carry an AWIPS identifier, also known as a product identifier or PIL, on the
+//! line following the WMO heading, e.g. `TORILX`:
+//!
+//! * `NNN`, the three-character product category, e.g. `TOR` for a tornado warning
+//! * `XXX`, a one- to three-character location, usually the issuing office, e.g. `ILX` for
+//!   Lincoln, Illinois
+//!
+//! EMWIN filenames end with a product name which usually starts with the same identifier, but
+//! that name is padded or truncated to eight characters with a state suffix, so it's ambiguous
+//! when the location is shorter than three characters. See
+//! [`ProductId::awips_id`](super::id::ProductId::awips_id).
+
+use super::wmo::WmoHeading;
+use std::fmt;
+use std::str::FromStr;
+
+/// An AWIPS identifier, e.g. `TORILX`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct AwipsId {
+    id: [u8; 6],
+    len: u8,
+}
+
+impl AwipsId {
+    /// The product category `NNN`, e.g. `TOR`.
+    pub fn category(&self) -> &str {
+        &self.as_str()[..3]
+    }
+
+    /// The location `XXX`, usually the issuing office, e.g. `ILX`.
+    pub fn office(&self) -> &str {
+        &self.as_str()[3..]
+    }
+
+    /// The whole identifier.
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.id[..usize::from(self.len)]).unwrap_or("")
+    }
+
+    /// Find the identifier on the line after the WMO heading of a text product.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::product::awips::AwipsId;
+    ///
+    /// let contents = b"\x01\r\r\n123 \r\r\nWFUS53 KILX 202245\r\r\nTORILX\r\r\n\r\r\nBULLETIN";
+    /// let id = AwipsId::from_contents(contents).unwrap();
+    /// assert_eq!((id.category(), id.office()), ("TOR", "ILX"));
+    ///
+    /// assert_eq!(AwipsId::from_contents(b"WFUS53 KILX 202245\r\r\n\r\r\nBULLETIN"), None);
+    /// ```
+    pub fn from_contents(contents: &[u8]) -> Option<Self> {
+        let (_, body) = WmoHeading::split_contents(contents)?;
+        let line = body.split(|b| *b == b'\n').next()?;
+        std::str::from_utf8(line)
+            .ok()?
+            .trim_matches(|c: char| c.is_ascii_control() || c == ' ')
+            .parse()
+            .ok()
+    }
+}
+
+impl fmt::Display for AwipsId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The error returned when a string is not a valid [`AwipsId`].
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error("invalid AWIPS identifier")]
+pub struct ParseAwipsIdError;
+
+impl FromStr for AwipsId {
+    type Err = ParseAwipsIdError;
+
+    /// Parse an identifier of four to six uppercase letters and digits.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::product::awips::AwipsId;
+    ///
+    /// let id: AwipsId = "RR1K".parse().unwrap();
+    /// assert_eq!((id.category(), id.office()), ("RR1", "K"));
+    ///
+    /// assert!("TOR".parse::<AwipsId>().is_err());
+    /// assert!("TOR ILX".parse::<AwipsId>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = s.as_bytes();
+        if !(4..=6).contains(&bytes.len())
+            || !bytes
+                .iter()
+                .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+        {
+            return Err(ParseAwipsIdError);
+        }
+        let mut id = [0; 6];
+        id[..bytes.len()].copy_from_slice(bytes);
+        Ok(Self {
+            id,
+            len: bytes.len() as u8,
+        })
+    }
+}
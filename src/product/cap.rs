@@ -0,0 +1,238 @@
+//! Common Alerting Protocol (CAP) alerts.
+//!
+//! CAP is an XML format for alerts and warnings, standardized by OASIS. NWS alerts carry the
+//! same information as the corresponding text products, broken out into fields. See
+//! [`Product::parse_cap`](crate::Product::parse_cap).
+//!
+//! Only the parts of CAP 1.1 and 1.2 which NWS alerts use are parsed. Times are left as they
+//! appear in the document, e.g. `2022-02-20T16:45:00-06:00`.
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+/// A CAP `<alert>`.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Alert {
+    /// A unique identifier for this alert, assigned by the sender
+    pub identifier: String,
+    /// The sender, e.g. `w-nws.webmaster@noaa.gov`
+    pub sender: String,
+    /// When the alert was sent
+    pub sent: String,
+    /// `Actual`, `Exercise`, `System`, `Test`, or `Draft`
+    pub status: String,
+    /// `Alert`, `Update`, `Cancel`, `Ack`, or `Error`
+    pub msg_type: String,
+    /// `Public`, `Restricted`, or `Private`
+    pub scope: String,
+    /// The alerts this one updates or cancels, as `sender,identifier,sent` triples separated by
+    /// spaces
+    pub references: Option<String>,
+    /// The alert's information blocks, usually one per language
+    pub info: Vec<Info>,
+}
+
+/// A CAP `<info>` block, describing an event.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Info {
+    /// The event, e.g. `Tornado Warning`
+    pub event: String,
+    /// `Immediate`, `Expected`, `Future`, `Past`, or `Unknown`
+    pub urgency: String,
+    /// `Extreme`, `Severe`, `Moderate`, `Minor`, or `Unknown`
+    pub severity: String,
+    /// `Observed`, `Likely`, `Possible`, `Unlikely`, or `Unknown`
+    pub certainty: String,
+    /// When the information takes effect
+    pub effective: Option<String>,
+    /// When the event is expected to begin
+    pub onset: Option<String>,
+    /// When the information expires
+    pub expires: Option<String>,
+    /// The human-readable sender, e.g. `NWS Chicago IL`
+    pub sender_name: Option<String>,
+    /// A brief headline
+    pub headline: Option<String>,
+    /// The full description of the event
+    pub description: Option<String>,
+    /// The recommended action
+    pub instruction: Option<String>,
+    /// System-specific parameters as `(valueName, value)` pairs, e.g. `("VTEC", "/O.NEW.../")`
+    pub parameters: Vec<(String, String)>,
+    /// The areas affected
+    pub areas: Vec<Area>,
+}
+
+/// A CAP `<area>`.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Area {
+    /// A description of the area, e.g. `Cook, IL; Will, IL`
+    pub area_desc: String,
+    /// Polygons bounding the area, as `(latitude, longitude)` pairs in degrees
+    pub polygons: Vec<Vec<(f64, f64)>>,
+    /// Codes identifying the area as `(valueName, value)` pairs, e.g. `("UGC", "ILC031")`
+    pub geocodes: Vec<(String, String)>,
+}
+
+/// The error returned when a document is not a valid CAP alert.
+#[derive(Debug, thiserror::Error)]
+pub enum ParseCapError {
+    /// The document is not well-formed XML
+    #[error("XML error: {0}")]
+    Xml(#[from] quick_xml::Error),
+    /// The document's root element is not `<alert>`, or it has no `<identifier>`
+    #[error("not a CAP alert")]
+    NotAnAlert,
+}
+
+/// Parse a CAP alert.
+///
+/// Unrecognized elements are ignored. Only the `<alert>` root and its `<identifier>` are required.
+///
+/// # Example
+///
+/// ```
+/// let alert = emwin_tg::product::cap::parse(
+///     br#"<?xml version="1.0" encoding="UTF-8"?>
+/// <alert xmlns="urn:oasis:names:tc:emergency:cap:1.2">
+///   <identifier>NWS-IDP-PROD-1</identifier>
+///   <msgType>Alert</msgType>
+///   <info>
+///     <event>Tornado Warning</event>
+///     <area>
+///       <areaDesc>Cook, IL</areaDesc>
+///       <polygon>41.55,-88.06 41.54,-87.83 41.39,-87.82 41.55,-88.06</polygon>
+///     </area>
+///   </info>
+/// </alert>"#,
+/// )
+/// .unwrap();
+/// assert_eq!(alert.identifier, "NWS-IDP-PROD-1");
+/// assert_eq!(alert.info[0].event, "Tornado Warning");
+/// assert_eq!(alert.info[0].areas[0].polygons[0][1], (41.54, -87.83));
+/// ```
+pub fn parse(xml: &[u8]) -> Result<Alert, ParseCapError> {
+    let mut reader = Reader::from_reader(xml);
+    let mut buf = Vec::new();
+
+    let mut alert = None::<Alert>;
+    let mut path: Vec<Vec<u8>> = Vec::new();
+    let mut text = String::new();
+    let mut pair = (String::new(), String::new());
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) => {
+                let name = e.local_name().as_ref().to_vec();
+                match (path.len(), name.as_slice()) {
+                    (0, b"alert") => alert = Some(Alert::default()),
+                    (0, _) => return Err(ParseCapError::NotAnAlert),
+                    _ => (),
+                }
+                if let Some(alert) = alert.as_mut() {
+                    match (path.len(), name.as_slice()) {
+                        (1, b"info") => alert.info.push(Info::default()),
+                        (2, b"area") => {
+                            if let Some(info) = alert.info.last_mut() {
+                                info.areas.push(Area::default());
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                path.push(name);
+                text.clear();
+            }
+            Event::Text(e) => text.push_str(&e.unescape()?),
+            Event::CData(e) => text.push_str(&String::from_utf8_lossy(&e)),
+            Event::End(_) => {
+                if let Some(alert) = alert.as_mut() {
+                    let path: Vec<&[u8]> = path.iter().map(Vec::as_slice).collect();
+                    assign(alert, &path, text.trim(), &mut pair);
+                }
+                path.pop();
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    alert
+        .filter(|alert| !alert.identifier.is_empty())
+        .ok_or(ParseCapError::NotAnAlert)
+}
+
+/// Store the text of the element at `path`, which just ended.
+///
+/// `<parameter>` and `<geocode>` are pairs of child elements, which are collected in `pair`.
+fn assign(alert: &mut Alert, path: &[&[u8]], text: &str, pair: &mut (String, String)) {
+    let owned = || text.to_string();
+    let info = alert.info.last_mut();
+    match path {
+        [_, b"identifier"] => alert.identifier = owned(),
+        [_, b"sender"] => alert.sender = owned(),
+        [_, b"sent"] => alert.sent = owned(),
+        [_, b"status"] => alert.status = owned(),
+        [_, b"msgType"] => alert.msg_type = owned(),
+        [_, b"scope"] => alert.scope = owned(),
+        [_, b"references"] => alert.references = Some(owned()),
+        [_, b"info", rest @ ..] => {
+            let info = match info {
+                Some(info) => info,
+                None => return,
+            };
+            match rest {
+                [b"event"] => info.event = owned(),
+                [b"urgency"] => info.urgency = owned(),
+                [b"severity"] => info.severity = owned(),
+                [b"certainty"] => info.certainty = owned(),
+                [b"effective"] => info.effective = Some(owned()),
+                [b"onset"] => info.onset = Some(owned()),
+                [b"expires"] => info.expires = Some(owned()),
+                [b"senderName"] => info.sender_name = Some(owned()),
+                [b"headline"] => info.headline = Some(owned()),
+                [b"description"] => info.description = Some(owned()),
+                [b"instruction"] => info.instruction = Some(owned()),
+                [b"parameter", b"valueName"] | [b"area", b"geocode", b"valueName"] => {
+                    pair.0 = owned()
+                }
+                [b"parameter", b"value"] | [b"area", b"geocode", b"value"] => pair.1 = owned(),
+                [b"parameter"] => info.parameters.push(std::mem::take(pair)),
+                [b"area", rest @ ..] => {
+                    let area = match info.areas.last_mut() {
+                        Some(area) => area,
+                        None => return,
+                    };
+                    match rest {
+                        [b"areaDesc"] => area.area_desc = owned(),
+                        [b"polygon"] => area.polygons.extend(parse_polygon(text)),
+                        [b"geocode"] => area.geocodes.push(std::mem::take(pair)),
+                        _ => (),
+                    }
+                }
+                _ => (),
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Parse a CAP polygon: space-separated `latitude,longitude` pairs.
+fn parse_polygon(text: &str) -> Option<Vec<(f64, f64)>> {
+    let points = text
+        .split_whitespace()
+        .map(|point| {
+            let (latitude, longitude) = point.split_once(',')?;
+            Some((latitude.parse().ok()?, longitude.parse().ok()?))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if points.len() < 3 {
+        return None;
+    }
+    Some(points)
+}
@@ -0,0 +1,169 @@
+//! EMWIN filename parsing.
+//!
+//! Products in the EMWIN archives are named like
+//! `A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT`:
+//!
+//! * `A_` and the WMO abbreviated heading, with the spaces removed; see [`super::wmo`]
+//! * `C_` and the ICAO indicator of the center which compiled the file, usually `KWIN`
+//! * the date and time the center received the product, as `YYYYMMDDhhmmss` (UTC)
+//! * a sequence number, which wraps
+//! * a priority, from 1 (highest) to 4
+//! * the legacy EMWIN product name, usually a six-character AWIPS identifier (a three-character
+//!   product category and a three-character station) followed by a two-character state
+//! * an extension indicating the format
+
+use super::wmo::WmoHeading;
+use std::str::FromStr;
+use time::OffsetDateTime;
+
+/// The metadata embedded in an EMWIN filename.
+///
+/// With the `serde` feature, this implements `Serialize` and `Deserialize`, as a map of its
+/// fields.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProductId {
+    heading: WmoHeading,
+    center: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::time::system_time"))]
+    received_at: OffsetDateTime,
+    sequence: u32,
+    priority: u8,
+    name: String,
+    extension: String,
+}
+
+impl ProductId {
+    /// The WMO abbreviated heading.
+    pub fn heading(&self) -> &WmoHeading {
+        &self.heading
+    }
+
+    /// The center which compiled the file, e.g. `KWIN`.
+    pub fn center(&self) -> &str {
+        &self.center
+    }
+
+    /// When the compiling center received the product.
+    pub fn received_at(&self) -> OffsetDateTime {
+        self.received_at
+    }
+
+    /// When the product was issued, per the heading's `YYGGgg` field resolved against
+    /// [`received_at`](Self::received_at).
+    pub fn issued_at(&self) -> Option<OffsetDateTime> {
+        self.heading.issued_at(self.received_at)
+    }
+
+    /// The sequence number assigned by the compiling center.
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    /// The priority, from 1 (highest) to 4.
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// The legacy EMWIN product name, e.g. `AFDDVNIA`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The AWIPS identifier, e.g. `AFDDVN`: the first six characters of the
+    /// [`name`](Self::name).
+    pub fn awips_id(&self) -> &str {
+        self.name.get(..6).unwrap_or(&self.name)
+    }
+
+    /// The AWIPS product category, e.g. `AFD` for an area forecast discussion.
+    pub fn product_class(&self) -> &str {
+        self.name.get(..3).unwrap_or(&self.name)
+    }
+
+    /// The AWIPS station, e.g. `DVN` for Quad Cities.
+    ///
+    /// Not every product name follows the AWIPS pattern, so this is empty if the name is too
+    /// short, and may be meaningless for names like `METAR1US`.
+    pub fn station(&self) -> &str {
+        self.name.get(3..6).unwrap_or_default()
+    }
+
+    /// The filename extension, e.g. `TXT`.
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+}
+
+/// The error returned when a string is not a valid EMWIN filename.
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error("invalid EMWIN filename")]
+pub struct ParseProductIdError;
+
+impl FromStr for ProductId {
+    type Err = ParseProductIdError;
+
+    /// Parse an EMWIN filename.
+    ///
+    /// The heading is parsed as tolerantly as [`Product::wmo_heading`](crate::Product::wmo_heading)
+    /// does, but the remaining fields are required.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::product::id::ProductId;
+    ///
+    /// let id: ProductId = "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT"
+    ///     .parse()
+    ///     .unwrap();
+    /// assert_eq!(id.heading().to_string(), "FXUS63 KDVN 151152");
+    /// assert_eq!(id.center(), "KWIN");
+    /// assert_eq!((id.sequence(), id.priority()), (60735, 2));
+    /// assert_eq!((id.product_class(), id.station()), ("AFD", "DVN"));
+    /// assert_eq!(id.extension(), "TXT");
+    ///
+    /// assert!("RADALLUS.GIF".parse::<ProductId>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s).ok_or(ParseProductIdError)
+    }
+}
+
+impl ProductId {
+    fn parse(filename: &str) -> Option<Self> {
+        let heading = WmoHeading::from_filename(filename)?;
+
+        let mut parts = filename.splitn(6, '_');
+        let (_, _, c, center, received, rest) = (
+            parts.next()?,
+            parts.next()?,
+            parts.next()?,
+            parts.next()?,
+            parts.next()?,
+            parts.next()?,
+        );
+        if c != "C" || center.len() != 4 || !center.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return None;
+        }
+        let received_at = super::parse_received(received)?;
+
+        let (stem, extension) = rest.rsplit_once('.')?;
+        let mut fields = stem.splitn(3, '-');
+        let (sequence, priority, name) = (fields.next()?, fields.next()?, fields.next()?);
+        let sequence = super::digits(sequence, 0..sequence.len())?;
+        let priority = super::digits(priority, 0..1).filter(|_| priority.len() == 1)? as u8;
+        if name.is_empty() || !name.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return None;
+        }
+
+        Some(Self {
+            heading,
+            center: center.to_string(),
+            received_at,
+            sequence,
+            priority,
+            name: name.to_string(),
+            extension: extension.to_string(),
+        })
+    }
+}
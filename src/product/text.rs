@@ -0,0 +1,153 @@
+//! Decoding of NWS text products, and parsing of the structured content embedded in them.
+
+/// Find and decode the `LAT...LON` polygon in a product's text, returning `(latitude, longitude)`
+/// pairs in degrees.
+///
+/// Warnings describe the area they cover with a polygon like:
+///
+/// ```text
+/// LAT...LON 4155 8806 4154 8783 4139 8782
+///       4140 8805
+/// ```
+///
+/// Coordinates are in hundredths of a degree, latitude first. Longitudes are measured west of
+/// Greenwich, so they're negated here to follow the usual east-positive convention. Offices west of
+/// the date line (e.g. Guam) keep counting west past 180°, and those longitudes are brought back
+/// into the -180..180 range.
+///
+/// Returns `None` if there is no polygon, or if it is malformed or has fewer than three points.
+/// Only the first polygon is returned; segmented products may repeat one per segment.
+///
+/// # Example
+///
+/// ```
+/// use emwin_tg::product::text::parse_polygon;
+///
+/// let text = "\
+/// LAT...LON 4155 8806 4154 8783 4139 8782\r\r\n      4140 8805\r\r\n\
+/// TIME...MOT...LOC 2245Z 250DEG 35KT 4148 8799\r\r\n";
+///
+/// let polygon = parse_polygon(text).unwrap();
+/// assert_eq!(polygon.len(), 4);
+/// assert_eq!(polygon[0], (41.55, -88.06));
+/// assert_eq!(polygon[3], (41.40, -88.05));
+/// ```
+pub fn parse_polygon(body: &str) -> Option<Vec<(f64, f64)>> {
+    let mut lines = body.lines().map(|line| line.trim_end_matches(['\r', ' ']));
+    let first = lines.find_map(|line| line.strip_prefix("LAT...LON"))?;
+
+    // The coordinates continue on indented lines
+    let mut values = Vec::new();
+    let continuations = lines.take_while(|line| line.starts_with(' '));
+    for token in std::iter::once(first)
+        .chain(continuations)
+        .flat_map(str::split_whitespace)
+    {
+        if !token.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        values.push(token.parse::<u32>().ok()?);
+    }
+
+    if values.len() % 2 != 0 || values.len() < 6 {
+        return None;
+    }
+
+    values
+        .chunks(2)
+        .map(|pair| {
+            let latitude = f64::from(pair[0]) / 100.0;
+            let mut longitude = -f64::from(pair[1]) / 100.0;
+            if longitude < -180.0 {
+                longitude += 360.0;
+            }
+            if latitude <= 90.0 && (-180.0..=180.0).contains(&longitude) {
+                Some((latitude, longitude))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// A character encoding used by text products.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum TextEncoding {
+    /// 7-bit ASCII, which is almost everything
+    Ascii,
+    /// UTF-8, as used by CAP messages and some newer products
+    Utf8,
+    /// ISO-8859-1, for the occasional accented letter
+    Latin1,
+    /// IBM code page 437, from older DOS-era systems
+    Cp437,
+}
+
+impl TextEncoding {
+    /// Guess the encoding of `bytes`.
+    ///
+    /// ASCII and valid UTF-8 are recognized as such. Anything else is a single-byte encoding: bytes
+    /// in `0x80..=0x9F` are control characters in ISO-8859-1 and never appear in real text, but are
+    /// accented letters in CP437, so their presence means CP437. Otherwise it's ISO-8859-1.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::product::text::TextEncoding;
+    ///
+    /// assert_eq!(TextEncoding::detect(b"TORNADO WARNING"), TextEncoding::Ascii);
+    /// assert_eq!(TextEncoding::detect("Montréal".as_bytes()), TextEncoding::Utf8);
+    /// assert_eq!(TextEncoding::detect(b"Montr\xe9al"), TextEncoding::Latin1);
+    /// assert_eq!(TextEncoding::detect(b"Montr\x82al"), TextEncoding::Cp437);
+    /// ```
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.is_ascii() {
+            TextEncoding::Ascii
+        } else if std::str::from_utf8(bytes).is_ok() {
+            TextEncoding::Utf8
+        } else if bytes.iter().any(|b| (0x80..=0x9f).contains(b)) {
+            TextEncoding::Cp437
+        } else {
+            TextEncoding::Latin1
+        }
+    }
+
+    /// Decode `bytes` in this encoding.
+    ///
+    /// Single-byte encodings map every byte to some character. Invalid UTF-8 is replaced with
+    /// U+FFFD.
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            TextEncoding::Ascii | TextEncoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            TextEncoding::Latin1 => bytes.iter().map(|&b| char::from(b)).collect(),
+            TextEncoding::Cp437 => bytes
+                .iter()
+                .map(|&b| match b {
+                    0x00..=0x7f => char::from(b),
+                    _ => CP437_HIGH[usize::from(b - 0x80)],
+                })
+                .collect(),
+        }
+    }
+}
+
+/// CP437 characters `0x80..=0xFF`. The low half matches ASCII, which is how EMWIN uses it.
+const CP437_HIGH: [char; 128] = [
+    '\u{00C7}', '\u{00FC}', '\u{00E9}', '\u{00E2}', '\u{00E4}', '\u{00E0}', '\u{00E5}', '\u{00E7}',
+    '\u{00EA}', '\u{00EB}', '\u{00E8}', '\u{00EF}', '\u{00EE}', '\u{00EC}', '\u{00C4}', '\u{00C5}',
+    '\u{00C9}', '\u{00E6}', '\u{00C6}', '\u{00F4}', '\u{00F6}', '\u{00F2}', '\u{00FB}', '\u{00F9}',
+    '\u{00FF}', '\u{00D6}', '\u{00DC}', '\u{00A2}', '\u{00A3}', '\u{00A5}', '\u{20A7}', '\u{0192}',
+    '\u{00E1}', '\u{00ED}', '\u{00F3}', '\u{00FA}', '\u{00F1}', '\u{00D1}', '\u{00AA}', '\u{00BA}',
+    '\u{00BF}', '\u{2310}', '\u{00AC}', '\u{00BD}', '\u{00BC}', '\u{00A1}', '\u{00AB}', '\u{00BB}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{2561}', '\u{2562}', '\u{2556}',
+    '\u{2555}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255D}', '\u{255C}', '\u{255B}', '\u{2510}',
+    '\u{2514}', '\u{2534}', '\u{252C}', '\u{251C}', '\u{2500}', '\u{253C}', '\u{255E}', '\u{255F}',
+    '\u{255A}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256C}', '\u{2567}',
+    '\u{2568}', '\u{2564}', '\u{2565}', '\u{2559}', '\u{2558}', '\u{2552}', '\u{2553}', '\u{256B}',
+    '\u{256A}', '\u{2518}', '\u{250C}', '\u{2588}', '\u{2584}', '\u{258C}', '\u{2590}', '\u{2580}',
+    '\u{03B1}', '\u{00DF}', '\u{0393}', '\u{03C0}', '\u{03A3}', '\u{03C3}', '\u{00B5}', '\u{03C4}',
+    '\u{03A6}', '\u{0398}', '\u{03A9}', '\u{03B4}', '\u{221E}', '\u{03C6}', '\u{03B5}', '\u{2229}',
+    '\u{2261}', '\u{00B1}', '\u{2265}', '\u{2264}', '\u{2320}', '\u{2321}', '\u{00F7}', '\u{2248}',
+    '\u{00B0}', '\u{2219}', '\u{00B7}', '\u{221A}', '\u{207F}', '\u{00B2}', '\u{25A0}', '\u{00A0}',
+];
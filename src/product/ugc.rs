@@ -0,0 +1,288 @@
+//! Universal Geographic Code (UGC) parsing.
+//!
+//! Segmented NWS text products identify the counties and zones they affect with UGC strings like
+//! `MNZ060>063-070-071-210300-`. Each string lists a state and a county (`C`) or zone (`Z`)
+//! indicator, followed by individual numbers or `>`-separated inclusive ranges, and ends with the
+//! `DDHHMM` time at which the segment expires. A UGC string may wrap across several lines, and may
+//! switch states or kinds partway through by repeating the `SSC`/`SSZ` prefix.
+
+use std::fmt;
+use std::str::FromStr;
+use time::OffsetDateTime;
+
+/// Whether a UGC refers to a county or a forecast zone.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum UgcKind {
+    /// A county, parish, or independent city (`C`).
+    County,
+    /// A public, fire, or marine forecast zone (`Z`).
+    Zone,
+}
+
+/// A single county or zone code, e.g. `MNZ060`.
+///
+/// The number `0` denotes the entire state or area, which UGC strings spell as `000` or `ALL`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct UgcCode {
+    state: [u8; 2],
+    kind: UgcKind,
+    number: u16,
+}
+
+impl UgcCode {
+    /// The two-letter state or marine area identifier.
+    pub fn state(&self) -> &str {
+        std::str::from_utf8(&self.state).unwrap_or("")
+    }
+
+    /// Whether this code refers to a county or a zone.
+    pub fn kind(&self) -> UgcKind {
+        self.kind
+    }
+
+    /// The county FIPS code or zone number within the state.
+    pub fn number(&self) -> u16 {
+        self.number
+    }
+}
+
+impl fmt::Display for UgcCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            UgcKind::County => 'C',
+            UgcKind::Zone => 'Z',
+        };
+        write!(f, "{}{}{:03}", self.state(), kind, self.number)
+    }
+}
+
+/// The error returned when a string is not a valid [`UgcCode`].
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error("invalid UGC code")]
+pub struct ParseUgcCodeError;
+
+impl FromStr for UgcCode {
+    type Err = ParseUgcCodeError;
+
+    /// Parse a single code like `MNZ060`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::product::ugc::{UgcCode, UgcKind};
+    ///
+    /// let code: UgcCode = "ILC031".parse().unwrap();
+    /// assert_eq!(code.state(), "IL");
+    /// assert_eq!(code.kind(), UgcKind::County);
+    /// assert_eq!(code.number(), 31);
+    /// assert_eq!(code.to_string(), "ILC031");
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_prefixed(s) {
+            Some((prefix, number, None)) => Ok(prefix.with_number(number)),
+            _ => Err(ParseUgcCodeError),
+        }
+    }
+}
+
+/// A parsed UGC string: the affected codes and the expiration time.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Ugc {
+    /// The counties and zones listed, with ranges expanded.
+    pub codes: Vec<UgcCode>,
+    /// The day of month of expiration (UTC).
+    pub expires_day: u8,
+    /// The hour of expiration (UTC).
+    pub expires_hour: u8,
+    /// The minute of expiration (UTC).
+    pub expires_minute: u8,
+}
+
+impl Ugc {
+    /// The expiration time, resolving the day of month to the nearest matching date around
+    /// `reference`, usually when the product was issued.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::product::ugc;
+    /// use time::{Date, Month};
+    ///
+    /// let ugc = &ugc::parse("ILC031-045-010300-\r\r\n")[0];
+    /// let issued = Date::from_calendar_date(2022, Month::January, 31)
+    ///     .unwrap()
+    ///     .with_hms(22, 45, 0)
+    ///     .unwrap()
+    ///     .assume_utc();
+    /// let expires = Date::from_calendar_date(2022, Month::February, 1)
+    ///     .unwrap()
+    ///     .with_hms(3, 0, 0)
+    ///     .unwrap()
+    ///     .assume_utc();
+    /// assert_eq!(ugc.expires_at(issued), Some(expires));
+    /// ```
+    pub fn expires_at(&self, reference: OffsetDateTime) -> Option<OffsetDateTime> {
+        crate::time::resolve_day_time(
+            self.expires_day,
+            self.expires_hour,
+            self.expires_minute,
+            reference,
+        )
+    }
+}
+
+/// Find and parse every UGC string in a product's text.
+///
+/// Malformed UGC strings are skipped.
+///
+/// # Example
+///
+/// ```
+/// use emwin_tg::product::ugc;
+///
+/// let text = "\
+/// WWUS53 KLOT 202245\r\r\n\
+/// SVSLOT\r\r\n\
+/// \r\r\n\
+/// ILC031-043-\r\r\n\
+/// 197-210300-\r\r\n\
+/// \r\r\n\
+/// MNZ060>063-WIZ014-210300-\r\r\n";
+///
+/// let ugcs = ugc::parse(text);
+/// assert_eq!(ugcs.len(), 2);
+///
+/// let codes: Vec<String> = ugcs[0].codes.iter().map(|c| c.to_string()).collect();
+/// assert_eq!(codes, ["ILC031", "ILC043", "ILC197"]);
+/// assert_eq!((ugcs[0].expires_day, ugcs[0].expires_hour, ugcs[0].expires_minute), (21, 3, 0));
+///
+/// let codes: Vec<String> = ugcs[1].codes.iter().map(|c| c.to_string()).collect();
+/// assert_eq!(codes, ["MNZ060", "MNZ061", "MNZ062", "MNZ063", "WIZ014"]);
+/// ```
+pub fn parse(text: &str) -> Vec<Ugc> {
+    let mut out = Vec::new();
+    let mut lines = text.lines().map(|line| line.trim_end_matches(['\r', ' ']));
+
+    while let Some(line) = lines.next() {
+        if !starts_ugc(line) {
+            continue;
+        }
+
+        // Gather continuation lines until we see the expiration time
+        let mut joined = String::from(line);
+        while !has_expiration(&joined) {
+            match lines.next() {
+                Some(next) if joined.ends_with('-') && !next.is_empty() => joined.push_str(next),
+                _ => break,
+            }
+        }
+
+        if let Some(ugc) = parse_one(&joined) {
+            out.push(ugc);
+        }
+    }
+
+    out
+}
+
+fn starts_ugc(line: &str) -> bool {
+    parse_prefixed(line.split('-').next().unwrap_or("")).is_some()
+}
+
+fn has_expiration(joined: &str) -> bool {
+    joined
+        .split('-')
+        .any(|token| token.len() == 6 && token.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn parse_one(joined: &str) -> Option<Ugc> {
+    let mut codes = Vec::new();
+    let mut prefix: Option<Prefix> = None;
+
+    for token in joined.split('-') {
+        if token.len() == 6 && token.bytes().all(|b| b.is_ascii_digit()) {
+            let field = |i: usize| token[i..i + 2].parse::<u8>().ok();
+            return Some(Ugc {
+                codes,
+                expires_day: field(0)?,
+                expires_hour: field(2)?,
+                expires_minute: field(4)?,
+            });
+        }
+
+        let (start, end) = if let Some((new_prefix, start, end)) = parse_prefixed(token) {
+            prefix = Some(new_prefix);
+            (start, end)
+        } else {
+            parse_range(token)?
+        };
+
+        let prefix = prefix?;
+        codes.extend((start..=end.unwrap_or(start)).map(|n| prefix.with_number(n)));
+    }
+
+    // Ran out of tokens without seeing an expiration time
+    None
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Prefix {
+    state: [u8; 2],
+    kind: UgcKind,
+}
+
+impl Prefix {
+    fn with_number(self, number: u16) -> UgcCode {
+        UgcCode {
+            state: self.state,
+            kind: self.kind,
+            number,
+        }
+    }
+}
+
+/// Parse `SSCNNN` or `SSCNNN>NNN`, returning the prefix and the range.
+fn parse_prefixed(token: &str) -> Option<(Prefix, u16, Option<u16>)> {
+    let bytes = token.as_bytes();
+    if bytes.len() < 6 || !bytes[..2].iter().all(u8::is_ascii_uppercase) {
+        return None;
+    }
+    let kind = match bytes[2] {
+        b'C' => UgcKind::County,
+        b'Z' => UgcKind::Zone,
+        _ => return None,
+    };
+    let prefix = Prefix {
+        state: [bytes[0], bytes[1]],
+        kind,
+    };
+
+    if &token[3..] == "ALL" {
+        return Some((prefix, 0, None));
+    }
+    let (start, end) = parse_range(&token[3..])?;
+    Some((prefix, start, end))
+}
+
+/// Parse `NNN` or `NNN>NNN`.
+fn parse_range(token: &str) -> Option<(u16, Option<u16>)> {
+    let number = |s: &str| {
+        if s.len() == 3 && s.bytes().all(|b| b.is_ascii_digit()) {
+            s.parse::<u16>().ok()
+        } else {
+            None
+        }
+    };
+
+    match token.split_once('>') {
+        Some((start, end)) => {
+            let (start, end) = (number(start)?, number(end)?);
+            if start <= end {
+                Some((start, Some(end)))
+            } else {
+                None
+            }
+        }
+        None => Some((number(token)?, None)),
+    }
+}
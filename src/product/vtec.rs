@@ -0,0 +1,399 @@
+//! Valid Time Event Code (VTEC) parsing.
+//!
+//! Watches, warnings, and advisories carry a primary VTEC string (P-VTEC) on the line after each
+//! segment's UGC string, like `/O.NEW.KILX.TO.W.0012.220220T2245Z-220220T2330Z/`:
+//!
+//! * the product class, e.g. `O` for operational
+//! * the action, e.g. `NEW` for a new event
+//! * the issuing office, e.g. `KILX`
+//! * the phenomenon, e.g. `TO` for tornado, and significance, e.g. `W` for warning
+//! * the event tracking number (ETN), which identifies the event among others of its kind from
+//!   the same office that year
+//! * the times the event begins and ends, as `yymmddThhnnZ`, with zeros for "until further notice"
+//!
+//! Hydrologic products follow the P-VTEC with a hydrologic VTEC string (H-VTEC), like
+//! `/ILLI2.2.ER.220221T0600Z.220222T1800Z.220224T0000Z.NR/`, describing the flood at a forecast
+//! point.
+
+use std::fmt;
+use std::str::FromStr;
+use time::{Date, Month, OffsetDateTime};
+
+/// The class of a VTEC product.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ProductClass {
+    /// `O`: an operational product
+    Operational,
+    /// `T`: a test product
+    Test,
+    /// `E`: an experimental product
+    Experimental,
+    /// `X`: experimental VTEC in an operational product
+    ExperimentalInOperational,
+}
+
+/// What a VTEC string says is happening to the event.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Action {
+    /// `NEW`: a new event
+    New,
+    /// `CON`: the event continues unchanged
+    Continue,
+    /// `EXT`: the event's end time changed
+    ExtendTime,
+    /// `EXA`: the event was extended to more area
+    ExtendArea,
+    /// `EXB`: the event was extended both in time and area
+    ExtendBoth,
+    /// `UPG`: the event was upgraded, e.g. from a watch to a warning
+    Upgrade,
+    /// `CAN`: the event was cancelled
+    Cancel,
+    /// `EXP`: the event expired, or will soon
+    Expire,
+    /// `COR`: a correction to a previous product
+    Correct,
+    /// `ROU`: a routine product, not tied to an event
+    Routine,
+}
+
+/// How serious a VTEC event is.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Significance {
+    /// `W`: a warning
+    Warning,
+    /// `A`: a watch
+    Watch,
+    /// `Y`: an advisory
+    Advisory,
+    /// `S`: a statement
+    Statement,
+    /// `F`: a forecast
+    Forecast,
+    /// `O`: an outlook
+    Outlook,
+    /// `N`: a synopsis
+    Synopsis,
+}
+
+/// A primary VTEC string, e.g. `/O.NEW.KILX.TO.W.0012.220220T2245Z-220220T2330Z/`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct PVtec {
+    /// The product class
+    pub class: ProductClass,
+    /// What's happening to the event
+    pub action: Action,
+    /// The ICAO identifier of the issuing office, e.g. `KILX`
+    pub office: String,
+    /// The two-character phenomenon code, e.g. `TO` for tornado or `WS` for winter storm
+    pub phenomena: String,
+    /// Whether this is a warning, watch, advisory, etc.
+    pub significance: Significance,
+    /// The event tracking number
+    pub etn: u16,
+    /// When the event begins, or `None` if it's already in progress
+    pub begins: Option<OffsetDateTime>,
+    /// When the event ends, or `None` if it's until further notice
+    pub ends: Option<OffsetDateTime>,
+    /// The hydrologic VTEC string following this one, if any
+    pub hydrologic: Option<HVtec>,
+}
+
+/// A hydrologic VTEC string, e.g. `/ILLI2.2.ER.220221T0600Z.220222T1800Z.220224T0000Z.NR/`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct HVtec {
+    /// The NWS location identifier of the forecast point, e.g. `ILLI2`, or `00000` if there's
+    /// none
+    pub nwsli: String,
+    /// The flood severity: `N` (none), `0` (areal or flash flood), `1` (minor), `2` (moderate),
+    /// `3` (major), or `U` (unknown)
+    pub severity: char,
+    /// The two-character immediate cause, e.g. `ER` for excessive rainfall or `IJ` for an ice jam
+    pub immediate_cause: String,
+    /// When the flood begins, if known
+    pub begins: Option<OffsetDateTime>,
+    /// When the flood crests, if known
+    pub crest: Option<OffsetDateTime>,
+    /// When the flood ends, if known
+    pub ends: Option<OffsetDateTime>,
+    /// How the flood compares to the flood of record: `NO` (no record expected), `NR` (near
+    /// record or record), `UU` (no period of record), or `OO` (areal or flash flood)
+    pub flood_record: String,
+}
+
+/// Find and parse every P-VTEC string in a product's text, with the H-VTEC strings which follow
+/// them.
+///
+/// Segmented products repeat VTEC strings per segment, and they're returned in order, including
+/// repeats. Malformed VTEC strings are skipped.
+///
+/// # Example
+///
+/// ```
+/// use emwin_tg::product::vtec::{self, Action, Significance};
+///
+/// let text = "\
+/// WFUS53 KILX 202245\r\r\n\
+/// TORILX\r\r\n\
+/// \r\r\n\
+/// ILC039-113-202330-\r\r\n\
+/// /O.NEW.KILX.TO.W.0012.220220T2245Z-220220T2330Z/\r\r\n";
+///
+/// let events = vtec::parse(text);
+/// assert_eq!(events.len(), 1);
+/// assert_eq!(events[0].action, Action::New);
+/// assert_eq!(events[0].office, "KILX");
+/// assert_eq!(events[0].phenomena, "TO");
+/// assert_eq!(events[0].significance, Significance::Warning);
+/// assert_eq!(events[0].etn, 12);
+/// assert_eq!(events[0].ends.unwrap().hour(), 23);
+/// ```
+pub fn parse(text: &str) -> Vec<PVtec> {
+    let mut out: Vec<PVtec> = Vec::new();
+    let mut last_was_pvtec = false;
+    for line in text.lines() {
+        let line = line.trim_matches(|c: char| c.is_ascii_control() || c == ' ');
+        if let Ok(pvtec) = line.parse::<PVtec>() {
+            out.push(pvtec);
+            last_was_pvtec = true;
+            continue;
+        }
+        if last_was_pvtec {
+            if let (Some(last), Ok(hvtec)) = (out.last_mut(), line.parse::<HVtec>()) {
+                last.hydrologic = Some(hvtec);
+            }
+        }
+        last_was_pvtec = false;
+    }
+    out
+}
+
+/// The error returned when a string is not a valid VTEC string.
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error("invalid VTEC string")]
+pub struct ParseVtecError;
+
+impl FromStr for PVtec {
+    type Err = ParseVtecError;
+
+    /// Parse a P-VTEC string, with or without its enclosing slashes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s).ok_or(ParseVtecError)
+    }
+}
+
+impl PVtec {
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim_start_matches('/').trim_end_matches('/');
+        let mut fields = s.split('.');
+        let class = match fields.next()? {
+            "O" => ProductClass::Operational,
+            "T" => ProductClass::Test,
+            "E" => ProductClass::Experimental,
+            "X" => ProductClass::ExperimentalInOperational,
+            _ => return None,
+        };
+        let action = match fields.next()? {
+            "NEW" => Action::New,
+            "CON" => Action::Continue,
+            "EXT" => Action::ExtendTime,
+            "EXA" => Action::ExtendArea,
+            "EXB" => Action::ExtendBoth,
+            "UPG" => Action::Upgrade,
+            "CAN" => Action::Cancel,
+            "EXP" => Action::Expire,
+            "COR" => Action::Correct,
+            "ROU" => Action::Routine,
+            _ => return None,
+        };
+        let office = fields.next().filter(|office| is_code(office, 4))?;
+        let phenomena = fields.next().filter(|phenomena| is_code(phenomena, 2))?;
+        let significance = match fields.next()? {
+            "W" => Significance::Warning,
+            "A" => Significance::Watch,
+            "Y" => Significance::Advisory,
+            "S" => Significance::Statement,
+            "F" => Significance::Forecast,
+            "O" => Significance::Outlook,
+            "N" => Significance::Synopsis,
+            _ => return None,
+        };
+        let etn = fields
+            .next()
+            .filter(|etn| etn.len() == 4)
+            .and_then(|etn| super::digits(etn, 0..4))? as u16;
+        let (begins, ends) = fields.next()?.split_once('-')?;
+        if fields.next().is_some() {
+            return None;
+        }
+
+        Some(Self {
+            class,
+            action,
+            office: office.to_string(),
+            phenomena: phenomena.to_string(),
+            significance,
+            etn,
+            begins: parse_time(begins)?,
+            ends: parse_time(ends)?,
+            hydrologic: None,
+        })
+    }
+}
+
+impl fmt::Display for PVtec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let class = match self.class {
+            ProductClass::Operational => 'O',
+            ProductClass::Test => 'T',
+            ProductClass::Experimental => 'E',
+            ProductClass::ExperimentalInOperational => 'X',
+        };
+        let action = match self.action {
+            Action::New => "NEW",
+            Action::Continue => "CON",
+            Action::ExtendTime => "EXT",
+            Action::ExtendArea => "EXA",
+            Action::ExtendBoth => "EXB",
+            Action::Upgrade => "UPG",
+            Action::Cancel => "CAN",
+            Action::Expire => "EXP",
+            Action::Correct => "COR",
+            Action::Routine => "ROU",
+        };
+        let significance = match self.significance {
+            Significance::Warning => 'W',
+            Significance::Watch => 'A',
+            Significance::Advisory => 'Y',
+            Significance::Statement => 'S',
+            Significance::Forecast => 'F',
+            Significance::Outlook => 'O',
+            Significance::Synopsis => 'N',
+        };
+        write!(
+            f,
+            "/{}.{}.{}.{}.{}.{:04}.{}-{}/",
+            class,
+            action,
+            self.office,
+            self.phenomena,
+            significance,
+            self.etn,
+            Time(self.begins),
+            Time(self.ends)
+        )
+    }
+}
+
+impl FromStr for HVtec {
+    type Err = ParseVtecError;
+
+    /// Parse an H-VTEC string, with or without its enclosing slashes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::product::vtec::HVtec;
+    ///
+    /// let hvtec: HVtec = "/ILLI2.2.ER.220221T0600Z.220222T1800Z.000000T0000Z.NR/"
+    ///     .parse()
+    ///     .unwrap();
+    /// assert_eq!((hvtec.nwsli.as_str(), hvtec.severity), ("ILLI2", '2'));
+    /// assert_eq!(hvtec.crest.unwrap().day(), 22);
+    /// assert_eq!(hvtec.ends, None);
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s).ok_or(ParseVtecError)
+    }
+}
+
+impl HVtec {
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim_start_matches('/').trim_end_matches('/');
+        let fields: Vec<&str> = s.split('.').collect();
+        let (nwsli, severity, cause, begins, crest, ends, record) = match fields[..] {
+            [a, b, c, d, e, f, g] => (a, b, c, d, e, f, g),
+            _ => return None,
+        };
+        let severity = match severity.as_bytes() {
+            [b @ (b'N' | b'0' | b'1' | b'2' | b'3' | b'U')] => char::from(*b),
+            _ => return None,
+        };
+        if !is_code(nwsli, 5) || !is_code(cause, 2) || !is_code(record, 2) {
+            return None;
+        }
+
+        Some(Self {
+            nwsli: nwsli.to_string(),
+            severity,
+            immediate_cause: cause.to_string(),
+            begins: parse_time(begins)?,
+            crest: parse_time(crest)?,
+            ends: parse_time(ends)?,
+            flood_record: record.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for HVtec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "/{}.{}.{}.{}.{}.{}.{}/",
+            self.nwsli,
+            self.severity,
+            self.immediate_cause,
+            Time(self.begins),
+            Time(self.crest),
+            Time(self.ends),
+            self.flood_record
+        )
+    }
+}
+
+/// Whether `field` is `len` uppercase letters or digits.
+fn is_code(field: &str, len: usize) -> bool {
+    field.len() == len
+        && field
+            .bytes()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+}
+
+/// Parse a `yymmddThhnnZ` time, returning `Some(None)` for the all-zeros placeholder.
+fn parse_time(field: &str) -> Option<Option<OffsetDateTime>> {
+    if field == "000000T0000Z" {
+        return Some(None);
+    }
+    if field.len() != 12 || field.as_bytes()[6] != b'T' || !field.ends_with('Z') {
+        return None;
+    }
+    let year = 2000 + super::digits(field, 0..2)? as i32;
+    let month = Month::try_from(super::digits(field, 2..4)? as u8).ok()?;
+    let day = super::digits(field, 4..6)? as u8;
+    let hour = super::digits(field, 7..9)? as u8;
+    let minute = super::digits(field, 9..11)? as u8;
+    let date = Date::from_calendar_date(year, month, day).ok()?;
+    Some(Some(date.with_hms(hour, minute, 0).ok()?.assume_utc()))
+}
+
+/// Formats an optional time as `yymmddThhnnZ`.
+struct Time(Option<OffsetDateTime>);
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(time) => write!(
+                f,
+                "{:02}{:02}{:02}T{:02}{:02}Z",
+                time.year() % 100,
+                u8::from(time.month()),
+                time.day(),
+                time.hour(),
+                time.minute()
+            ),
+            None => f.write_str("000000T0000Z"),
+        }
+    }
+}
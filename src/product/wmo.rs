@@ -0,0 +1,339 @@
+//! WMO abbreviated heading parsing.
+//!
+//! Products distributed over EMWIN carry a WMO abbreviated heading like `FXUS63 KDVN 151152`:
+//!
+//! * `TTAAii`, the data type designators `T1` and `T2`, the geographical designator `A1A2`, and a
+//!   two-digit number `ii` distinguishing otherwise-similar bulletins
+//! * `CCCC`, the ICAO location indicator of the originating center
+//! * `YYGGgg`, the day of month, hour, and minute of issuance (UTC)
+//! * optionally `BBB`, an indicator for corrections (`CCx`), amendments (`AAx`), and delayed
+//!   (`RRx`) bulletins
+//!
+//! EMWIN filenames embed the heading with the spaces removed, e.g.
+//! `A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT`, and text products begin with
+//! it. [`Product::wmo_heading`](crate::Product::wmo_heading) reads the former, and
+//! [`WmoHeading::from_contents`] the latter.
+
+use std::fmt;
+use std::str::FromStr;
+use time::OffsetDateTime;
+
+/// What a heading's `BBB` indicator says about the bulletin.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum BbbKind {
+    /// `RRx`: a delayed or repeated transmission of a bulletin
+    Delayed,
+    /// `CCx`: a correction to a previously issued bulletin
+    Correction,
+    /// `AAx`: an amendment to a previously issued bulletin
+    Amendment,
+    /// `Pxx`: one segment of a bulletin too large to send at once
+    Segment,
+}
+
+/// A WMO abbreviated heading, e.g. `FXUS63 KDVN 151152 CCA`.
+///
+/// Headings are occasionally damaged in transmission. As long as the `TTAAii` field is intact, a
+/// heading is parsed even if the `CCCC` or `YYGGgg` fields are missing or garbled, in which case
+/// those fields are `None`.
+///
+/// With the `serde` feature, this implements `Serialize` and `Deserialize`, as a string like
+/// `FXUS63 KDVN 151152`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct WmoHeading {
+    ttaaii: [u8; 6],
+    cccc: Option<[u8; 4]>,
+    yygggg: Option<[u8; 6]>,
+    bbb: Option<[u8; 3]>,
+}
+
+impl WmoHeading {
+    /// The data type designator `T1`, e.g. `F` for forecasts, `W` for warnings, or `S` for
+    /// surface observations.
+    pub fn t1(&self) -> char {
+        char::from(self.ttaaii[0])
+    }
+
+    /// The data type designator `T2`, which refines `T1`.
+    pub fn t2(&self) -> char {
+        char::from(self.ttaaii[1])
+    }
+
+    /// The full `TTAAii` data designator.
+    pub fn ttaaii(&self) -> &str {
+        as_str(&self.ttaaii)
+    }
+
+    /// The `CCCC` originating center, if present.
+    pub fn cccc(&self) -> Option<&str> {
+        self.cccc.as_ref().map(|cccc| as_str(cccc))
+    }
+
+    /// The `YYGGgg` issuance day, hour, and minute, if present.
+    pub fn yygggg(&self) -> Option<&str> {
+        self.yygggg.as_ref().map(|yygggg| as_str(yygggg))
+    }
+
+    /// The `BBB` indicator, if any.
+    pub fn bbb(&self) -> Option<&str> {
+        self.bbb.as_ref().map(|bbb| as_str(bbb))
+    }
+
+    /// What the `BBB` indicator says about the bulletin, if it's present and recognized.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::product::wmo::{BbbKind, WmoHeading};
+    ///
+    /// let heading: WmoHeading = "WFUS53 KLOT 202245 CCA".parse().unwrap();
+    /// assert_eq!(heading.bbb_kind(), Some(BbbKind::Correction));
+    ///
+    /// let heading: WmoHeading = "WFUS53 KLOT 202245".parse().unwrap();
+    /// assert_eq!(heading.bbb_kind(), None);
+    /// ```
+    pub fn bbb_kind(&self) -> Option<BbbKind> {
+        match self.bbb? {
+            [b'R', b'R', _] => Some(BbbKind::Delayed),
+            [b'C', b'C', _] => Some(BbbKind::Correction),
+            [b'A', b'A', _] => Some(BbbKind::Amendment),
+            [b'P', _, _] => Some(BbbKind::Segment),
+            _ => None,
+        }
+    }
+
+    /// The issuance time, resolving the `YYGGgg` day of month to the nearest matching date around
+    /// `reference`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::product::wmo::WmoHeading;
+    /// use time::{Date, Month};
+    ///
+    /// let heading: WmoHeading = "FXUS63 KDVN 312359".parse().unwrap();
+    /// let now = Date::from_calendar_date(2022, Month::February, 1)
+    ///     .unwrap()
+    ///     .with_hms(0, 5, 0)
+    ///     .unwrap()
+    ///     .assume_utc();
+    /// let issued = Date::from_calendar_date(2022, Month::January, 31)
+    ///     .unwrap()
+    ///     .with_hms(23, 59, 0)
+    ///     .unwrap()
+    ///     .assume_utc();
+    /// assert_eq!(heading.issued_at(now), Some(issued));
+    /// ```
+    pub fn issued_at(&self, reference: OffsetDateTime) -> Option<OffsetDateTime> {
+        let yygggg = self.yygggg()?;
+        crate::time::resolve_day_time(
+            super::digits(yygggg, 0..2)? as u8,
+            super::digits(yygggg, 2..4)? as u8,
+            super::digits(yygggg, 4..6)? as u8,
+            reference,
+        )
+    }
+
+    /// Parse the heading embedded in an EMWIN filename.
+    pub(crate) fn from_filename(filename: &str) -> Option<Self> {
+        let mut parts = filename.split('_');
+        if parts.next()? != "A" {
+            return None;
+        }
+        let heading = parts.next()?;
+        if !heading.is_ascii() || heading.len() > 19 {
+            return None;
+        }
+
+        // The fields are fixed-width, so each can be judged on its own
+        let field = |range: std::ops::Range<usize>| heading.get(range).unwrap_or_default();
+        Some(Self {
+            ttaaii: parse_ttaaii(heading.get(0..6)?)?,
+            cccc: parse_cccc(field(6..10)),
+            yygggg: parse_yygggg(field(10..16)),
+            bbb: parse_bbb(heading.get(16..).unwrap_or_default()),
+        })
+    }
+
+    /// Find the heading near the start of a text product.
+    ///
+    /// Text products begin with the heading, possibly preceded by a start-of-header byte and a
+    /// sequence number line. A line is only taken as a heading if it has a `CCCC` or `YYGGgg`
+    /// field, to avoid mistaking a lone word for one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::product::wmo::WmoHeading;
+    ///
+    /// let contents = b"\x01\r\r\n123 \r\r\nWUUS53 KLOT 202245 RRA\r\r\nSVRLOT\r\r\n";
+    /// let heading = WmoHeading::from_contents(contents).unwrap();
+    /// assert_eq!(heading.to_string(), "WUUS53 KLOT 202245 RRA");
+    ///
+    /// assert_eq!(WmoHeading::from_contents(b"GIF89a"), None);
+    /// ```
+    pub fn from_contents(contents: &[u8]) -> Option<Self> {
+        Self::split_contents(contents).map(|(heading, _)| heading)
+    }
+
+    /// Find the heading near the start of a text product, and the contents following its line.
+    pub(crate) fn split_contents(contents: &[u8]) -> Option<(Self, &[u8])> {
+        let mut offset = 0;
+        for line in contents.split_inclusive(|b| *b == b'\n').take(4) {
+            offset += line.len();
+            let heading = std::str::from_utf8(line)
+                .ok()
+                .map(|line| line.trim_matches(|c: char| c.is_ascii_control() || c == ' '))
+                .and_then(|line| line.parse::<Self>().ok())
+                .filter(|heading| heading.cccc.is_some() || heading.yygggg.is_some());
+            if let Some(heading) = heading {
+                return Some((heading, &contents[offset..]));
+            }
+        }
+        None
+    }
+
+    /// The same heading without its `BBB` indicator.
+    pub(crate) fn without_bbb(self) -> Self {
+        Self { bbb: None, ..self }
+    }
+}
+
+fn as_str(field: &[u8]) -> &str {
+    std::str::from_utf8(field).unwrap_or("")
+}
+
+fn parse_ttaaii(field: &str) -> Option<[u8; 6]> {
+    let field: [u8; 6] = field.as_bytes().try_into().ok()?;
+    if field[..4].iter().all(u8::is_ascii_uppercase) && field[4..].iter().all(u8::is_ascii_digit) {
+        Some(field)
+    } else {
+        None
+    }
+}
+
+fn parse_cccc(field: &str) -> Option<[u8; 4]> {
+    let field: [u8; 4] = field.as_bytes().try_into().ok()?;
+    if field
+        .iter()
+        .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+    {
+        Some(field)
+    } else {
+        None
+    }
+}
+
+fn parse_yygggg(field: &str) -> Option<[u8; 6]> {
+    let field: [u8; 6] = field.as_bytes().try_into().ok()?;
+    if field.iter().all(u8::is_ascii_digit) {
+        Some(field)
+    } else {
+        None
+    }
+}
+
+fn parse_bbb(field: &str) -> Option<[u8; 3]> {
+    let field: [u8; 3] = field.as_bytes().try_into().ok()?;
+    if field.iter().all(u8::is_ascii_uppercase) {
+        Some(field)
+    } else {
+        None
+    }
+}
+
+impl fmt::Display for WmoHeading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.ttaaii())?;
+        for field in [self.cccc(), self.yygggg(), self.bbb()].iter().flatten() {
+            write!(f, " {}", field)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for WmoHeading {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for WmoHeading {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// The error returned when a string is not a valid [`WmoHeading`].
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error("invalid WMO heading")]
+pub struct ParseWmoHeadingError;
+
+impl FromStr for WmoHeading {
+    type Err = ParseWmoHeadingError;
+
+    /// Parse a heading like `FXUS63 KDVN 151152` or `WFUS53 KLOT 202245 CCA`.
+    ///
+    /// The `TTAAii` field is required. Missing or garbled `CCCC` and `YYGGgg` fields are
+    /// tolerated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::product::wmo::WmoHeading;
+    ///
+    /// let heading: WmoHeading = "WFUS53 KLOT 202245 CCA".parse().unwrap();
+    /// assert_eq!((heading.t1(), heading.t2()), ('W', 'F'));
+    /// assert_eq!(heading.cccc(), Some("KLOT"));
+    /// assert_eq!(heading.bbb(), Some("CCA"));
+    /// assert_eq!(heading.to_string(), "WFUS53 KLOT 202245 CCA");
+    ///
+    /// let garbled: WmoHeading = "WFUS53 KLOT 20#245".parse().unwrap();
+    /// assert_eq!((garbled.t1(), garbled.t2()), ('W', 'F'));
+    /// assert_eq!(garbled.cccc(), Some("KLOT"));
+    /// assert_eq!(garbled.yygggg(), None);
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_ascii_whitespace();
+        let ttaaii = fields
+            .next()
+            .and_then(parse_ttaaii)
+            .ok_or(ParseWmoHeadingError)?;
+        let rest: Vec<&str> = fields.collect();
+        if rest.len() > 3 {
+            return Err(ParseWmoHeadingError);
+        }
+
+        // Take each field in order if it's recognizable, skipping over damaged ones
+        let mut heading = Self {
+            ttaaii,
+            cccc: None,
+            yygggg: None,
+            bbb: None,
+        };
+        let mut position = 0;
+        for field in rest {
+            if position < 1 && parse_cccc(field).is_some() {
+                heading.cccc = parse_cccc(field);
+                position = 1;
+            } else if position < 2 && parse_yygggg(field).is_some() {
+                heading.yygggg = parse_yygggg(field);
+                position = 2;
+            } else if position < 3 && parse_bbb(field).is_some() {
+                heading.bbb = parse_bbb(field);
+                position = 3;
+            } else if field.len() <= 6 && position < 3 {
+                // A damaged field
+                position += 1;
+            } else {
+                return Err(ParseWmoHeadingError);
+            }
+        }
+
+        Ok(heading)
+    }
+}
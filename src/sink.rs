@@ -0,0 +1,13 @@
+//! Destinations for received products.
+
+mod dir;
+#[cfg(feature = "serde")]
+mod jsonl;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use dir::DirSink;
+#[cfg(feature = "serde")]
+pub use jsonl::JsonlWriter;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteSink;
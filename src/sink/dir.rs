@@ -0,0 +1,125 @@
+use crate::{Error, Product};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
+
+/// Writes each product to its own file, mirroring the layout of an EMWIN archive on disk.
+///
+/// Products are written under the root directory by their
+/// [normalized filename](Product::normalize_filename), in subdirectories by the UTC date EMWIN
+/// [received](Product::received_at) them, e.g.
+/// `mirror/2022/02/15/A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT`.
+///
+/// Each file is written to a temporary file alongside it and renamed into place, so readers never
+/// see a partial product. Products whose file already exists are skipped, so a product which
+/// appears in several archives, or which is received again after a restart, is only written once.
+///
+/// # Example
+///
+/// ```
+/// use emwin_tg::{sink::DirSink, Product};
+///
+/// let root = std::env::temp_dir().join("emwin-tg-dir-sink-example");
+/// let mut sink = DirSink::new(&root);
+/// let product = Product::new(
+///     "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+///     "Area Forecast Discussion",
+/// );
+/// assert!(sink.write(&product).unwrap());
+/// assert!(!sink.write(&product).unwrap());
+/// assert!(root
+///     .join("2022/02/15/A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT")
+///     .exists());
+/// # std::fs::remove_dir_all(&root).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct DirSink {
+    root: PathBuf,
+    dated: bool,
+}
+
+impl DirSink {
+    /// Write under `root`, creating directories as needed.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            dated: true,
+        }
+    }
+
+    /// Write every product directly into the root directory, without dated subdirectories.
+    pub fn flat(mut self) -> Self {
+        self.dated = false;
+        self
+    }
+
+    /// The path at which `product` is or would be stored.
+    pub fn path_for(&self, product: &Product) -> PathBuf {
+        let mut path = self.root.clone();
+        if self.dated {
+            let date = OffsetDateTime::from(product.received_at).date();
+            path.push(format!("{:04}", date.year()));
+            path.push(format!("{:02}", u8::from(date.month())));
+            path.push(format!("{:02}", date.day()));
+        }
+        path.push(product.normalize_filename());
+        path
+    }
+
+    /// Store a product.
+    ///
+    /// Returns `false` if its file already exists.
+    pub fn write(&mut self, product: &Product) -> Result<bool, Error> {
+        let name = product.normalize_filename();
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("can't store a product named {:?}", product.filename),
+            )
+            .into());
+        }
+
+        let path = self.path_for(product);
+        if path.exists() {
+            return Ok(false);
+        }
+        let dir = path.parent().expect("path has a parent");
+        std::fs::create_dir_all(dir)?;
+
+        let temp = dir.join(format!(".{}.tmp", name));
+        let written = File::create(&temp)
+            .and_then(|mut file| {
+                file.write_all(&product.contents)?;
+                file.sync_all()
+            })
+            .and_then(|()| std::fs::rename(&temp, &path));
+        if let Err(e) = written {
+            std::fs::remove_file(&temp).ok();
+            return Err(e.into());
+        }
+        log::debug!("wrote {}", path.display());
+        Ok(true)
+    }
+
+    /// Store each product from `stream` until it ends.
+    ///
+    /// Errors from the stream are logged and skipped, since streams retry on their own. Returns
+    /// the first error writing a product.
+    pub async fn write_stream<S>(&mut self, mut stream: S) -> Result<(), Error>
+    where
+        S: futures::Stream<Item = Result<Product, Error>> + Unpin,
+    {
+        use futures::StreamExt;
+
+        while let Some(result) = stream.next().await {
+            match result {
+                Ok(product) => {
+                    self.write(&product)?;
+                }
+                Err(e) => log::warn!("not writing: {}", e),
+            }
+        }
+        Ok(())
+    }
+}
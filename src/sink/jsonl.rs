@@ -0,0 +1,223 @@
+use crate::{Error, Product};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Appends products to a file, or another writer, as JSON Lines, one object per product.
+///
+/// Each line is an object with these fields:
+///
+/// | Field         | Contents                                                         |
+/// |---------------|------------------------------------------------------------------|
+/// | `filename`    | The product's filename                                           |
+/// | `received_at` | When EMWIN [received](Product::received_at) it, in Unix seconds  |
+/// | `issued_at`   | The [issuance time](Product::issued_at), in Unix seconds, or null |
+/// | `wmo_heading` | `TTAAii CCCC DDHHMM [BBB]`, from the filename, or null           |
+/// | `pil`         | The AWIPS identifier, from the filename, or null                 |
+/// | `mime_type`   | The [MIME type](Product::mime_type), or null                     |
+/// | `body`        | The contents, if they're valid UTF-8                             |
+/// | `body_base64` | Otherwise, the contents in base64                                |
+///
+/// Each line is written with a single call and flushed, so a crash loses at most the product being
+/// written. Optionally, a file is rotated once it reaches a given size.
+///
+/// Requires the `serde` feature.
+///
+/// # Example
+///
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use emwin_tg::sink::JsonlWriter;
+///
+/// let mut writer = JsonlWriter::open("products.jsonl")
+///     .unwrap()
+///     .rotate_at(64 << 20)
+///     .keep(10);
+/// writer.write_stream(emwin_tg::TextStream::new()).await.unwrap();
+/// # })
+/// ```
+pub struct JsonlWriter {
+    out: Box<dyn Write + Send>,
+    /// The file being written, if any
+    path: Option<PathBuf>,
+    size: u64,
+    rotate_at: Option<u64>,
+    keep: usize,
+}
+
+impl std::fmt::Debug for JsonlWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JsonlWriter")
+            .field("path", &self.path)
+            .field("size", &self.size)
+            .field("rotate_at", &self.rotate_at)
+            .field("keep", &self.keep)
+            .finish()
+    }
+}
+
+impl JsonlWriter {
+    /// Append to the file at `path`, creating it if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let file = open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: Some(path),
+            size,
+            ..Self::new(file)
+        })
+    }
+
+    /// Write to `out`, e.g. standard output.
+    ///
+    /// Only files opened with [`open`](Self::open) are rotated.
+    pub fn new<W: Write + Send + 'static>(out: W) -> Self {
+        Self {
+            out: Box::new(out),
+            path: None,
+            size: 0,
+            rotate_at: None,
+            keep: 5,
+        }
+    }
+
+    /// Rotate the file before it would grow past `size` bytes.
+    ///
+    /// The file is renamed by appending `.1`, earlier rotations are renumbered, and a new file is
+    /// started. A single product larger than `size` is still written, alone in its file.
+    pub fn rotate_at(mut self, size: u64) -> Self {
+        self.rotate_at = Some(size);
+        self
+    }
+
+    /// Keep `count` rotated files, deleting older ones. Defaults to 5.
+    pub fn keep(mut self, count: usize) -> Self {
+        self.keep = count;
+        self
+    }
+
+    /// Append a product.
+    pub fn write(&mut self, product: &Product) -> Result<(), Error> {
+        let mut line = serde_json::to_vec(&Record::new(product))?;
+        line.push(b'\n');
+
+        let len = line.len() as u64;
+        if let (Some(path), Some(limit)) = (&self.path, self.rotate_at) {
+            if self.size > 0 && self.size + len > limit {
+                let path = path.clone();
+                self.rotate(&path)?;
+            }
+        }
+        self.out.write_all(&line)?;
+        self.out.flush()?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Append each product from `stream` until it ends.
+    ///
+    /// Errors from the stream are logged and skipped, since streams retry on their own. Returns
+    /// the first error writing to the file.
+    pub async fn write_stream<S>(&mut self, mut stream: S) -> Result<(), Error>
+    where
+        S: futures::Stream<Item = Result<Product, Error>> + Unpin,
+    {
+        use futures::StreamExt;
+
+        while let Some(result) = stream.next().await {
+            match result {
+                Ok(product) => self.write(&product)?,
+                Err(e) => log::warn!("not writing: {}", e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Rename the file at `path` out of the way and start a new one.
+    fn rotate(&mut self, path: &Path) -> Result<(), Error> {
+        log::debug!("rotating {}", path.display());
+        if self.keep == 0 {
+            std::fs::remove_file(path)?;
+        } else {
+            for n in (1..self.keep).rev() {
+                let from = rotated(path, n);
+                if from.exists() {
+                    std::fs::rename(&from, rotated(path, n + 1))?;
+                }
+            }
+            std::fs::rename(path, rotated(path, 1))?;
+        }
+        self.out = Box::new(open(path)?);
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open(path: &Path) -> std::io::Result<std::fs::File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// `path` with `.n` appended.
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    name.into()
+}
+
+/// One line of the file.
+#[derive(serde::Serialize)]
+struct Record<'a> {
+    filename: &'a str,
+    received_at: Option<u64>,
+    issued_at: Option<i64>,
+    wmo_heading: Option<String>,
+    pil: Option<&'a str>,
+    mime_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body_base64: Option<String>,
+}
+
+impl<'a> Record<'a> {
+    fn new(product: &'a Product) -> Self {
+        let body = product.as_str_checked().ok();
+        Self {
+            filename: &product.filename,
+            received_at: product
+                .received_at
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs()),
+            issued_at: product.issued_at().map(|t| t.unix_timestamp()),
+            wmo_heading: product.wmo_heading().map(|heading| heading.to_string()),
+            pil: product.filename_pil(),
+            mime_type: product.mime_type(),
+            body,
+            body_base64: body.is_none().then(|| base64(&product.contents)),
+        }
+    }
+}
+
+/// Encode `bytes` as standard, padded base64.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,106 @@
+use crate::{Error, Product};
+use std::path::Path;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS products (
+    bulletin_key TEXT PRIMARY KEY NOT NULL,
+    filename TEXT NOT NULL,
+    wmo_heading TEXT,
+    pil TEXT,
+    issued_at INTEGER,
+    contents BLOB NOT NULL
+)";
+
+/// Writes products into a SQLite table.
+///
+/// Each product becomes a row in the `products` table:
+///
+/// | Column         | Contents                                                       |
+/// |----------------|----------------------------------------------------------------|
+/// | `bulletin_key` | The month of issue, WMO heading and PIL, or else the filename  |
+/// | `filename`     | The product's filename                                         |
+/// | `wmo_heading`  | `TTAAii CCCC DDHHMM [BBB]`, from the filename                  |
+/// | `pil`          | The AWIPS identifier, from the filename                        |
+/// | `issued_at`    | The [issuance time](Product::issued_at), in Unix seconds       |
+/// | `contents`     | The product's contents                                         |
+///
+/// Products whose bulletin key is already in the table are skipped, so a product which appears
+/// in several archives, or which is received again after a restart, is only stored once. The
+/// heading's `DDHHMM` repeats every month, so the key starts with the year and month of issue,
+/// e.g. `2022-02 FXUS63 KDVN 151152 AFDDVNIA`.
+///
+/// Requires the `sqlite` feature.
+///
+/// # Example
+///
+/// ```
+/// use emwin_tg::{sink::SqliteSink, Product};
+///
+/// let mut sink = SqliteSink::open_in_memory().unwrap();
+/// let product = Product::new(
+///     "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+///     "Area Forecast Discussion",
+/// );
+/// assert!(sink.insert(&product).unwrap());
+/// assert!(!sink.insert(&product).unwrap());
+/// ```
+#[derive(Debug)]
+pub struct SqliteSink {
+    connection: rusqlite::Connection,
+}
+
+impl SqliteSink {
+    /// Open or create a database at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_connection(rusqlite::Connection::open(path)?)
+    }
+
+    /// Create a database in memory.
+    pub fn open_in_memory() -> Result<Self, Error> {
+        Self::from_connection(rusqlite::Connection::open_in_memory()?)
+    }
+
+    /// Write into an existing connection, creating the `products` table if needed.
+    pub fn from_connection(connection: rusqlite::Connection) -> Result<Self, Error> {
+        connection.execute(SCHEMA, [])?;
+        Ok(Self { connection })
+    }
+
+    /// The underlying connection, for querying stored products.
+    pub fn connection(&self) -> &rusqlite::Connection {
+        &self.connection
+    }
+
+    /// Store a product.
+    ///
+    /// Returns `false` if a product with the same bulletin key was already stored.
+    pub fn insert(&mut self, product: &Product) -> Result<bool, Error> {
+        let wmo_heading = product.wmo_heading().map(|heading| heading.to_string());
+        let pil = product.filename_pil();
+        let bulletin_key = match (product.bulletin_key(), product.issued_at()) {
+            (Some(key), Some(issued_at)) => format!(
+                "{:04}-{:02} {}",
+                issued_at.year(),
+                u8::from(issued_at.month()),
+                key
+            ),
+            (Some(key), None) => key,
+            (None, _) => product.normalize_filename(),
+        };
+
+        let inserted = self.connection.execute(
+            "INSERT OR IGNORE INTO products
+                (bulletin_key, filename, wmo_heading, pil, issued_at, contents)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![
+                bulletin_key,
+                product.filename,
+                wmo_heading,
+                pil,
+                product.issued_at().map(|t| t.unix_timestamp()),
+                &product.contents[..],
+            ],
+        )?;
+        Ok(inserted > 0)
+    }
+}
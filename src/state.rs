@@ -1,28 +1,218 @@
+use crate::product::wmo::BbbKind;
+use crate::product::{normalize_filename, received_from_filename, MAX_MEMBER_SIZE};
 use crate::{Error, Product};
 use bytes::Bytes;
-use std::collections::{btree_map::Entry, BTreeMap};
-use std::time::{Duration, Instant};
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet, VecDeque};
+use std::time::{Duration, Instant, SystemTime};
 
+/// How long to remember a filename after it was last seen, by default.
+const DEFAULT_RETENTION: Duration = Duration::from_secs(6 * 3600);
+
+/// How many members to decompress at once, in parallel.
+#[cfg(feature = "parallel")]
+const PARALLEL_THRESHOLD: usize = 32;
+
+type Archive = zip::ZipArchive<std::io::Cursor<Bytes>>;
+
+/// What was found in an archive.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ArchiveStats {
+    /// The number of members not seen in a previous archive.
+    pub new_members: usize,
+    /// The number of members in the archive.
+    pub total_members: usize,
+}
+
+impl ArchiveStats {
+    /// The fraction of members which are new, from 0 to 1.
+    ///
+    /// The gateway regenerates each archive periodically, typically adding a few members and
+    /// dropping a few old ones. A ratio near 1 for a large archive means it was replaced wholesale,
+    /// which may indicate a problem upstream. An empty archive has a ratio of 0.
+    pub fn churn_ratio(&self) -> f64 {
+        if self.total_members == 0 {
+            0.0
+        } else {
+            self.new_members as f64 / self.total_members as f64
+        }
+    }
+}
+
+/// A span of time in which products were likely missed.
+///
+/// The archives a stream polls overlap, so each archive normally contains some products seen in
+/// an earlier one. An archive with no products in common with any before it means that none of
+/// them covered the time in between, e.g. because fetches failed for longer than the archives
+/// span. Products the gateway received in that time were likely missed, and may be recoverable
+/// from another dissemination system.
+///
+/// See [`StreamEvent::Gap`](crate::StreamEvent::Gap) and [`Observer::on_gap`](crate::Observer::on_gap).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Gap {
+    /// When the gateway received the newest product seen before the gap
+    pub after: SystemTime,
+    /// When the gateway received the oldest product in the archive which followed the gap
+    pub before: SystemTime,
+}
+
+impl Gap {
+    /// How long the gap lasted.
+    pub fn duration(&self) -> Duration {
+        self.before.duration_since(self.after).unwrap_or_default()
+    }
+}
+
+/// A serializable record of the filenames a [`Stream`](crate::Stream) has seen, for carrying its
+/// progress across restarts.
+///
+/// Obtained from [`Stream::snapshot_state`](crate::Stream::snapshot_state) and restored with
+/// [`Stream::with_state`](crate::Stream::with_state). With the `serde` feature, this implements
+/// `Serialize` and `Deserialize`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateSnapshot {
+    /// Each filename being tracked, with when it was last seen in an archive
+    pub filenames: BTreeMap<String, SystemTime>,
+}
+
+/// What's been seen of a bulletin, its corrections, and its retransmissions.
+#[derive(Debug)]
+struct BulletinHistory {
+    body_hashes: BTreeSet<u64>,
+    corrections: BTreeSet<String>,
+    last_seen_at: Instant,
+}
+
+/// The record of which products a [`Stream`](crate::Stream) has already seen, used to avoid
+/// yielding duplicates.
+///
+/// Obtained from [`Stream::into_inner`](crate::Stream::into_inner) and passed to
+/// [`Stream::from_parts`](crate::Stream::from_parts) to move a stream's progress to a new
+/// source.
 #[derive(Debug)]
 pub struct StreamState {
     last_seen_at: BTreeMap<String, Instant>,
+    retention: Duration,
+    redeliver_after: Option<Duration>,
+    max_filenames: Option<usize>,
+    seen_bulletins: Option<BTreeMap<String, Instant>>,
+    bulletin_histories: Option<BTreeMap<String, BulletinHistory>>,
+    seen_contents: Option<BTreeMap<u64, Instant>>,
+    last_archive: Option<ArchiveStats>,
+    newest_received_at: Option<SystemTime>,
+    last_gap: Option<Gap>,
 }
 
 impl StreamState {
-    pub fn new_products_in(&mut self, bytes: Bytes) -> Result<Vec<Result<Product, Error>>, Error> {
-        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))?;
+    pub(crate) fn new_products_in(
+        &mut self,
+        bytes: Bytes,
+        unpack_nested: bool,
+    ) -> Result<Vec<Result<Product, Error>>, Error> {
+        let mut members = self.new_members_in(bytes, unpack_nested)?;
+        let mut products = Vec::new();
+        while !members.is_empty() {
+            products.extend(members.next_batch());
+        }
+        Ok(products)
+    }
+
+    /// Find the members of an archive which haven't been seen before, without decompressing them.
+    pub(crate) fn new_members_in(
+        &mut self,
+        bytes: Bytes,
+        unpack_nested: bool,
+    ) -> Result<NewMembers, Error> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "archive",
+            bytes = bytes.len(),
+            members = tracing::field::Empty,
+            new_members = tracing::field::Empty,
+        )
+        .entered();
 
-        let mut names: Vec<_> = archive.file_names().map(String::from).collect();
-        names.sort();
+        let (archive, bytes, damage) = open(bytes)?;
 
-        let names = self.add_filenames_in(names);
+        // Track products by their canonical filename, but look them up by their member name
+        // Skip directory entries; they have names but no contents
+        let mut members: BTreeMap<String, String> = archive
+            .file_names()
+            .filter(|name| !name.ends_with('/'))
+            .map(|name| (normalize_filename(name), String::from(name)))
+            .collect();
+        let total = members.len();
 
-        log::info!("{} of {} products are new", names.len(), archive.len());
+        let names = self.add_filenames_in(members.keys().cloned().collect());
+
+        let stats = ArchiveStats {
+            new_members: names.len(),
+            total_members: total,
+        };
+        // Overlapping archives mostly repeat the last one, so only mention those with news
+        let level = match stats.new_members {
+            0 => log::Level::Debug,
+            _ => log::Level::Info,
+        };
+        log::log!(
+            level,
+            "{} of {} products are new (churn {:.2})",
+            stats.new_members,
+            stats.total_members,
+            stats.churn_ratio()
+        );
+        #[cfg(feature = "tracing")]
+        span.record("members", stats.total_members)
+            .record("new_members", stats.new_members);
+        self.last_archive = Some(stats);
+        self.last_gap = self.check_coverage(&members, stats);
 
-        Ok(names
-            .into_iter()
-            .map(|name| Product::new(archive.by_name(&name)))
-            .collect())
+        Ok(NewMembers {
+            archive,
+            bytes,
+            damage,
+            names: names
+                .iter()
+                .filter_map(|name| members.remove(name))
+                .collect(),
+            unpack_nested,
+        })
+    }
+
+    /// Look for a gap between the products seen before and the members of an archive, and note the
+    /// newest receipt time seen.
+    fn check_coverage(
+        &mut self,
+        members: &BTreeMap<String, String>,
+        stats: ArchiveStats,
+    ) -> Option<Gap> {
+        let times = members
+            .keys()
+            .filter_map(|name| received_from_filename(name));
+        let (oldest, newest) = times.fold((None, None), |(oldest, newest), time| {
+            (
+                Some(oldest.map_or(time, |oldest: SystemTime| oldest.min(time))),
+                Some(newest.map_or(time, |newest: SystemTime| newest.max(time))),
+            )
+        });
+
+        let previous = self.newest_received_at;
+        self.newest_received_at = previous.max(newest);
+
+        // Any overlap means the archives are keeping up
+        let (previous, oldest) = (previous?, oldest?);
+        if stats.new_members < stats.total_members || oldest <= previous {
+            return None;
+        }
+        let gap = Gap {
+            after: previous,
+            before: oldest,
+        };
+        log::warn!(
+            "products received over {:?} were likely missed",
+            gap.duration()
+        );
+        Some(gap)
     }
 
     fn add_filenames_in(&mut self, names: Vec<String>) -> Vec<String> {
@@ -35,11 +225,18 @@ impl StreamState {
             match self.last_seen_at.entry(name) {
                 Entry::Occupied(mut e) => {
                     // We have seen this before
+                    // Has it been gone long enough to count as new again?
+                    let absent_for = now.duration_since(*e.get());
+                    if self
+                        .redeliver_after
+                        .is_some_and(|window| absent_for >= window)
+                    {
+                        log::trace!("reappeared after {:?}: {}", absent_for, e.key());
+                        out.push(e.key().clone());
+                    }
+
                     // Update the timestamp
                     e.insert(now);
-
-                    // Skip this file
-                    continue;
                 }
                 Entry::Vacant(e) => {
                     // We have not seen this before
@@ -53,11 +250,38 @@ impl StreamState {
             };
         }
 
-        // Cull everything we haven't seen in a while
+        let retention = self.retention;
         let before_count = self.last_seen_at.len();
-        self.last_seen_at
-            .retain(|_, value| now.duration_since(*value) < Duration::from_secs(6 * 3600));
+        match self.max_filenames {
+            Some(max) if before_count > max => {
+                // Evict the least recently seen until we're back under the limit
+                let mut by_age: Vec<(Instant, String)> = self
+                    .last_seen_at
+                    .iter()
+                    .map(|(name, seen_at)| (*seen_at, name.clone()))
+                    .collect();
+                by_age.sort();
+                for (_, name) in by_age.into_iter().take(before_count - max) {
+                    self.last_seen_at.remove(&name);
+                }
+            }
+            Some(_) => {}
+            None => {
+                // Cull everything we haven't seen in a while
+                self.last_seen_at
+                    .retain(|_, value| now.duration_since(*value) < retention);
+            }
+        }
         let after_count = self.last_seen_at.len();
+        if let Some(seen_bulletins) = &mut self.seen_bulletins {
+            seen_bulletins.retain(|_, seen_at| now.duration_since(*seen_at) < retention);
+        }
+        if let Some(seen_contents) = &mut self.seen_contents {
+            seen_contents.retain(|_, seen_at| now.duration_since(*seen_at) < retention);
+        }
+        if let Some(histories) = &mut self.bulletin_histories {
+            histories.retain(|_, history| now.duration_since(history.last_seen_at) < retention);
+        }
 
         log::trace!(
             "last_seen_at culled from {} -> {} entries",
@@ -69,10 +293,394 @@ impl StreamState {
     }
 }
 
+/// The new members of an archive, decompressed a few at a time as they're needed.
+///
+/// The compressed archive is held until every member has been decompressed or skipped, so the
+/// products themselves never need to be in memory all at once. If the archive was damaged, the
+/// error follows the members which could be recovered.
+#[derive(Debug)]
+pub(crate) struct NewMembers {
+    archive: Archive,
+    bytes: Bytes,
+    damage: Option<Error>,
+    names: VecDeque<String>,
+    unpack_nested: bool,
+}
+
+impl NewMembers {
+    /// The number of members not yet decompressed.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.damage.is_none()
+    }
+
+    /// Skip the next `n` members without decompressing them.
+    pub fn skip(&mut self, n: usize) {
+        self.names.drain(..n.min(self.names.len()));
+    }
+
+    /// Skip the members whose names `f` rejects, without decompressing them.
+    pub fn retain<F: FnMut(&str) -> bool>(&mut self, mut f: F) {
+        self.names.retain(|name| f(name));
+    }
+
+    /// Skip all but the next `n` members without decompressing them.
+    pub fn truncate(&mut self, n: usize) {
+        self.names.truncate(n);
+    }
+
+    /// Decompress the next member, or with the `parallel` feature, the next batch of members.
+    pub fn next_batch(&mut self) -> Vec<Result<Product, Error>> {
+        if self.names.is_empty() {
+            return self.damage.take().map(Err).into_iter().collect();
+        }
+
+        #[cfg(feature = "parallel")]
+        let batch = PARALLEL_THRESHOLD;
+        #[cfg(not(feature = "parallel"))]
+        let batch = 1;
+
+        let names: Vec<String> = self.names.drain(..batch.min(self.names.len())).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "decode",
+            members = names.len(),
+            products = tracing::field::Empty,
+        )
+        .entered();
+
+        let products = decompress(&mut self.archive, &self.bytes, &names, self.unpack_nested);
+        #[cfg(feature = "tracing")]
+        span.record("products", products.len());
+        products
+    }
+}
+
 impl StreamState {
+    /// Every filename being tracked, with the time since it was last seen in an archive.
+    pub(crate) fn recently_seen(&self) -> Vec<(String, Duration)> {
+        let now = Instant::now();
+        self.last_seen_at
+            .iter()
+            .map(|(name, seen_at)| (name.clone(), now.duration_since(*seen_at)))
+            .collect()
+    }
+
+    /// The tracked filenames, with their monotonic timestamps converted to wall-clock time.
+    pub(crate) fn snapshot(&self) -> StateSnapshot {
+        let (now, wall_now) = (Instant::now(), SystemTime::now());
+        let filenames = self
+            .last_seen_at
+            .iter()
+            .map(|(name, seen_at)| (name.clone(), wall_now - now.duration_since(*seen_at)))
+            .collect();
+        StateSnapshot { filenames }
+    }
+
+    /// Track the filenames in a snapshot, as if they had been seen when it says.
+    ///
+    /// Filenames seen longer ago than the retention period are skipped, and those seen in the
+    /// future, e.g. due to clock changes, are treated as seen now.
+    pub(crate) fn restore(&mut self, snapshot: StateSnapshot) {
+        let (now, wall_now) = (Instant::now(), SystemTime::now());
+        for (name, seen_at) in snapshot.filenames {
+            let age = wall_now.duration_since(seen_at).unwrap_or(Duration::ZERO);
+            if age >= self.retention {
+                continue;
+            }
+            if let Some(seen_at) = now.checked_sub(age) {
+                let entry = self.last_seen_at.entry(name).or_insert(seen_at);
+                *entry = (*entry).max(seen_at);
+            }
+        }
+    }
+
+    /// The number of filenames being tracked.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn tracked(&self) -> usize {
+        self.last_seen_at.len()
+    }
+
+    /// What was found in the most recent archive.
+    pub(crate) fn last_archive(&self) -> Option<ArchiveStats> {
+        self.last_archive
+    }
+
+    /// The gap before the last archive, if there was one.
+    pub(crate) fn take_gap(&mut self) -> Option<Gap> {
+        self.last_gap.take()
+    }
+
+    /// Remember each filename for `retention` after it was last seen.
+    pub(crate) fn set_retention(&mut self, retention: Duration) {
+        self.retention = retention;
+    }
+
+    /// Treat a filename as new again if it reappears after being absent for `window`.
+    pub(crate) fn set_redeliver_after(&mut self, window: Duration) {
+        self.redeliver_after = Some(window);
+    }
+
+    /// Remember at most `max` filenames, forgetting the least recently seen, instead of
+    /// forgetting filenames after a fixed retention period.
+    pub(crate) fn set_max_filenames(&mut self, max: usize) {
+        self.max_filenames = Some(max);
+    }
+
+    /// Also suppress products whose bulletin key has been seen, even under another filename.
+    pub(crate) fn set_dedup_bulletins(&mut self) {
+        self.seen_bulletins.get_or_insert_with(BTreeMap::new);
+    }
+
+    /// Suppress retransmissions of bulletins, but not corrections or amendments.
+    pub(crate) fn set_dedup_retransmissions(&mut self) {
+        self.bulletin_histories.get_or_insert_with(BTreeMap::new);
+    }
+
+    /// Suppress products whose contents exactly match a product already seen.
+    pub(crate) fn set_dedup_contents(&mut self) {
+        self.seen_contents.get_or_insert_with(BTreeMap::new);
+    }
+
+    /// Whether a product passes every enabled bulletin-level dedup, recording it if so.
+    pub(crate) fn accepts(&mut self, product: &Product) -> bool {
+        self.is_new_contents(product)
+            && self.is_new_bulletin(product)
+            && self.is_new_or_corrected(product)
+    }
+
+    /// Whether a product's contents haven't been seen before, recording them if so.
+    ///
+    /// Always true unless content dedup is enabled.
+    fn is_new_contents(&mut self, product: &Product) -> bool {
+        let seen_contents = match &mut self.seen_contents {
+            Some(seen_contents) => seen_contents,
+            None => return true,
+        };
+
+        match seen_contents.entry(product.content_hash()) {
+            Entry::Occupied(mut e) => {
+                log::trace!("duplicate contents: {}", product.filename);
+                e.insert(Instant::now());
+                false
+            }
+            Entry::Vacant(e) => {
+                e.insert(Instant::now());
+                true
+            }
+        }
+    }
+
+    /// Whether a product is a bulletin not seen before, or a correction or amendment of one, as
+    /// opposed to a retransmission.
+    ///
+    /// Always true unless retransmission dedup is enabled.
+    fn is_new_or_corrected(&mut self, product: &Product) -> bool {
+        let (histories, key) = match (&mut self.bulletin_histories, product.base_bulletin_key()) {
+            (Some(histories), Some(key)) => (histories, key),
+            _ => return true,
+        };
+        let heading = product.wmo_heading();
+        let hash = product.body_hash();
+
+        let history = histories.entry(key).or_insert_with(|| BulletinHistory {
+            body_hashes: BTreeSet::new(),
+            corrections: BTreeSet::new(),
+            last_seen_at: Instant::now(),
+        });
+        history.last_seen_at = Instant::now();
+
+        // Corrections (CCx) and amendments (AAx) are news the first time each is seen
+        let is_new_correction = match heading.as_ref().map(|h| (h.bbb_kind(), h.bbb())) {
+            Some((Some(BbbKind::Correction | BbbKind::Amendment), Some(bbb))) => {
+                history.corrections.insert(bbb.to_string())
+            }
+            _ => false,
+        };
+        let is_new_body = history.body_hashes.insert(hash);
+
+        if is_new_body || is_new_correction {
+            true
+        } else {
+            log::trace!("retransmission: {}", product.filename);
+            false
+        }
+    }
+
+    /// Whether a product's bulletin hasn't been seen before, recording it if so.
+    ///
+    /// Always true unless bulletin dedup is enabled.
+    fn is_new_bulletin(&mut self, product: &Product) -> bool {
+        let (seen_bulletins, key) = match (&mut self.seen_bulletins, product.bulletin_key()) {
+            (Some(seen_bulletins), Some(key)) => (seen_bulletins, key),
+            _ => return true,
+        };
+
+        match seen_bulletins.entry(key) {
+            Entry::Occupied(e) => {
+                log::trace!("duplicate bulletin: {} as {}", e.key(), product.filename);
+                false
+            }
+            Entry::Vacant(e) => {
+                e.insert(Instant::now());
+                true
+            }
+        }
+    }
+
+    /// A state which has seen nothing.
     pub fn new() -> Self {
         Self {
             last_seen_at: BTreeMap::new(),
+            retention: DEFAULT_RETENTION,
+            redeliver_after: None,
+            max_filenames: None,
+            seen_bulletins: None,
+            bulletin_histories: None,
+            seen_contents: None,
+            last_archive: None,
+            newest_received_at: None,
+            last_gap: None,
+        }
+    }
+}
+
+/// Decompress the named members of an archive, in order.
+#[cfg(not(feature = "parallel"))]
+fn decompress(
+    archive: &mut Archive,
+    bytes: &Bytes,
+    names: &[&str],
+    unpack_nested: bool,
+) -> Vec<Result<Product, Error>> {
+    names
+        .iter()
+        .flat_map(|name| {
+            flatten(
+                name,
+                Product::from_zip(archive.by_name(name), bytes, unpack_nested),
+            )
+        })
+        .collect()
+}
+
+/// Decompress the named members of an archive, in order, using the rayon thread pool for full
+/// batches.
+#[cfg(feature = "parallel")]
+fn decompress(
+    archive: &mut Archive,
+    bytes: &Bytes,
+    names: &[&str],
+    unpack_nested: bool,
+) -> Vec<Result<Product, Error>> {
+    use rayon::prelude::*;
+
+    if names.len() < PARALLEL_THRESHOLD {
+        return names
+            .iter()
+            .flat_map(|name| {
+                flatten(
+                    name,
+                    Product::from_zip(archive.by_name(name), bytes, unpack_nested),
+                )
+            })
+            .collect();
+    }
+
+    // Each thread reads through its own handle; cloning shares the underlying bytes
+    names
+        .par_iter()
+        .map_init(
+            || archive.clone(),
+            |archive, name| Product::from_zip(archive.by_name(name), bytes, unpack_nested),
+        )
+        .collect::<Vec<_>>()
+        .into_iter()
+        .zip(names)
+        .flat_map(|(member, name)| flatten(name, member))
+        .collect()
+}
+
+/// Open an archive, recovering what members we can if it's damaged.
+///
+/// Returns the archive, the buffer it reads from, and the damage, if any, to report after its
+/// members.
+fn open(bytes: Bytes) -> Result<(Archive, Bytes, Option<Error>), Error> {
+    let e = match zip::ZipArchive::new(std::io::Cursor::new(bytes.clone())) {
+        Ok(archive) => return Ok((archive, bytes, None)),
+        Err(e) => e,
+    };
+    let (salvaged, recovered) = match salvage(&bytes) {
+        Some(salvaged) => salvaged,
+        None => return Err(e.into()),
+    };
+    log::warn!("damaged archive, recovered {} members: {}", recovered, e);
+    let archive = zip::ZipArchive::new(std::io::Cursor::new(salvaged.clone()))?;
+    let damage = Error::DamagedArchive {
+        recovered,
+        source: e,
+    };
+    Ok((archive, salvaged, Some(damage)))
+}
+
+/// Split a member's products into individual results, naming the member if it was corrupt.
+fn flatten(name: &str, member: Result<Vec<Product>, Error>) -> Vec<Result<Product, Error>> {
+    match member {
+        Ok(products) => products.into_iter().map(Ok).collect(),
+        Err(Error::ArchiveFormat(source)) => vec![Err(Error::CorruptMember {
+            name: normalize_filename(name),
+            source,
+        })],
+        Err(e) => vec![Err(e)],
+    }
+}
+
+/// Recover the members of an archive which can't be opened, e.g. because it was truncated and
+/// lost its central directory, by reading their local headers in turn.
+///
+/// Returns the intact members rebuilt into a new archive, and how many there were, or `None` if
+/// there were none.
+fn salvage(bytes: &[u8]) -> Option<(Bytes, usize)> {
+    use std::io::{Read, Write};
+
+    let mut reader = std::io::Cursor::new(bytes);
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let mut recovered = 0;
+    while let Ok(Some(mut file)) = zip::read::read_zipfile_from_stream(&mut reader) {
+        // Reading to the end checks the member's CRC, and stops at one which was cut short. A
+        // damaged archive's sizes can't be trusted, so read no more than any member may hold.
+        let mut contents = Vec::with_capacity(file.size().clamp(4 << 10, 8 << 20) as usize);
+        if (&mut file)
+            .take(MAX_MEMBER_SIZE + 1)
+            .read_to_end(&mut contents)
+            .is_err()
+        {
+            break;
         }
+        if contents.len() as u64 > MAX_MEMBER_SIZE {
+            log::warn!("{} is too large to salvage; stopping", file.name());
+            break;
+        }
+        let options = zip::write::FileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored)
+            .last_modified_time(file.last_modified());
+        writer.start_file(file.name(), options).ok()?;
+        writer.write_all(&contents).ok()?;
+        recovered += 1;
+    }
+    if recovered == 0 {
+        return None;
+    }
+    let rebuilt = writer.finish().ok()?.into_inner();
+    Some((Bytes::from(rebuilt), recovered))
+}
+
+impl Default for StreamState {
+    fn default() -> Self {
+        Self::new()
     }
 }
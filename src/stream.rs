@@ -1,9 +1,18 @@
-use crate::{Error, Product, StreamState};
+use crate::product::ugc::UgcCode;
+use crate::state::NewMembers;
+use crate::{
+    filter, ArchiveStats, BoundingBox, Error, Filters, Gap, Product, SourceConfig, StateSnapshot,
+    StreamState, ZoneResolver,
+};
 use bytes::Bytes;
+use futures::stream::BoxStream;
 use pin_project_lite::pin_project;
 use std::collections::VecDeque;
+use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use std::time::Duration;
+use time::OffsetDateTime;
 
 pin_project! {
 /// A stream of products from the EMWIN TG text feed.
@@ -19,6 +28,10 @@ pin_project! {
 /// available products as they are first detected, but also seeks to avoid overloading the
 /// telecommunications gateway with a high rate of requests.
 ///
+/// Polling is cancel-safe: products extracted from an archive are held in the `Stream` until
+/// they're yielded, so `StreamExt::next()` can be used in `tokio::select!` or dropped before it
+/// completes without losing products.
+///
 /// # Example
 ///
 /// ```rust
@@ -49,8 +62,39 @@ pub struct Stream<S: Source> {
     #[pin]
     source: S,
     state: StreamState,
+    filters: Filters,
     output_buffer: VecDeque<Result<Product, Error>>,
+    pending: Option<NewMembers>,
+    gaps: Option<VecDeque<Gap>>,
+    max_buffered: Option<(usize, OverflowPolicy)>,
+    newest_issued_at: Option<OffsetDateTime>,
+    unpack_nested: bool,
+    idle_timeout: Option<(Duration, Pin<Box<tokio::time::Sleep>>)>,
+    observers: Observers,
+    shutdown: Shutdown,
+    shutdown_signal: Option<ShutdownSignal>,
+}
+}
+
+/// How far along a `Stream` is in shutting down.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Shutdown {
+    Running,
+    /// Requested, but not yet passed on to the source
+    Requested,
+    /// The source is finishing its fetches in flight
+    Draining,
+    /// The source can't shut down, so it's no longer polled
+    Abandoned,
 }
+
+/// A future which shuts a `Stream` down when it completes.
+struct ShutdownSignal(futures::future::BoxFuture<'static, ()>);
+
+impl std::fmt::Debug for ShutdownSignal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ShutdownSignal").finish()
+    }
 }
 
 impl<S: Source + From<reqwest::Client>> Default for Stream<S> {
@@ -93,12 +137,874 @@ impl<S: Source + From<reqwest::Client>> Stream<S> {
     /// # })
     /// ```
     pub fn from_client(client: reqwest::Client) -> Self {
+        Self::from_source(S::from(client))
+    }
+}
+
+impl<S: Source + From<(reqwest::Client, SourceConfig)>> Stream<S> {
+    /// Configure a stream using a [`StreamBuilder`].
+    pub fn builder() -> StreamBuilder<S> {
+        StreamBuilder::default()
+    }
+}
+
+impl<S: Source> Stream<S> {
+    /// Start a stream which reads archives from a particular source.
+    ///
+    /// Any boxed stream of archive bytes is a [`Source`], which makes it easy to process archives
+    /// obtained some other way.
+    ///
+    /// Directory entries within archives are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use futures::StreamExt;
+    /// use std::io::Write;
+    ///
+    /// let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// zip.add_directory("SUBDIR/", Default::default()).unwrap();
+    /// zip.start_file("AFDOKXNY.TXT", Default::default()).unwrap();
+    /// zip.write_all(b"hello").unwrap();
+    /// let archive = bytes::Bytes::from(zip.finish().unwrap().into_inner());
+    ///
+    /// let source = futures::stream::iter(vec![Ok(archive)]).boxed();
+    /// let mut stream = emwin_tg::Stream::from_source(source);
+    ///
+    /// let product = stream.next().await.unwrap().unwrap();
+    /// assert_eq!(product.filename, "AFDOKXNY.TXT");
+    /// assert!(stream.next().await.is_none());
+    /// # })
+    /// ```
+    pub fn from_source(source: S) -> Self {
+        Self::with_buffer_capacity(source, DEFAULT_BUFFER_CAPACITY)
+    }
+
+    pub(super) fn with_buffer_capacity(source: S, buffer_capacity: usize) -> Self {
+        let mut state = StreamState::new();
+        if let Some(window) = source.dedup_window() {
+            state.set_retention(window);
+        }
         Self {
-            source: S::from(client),
-            state: StreamState::new(),
-            output_buffer: VecDeque::with_capacity(50),
+            source,
+            state,
+            filters: Filters::default(),
+            output_buffer: VecDeque::with_capacity(buffer_capacity),
+            pending: None,
+            gaps: None,
+            max_buffered: None,
+            newest_issued_at: None,
+            unpack_nested: true,
+            idle_timeout: None,
+            observers: Observers::default(),
+            shutdown: Shutdown::Running,
+            shutdown_signal: None,
+        }
+    }
+
+    /// Assemble a stream from a source, a dedup state, and products waiting to be yielded.
+    ///
+    /// This is the inverse of [`into_inner`](Self::into_inner). Other settings, like filters,
+    /// take their defaults.
+    pub fn from_parts(
+        source: S,
+        state: StreamState,
+        buffered: VecDeque<Result<Product, Error>>,
+    ) -> Self {
+        let mut stream = Self::from_source(source);
+        stream.state = state;
+        stream.output_buffer = buffered;
+        stream
+    }
+
+    /// Take the stream apart into its source, its dedup state, and any products extracted from
+    /// archives but not yet yielded.
+    ///
+    /// Settings like filters are discarded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use futures::StreamExt;
+    ///
+    /// let source = futures::stream::empty().boxed();
+    /// let (source, state, buffered) = emwin_tg::Stream::from_source(source).into_inner();
+    /// assert!(buffered.is_empty());
+    ///
+    /// let stream = emwin_tg::Stream::from_parts(source, state, buffered);
+    /// # std::mem::drop(stream);
+    /// # })
+    /// ```
+    pub fn into_inner(mut self) -> (S, StreamState, VecDeque<Result<Product, Error>>) {
+        if let Some(mut members) = self.pending.take() {
+            while !members.is_empty() {
+                unpack(
+                    &mut members,
+                    &self.filters,
+                    &mut self.state,
+                    &mut self.output_buffer,
+                );
+            }
+        }
+        (self.source, self.state, self.output_buffer)
+    }
+
+    /// Record the filenames this stream has seen, so a later stream can pick up where it left off.
+    ///
+    /// Persist the snapshot when shutting down, e.g. using the `serde` feature, and pass it to
+    /// [`with_state`](Self::with_state) on startup. Otherwise a restarted stream yields every
+    /// product in the archives again, which can be hours' worth.
+    pub fn snapshot_state(&self) -> StateSnapshot {
+        self.state.snapshot()
+    }
+
+    /// Treat the filenames in `snapshot` as already seen.
+    ///
+    /// Filenames are remembered as if they'd been seen at the times recorded in the snapshot, so
+    /// those too old to still be remembered are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use futures::StreamExt;
+    /// use std::io::Write;
+    ///
+    /// let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// zip.start_file("AFDOKXNY.TXT", Default::default()).unwrap();
+    /// zip.write_all(b"hello").unwrap();
+    /// let archive = bytes::Bytes::from(zip.finish().unwrap().into_inner());
+    ///
+    /// let source = futures::stream::iter(vec![Ok(archive.clone())]).boxed();
+    /// let mut stream = emwin_tg::Stream::from_source(source);
+    /// while let Some(_) = stream.next().await {}
+    /// let snapshot = stream.snapshot_state();
+    ///
+    /// // After a restart
+    /// let source = futures::stream::iter(vec![Ok(archive)]).boxed();
+    /// let mut stream = emwin_tg::Stream::from_source(source).with_state(snapshot);
+    /// assert!(stream.next().await.is_none());
+    /// # })
+    /// ```
+    pub fn with_state(mut self, snapshot: StateSnapshot) -> Self {
+        self.state.restore(snapshot);
+        self
+    }
+
+    /// The number of products the output buffer can hold before it needs to reallocate.
+    pub fn buffer_capacity(&self) -> usize {
+        self.output_buffer.capacity()
+    }
+
+    /// The filenames this stream is tracking to avoid yielding duplicates, with the time since
+    /// each was last seen in an archive.
+    ///
+    /// This is intended for debugging why a product was or wasn't yielded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use futures::StreamExt;
+    /// use std::io::Write;
+    /// use std::time::Duration;
+    ///
+    /// let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// zip.start_file("AFDOKXNY.TXT", Default::default()).unwrap();
+    /// zip.write_all(b"hello").unwrap();
+    /// let archive = bytes::Bytes::from(zip.finish().unwrap().into_inner());
+    ///
+    /// let source = futures::stream::iter(vec![Ok(archive)]).boxed();
+    /// let mut stream = emwin_tg::Stream::from_source(source);
+    /// assert!(stream.recently_seen_filenames().is_empty());
+    ///
+    /// stream.next().await.unwrap().unwrap();
+    /// let seen = stream.recently_seen_filenames();
+    /// assert_eq!(seen.len(), 1);
+    /// assert_eq!(seen[0].0, "AFDOKXNY.TXT");
+    /// assert!(seen[0].1 < Duration::from_secs(1));
+    /// # })
+    /// ```
+    pub fn recently_seen_filenames(&self) -> Vec<(String, Duration)> {
+        self.state.recently_seen()
+    }
+
+    /// How many new members were found in the most recently unpacked archive.
+    ///
+    /// Each archive fetched after it's regenerated mostly repeats members from the previous one.
+    /// Watching [`ArchiveStats::churn_ratio`] over time reveals anomalies like an archive being
+    /// replaced wholesale. Returns `None` until an archive has been unpacked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use futures::StreamExt;
+    /// use std::io::Write;
+    ///
+    /// let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// zip.start_file("AFDOKXNY.TXT", Default::default()).unwrap();
+    /// zip.write_all(b"hello").unwrap();
+    /// let archive = bytes::Bytes::from(zip.finish().unwrap().into_inner());
+    ///
+    /// let source = futures::stream::iter(vec![Ok(archive)]).boxed();
+    /// let mut stream = emwin_tg::Stream::from_source(source);
+    /// assert_eq!(stream.last_archive_stats(), None);
+    ///
+    /// stream.next().await.unwrap().unwrap();
+    /// assert_eq!(stream.last_archive_stats().unwrap().churn_ratio(), 1.0);
+    /// # })
+    /// ```
+    pub fn last_archive_stats(&self) -> Option<ArchiveStats> {
+        self.state.last_archive()
+    }
+
+    /// Yield a product again if it reappears after being absent from every archive for `window`.
+    ///
+    /// By default, `Stream` yields each product once and suppresses it for as long as it
+    /// remembers the filename, which is its [`dedup_window`](Self::dedup_window) after it was last
+    /// seen. Setting a shorter window
+    /// lets a product which drops out of the archives and later returns, e.g. a reissued
+    /// correction, be yielded again. Products which remain continuously available are still
+    /// yielded only once.
+    ///
+    /// Absence is measured from the last archive containing the product, and an archive which is
+    /// refetched hourly, like `txthrs03.zip`, leaves its products absent for an hour at a time. The
+    /// window is therefore raised to at least twice the source's longest
+    /// [`refetch_interval`](Source::refetch_interval).
+    pub fn redeliver_after(mut self, window: Duration) -> Self {
+        let window = match self.source.refetch_interval() {
+            Some(interval) => window.max(interval * 2),
+            None => window,
+        };
+        self.state.set_redeliver_after(window);
+        self
+    }
+
+    /// Remember each filename for `window` after it was last seen, for suppressing duplicates.
+    ///
+    /// By default, the window is twice as long as the longest-lived archive the source polls:
+    /// six hours for [`TextSource`] and [`ImageSource`], which include three-hour archives, but
+    /// only 12 minutes for a [`TextSource::realtime_only`], whose longest archive spans six
+    /// minutes. A product which is forgotten while it's still in an archive is yielded again, so
+    /// `window` should comfortably exceed the span of every archive polled. Shorter windows use
+    /// less memory. This also bounds how long [`filter_new_or_corrected`](Self::filter_new_or_corrected)
+    /// and [`filter_duplicate_contents`](Self::filter_duplicate_contents) remember what they've
+    /// seen.
+    ///
+    /// This can also be set with [`StreamBuilder::dedup_window`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use std::time::Duration;
+    ///
+    /// let source = emwin_tg::TextSource::from(reqwest::Client::new()).realtime_only();
+    /// let stream = emwin_tg::Stream::from_source(source).dedup_window(Duration::from_secs(30 * 60));
+    /// # std::mem::drop(stream);
+    /// # })
+    /// ```
+    pub fn dedup_window(mut self, window: Duration) -> Self {
+        self.state.set_retention(window);
+        self
+    }
+
+    /// Remember at most `max` filenames for suppressing duplicates, forgetting the least recently
+    /// seen when full.
+    ///
+    /// By default, `Stream` remembers every filename for its
+    /// [`dedup_window`](Self::dedup_window) after it was last seen, so memory use grows with the
+    /// volume of products. This bounds it instead. A product which is
+    /// forgotten and later appears again is yielded again, so `max` should comfortably exceed the
+    /// number of products in an archive.
+    ///
+    /// This can also be set with [`StreamBuilder::dedup_capacity`].
+    pub fn dedup_capacity(mut self, max: usize) -> Self {
+        self.state.set_max_filenames(max);
+        self
+    }
+
+    /// Hold at most `max` products waiting to be yielded, handling the rest according to `policy`.
+    ///
+    /// `Stream` decompresses an archive's new products as they're yielded, and doesn't poll its
+    /// source for another archive until it has yielded every product from the last one. A single
+    /// archive can still hold thousands of new products, e.g. the three-hour archive when the
+    /// stream starts, and a slow consumer falls behind while working through them. Dropping
+    /// products lets it catch up. Dropped products are never decompressed; they're logged, and
+    /// count as seen, so they won't be yielded later.
+    ///
+    /// Filters which look only at the filename, like [`filter_awips`](Self::filter_awips), run
+    /// first, so only products which the stream might yield count toward `max`.
+    ///
+    /// This can also be set with [`StreamBuilder::max_buffered`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use emwin_tg::{OverflowPolicy, TextStream};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let stream = TextStream::new().max_buffered(1000, OverflowPolicy::DropOldest);
+    /// # std::mem::drop(stream);
+    /// # })
+    /// ```
+    pub fn max_buffered(mut self, max: usize, policy: OverflowPolicy) -> Self {
+        self.max_buffered = Some((max, policy));
+        self
+    }
+
+    /// Mark every product in `archives` as already seen, without yielding any of them.
+    ///
+    /// This warm-starts a stream from archives captured earlier, so that on startup it yields
+    /// only products which weren't in them. Archives which can't be opened are skipped. Call this
+    /// after any other deduplication settings, like
+    /// [`filter_new_or_corrected`](Self::filter_new_or_corrected), so that they see the replayed
+    /// products too.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use futures::StreamExt;
+    /// use std::io::Write;
+    ///
+    /// let archive = |names: &[&str]| {
+    ///     let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    ///     for name in names {
+    ///         zip.start_file(*name, Default::default()).unwrap();
+    ///         zip.write_all(b"hello").unwrap();
+    ///     }
+    ///     bytes::Bytes::from(zip.finish().unwrap().into_inner())
+    /// };
+    ///
+    /// let captured = archive(&["AFDOKXNY.TXT"]);
+    /// let live = archive(&["AFDOKXNY.TXT", "ZFPOKXNY.TXT"]);
+    ///
+    /// let source = futures::stream::iter(vec![Ok(live)]).boxed();
+    /// let mut stream = emwin_tg::Stream::from_source(source).replay_from(vec![captured]);
+    /// assert_eq!(stream.next().await.unwrap().unwrap().filename, "ZFPOKXNY.TXT");
+    /// assert!(stream.next().await.is_none());
+    /// # })
+    /// ```
+    pub fn replay_from(mut self, archives: Vec<Bytes>) -> Self {
+        for archive in archives {
+            match self.state.new_products_in(archive, self.unpack_nested) {
+                Ok(products) => {
+                    for product in products.iter().flatten() {
+                        self.state.accepts(product);
+                    }
+                }
+                Err(e) => log::warn!("skipping replayed archive: {}", e),
+            }
+        }
+        self
+    }
+
+    /// Transform each product with `f`, dropping products for which it returns `None`.
+    ///
+    /// This suits pipelines which parse products and discard the ones they don't care about.
+    /// Errors are passed through unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use futures::StreamExt;
+    /// use std::io::Write;
+    ///
+    /// let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// for (name, contents) in [("AFDOKXNY.TXT", &b"hello"[..]), ("RADALLUS.GIF", b"GIF89a")] {
+    ///     zip.start_file(name, Default::default()).unwrap();
+    ///     zip.write_all(contents).unwrap();
+    /// }
+    /// let archive = bytes::Bytes::from(zip.finish().unwrap().into_inner());
+    ///
+    /// let source = futures::stream::iter(vec![Ok(archive)]).boxed();
+    /// let texts: Vec<String> = emwin_tg::Stream::from_source(source)
+    ///     .filter_map_product(|product| match product.mime_type() {
+    ///         Some("text/plain") => Some(product.into_string_lossy()),
+    ///         _ => None,
+    ///     })
+    ///     .map(Result::unwrap)
+    ///     .collect()
+    ///     .await;
+    /// assert_eq!(texts, ["hello"]);
+    /// # })
+    /// ```
+    pub fn filter_map_product<F, T>(self, f: F) -> FilterMapProduct<S, F>
+    where
+        F: FnMut(Product) -> Option<T>,
+    {
+        FilterMapProduct::new(self, f)
+    }
+
+    /// Hold each product for `delay`, yielding held products in order of issuance.
+    ///
+    /// Products arrive in whatever order the archives list them, and the overlapping archives can
+    /// deliver a product well after others issued later. Holding products for a while lets
+    /// stragglers be put in order, at the cost of `delay` extra latency. Ordering is by
+    /// [`Product::issued_at`]; products without an issuance time are ordered as if issued when
+    /// they arrived. Errors are yielded immediately.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use std::time::Duration;
+    ///
+    /// let stream = emwin_tg::TextStream::new().sorted_within(Duration::from_secs(60));
+    /// # std::mem::drop(stream);
+    /// # })
+    /// ```
+    pub fn sorted_within(self, delay: Duration) -> SortedStream<S> {
+        SortedStream::new(self, delay)
+    }
+
+    /// Drive this stream from a background task, buffering up to `n` results ahead of the
+    /// consumer.
+    ///
+    /// A `Stream` only makes progress while it's being polled, so a consumer which spends a while
+    /// on each product also delays the fetches behind it. A buffered stream keeps fetching and
+    /// unpacking in the meantime, smoothing out both slow consumers and gateway latency spikes.
+    /// When the buffer is full, the background task waits for the consumer to catch up.
+    ///
+    /// Must be called from within a Tokio runtime.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// let stream = emwin_tg::TextStream::new().buffered(100);
+    /// # std::mem::drop(stream);
+    /// # })
+    /// ```
+    pub fn buffered(self, n: usize) -> BufferedStream
+    where
+        S: Send + 'static,
+    {
+        BufferedStream::new(self, n)
+    }
+
+    /// Suppress products whose bulletin has already been yielded under a different filename.
+    ///
+    /// GOES-East and GOES-West both carry EMWIN, and a feed merged from both delivers each
+    /// bulletin twice, with filenames that differ in their receipt time and sequence number. This
+    /// identifies bulletins by their WMO heading and AWIPS identifier instead, yielding each once.
+    /// Corrections and amendments have a distinct heading, so they're still yielded. Products
+    /// without a WMO heading in their filename are only deduplicated by filename.
+    pub fn filter_duplicates_across_satellites(mut self) -> Self {
+        self.state.set_dedup_bulletins();
+        self
+    }
+
+    /// Yield [`Error::Stalled`] whenever `window` elapses without a product.
+    ///
+    /// The stream keeps going after reporting a stall, and reports again after each further
+    /// `window` of silence. This distinguishes a feed which has stopped delivering from one which
+    /// is merely quiet, provided `window` is longer than the usual gap between products.
+    ///
+    /// Must be called from within a Tokio runtime.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use std::time::Duration;
+    ///
+    /// let stream = emwin_tg::TextStream::new().with_idle_timeout(Duration::from_secs(15 * 60));
+    /// # std::mem::drop(stream);
+    /// # })
+    /// ```
+    pub fn with_idle_timeout(mut self, window: Duration) -> Self {
+        let sleep = Box::pin(tokio::time::sleep(window));
+        self.idle_timeout = Some((window, sleep));
+        self
+    }
+
+    /// Stop fetching new archives, and end the stream once everything already downloaded has been
+    /// yielded.
+    ///
+    /// Fetches in flight are allowed to finish, and the products from their archives are yielded
+    /// along with any already buffered before the stream returns `None`. This lets a service
+    /// terminate without dropping products it has downloaded. A [`Source`] which can't shut down
+    /// gracefully is no longer polled, so only the products already buffered are yielded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use futures::StreamExt;
+    ///
+    /// let mut stream = emwin_tg::TextStream::new();
+    /// stream.shutdown();
+    /// while let Some(product) = stream.next().await {
+    ///     // Handle the remaining products
+    ///     # std::mem::drop(product);
+    /// }
+    /// # })
+    /// ```
+    pub fn shutdown(&mut self) {
+        if self.shutdown == Shutdown::Running {
+            self.shutdown = Shutdown::Requested;
         }
     }
+
+    /// [`shutdown`](Self::shutdown) once `signal` completes, e.g. when a cancellation token is
+    /// cancelled or the process receives a signal.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// let stream = emwin_tg::TextStream::new().shutdown_on(async {
+    ///     tokio::signal::ctrl_c().await.ok();
+    /// });
+    /// # std::mem::drop(stream);
+    /// # })
+    /// ```
+    pub fn shutdown_on<F: Future<Output = ()> + Send + 'static>(mut self, signal: F) -> Self {
+        self.shutdown_signal = Some(ShutdownSignal(Box::pin(signal)));
+        self
+    }
+
+    /// Yield each bulletin once, plus each correction or amendment of it, suppressing
+    /// retransmissions.
+    ///
+    /// Bulletins are identified by their WMO heading, less the `BBB` indicator, and AWIPS
+    /// identifier. A product is yielded if its text hasn't been seen for that bulletin, or if it's
+    /// the first appearance of a correction (`CCx`) or amendment (`AAx`). Retransmissions, whether
+    /// marked `RRx` or not, repeat text already seen and are dropped. This is a good policy for
+    /// alerting, where each distinct message should be acted on exactly once.
+    ///
+    /// Products without a WMO heading in their filename are only deduplicated by filename.
+    pub fn filter_new_or_corrected(mut self) -> Self {
+        self.state.set_dedup_retransmissions();
+        self
+    }
+
+    /// Yield each distinct set of contents once, regardless of filename.
+    ///
+    /// The overlapping archives are deduplicated by filename, but the same product sometimes
+    /// appears under more than one name, e.g. when it's resent with a new receipt time. This drops
+    /// any product whose contents exactly match one seen within the
+    /// [`dedup_window`](Self::dedup_window). A correction
+    /// differs from the original in at least its heading, so it's still yielded.
+    ///
+    /// Unlike [`filter_duplicates_across_satellites`](Self::filter_duplicates_across_satellites)
+    /// and [`filter_new_or_corrected`](Self::filter_new_or_corrected), this applies to products
+    /// without a WMO heading, like images.
+    pub fn filter_duplicate_contents(mut self) -> Self {
+        self.state.set_dedup_contents();
+        self
+    }
+
+    /// Notify `observer` of this stream's activity.
+    ///
+    /// Observers see each archive received from the source, each archive unpacked, and each
+    /// product or error yielded. Several observers may be installed; each is notified in the order
+    /// they were added.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use emwin_tg::{Error, Observer};
+    ///
+    /// struct LogErrors;
+    ///
+    /// impl Observer for LogErrors {
+    ///     fn on_error(&self, error: &Error) {
+    ///         eprintln!("stream error: {}", error);
+    ///     }
+    /// }
+    ///
+    /// let stream = emwin_tg::TextStream::new().observe(LogErrors);
+    /// # std::mem::drop(stream);
+    /// # })
+    /// ```
+    pub fn observe<O: Observer + 'static>(mut self, observer: O) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Yield [`StreamEvent`]s instead of bare products.
+    ///
+    /// Besides products, the event stream reports [gaps](StreamEvent::Gap) in coverage.
+    pub fn events(mut self) -> EventStream<S> {
+        self.gaps.get_or_insert_with(VecDeque::new);
+        EventStream::new(self)
+    }
+
+    /// Take the oldest gap detected but not yet reported.
+    pub(super) fn take_gap(self: Pin<&mut Self>) -> Option<Gap> {
+        self.project().gaps.as_mut()?.pop_front()
+    }
+
+    /// The issuance time of the newest product yielded so far.
+    ///
+    /// See [`Product::issued_at`].
+    pub fn newest_issued_at(&self) -> Option<OffsetDateTime> {
+        self.newest_issued_at
+    }
+
+    /// Estimate how far behind real time this stream is.
+    ///
+    /// This is the time elapsed since the issuance of the newest product yielded so far, or `None`
+    /// if no yielded product had a known issuance time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use futures::StreamExt;
+    /// use std::io::Write;
+    /// use time::{Date, Month};
+    ///
+    /// let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// for name in [
+    ///     "A_FXUS63KDVN151152_C_KWIN_20220215115257_060736-2-AFDDVNIA.TXT",
+    ///     "A_FXUS63KDVN151352_C_KWIN_20220215135257_060737-2-AFDDVNIA.TXT",
+    ///     "A_FXUS63KDVN150952_C_KWIN_20220215095257_060735-2-AFDDVNIA.TXT",
+    /// ] {
+    ///     zip.start_file(name, Default::default()).unwrap();
+    ///     zip.write_all(b"hello").unwrap();
+    /// }
+    /// let archive = bytes::Bytes::from(zip.finish().unwrap().into_inner());
+    ///
+    /// let source = futures::stream::iter(vec![Ok(archive)]).boxed();
+    /// let mut stream = emwin_tg::Stream::from_source(source);
+    /// assert_eq!(stream.lag(), None);
+    ///
+    /// while let Some(_) = stream.next().await {}
+    ///
+    /// let newest = Date::from_calendar_date(2022, Month::February, 15)
+    ///     .unwrap()
+    ///     .with_hms(13, 52, 0)
+    ///     .unwrap()
+    ///     .assume_utc();
+    /// assert_eq!(stream.newest_issued_at(), Some(newest));
+    ///
+    /// let elapsed = std::time::Duration::try_from(time::OffsetDateTime::now_utc() - newest).unwrap();
+    /// assert!(stream.lag().unwrap() >= elapsed);
+    /// # })
+    /// ```
+    pub fn lag(&self) -> Option<Duration> {
+        let elapsed = OffsetDateTime::now_utc() - self.newest_issued_at?;
+        Some(Duration::try_from(elapsed).unwrap_or(Duration::ZERO))
+    }
+
+    /// Yield nested `.ZIP` archive members as-is, instead of the product inside them.
+    ///
+    /// Some products are distributed as single-member ZIP files within the EMWIN archives. By
+    /// default, `Stream` unpacks these and yields the inner product. Archives nested within
+    /// those are unpacked too, but no deeper; any further archives are yielded as-is.
+    pub fn keep_nested_archives(mut self) -> Self {
+        self.unpack_nested = false;
+        self
+    }
+
+    /// Only yield products affecting a geographic area.
+    ///
+    /// Products are matched by resolving the counties and zones in their UGC strings to centroids
+    /// using `resolver`, and checking whether any centroid falls within `bbox`. See
+    /// [`BoundingBox::affects`] for details. Errors are always yielded.
+    pub fn filter_bbox<R: ZoneResolver + 'static>(
+        mut self,
+        bbox: BoundingBox,
+        resolver: R,
+    ) -> Self {
+        self.filters
+            .push(move |product| bbox.affects(product, &resolver));
+        self
+    }
+
+    /// Only yield products whose AWIPS identifier starts with one of `prefixes`, e.g. `["TOR",
+    /// "SVR"]` for tornado and severe thunderstorm warnings, or `["AFDDVN"]` for one office's area
+    /// forecast discussions.
+    ///
+    /// The identifier comes from the end of the filename, e.g. `AFDDVNIA` in
+    /// `A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT`. Prefixes are matched
+    /// without regard to case. Errors are always yielded.
+    ///
+    /// This can also be set with [`StreamBuilder::filter_awips`].
+    pub fn filter_awips<I, T>(mut self, prefixes: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let prefixes = prefixes.into_iter().map(Into::into).collect();
+        self.filters.push_by_name(filter::awips(prefixes));
+        self
+    }
+
+    /// Only yield products issued by `office`, e.g. `KOKX`, per [`Product::originator`].
+    ///
+    /// Errors are always yielded. This can also be set with [`StreamBuilder::filter_office`].
+    pub fn filter_office<T: Into<String>>(mut self, office: T) -> Self {
+        self.filters.push(filter::office(office.into()));
+        self
+    }
+
+    /// Only yield products issued by the weather forecast office `wfo`, e.g. `MPX` or `KMPX`.
+    ///
+    /// The office comes from the AWIPS identifier in the product's contents, e.g. `TORMPX`, or
+    /// failing that from [`Product::originator`]. Errors are always yielded. This can also be set
+    /// with [`StreamBuilder::filter_wfo`].
+    pub fn filter_wfo<T: Into<String>>(mut self, wfo: T) -> Self {
+        self.filters.push(filter::wfo(wfo.into()));
+        self
+    }
+
+    /// Only yield products which affect any of `states`, e.g. `["MN", "WI"]`, per the counties and
+    /// zones listed in their UGC strings.
+    ///
+    /// Marine areas, like `LM` for Lake Michigan, count as states. Products without UGC strings
+    /// are dropped. Errors are always yielded. This can also be set with
+    /// [`StreamBuilder::filter_states`].
+    pub fn filter_states<I, T>(mut self, states: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let states = states.into_iter().map(Into::into).collect();
+        self.filters.push(filter::states(states));
+        self
+    }
+
+    /// Only yield products which affect any of the counties or zones `codes`, per their UGC
+    /// strings.
+    ///
+    /// Products listing a whole state, like `MNZALL`, affect every zone in it. Products without
+    /// UGC strings are dropped. Errors are always yielded. This can also be set with
+    /// [`StreamBuilder::filter_zones`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use emwin_tg::product::ugc::UgcCode;
+    /// use futures::StreamExt;
+    /// use std::io::Write;
+    ///
+    /// let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// for (name, ugc) in [("A.TXT", "MNZ060-210300-"), ("B.TXT", "WIZ014-210300-")] {
+    ///     zip.start_file(name, Default::default()).unwrap();
+    ///     write!(zip, "WWUS53 KMPX 202245\r\r\nSVSMPX\r\r\n\r\r\n{}\r\r\n", ugc).unwrap();
+    /// }
+    /// let archive = bytes::Bytes::from(zip.finish().unwrap().into_inner());
+    ///
+    /// let source = futures::stream::iter(vec![Ok(archive)]).boxed();
+    /// let zone: UgcCode = "MNZ060".parse().unwrap();
+    /// let stream = emwin_tg::Stream::from_source(source).filter_zones([zone]);
+    ///
+    /// let filenames: Vec<String> = stream.map(|product| product.unwrap().filename).collect().await;
+    /// assert_eq!(filenames, ["A.TXT"]);
+    /// # })
+    /// ```
+    pub fn filter_zones<I: IntoIterator<Item = UgcCode>>(mut self, codes: I) -> Self {
+        self.filters
+            .push(filter::zones(codes.into_iter().collect()));
+        self
+    }
+
+    /// Only yield products whose WMO heading starts with `prefix`, e.g. `WU` for severe
+    /// thunderstorm warnings or `FXUS63 KDVN`.
+    ///
+    /// The heading is taken from the filename and written with spaces, as in
+    /// [`WmoHeading`](crate::product::wmo::WmoHeading)'s `Display`. Errors are always yielded.
+    /// This can also be set with [`StreamBuilder::filter_heading`].
+    pub fn filter_heading<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.filters.push_by_name(filter::heading(prefix.into()));
+        self
+    }
+
+    /// Only yield products whose filename matches `pattern`, where `*` matches any run of
+    /// characters and `?` matches any one character, ignoring case.
+    ///
+    /// Errors are always yielded. This can also be set with [`StreamBuilder::filter_filename`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use futures::StreamExt;
+    /// use std::io::Write;
+    ///
+    /// let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// for name in ["AFDOKXNY.TXT", "ZFPOKXNY.TXT", "RADALLUS.GIF"] {
+    ///     zip.start_file(name, Default::default()).unwrap();
+    ///     zip.write_all(b"hello").unwrap();
+    /// }
+    /// let archive = bytes::Bytes::from(zip.finish().unwrap().into_inner());
+    ///
+    /// let source = futures::stream::iter(vec![Ok(archive)]).boxed();
+    /// let stream = emwin_tg::Stream::from_source(source).filter_filename("*OKX??.txt");
+    ///
+    /// let filenames: Vec<String> = stream.map(|product| product.unwrap().filename).collect().await;
+    /// assert_eq!(filenames, ["AFDOKXNY.TXT", "ZFPOKXNY.TXT"]);
+    /// # })
+    /// ```
+    pub fn filter_filename<T: Into<String>>(mut self, pattern: T) -> Self {
+        self.filters.push_by_name(filter::filename(pattern.into()));
+        self
+    }
+
+    /// Only yield products issued within `start..end`.
+    ///
+    /// Products are matched on [`Product::issued_at`]. Products whose issuance time can't be
+    /// determined are yielded if `keep_undated` is true, and dropped otherwise. Errors are always
+    /// yielded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use futures::StreamExt;
+    /// use std::io::Write;
+    /// use time::{Date, Month};
+    ///
+    /// let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// for name in [
+    ///     "A_FXUS63KDVN150952_C_KWIN_20220215095257_060735-2-AFDDVNIA.TXT",
+    ///     "A_FXUS63KDVN151152_C_KWIN_20220215115257_060736-2-AFDDVNIA.TXT",
+    ///     "A_FXUS63KDVN151352_C_KWIN_20220215135257_060737-2-AFDDVNIA.TXT",
+    ///     "RADALLUS.GIF",
+    /// ] {
+    ///     zip.start_file(name, Default::default()).unwrap();
+    ///     zip.write_all(b"hello").unwrap();
+    /// }
+    /// let archive = bytes::Bytes::from(zip.finish().unwrap().into_inner());
+    ///
+    /// let date = Date::from_calendar_date(2022, Month::February, 15).unwrap();
+    /// let start = date.with_hms(11, 0, 0).unwrap().assume_utc();
+    /// let end = date.with_hms(12, 0, 0).unwrap().assume_utc();
+    ///
+    /// let source = futures::stream::iter(vec![Ok(archive)]).boxed();
+    /// let stream = emwin_tg::Stream::from_source(source).filter_time_range(start, end, false);
+    ///
+    /// let filenames: Vec<String> = stream.map(|product| product.unwrap().filename).collect().await;
+    /// assert_eq!(
+    ///     filenames,
+    ///     ["A_FXUS63KDVN151152_C_KWIN_20220215115257_060736-2-AFDDVNIA.TXT"]
+    /// );
+    /// # })
+    /// ```
+    pub fn filter_time_range(
+        mut self,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+        keep_undated: bool,
+    ) -> Self {
+        self.filters
+            .push_by_name(move |product| match product.issued_at() {
+                Some(issued_at) => (start..end).contains(&issued_at),
+                None => keep_undated,
+            });
+        self
+    }
 }
 
 impl<S: Source> futures::Stream for Stream<S> {
@@ -107,29 +1013,237 @@ impl<S: Source> futures::Stream for Stream<S> {
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let mut this = self.project();
 
-        loop {
+        if let Some(signal) = this.shutdown_signal {
+            if signal.0.as_mut().poll(cx).is_ready() {
+                *this.shutdown_signal = None;
+                if *this.shutdown == Shutdown::Running {
+                    *this.shutdown = Shutdown::Requested;
+                }
+            }
+        }
+        if *this.shutdown == Shutdown::Requested {
+            log::info!("shutting down");
+            *this.shutdown = if this.source.as_mut().shutdown() {
+                Shutdown::Draining
+            } else {
+                Shutdown::Abandoned
+            };
+        }
+
+        let poll = loop {
             if let Some(value) = this.output_buffer.pop_front() {
+                if let Some(issued_at) = value.as_ref().ok().and_then(Product::issued_at) {
+                    if this
+                        .newest_issued_at
+                        .is_none_or(|newest| issued_at > newest)
+                    {
+                        *this.newest_issued_at = Some(issued_at);
+                    }
+                }
                 break Poll::Ready(Some(value));
             }
 
+            // Decompress more of the last archive before fetching another
+            if let Some(members) = this.pending {
+                if !members.is_empty() {
+                    unpack(members, this.filters, this.state, this.output_buffer);
+                    continue;
+                }
+                *this.pending = None;
+            }
+
+            if *this.shutdown == Shutdown::Abandoned {
+                break Poll::Ready(None);
+            }
+
             match this.source.as_mut().poll_next(cx) {
-                Poll::Ready(Some(Ok(bytes))) => match this.state.new_products_in(bytes) {
-                    Ok(vec) => this.output_buffer.extend(vec),
-                    Err(e) => break Poll::Ready(Some(Err(e))),
-                },
+                Poll::Ready(Some(Ok(bytes))) => {
+                    this.observers.fetched(&bytes);
+                    match this.state.new_members_in(bytes, *this.unpack_nested) {
+                        Ok(mut members) => {
+                            if let Some(stats) = this.state.last_archive() {
+                                this.observers.cycled(&stats);
+                            }
+                            if let Some(gap) = this.state.take_gap() {
+                                this.observers.gapped(&gap);
+                                if let Some(gaps) = this.gaps {
+                                    gaps.push_back(gap);
+                                }
+                            }
+                            // Count only the products the filters might keep
+                            let unpack_nested = *this.unpack_nested;
+                            members.retain(|name| {
+                                (unpack_nested && name.to_ascii_uppercase().ends_with(".ZIP"))
+                                    || this.filters.accepts_name(name)
+                            });
+                            let dropped = match *this.max_buffered {
+                                Some((max, OverflowPolicy::DropOldest)) => {
+                                    let excess = members.len().saturating_sub(max);
+                                    members.skip(excess);
+                                    excess
+                                }
+                                Some((max, OverflowPolicy::DropNewest)) => {
+                                    let excess = members.len().saturating_sub(max);
+                                    members.truncate(max);
+                                    excess
+                                }
+                                _ => 0,
+                            };
+                            if dropped > 0 {
+                                log::warn!("output buffer full; dropped {} products", dropped);
+                            }
+                            *this.pending = Some(members);
+                        }
+                        Err(e) => break Poll::Ready(Some(Err(e))),
+                    }
+                }
                 Poll::Ready(Some(Err(e))) => break Poll::Ready(Some(Err(e))),
                 Poll::Ready(None) => break Poll::Ready(None),
                 Poll::Pending => break Poll::Pending,
             }
+        };
+
+        let poll = match this.idle_timeout {
+            Some((window, sleep)) => match poll {
+                Poll::Ready(Some(Ok(_))) => {
+                    sleep.as_mut().reset(tokio::time::Instant::now() + *window);
+                    poll
+                }
+                Poll::Pending
+                    if *this.shutdown == Shutdown::Running
+                        && sleep.as_mut().poll(cx).is_ready() =>
+                {
+                    // Report the stall, then start waiting again
+                    sleep.as_mut().reset(tokio::time::Instant::now() + *window);
+                    Poll::Ready(Some(Err(Error::Stalled { idle: *window })))
+                }
+                _ => poll,
+            },
+            None => poll,
+        };
+
+        if let Poll::Ready(Some(result)) = &poll {
+            this.observers.yielded(result);
+            #[cfg(feature = "tracing")]
+            if let Ok(product) = result {
+                let latency = std::time::SystemTime::now()
+                    .duration_since(product.received_at)
+                    .unwrap_or_default();
+                tracing::debug!(
+                    filename = %product.filename,
+                    bytes = product.contents.len(),
+                    latency_ms = latency.as_millis() as u64,
+                    "yielded product"
+                );
+            }
         }
+
+        #[cfg(feature = "metrics")]
+        {
+            let pending = this.pending.as_ref().map_or(0, NewMembers::len);
+            metrics::gauge!("emwin_tg_dedup_filenames").set(this.state.tracked() as f64);
+            metrics::gauge!("emwin_tg_buffered_products")
+                .set((this.output_buffer.len() + pending) as f64);
+        }
+        poll
     }
 }
 
+/// Decompress the next of an archive's new members, buffering the products which pass the
+/// filters and haven't been seen before.
+fn unpack(
+    members: &mut NewMembers,
+    filters: &Filters,
+    state: &mut StreamState,
+    buffer: &mut VecDeque<Result<Product, Error>>,
+) {
+    buffer.extend(
+        members
+            .next_batch()
+            .into_iter()
+            .filter(|result| match result {
+                Ok(product) => filters.accepts(product) && state.accepts(product),
+                Err(_) => true,
+            }),
+    )
+}
+
+/// What [`Stream::max_buffered`] does with products which don't fit.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum OverflowPolicy {
+    /// Discard the products which would be yielded first
+    DropOldest,
+    /// Discard the products which would be yielded last
+    DropNewest,
+    /// Keep every product, and stop fetching until they've all been yielded
+    ///
+    /// This is what `Stream` does without a limit. The compressed archive is held until each of
+    /// its new products has been decompressed and yielded, and no more archives are fetched until
+    /// the consumer catches up.
+    Backpressure,
+}
+
+mod archives;
+pub use archives::ArchiveStream;
+
+mod buffered;
+pub use buffered::BufferedStream;
+
+mod builder;
+pub use builder::StreamBuilder;
+use builder::DEFAULT_BUFFER_CAPACITY;
+
+mod events;
+pub use events::{EventStream, StreamEvent};
+
+mod filter_map;
+pub use filter_map::FilterMapProduct;
+
+mod observer;
+pub use observer::Observer;
+use observer::Observers;
+
+mod sorted;
+pub use sorted::SortedStream;
+
 /// A source of EMWIN TG data.
-pub trait Source: futures::stream::Stream<Item = Result<Bytes, crate::Error>> {}
+pub trait Source: futures::stream::Stream<Item = Result<Bytes, crate::Error>> {
+    /// Stop scheduling new fetches, letting any already in flight finish.
+    ///
+    /// Returns true if the source will end once its in-flight fetches are yielded, or false if it
+    /// can't shut down gracefully, in which case [`Stream::shutdown`] stops polling it right away.
+    /// The default implementation returns false.
+    fn shutdown(self: Pin<&mut Self>) -> bool {
+        false
+    }
+
+    /// How long a [`Stream`] should remember filenames from this source, if the source knows.
+    ///
+    /// A filename must be remembered for as long as it may reappear in the archives the source
+    /// polls, or its product will be yielded again. Streams use this as their default
+    /// [`dedup_window`](Stream::dedup_window), or six hours if it returns `None`, as the default
+    /// implementation does.
+    fn dedup_window(&self) -> Option<Duration> {
+        None
+    }
+
+    /// The longest time between fetches of any archive this source polls, if the source knows.
+    ///
+    /// A product which remains in a slowly refetched archive is absent in between, so
+    /// [`Stream::redeliver_after`] waits at least twice this long before yielding it again. The
+    /// default implementation returns `None`.
+    fn refetch_interval(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Source for BoxStream<'static, Result<Bytes, crate::Error>> {}
 
 mod text;
 pub use text::TextSource;
+#[cfg(feature = "blocking")]
+pub(crate) use text::{Text20Min, Text2Min, Text3Hour, Text6Min};
 
 /// A `Stream` of text products.
 pub type TextStream = Stream<TextSource>;
@@ -139,3 +1253,15 @@ pub use image::ImageSource;
 
 // A `Stream` of image products.
 pub type ImageStream = Stream<ImageSource>;
+
+mod combined;
+pub use combined::CombinedSource;
+
+/// A `Stream` of text and image products.
+pub type CombinedStream = Stream<CombinedSource>;
+
+mod url;
+pub use url::UrlSource;
+
+mod directory;
+pub use directory::DirectorySource;
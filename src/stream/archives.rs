@@ -0,0 +1,57 @@
+use crate::{Error, FetchedArchive};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A stream of the archives retrieved from EMWIN TG, without unpacking them.
+///
+/// Created by [`TextSource::archives`](crate::TextSource::archives) or
+/// [`ImageSource::archives`](crate::ImageSource::archives). Like a [`Stream`](crate::Stream), it
+/// continues retrying after errors until it's dropped.
+///
+/// # Example
+///
+/// ```rust
+/// # tokio_test::block_on(async {
+/// use futures::StreamExt;
+///
+/// let mut archives = emwin_tg::TextSource::from(reqwest::Client::new()).archives();
+///
+/// while let Some(result) = archives.next().await {
+///     # break;
+///     if let Ok(archive) = result {
+///         println!("{}: {} bytes", archive.name, archive.contents.len());
+///     }
+/// }
+/// # })
+/// ```
+pub struct ArchiveStream(BoxStream<'static, Result<FetchedArchive, Error>>);
+
+/// A source's method for polling the next archive.
+type PollArchive<S> =
+    fn(Pin<&mut S>, &mut Context<'_>) -> Poll<Option<Result<FetchedArchive, Error>>>;
+
+impl ArchiveStream {
+    pub(super) fn new<S>(source: S, poll: PollArchive<S>) -> Self
+    where
+        S: Send + 'static,
+    {
+        let mut source = Box::pin(source);
+        Self(futures::stream::poll_fn(move |cx| poll(source.as_mut(), cx)).boxed())
+    }
+}
+
+impl std::fmt::Debug for ArchiveStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArchiveStream").finish_non_exhaustive()
+    }
+}
+
+impl futures::stream::Stream for ArchiveStream {
+    type Item = Result<FetchedArchive, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
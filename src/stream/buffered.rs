@@ -0,0 +1,49 @@
+use super::{Source, Stream};
+use crate::{Error, Product};
+use futures::StreamExt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// A [`Stream`] which is driven in the background, buffering products until they're consumed.
+///
+/// Created by [`Stream::buffered`]. Dropping it stops the background task.
+#[derive(Debug)]
+pub struct BufferedStream {
+    receiver: mpsc::Receiver<Result<Product, Error>>,
+    task: JoinHandle<()>,
+}
+
+impl BufferedStream {
+    pub(super) fn new<S>(stream: Stream<S>, capacity: usize) -> Self
+    where
+        S: Source + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        let task = tokio::spawn(async move {
+            futures::pin_mut!(stream);
+            while let Some(result) = stream.next().await {
+                if sender.send(result).await.is_err() {
+                    // The receiver was dropped
+                    break;
+                }
+            }
+        });
+        Self { receiver, task }
+    }
+}
+
+impl Drop for BufferedStream {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl futures::Stream for BufferedStream {
+    type Item = Result<Product, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
@@ -0,0 +1,198 @@
+use super::{OverflowPolicy, Source, Stream};
+use crate::product::ugc::UgcCode;
+use crate::{filter, Filters, RateLimiter, SourceConfig};
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// The number of products `Stream` has room to buffer before it needs to reallocate, by default.
+pub(super) const DEFAULT_BUFFER_CAPACITY: usize = 50;
+
+/// Configures and starts a [`Stream`].
+///
+/// # Example
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// let stream = emwin_tg::TextStream::builder()
+///     .buffer_capacity(500)
+///     .filter_awips(["TOR", "SVR"])
+///     .filter_office("KOKX")
+///     .build();
+///
+/// assert!(stream.buffer_capacity() >= 500);
+/// # })
+/// ```
+#[derive(Debug)]
+pub struct StreamBuilder<S> {
+    client: Option<reqwest::Client>,
+    config: SourceConfig,
+    buffer_capacity: usize,
+    dedup_capacity: Option<usize>,
+    dedup_window: Option<Duration>,
+    max_buffered: Option<(usize, OverflowPolicy)>,
+    filters: Filters,
+    source: PhantomData<S>,
+}
+
+impl<S: Source + From<(reqwest::Client, SourceConfig)>> Default for StreamBuilder<S> {
+    fn default() -> Self {
+        Self {
+            client: None,
+            config: SourceConfig::new(),
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+            dedup_capacity: None,
+            dedup_window: None,
+            max_buffered: None,
+            filters: Filters::default(),
+            source: PhantomData,
+        }
+    }
+}
+
+impl<S: Source + From<(reqwest::Client, SourceConfig)>> StreamBuilder<S> {
+    /// Use a particular HTTP client.
+    ///
+    /// The NWS has restricted other APIs by requiring a `User-Agent`. If you supply your own
+    /// client, be sure to specify one.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Configure the source, e.g. its mirrors and polling intervals.
+    ///
+    /// This replaces any [`rate_limiter`](Self::rate_limiter) set earlier.
+    pub fn source_config(mut self, config: SourceConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Share a limit on the rate of requests with other sources.
+    ///
+    /// See [`RateLimiter`].
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.config = self.config.rate_limiter(limiter);
+        self
+    }
+
+    /// Set the number of products the output buffer can hold before it needs to reallocate.
+    ///
+    /// Products are decompressed into the buffer a member at a time, or a batch at a time with
+    /// the `parallel` feature, and held until they're consumed. A member which is itself an
+    /// archive can fill the buffer with many products at once, and a larger capacity avoids
+    /// reallocating when one lands.
+    pub fn buffer_capacity(mut self, capacity: usize) -> Self {
+        self.buffer_capacity = capacity;
+        self
+    }
+
+    /// Hold at most `max` products waiting to be yielded, handling the rest according to
+    /// `policy`.
+    ///
+    /// See [`Stream::max_buffered`].
+    pub fn max_buffered(mut self, max: usize, policy: OverflowPolicy) -> Self {
+        self.max_buffered = Some((max, policy));
+        self
+    }
+
+    /// Remember at most `max` filenames for suppressing duplicates, instead of remembering each
+    /// for the dedup window.
+    ///
+    /// See [`Stream::dedup_capacity`].
+    pub fn dedup_capacity(mut self, max: usize) -> Self {
+        self.dedup_capacity = Some(max);
+        self
+    }
+
+    /// Remember each filename for `window` after it was last seen, instead of for the source's
+    /// default window.
+    ///
+    /// See [`Stream::dedup_window`].
+    pub fn dedup_window(mut self, window: Duration) -> Self {
+        self.dedup_window = Some(window);
+        self
+    }
+
+    /// Only yield products whose AWIPS identifier starts with one of `prefixes`.
+    ///
+    /// See [`Stream::filter_awips`].
+    pub fn filter_awips<I, T>(mut self, prefixes: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let prefixes = prefixes.into_iter().map(Into::into).collect();
+        self.filters.push_by_name(filter::awips(prefixes));
+        self
+    }
+
+    /// Only yield products issued by `office`.
+    ///
+    /// See [`Stream::filter_office`].
+    pub fn filter_office<T: Into<String>>(mut self, office: T) -> Self {
+        self.filters.push(filter::office(office.into()));
+        self
+    }
+
+    /// Only yield products issued by the weather forecast office `wfo`.
+    ///
+    /// See [`Stream::filter_wfo`].
+    pub fn filter_wfo<T: Into<String>>(mut self, wfo: T) -> Self {
+        self.filters.push(filter::wfo(wfo.into()));
+        self
+    }
+
+    /// Only yield products which affect any of `states`.
+    ///
+    /// See [`Stream::filter_states`].
+    pub fn filter_states<I, T>(mut self, states: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let states = states.into_iter().map(Into::into).collect();
+        self.filters.push(filter::states(states));
+        self
+    }
+
+    /// Only yield products which affect any of the counties or zones `codes`.
+    ///
+    /// See [`Stream::filter_zones`].
+    pub fn filter_zones<I: IntoIterator<Item = UgcCode>>(mut self, codes: I) -> Self {
+        self.filters
+            .push(filter::zones(codes.into_iter().collect()));
+        self
+    }
+
+    /// Only yield products whose WMO heading starts with `prefix`.
+    ///
+    /// See [`Stream::filter_heading`].
+    pub fn filter_heading<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.filters.push_by_name(filter::heading(prefix.into()));
+        self
+    }
+
+    /// Only yield products whose filename matches a glob `pattern`.
+    ///
+    /// See [`Stream::filter_filename`].
+    pub fn filter_filename<T: Into<String>>(mut self, pattern: T) -> Self {
+        self.filters.push_by_name(filter::filename(pattern.into()));
+        self
+    }
+
+    /// Start the stream.
+    pub fn build(self) -> Stream<S> {
+        let client = self.client.unwrap_or_else(crate::default_client);
+        let source = S::from((client, self.config));
+        let mut stream = Stream::with_buffer_capacity(source, self.buffer_capacity);
+        stream.filters = self.filters;
+        stream.max_buffered = self.max_buffered;
+        if let Some(window) = self.dedup_window {
+            stream = stream.dedup_window(window);
+        }
+        match self.dedup_capacity {
+            Some(max) => stream.dedup_capacity(max),
+            None => stream,
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use super::{ArchiveStream, ImageSource, Source, TextSource};
+use crate::{Error, FetchedArchive, Mirrors, SourceConfig};
+use bytes::Bytes;
+use futures::Stream;
+use pin_project_lite::pin_project;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+pin_project! {
+/// The text and image feeds from EMWIN TG, merged into one source.
+///
+/// Both feeds share one HTTP client and one [`SourceConfig`], including its mirrors and any
+/// [`RateLimiter`](crate::RateLimiter), and a [`CombinedStream`](crate::CombinedStream) built on
+/// this source tracks the products of both in one dedup state. Use
+/// [`Product::feed`](crate::Product::feed) to tell which feed a product came from.
+///
+/// # Example
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use emwin_tg::product::Feed;
+/// use futures::StreamExt;
+///
+/// let mut stream = emwin_tg::CombinedStream::new();
+/// while let Some(Ok(product)) = stream.next().await {
+///     # break;
+///     match product.feed() {
+///         Some(Feed::Text) => println!("text: {}", product.filename),
+///         Some(Feed::Image) => println!("image: {}", product.filename),
+///         None => {}
+///     }
+/// }
+/// # })
+/// ```
+pub struct CombinedSource {
+    #[pin]
+    text: TextSource,
+    #[pin]
+    image: ImageSource,
+}
+}
+
+impl Stream for CombinedSource {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_archive(cx)
+            .map(|result| result.map(|archive| archive.map(|archive| archive.contents)))
+    }
+}
+
+impl CombinedSource {
+    fn poll_archive(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<FetchedArchive, Error>>> {
+        let this = self.project();
+
+        // The feeds only end once shut down, and then the source ends when both have
+        let text_ended = match this.text.poll_archive(cx) {
+            Poll::Ready(Some(value)) => return Poll::Ready(Some(value)),
+            Poll::Ready(None) => true,
+            Poll::Pending => false,
+        };
+        match this.image.poll_archive(cx) {
+            Poll::Ready(None) if !text_ended => Poll::Pending,
+            image => image,
+        }
+    }
+
+    /// Yield the archives themselves, rather than the products inside them.
+    ///
+    /// See [`TextSource::archives`].
+    pub fn archives(self) -> ArchiveStream {
+        ArchiveStream::new(self, Self::poll_archive)
+    }
+}
+
+impl CombinedSource {
+    /// Retrieve archives from a prioritized list of gateway directories.
+    pub fn with_mirrors(client: reqwest::Client, mirrors: Mirrors) -> Self {
+        Self::with_config(client, SourceConfig::new().mirrors(mirrors))
+    }
+
+    /// Retrieve archives using custom settings, like polling intervals.
+    pub fn with_config(client: reqwest::Client, config: SourceConfig) -> Self {
+        Self {
+            text: TextSource::with_config(client.clone(), config.clone()),
+            image: ImageSource::with_config(client, config),
+        }
+    }
+
+    /// Skip the longer archives of both feeds.
+    ///
+    /// See [`TextSource::realtime_only`] and [`ImageSource::realtime_only`].
+    pub fn realtime_only(self) -> Self {
+        Self {
+            text: self.text.realtime_only(),
+            image: self.image.realtime_only(),
+        }
+    }
+}
+
+impl From<reqwest::Client> for CombinedSource {
+    fn from(c: reqwest::Client) -> Self {
+        Self::with_mirrors(c, Mirrors::default())
+    }
+}
+
+impl From<(reqwest::Client, SourceConfig)> for CombinedSource {
+    fn from((client, config): (reqwest::Client, SourceConfig)) -> Self {
+        Self::with_config(client, config)
+    }
+}
+
+impl Source for CombinedSource {
+    /// Stop fetching from both feeds, ending once the archives in flight have been yielded.
+    fn shutdown(self: Pin<&mut Self>) -> bool {
+        let this = self.project();
+        this.text.shutdown();
+        this.image.shutdown();
+        true
+    }
+
+    /// The longer of the two feeds' windows.
+    fn dedup_window(&self) -> Option<Duration> {
+        self.text.dedup_window().max(self.image.dedup_window())
+    }
+
+    /// The longer of the two feeds' intervals.
+    fn refetch_interval(&self) -> Option<Duration> {
+        self.text
+            .refetch_interval()
+            .max(self.image.refetch_interval())
+    }
+}
@@ -0,0 +1,119 @@
+use crate::Error;
+use bytes::Bytes;
+use futures::Stream;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+/// A feed of previously downloaded archives, read from a local directory.
+///
+/// `DirectorySource` yields each `.zip` file in the directory once, then ends. It's useful for
+/// testing consumers and replaying historical data without touching the gateway.
+///
+/// Archives are yielded in filename order, or in order of modification time with
+/// [`in_timestamp_order`](Self::in_timestamp_order). Each is read when it's due, so changes to
+/// the directory after the source is created may cause read errors, which are yielded like any
+/// other error.
+///
+/// # Example
+///
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use futures::StreamExt;
+/// use std::time::Duration;
+///
+/// let source = emwin_tg::DirectorySource::new("archives/2022-02-20")?
+///     .in_timestamp_order()
+///     .delay(Duration::from_millis(100));
+/// let mut stream = emwin_tg::Stream::from_source(source);
+///
+/// while let Some(product) = stream.next().await {
+///     println!("{}", product?.filename);
+/// }
+/// # Ok::<(), emwin_tg::Error>(())
+/// # }).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct DirectorySource {
+    archives: VecDeque<(PathBuf, SystemTime)>,
+    delay: Duration,
+    sleep: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+impl DirectorySource {
+    /// List the archives in `dir`.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let mut archives = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let is_zip = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+            let metadata = entry.metadata()?;
+            if is_zip && metadata.is_file() {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                archives.push((path, modified));
+            }
+        }
+        archives.sort();
+
+        Ok(Self {
+            archives: archives.into(),
+            delay: Duration::ZERO,
+            sleep: None,
+        })
+    }
+
+    /// Yield archives in order of modification time, oldest first, rather than by filename.
+    pub fn in_timestamp_order(mut self) -> Self {
+        self.archives
+            .make_contiguous()
+            .sort_by(|(a_path, a_time), (b_path, b_time)| (a_time, a_path).cmp(&(b_time, b_path)));
+        self
+    }
+
+    /// Wait this long before yielding each archive after the first, to simulate a live feed.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+impl Stream for DirectorySource {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if let Some(sleep) = this.sleep.as_mut() {
+            if sleep.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.sleep = None;
+        }
+
+        let (path, _) = match this.archives.pop_front() {
+            Some(archive) => archive,
+            None => return Poll::Ready(None),
+        };
+        if !this.delay.is_zero() && !this.archives.is_empty() {
+            this.sleep = Some(Box::pin(tokio::time::sleep(this.delay)));
+        }
+        log::debug!("reading {}", path.display());
+        Poll::Ready(Some(
+            std::fs::read(path).map(Bytes::from).map_err(Error::from),
+        ))
+    }
+}
+
+impl super::Source for DirectorySource {
+    /// Skip the archives which haven't been read yet, ending right away.
+    fn shutdown(mut self: Pin<&mut Self>) -> bool {
+        self.archives.clear();
+        self.sleep = None;
+        true
+    }
+}
@@ -0,0 +1,122 @@
+use super::{Source, Stream};
+use crate::{Error, Gap, Product};
+use pin_project_lite::pin_project;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+
+/// Something which happened on a [`Stream`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum StreamEvent {
+    /// A product was received.
+    Product(Product),
+    /// Nothing was received for the configured heartbeat interval.
+    ///
+    /// Heartbeats distinguish a quiet feed from a stream which is no longer being driven.
+    Heartbeat,
+    /// Products were likely missed; see [`Gap`].
+    ///
+    /// Gaps are reported just before the first product from the archive which revealed them.
+    Gap(Gap),
+}
+
+pin_project! {
+/// A [`Stream`] which yields [`StreamEvent`]s instead of bare products.
+///
+/// Created by [`Stream::events`].
+#[derive(Debug)]
+pub struct EventStream<S: Source> {
+    #[pin]
+    stream: Stream<S>,
+    heartbeat: Option<(Duration, Pin<Box<Sleep>>)>,
+    held: Option<Result<StreamEvent, Error>>,
+}
+}
+
+impl<S: Source> EventStream<S> {
+    pub(super) fn new(stream: Stream<S>) -> Self {
+        Self {
+            stream,
+            heartbeat: None,
+            held: None,
+        }
+    }
+
+    /// Emit a [`StreamEvent::Heartbeat`] whenever `interval` elapses without any other event.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// use emwin_tg::StreamEvent;
+    /// use futures::StreamExt;
+    /// use std::time::Duration;
+    ///
+    /// // A source which never produces anything
+    /// let source = futures::stream::pending().boxed();
+    /// let events = emwin_tg::Stream::from_source(source)
+    ///     .events()
+    ///     .heartbeat(Duration::from_millis(10));
+    ///
+    /// let events: Vec<_> = events.take(3).collect().await;
+    /// assert!(events
+    ///     .iter()
+    ///     .all(|event| matches!(event, Ok(StreamEvent::Heartbeat))));
+    /// # })
+    /// ```
+    pub fn heartbeat(mut self, interval: Duration) -> Self {
+        let sleep = Box::pin(tokio::time::sleep(interval));
+        self.heartbeat = Some((interval, sleep));
+        self
+    }
+}
+
+impl<S: Source> futures::Stream for EventStream<S> {
+    type Item = Result<StreamEvent, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        let event = match this.held.take() {
+            Some(event) => event,
+            None => match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(result)) => {
+                    let event = result.map(StreamEvent::Product);
+                    // Report any gap ahead of the product which revealed it
+                    match this.stream.as_mut().take_gap() {
+                        Some(gap) => {
+                            *this.held = Some(event);
+                            Ok(StreamEvent::Gap(gap))
+                        }
+                        None => event,
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => match this.stream.as_mut().take_gap() {
+                    // The archive's products were all filtered out
+                    Some(gap) => Ok(StreamEvent::Gap(gap)),
+                    None => {
+                        let quiet = match this.heartbeat {
+                            Some((_, sleep)) => sleep.as_mut().poll(cx).is_ready(),
+                            None => false,
+                        };
+                        if !quiet {
+                            return Poll::Pending;
+                        }
+                        Ok(StreamEvent::Heartbeat)
+                    }
+                },
+            },
+        };
+
+        // Any event restarts the quiet period
+        if let Some((interval, sleep)) = this.heartbeat {
+            sleep.as_mut().reset(Instant::now() + *interval);
+        }
+
+        Poll::Ready(Some(event))
+    }
+}
@@ -0,0 +1,46 @@
+use super::{Source, Stream};
+use crate::{Error, Product};
+use pin_project_lite::pin_project;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+pin_project! {
+/// A [`Stream`] which transforms products, dropping those which don't map to anything.
+///
+/// Created by [`Stream::filter_map_product`].
+pub struct FilterMapProduct<S: Source, F> {
+    #[pin]
+    stream: Stream<S>,
+    f: F,
+}
+}
+
+impl<S: Source, F> FilterMapProduct<S, F> {
+    pub(super) fn new(stream: Stream<S>, f: F) -> Self {
+        Self { stream, f }
+    }
+}
+
+impl<S: Source, F, T> futures::Stream for FilterMapProduct<S, F>
+where
+    F: FnMut(Product) -> Option<T>,
+{
+    type Item = Result<T, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        loop {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(product))) => {
+                    if let Some(item) = (this.f)(product) {
+                        return Poll::Ready(Some(Ok(item)));
+                    }
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{Error, FetchStream, Fetchable};
+use super::ArchiveStream;
+use crate::{Error, FetchStream, Fetchable, FetchedArchive, Mirrors, SourceConfig};
 use bytes::Bytes;
 use futures::Stream;
 use pin_project_lite::pin_project;
@@ -18,9 +19,9 @@ pin_project! {
 /// ~90 seconds of latency, and yielding products up to 3-4 hours old.
 pub struct ImageSource {
     #[pin]
-    image15min: FetchStream<Image15Min>,
+    image15min: FetchStream,
     #[pin]
-    image3hour: FetchStream<Image3Hour>,
+    image3hour: FetchStream,
 }
 }
 
@@ -28,29 +29,120 @@ impl Stream for ImageSource {
     type Item = Result<Bytes, Error>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let this = self.project();
-        if let Poll::Ready(value) = this.image15min.poll_next(cx) {
-            return Poll::Ready(value);
+        self.poll_archive(cx)
+            .map(|result| result.map(|archive| archive.map(|archive| archive.contents)))
+    }
+}
+
+impl ImageSource {
+    pub(super) fn poll_archive(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<FetchedArchive, Error>>> {
+        let mut this = self.project();
+
+        // Drive every feed before yielding anything, so that each feed's fetches start and
+        // progress concurrently even while another feed has results ready
+        this.image15min.as_mut().poll_fetches(cx);
+        this.image3hour.as_mut().poll_fetches(cx);
+
+        let result = this
+            .image15min
+            .as_mut()
+            .take_result()
+            .or_else(|| this.image3hour.as_mut().take_result());
+        match result {
+            Some(value) => Poll::Ready(Some(value)),
+            None if this.image15min.is_drained() && this.image3hour.is_drained() => {
+                Poll::Ready(None)
+            }
+            None => Poll::Pending,
         }
-        if let Poll::Ready(value) = this.image3hour.poll_next(cx) {
-            return Poll::Ready(value);
+    }
+
+    /// Yield the archives themselves, rather than the products inside them.
+    ///
+    /// Each archive is yielded as it was retrieved, along with its name and when it was fetched,
+    /// for consumers which archive or relay the zips.
+    pub fn archives(self) -> ArchiveStream {
+        ArchiveStream::new(self, Self::poll_archive)
+    }
+}
+
+impl ImageSource {
+    /// Retrieve archives from a prioritized list of gateway directories.
+    pub fn with_mirrors(client: reqwest::Client, mirrors: Mirrors) -> Self {
+        Self::with_config(client, SourceConfig::new().mirrors(mirrors))
+    }
+
+    /// Retrieve archives using custom settings, like polling intervals.
+    pub fn with_config(client: reqwest::Client, config: SourceConfig) -> Self {
+        let mirrors = config.shared_mirrors();
+        Self {
+            image15min: FetchStream::new::<Image15Min>(client.clone(), mirrors.clone(), &config),
+            image3hour: FetchStream::new::<Image3Hour>(client, mirrors, &config),
         }
-        Poll::Pending
     }
 }
 
-impl super::Source for ImageSource {}
+impl ImageSource {
+    /// Skip the 3-hour archive, polling only the 15-minute one.
+    ///
+    /// See [`TextSource::realtime_only`](crate::TextSource::realtime_only).
+    pub fn realtime_only(mut self) -> Self {
+        self.image3hour.disable();
+        self
+    }
+}
+
+impl From<reqwest::Client> for ImageSource {
+    fn from(c: reqwest::Client) -> Self {
+        Self::with_mirrors(c, Mirrors::default())
+    }
+}
+
+impl From<(reqwest::Client, SourceConfig)> for ImageSource {
+    fn from((client, config): (reqwest::Client, SourceConfig)) -> Self {
+        Self::with_config(client, config)
+    }
+}
+
+impl super::Source for ImageSource {
+    /// Stop fetching archives, ending once those in flight have been yielded.
+    fn shutdown(self: Pin<&mut Self>) -> bool {
+        let this = self.project();
+        this.image15min.shut_down();
+        this.image3hour.shut_down();
+        true
+    }
+    /// Twice the span of the longest archive being polled.
+    fn dedup_window(&self) -> Option<Duration> {
+        [&self.image15min, &self.image3hour]
+            .iter()
+            .filter_map(|feed| feed.span())
+            .max()
+            .map(|span| span * 2)
+    }
+
+    /// The longest interval of the archives being polled.
+    fn refetch_interval(&self) -> Option<Duration> {
+        [&self.image15min, &self.image3hour]
+            .iter()
+            .filter_map(|feed| feed.refetch_interval())
+            .max()
+    }
+}
 
 struct Image3Hour;
 impl Fetchable for Image3Hour {
-    const URL: &'static str =
-        "https://tgftp.nws.noaa.gov/SL.us008001/CU.EMWIN/DF.xt/DC.gsatR/OPS/imghrs03.zip";
+    const FILENAME: &'static str = "imghrs03.zip";
     const REFETCH_INTERVAL: Duration = Duration::from_secs(60 * 60); // it's regenerated hourly
+    const SPAN: Duration = Duration::from_secs(60 * 60 * 3);
 }
 
 struct Image15Min;
 impl Fetchable for Image15Min {
-    const URL: &'static str =
-        "https://tgftp.nws.noaa.gov/SL.us008001/CU.EMWIN/DF.xt/DC.gsatR/OPS/imgmin15.zip";
+    const FILENAME: &'static str = "imgmin15.zip";
     const REFETCH_INTERVAL: Duration = Duration::from_secs(352);
+    const SPAN: Duration = Duration::from_secs(15 * 60);
 }
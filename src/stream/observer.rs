@@ -0,0 +1,103 @@
+use crate::{ArchiveStats, Error, Gap, Product};
+use bytes::Bytes;
+use std::fmt;
+use std::sync::Arc;
+
+/// Callbacks for watching a [`Stream`](crate::Stream) at work, e.g. to feed metrics or logs.
+///
+/// Every method has a default which does nothing, so implementations need only provide the ones
+/// they're interested in. Methods are called from within the stream's `poll_next`, and should
+/// return promptly.
+///
+/// Install an observer with [`Stream::observe`](crate::Stream::observe).
+///
+/// # Example
+///
+/// ```
+/// use emwin_tg::{Observer, Product};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// #[derive(Default)]
+/// struct Counter {
+///     products: AtomicUsize,
+/// }
+///
+/// impl Observer for Counter {
+///     fn on_product(&self, _product: &Product) {
+///         self.products.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+/// ```
+pub trait Observer: Send + Sync {
+    /// An archive was received from the source.
+    fn on_fetch(&self, _archive: &Bytes) {}
+
+    /// An archive was unpacked.
+    fn on_cycle(&self, _stats: &ArchiveStats) {}
+
+    /// An archive revealed a gap in coverage.
+    fn on_gap(&self, _gap: &Gap) {}
+
+    /// The stream yielded a product.
+    fn on_product(&self, _product: &Product) {}
+
+    /// The stream yielded an error.
+    fn on_error(&self, _error: &Error) {}
+}
+
+impl<O: Observer + ?Sized> Observer for Arc<O> {
+    fn on_fetch(&self, archive: &Bytes) {
+        (**self).on_fetch(archive)
+    }
+
+    fn on_cycle(&self, stats: &ArchiveStats) {
+        (**self).on_cycle(stats)
+    }
+
+    fn on_gap(&self, gap: &Gap) {
+        (**self).on_gap(gap)
+    }
+
+    fn on_product(&self, product: &Product) {
+        (**self).on_product(product)
+    }
+
+    fn on_error(&self, error: &Error) {
+        (**self).on_error(error)
+    }
+}
+
+/// The observers installed on a `Stream`.
+#[derive(Default)]
+pub(crate) struct Observers(Vec<Box<dyn Observer>>);
+
+impl Observers {
+    pub fn push<O: Observer + 'static>(&mut self, observer: O) {
+        self.0.push(Box::new(observer));
+    }
+
+    pub fn fetched(&self, archive: &Bytes) {
+        self.0.iter().for_each(|o| o.on_fetch(archive));
+    }
+
+    pub fn cycled(&self, stats: &ArchiveStats) {
+        self.0.iter().for_each(|o| o.on_cycle(stats));
+    }
+
+    pub fn gapped(&self, gap: &Gap) {
+        self.0.iter().for_each(|o| o.on_gap(gap));
+    }
+
+    pub fn yielded(&self, result: &Result<Product, Error>) {
+        match result {
+            Ok(product) => self.0.iter().for_each(|o| o.on_product(product)),
+            Err(error) => self.0.iter().for_each(|o| o.on_error(error)),
+        }
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Observers").field(&self.0.len()).finish()
+    }
+}
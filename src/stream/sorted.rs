@@ -0,0 +1,114 @@
+use super::{Source, Stream};
+use crate::{Error, Product};
+use pin_project_lite::pin_project;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::time::{Instant, Sleep};
+
+/// A held product, ordered by issuance time and then by arrival.
+struct Held {
+    issued_at: OffsetDateTime,
+    sequence: u64,
+    product: Product,
+}
+
+impl PartialEq for Held {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for Held {}
+
+impl PartialOrd for Held {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Held {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.issued_at, self.sequence).cmp(&(other.issued_at, other.sequence))
+    }
+}
+
+pin_project! {
+/// A [`Stream`] which holds products for a while and yields them in order of issuance.
+///
+/// Created by [`Stream::sorted_within`].
+pub struct SortedStream<S: Source> {
+    #[pin]
+    stream: Stream<S>,
+    delay: Duration,
+    held: BinaryHeap<Reverse<Held>>,
+    arrivals: VecDeque<Instant>,
+    sequence: u64,
+    sleep: Pin<Box<Sleep>>,
+    finished: bool,
+}
+}
+
+impl<S: Source> SortedStream<S> {
+    pub(super) fn new(stream: Stream<S>, delay: Duration) -> Self {
+        Self {
+            stream,
+            delay,
+            held: BinaryHeap::new(),
+            arrivals: VecDeque::new(),
+            sequence: 0,
+            sleep: Box::pin(tokio::time::sleep(delay)),
+            finished: false,
+        }
+    }
+}
+
+impl<S: Source> futures::Stream for SortedStream<S> {
+    type Item = Result<Product, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        // Take in everything that's ready
+        while !*this.finished {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(product))) => {
+                    // Undated products are ordered as if they were issued on arrival
+                    let issued_at = product.issued_at().unwrap_or_else(OffsetDateTime::now_utc);
+                    *this.sequence += 1;
+                    this.held.push(Reverse(Held {
+                        issued_at,
+                        sequence: *this.sequence,
+                        product,
+                    }));
+                    this.arrivals.push_back(Instant::now() + *this.delay);
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => *this.finished = true,
+                Poll::Pending => break,
+            }
+        }
+
+        // Once the oldest product has waited long enough, or there's nothing more coming, yield
+        // the earliest-issued product held
+        let release_at = match this.arrivals.front() {
+            Some(release_at) => *release_at,
+            None if *this.finished => return Poll::Ready(None),
+            None => return Poll::Pending,
+        };
+        if !*this.finished {
+            this.sleep.as_mut().reset(release_at);
+            if this.sleep.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+
+        this.arrivals.pop_front();
+        let Reverse(held) = this.held.pop().expect("held product");
+        Poll::Ready(Some(Ok(held.product)))
+    }
+}
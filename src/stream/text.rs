@@ -1,4 +1,5 @@
-use crate::{Error, FetchStream, Fetchable};
+use super::ArchiveStream;
+use crate::{Error, FetchStream, Fetchable, FetchedArchive, Mirrors, SourceConfig};
 use bytes::Bytes;
 use futures::Stream;
 use pin_project_lite::pin_project;
@@ -18,13 +19,13 @@ pin_project! {
 /// ~90 seconds of latency, and yielding products up to 3-4 hours old.
 pub struct TextSource {
     #[pin]
-    text2min: FetchStream<Text2Min>,
+    text2min: FetchStream,
     #[pin]
-    text6min: FetchStream<Text6Min>,
+    text6min: FetchStream,
     #[pin]
-    text20min: FetchStream<Text20Min>,
+    text20min: FetchStream,
     #[pin]
-    text3hour: FetchStream<Text3Hour>,
+    text3hour: FetchStream,
 }
 }
 
@@ -32,61 +33,170 @@ impl Stream for TextSource {
     type Item = Result<Bytes, Error>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let this = self.project();
-        if let Poll::Ready(value) = this.text2min.poll_next(cx) {
-            return Poll::Ready(value);
-        }
-        if let Poll::Ready(value) = this.text6min.poll_next(cx) {
-            return Poll::Ready(value);
-        }
-        if let Poll::Ready(value) = this.text20min.poll_next(cx) {
-            return Poll::Ready(value);
+        self.poll_archive(cx)
+            .map(|result| result.map(|archive| archive.map(|archive| archive.contents)))
+    }
+}
+
+impl TextSource {
+    pub(super) fn poll_archive(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<FetchedArchive, Error>>> {
+        let mut this = self.project();
+
+        // Drive every feed before yielding anything, so that each feed's fetches start and
+        // progress concurrently even while another feed has results ready
+        this.text2min.as_mut().poll_fetches(cx);
+        this.text6min.as_mut().poll_fetches(cx);
+        this.text20min.as_mut().poll_fetches(cx);
+        this.text3hour.as_mut().poll_fetches(cx);
+
+        let result = this
+            .text2min
+            .as_mut()
+            .take_result()
+            .or_else(|| this.text6min.as_mut().take_result())
+            .or_else(|| this.text20min.as_mut().take_result())
+            .or_else(|| this.text3hour.as_mut().take_result());
+        match result {
+            Some(value) => Poll::Ready(Some(value)),
+            None if this.text2min.is_drained()
+                && this.text6min.is_drained()
+                && this.text20min.is_drained()
+                && this.text3hour.is_drained() =>
+            {
+                Poll::Ready(None)
+            }
+            None => Poll::Pending,
         }
-        if let Poll::Ready(value) = this.text3hour.poll_next(cx) {
-            return Poll::Ready(value);
+    }
+
+    /// Yield the archives themselves, rather than the products inside them.
+    ///
+    /// Each archive is yielded as it was retrieved, along with its name and when it was fetched,
+    /// for consumers which archive or relay the zips.
+    pub fn archives(self) -> ArchiveStream {
+        ArchiveStream::new(self, Self::poll_archive)
+    }
+}
+
+impl TextSource {
+    /// Retrieve archives from a prioritized list of gateway directories.
+    pub fn with_mirrors(client: reqwest::Client, mirrors: Mirrors) -> Self {
+        Self::with_config(client, SourceConfig::new().mirrors(mirrors))
+    }
+
+    /// Retrieve archives using custom settings, like polling intervals.
+    pub fn with_config(client: reqwest::Client, config: SourceConfig) -> Self {
+        let mirrors = config.shared_mirrors();
+        Self {
+            text2min: FetchStream::new::<Text2Min>(client.clone(), mirrors.clone(), &config),
+            text6min: FetchStream::new::<Text6Min>(client.clone(), mirrors.clone(), &config),
+            text20min: FetchStream::new::<Text20Min>(client.clone(), mirrors.clone(), &config),
+            text3hour: FetchStream::new::<Text3Hour>(client, mirrors, &config),
         }
-        Poll::Pending
+    }
+}
+
+impl TextSource {
+    /// Skip the 20-minute and 3-hour archives, polling only the 2- and 6-minute ones.
+    ///
+    /// The longer archives mostly repeat the shorter ones, but on startup they deliver hours of
+    /// old products, and after an outage they fill in what was missed. Consumers which only want
+    /// current products can skip them to save bandwidth.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// let source = emwin_tg::TextSource::from(reqwest::Client::new()).realtime_only();
+    /// let stream = emwin_tg::Stream::from_source(source);
+    /// # std::mem::drop(stream);
+    /// # })
+    /// ```
+    pub fn realtime_only(mut self) -> Self {
+        self.text20min.disable();
+        self.text3hour.disable();
+        self
     }
 }
 
 impl From<reqwest::Client> for TextSource {
     fn from(c: reqwest::Client) -> Self {
-        Self {
-            text2min: FetchStream::from(c.clone()),
-            text6min: FetchStream::from(c.clone()),
-            text20min: FetchStream::from(c.clone()),
-            text3hour: FetchStream::from(c),
-        }
+        Self::with_mirrors(c, Mirrors::default())
     }
 }
 
-impl super::Source for TextSource {}
+impl From<(reqwest::Client, SourceConfig)> for TextSource {
+    fn from((client, config): (reqwest::Client, SourceConfig)) -> Self {
+        Self::with_config(client, config)
+    }
+}
+
+impl super::Source for TextSource {
+    /// Stop fetching archives, ending once those in flight have been yielded.
+    fn shutdown(self: Pin<&mut Self>) -> bool {
+        let this = self.project();
+        this.text2min.shut_down();
+        this.text6min.shut_down();
+        this.text20min.shut_down();
+        this.text3hour.shut_down();
+        true
+    }
+    /// Twice the span of the longest archive being polled.
+    fn dedup_window(&self) -> Option<Duration> {
+        [
+            &self.text2min,
+            &self.text6min,
+            &self.text20min,
+            &self.text3hour,
+        ]
+        .iter()
+        .filter_map(|feed| feed.span())
+        .max()
+        .map(|span| span * 2)
+    }
+
+    /// The longest interval of the archives being polled.
+    fn refetch_interval(&self) -> Option<Duration> {
+        [
+            &self.text2min,
+            &self.text6min,
+            &self.text20min,
+            &self.text3hour,
+        ]
+        .iter()
+        .filter_map(|feed| feed.refetch_interval())
+        .max()
+    }
+}
 
-struct Text3Hour;
+pub(crate) struct Text3Hour;
 impl Fetchable for Text3Hour {
-    const URL: &'static str =
-        "https://tgftp.nws.noaa.gov/SL.us008001/CU.EMWIN/DF.xt/DC.gsatR/OPS/txthrs03.zip";
+    const FILENAME: &'static str = "txthrs03.zip";
     const REFETCH_INTERVAL: Duration = Duration::from_secs(60 * 60); // it's regenerated hourly
+    const SPAN: Duration = Duration::from_secs(60 * 60 * 3);
 }
 
-struct Text20Min;
+pub(crate) struct Text20Min;
 impl Fetchable for Text20Min {
-    const URL: &'static str =
-        "https://tgftp.nws.noaa.gov/SL.us008001/CU.EMWIN/DF.xt/DC.gsatR/OPS/txtmin20.zip";
+    const FILENAME: &'static str = "txtmin20.zip";
     const REFETCH_INTERVAL: Duration = Duration::from_secs(20 * 60);
+    const SPAN: Duration = Duration::from_secs(20 * 60);
 }
 
-struct Text6Min;
+pub(crate) struct Text6Min;
 impl Fetchable for Text6Min {
-    const URL: &'static str =
-        "https://tgftp.nws.noaa.gov/SL.us008001/CU.EMWIN/DF.xt/DC.gsatR/OPS/txtmin06.zip";
+    const FILENAME: &'static str = "txtmin06.zip";
     const REFETCH_INTERVAL: Duration = Duration::from_secs(6 * 60);
+    const SPAN: Duration = Duration::from_secs(6 * 60);
 }
 
 #[derive(Debug, Clone)]
-struct Text2Min;
+pub(crate) struct Text2Min;
 impl Fetchable for Text2Min {
-    const URL: &'static str =
-        "https://tgftp.nws.noaa.gov/SL.us008001/CU.EMWIN/DF.xt/DC.gsatR/OPS/txtmin02.zip";
+    const FILENAME: &'static str = "txtmin02.zip";
     const REFETCH_INTERVAL: Duration = Duration::from_secs(47);
+    const SPAN: Duration = Duration::from_secs(2 * 60);
 }
@@ -0,0 +1,107 @@
+use super::ArchiveStream;
+use crate::{Error, FetchStream, FetchedArchive, Mirrors, SourceConfig};
+use bytes::Bytes;
+use futures::Stream;
+use pin_project_lite::pin_project;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+pin_project! {
+/// A feed of products from an archive at any URL.
+///
+/// `UrlSource` polls a single zip archive on a fixed interval, like [`TextSource`] does for each
+/// of the gateway's archives. This allows the same decoding pipeline to be pointed at a local
+/// mirror, a test server, or another gateway path.
+///
+/// Archives are fetched with conditional requests, and failed fetches are retried according to
+/// the default [`RetryPolicy`](crate::RetryPolicy).
+///
+/// [`TextSource`]: crate::TextSource
+///
+/// # Example
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use std::time::Duration;
+///
+/// let source = emwin_tg::UrlSource::new(
+///     "https://mirror.example.com/emwin/txtmin02.zip",
+///     Duration::from_secs(60),
+/// );
+/// let stream = emwin_tg::Stream::from_source(source);
+/// # std::mem::drop(stream);
+/// # })
+/// ```
+pub struct UrlSource {
+    #[pin]
+    archive: FetchStream,
+}
+}
+
+impl UrlSource {
+    /// Fetch the archive at `url` every `interval`.
+    pub fn new<U: Into<String>>(url: U, interval: Duration) -> Self {
+        Self::with_client(crate::default_client(), url, interval)
+    }
+
+    /// Fetch the archive at `url` every `interval`, using `client`.
+    pub fn with_client<U: Into<String>>(
+        client: reqwest::Client,
+        url: U,
+        interval: Duration,
+    ) -> Self {
+        let url = url.into();
+        let (directory, filename) = match url.rfind('/') {
+            Some(i) => url.split_at(i + 1),
+            None => ("", url.as_str()),
+        };
+        let mirrors = Arc::new(Mirrors::with_prefix(directory));
+        Self {
+            archive: FetchStream::with_filename(
+                filename,
+                interval,
+                client,
+                mirrors,
+                &SourceConfig::new(),
+            ),
+        }
+    }
+
+    fn poll_archive(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<FetchedArchive, Error>>> {
+        self.project().archive.poll_next(cx)
+    }
+
+    /// Yield the archives themselves, rather than the products inside them.
+    ///
+    /// See [`TextSource::archives`](crate::TextSource::archives).
+    pub fn archives(self) -> ArchiveStream {
+        ArchiveStream::new(self, Self::poll_archive)
+    }
+}
+
+impl Stream for UrlSource {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_archive(cx)
+            .map(|result| result.map(|archive| archive.map(|archive| archive.contents)))
+    }
+}
+
+impl super::Source for UrlSource {
+    /// Stop fetching the archive, ending once any fetch in flight has been yielded.
+    fn shutdown(self: Pin<&mut Self>) -> bool {
+        self.project().archive.shut_down();
+        true
+    }
+
+    /// The interval the archive is fetched at.
+    fn refetch_interval(&self) -> Option<Duration> {
+        self.archive.refetch_interval()
+    }
+}
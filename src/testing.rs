@@ -0,0 +1,138 @@
+//! Canned sources and synthetic archives, for testing consumers without network access.
+//!
+//! # Example
+//!
+//! ```
+//! # tokio_test::block_on(async {
+//! use emwin_tg::testing::{ArchiveBuilder, MockSource};
+//! use futures::StreamExt;
+//!
+//! let source = MockSource::new()
+//!     .archive(ArchiveBuilder::new().member("AFDOKXNY.TXT", "hello").build())
+//!     .archive(
+//!         ArchiveBuilder::new()
+//!             .member("AFDOKXNY.TXT", "hello")
+//!             .member("ZFPOKXNY.TXT", "world")
+//!             .build(),
+//!     );
+//! let filenames: Vec<String> = emwin_tg::Stream::from_source(source)
+//!     .map(|result| result.unwrap().filename)
+//!     .collect()
+//!     .await;
+//! assert_eq!(filenames, ["AFDOKXNY.TXT", "ZFPOKXNY.TXT"]);
+//! # })
+//! ```
+//!
+//! Requires the `testing` feature.
+
+use crate::{Error, Product, Source};
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Builds zip archives like those on the gateway, in memory.
+///
+/// Members are stored in the order they're added, with the current time as their modification
+/// time.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveBuilder {
+    members: Vec<(String, Bytes)>,
+}
+
+impl ArchiveBuilder {
+    /// An empty archive.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a member.
+    pub fn member<N: Into<String>, C: Into<Bytes>>(mut self, name: N, contents: C) -> Self {
+        self.members.push((name.into(), contents.into()));
+        self
+    }
+
+    /// Add a member holding a product, under its filename.
+    pub fn product(self, product: &Product) -> Self {
+        self.member(product.filename.clone(), product.contents.clone())
+    }
+
+    /// Write the archive.
+    pub fn build(&self) -> Bytes {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, contents) in &self.members {
+            zip.start_file(name.as_str(), Default::default())
+                .and_then(|()| Ok(zip.write_all(contents)?))
+                .expect("write to memory");
+        }
+        let archive = zip.finish().expect("write to memory").into_inner();
+        Bytes::from(archive)
+    }
+}
+
+/// A [`Source`] which yields canned archives and errors, in order, then ends.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Debug, Default)]
+pub struct MockSource {
+    items: VecDeque<Result<Bytes, Error>>,
+    keep_open: bool,
+    shut_down: bool,
+}
+
+impl MockSource {
+    /// A source which yields nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Yield an archive.
+    pub fn archive<A: Into<Bytes>>(mut self, archive: A) -> Self {
+        self.items.push_back(Ok(archive.into()));
+        self
+    }
+
+    /// Yield an archive holding `products`.
+    pub fn products<'a, I: IntoIterator<Item = &'a Product>>(self, products: I) -> Self {
+        let archive = products
+            .into_iter()
+            .fold(ArchiveBuilder::new(), ArchiveBuilder::product)
+            .build();
+        self.archive(archive)
+    }
+
+    /// Yield an error, as a real source does when a fetch fails.
+    pub fn error(mut self, error: Error) -> Self {
+        self.items.push_back(Err(error));
+        self
+    }
+
+    /// Stay pending after the last item, like a live source, rather than ending.
+    ///
+    /// The source still ends once it's [shut down](crate::Stream::shutdown).
+    pub fn keep_open(mut self) -> Self {
+        self.keep_open = true;
+        self
+    }
+}
+
+impl futures::Stream for MockSource {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.items.pop_front() {
+            Some(item) => Poll::Ready(Some(item)),
+            None if self.keep_open && !self.shut_down => Poll::Pending,
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+impl Source for MockSource {
+    /// End once the remaining items have been yielded.
+    fn shutdown(mut self: Pin<&mut Self>) -> bool {
+        self.shut_down = true;
+        true
+    }
+}
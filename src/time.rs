@@ -1,5 +1,6 @@
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use time::{Date, Month, OffsetDateTime, Time};
 
 #[derive(Debug)]
 pub struct Ticker(tokio::time::Interval);
@@ -19,3 +20,56 @@ impl futures::Stream for Ticker {
         self.0.poll_tick(cx).map(|_| Some(()))
     }
 }
+
+/// Resolve a day-of-month and time, as found in WMO headings and UGC strings, to the instant
+/// nearest `reference`.
+///
+/// The day may fall in the month before or after `reference`, e.g. a product issued on the 31st
+/// and received on the 1st. Returns `None` if the fields are out of range.
+pub fn resolve_day_time(
+    day: u8,
+    hour: u8,
+    minute: u8,
+    reference: OffsetDateTime,
+) -> Option<OffsetDateTime> {
+    let time = Time::from_hms(hour, minute, 0).ok()?;
+    let (year, month) = (reference.year(), reference.month());
+
+    let previous = if month == Month::January {
+        (year - 1, Month::December)
+    } else {
+        (year, month.previous())
+    };
+    let next = if month == Month::December {
+        (year + 1, Month::January)
+    } else {
+        (year, month.next())
+    };
+
+    [previous, (year, month), next]
+        .iter()
+        .filter_map(|&(year, month)| Date::from_calendar_date(year, month, day).ok())
+        .map(|date| date.with_time(time).assume_utc())
+        .min_by_key(|candidate| (*candidate - reference).abs())
+}
+
+/// Serialize an `OffsetDateTime` as a `SystemTime`, like the other timestamps in the crate.
+#[cfg(feature = "serde")]
+pub mod system_time {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::SystemTime;
+    use time::OffsetDateTime;
+
+    pub fn serialize<S: Serializer>(
+        value: &OffsetDateTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        SystemTime::from(*value).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<OffsetDateTime, D::Error> {
+        SystemTime::deserialize(deserializer).map(OffsetDateTime::from)
+    }
+}
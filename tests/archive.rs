@@ -0,0 +1,347 @@
+mod common;
+
+use futures::StreamExt;
+
+async fn products(archives: Vec<Vec<u8>>) -> Vec<emwin_tg::Product> {
+    emwin_tg::Stream::from_source(common::source(archives))
+        .map(|result| result.unwrap())
+        .collect()
+        .await
+}
+
+#[tokio::test]
+async fn entry_with_unrecorded_size() {
+    let contents = b"WWUS53 KMPX 202245\r\r\nSVSMPX\r\r\n".repeat(100);
+    let mut archive = common::archive(&[("SVSMPX.TXT", &contents)]);
+
+    // Zero the uncompressed size in the central directory, as if written in streaming mode
+    let central = archive.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    archive[central + 24..central + 28].copy_from_slice(&[0; 4]);
+
+    let products = products(vec![archive]).await;
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].contents, contents);
+}
+
+#[tokio::test]
+async fn nested_archives() {
+    let inner = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let outer = common::archive(&[("AFDOKXNY.ZIP", &inner)]);
+
+    // Unpacked by default
+    let products = products(vec![outer.clone()]).await;
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].filename, "AFDOKXNY.TXT");
+    assert_eq!(products[0].contents, &b"hello"[..]);
+
+    // Kept as-is on request
+    let products: Vec<_> = emwin_tg::Stream::from_source(common::source(vec![outer]))
+        .keep_nested_archives()
+        .map(|result| result.unwrap())
+        .collect()
+        .await;
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].filename, "AFDOKXNY.ZIP");
+    assert_eq!(products[0].mime_type(), Some("application/zip"));
+    assert_eq!(products[0].contents, inner);
+}
+
+#[tokio::test]
+async fn self_nesting_archives_stop_unpacking() {
+    // An archive which contains itself, as far as the unpacker can tell
+    let mut levels = vec![common::archive(&[("AFDOKXNY.TXT", b"hello")])];
+    for _ in 0..4 {
+        let inner = levels.last().unwrap().clone();
+        levels.push(common::archive(&[("NESTED.ZIP", &inner)]));
+    }
+
+    let products = products(vec![levels[4].clone()]).await;
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].filename, "NESTED.ZIP");
+    assert_eq!(products[0].contents, levels[1]);
+}
+
+#[tokio::test]
+async fn large_archives_keep_member_order() {
+    let names: Vec<String> = (0..200).map(|i| format!("P{:03}.TXT", i)).collect();
+    let members: Vec<(&str, &[u8])> = names
+        .iter()
+        .map(|name| (name.as_str(), name.as_bytes()))
+        .collect();
+    let archive = common::archive(&members);
+
+    let products = products(vec![archive]).await;
+    assert_eq!(products.len(), names.len());
+    for (product, name) in products.iter().zip(&names) {
+        assert_eq!(&product.filename, name);
+        assert_eq!(product.contents, name.as_bytes());
+    }
+}
+
+#[tokio::test]
+async fn nested_bundles_of_image_tiles() {
+    let png = |n: u8| {
+        let mut tile = b"\x89PNG\r\n\x1a\n".to_vec();
+        tile.push(n);
+        tile
+    };
+    let (nw, ne, sw) = (png(1), png(2), png(3));
+    let inner = common::archive(&[
+        ("tiles/", b""),
+        ("tiles/G16TILENW.PNG", &nw),
+        ("tiles/G16TILENE.PNG", &ne),
+        ("tiles/G16TILESW.PNG", &sw),
+    ]);
+    let outer = common::archive(&[("G16TILES.ZIP", &inner)]);
+
+    let products = products(vec![outer]).await;
+    let names: Vec<_> = products.iter().map(|p| p.filename.as_str()).collect();
+    assert_eq!(names, ["G16TILENW.PNG", "G16TILENE.PNG", "G16TILESW.PNG"]);
+    for (product, contents) in products.iter().zip([&nw, &ne, &sw]) {
+        assert_eq!(product.mime_type(), Some("image/png"));
+        assert_eq!(&product.contents, contents);
+    }
+
+    // An empty bundle is an error
+    let outer = common::archive(&[("EMPTY.ZIP", &common::archive(&[]))]);
+    let results: Vec<_> = emwin_tg::Stream::from_source(common::source(vec![outer]))
+        .collect()
+        .await;
+    assert_eq!(results.len(), 1);
+    assert!(
+        matches!(&results[0], Err(emwin_tg::Error::ArchiveMember(name)) if name == "EMPTY.ZIP")
+    );
+}
+
+#[tokio::test]
+async fn products_know_when_they_were_received() {
+    use std::io::Write;
+    use std::time::{Duration, SystemTime};
+
+    let modified = zip::DateTime::from_date_and_time(2022, 2, 15, 12, 1, 30).unwrap();
+    let options = zip::write::FileOptions::default().last_modified_time(modified);
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for name in [
+        "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+        "AFDOKXNY.TXT",
+    ] {
+        zip.start_file(name, options).unwrap();
+        zip.write_all(b"hello").unwrap();
+    }
+    zip.start_file(
+        "ZFPOKXNY.TXT",
+        options.last_modified_time(Default::default()),
+    )
+    .unwrap();
+    zip.write_all(b"hello").unwrap();
+    let archive = zip.finish().unwrap().into_inner();
+
+    let products = products(vec![archive]).await;
+    let find = |filename: &str| products.iter().find(|p| p.filename == filename).unwrap();
+    let at = |h, m, s| {
+        let time = time::Date::from_calendar_date(2022, time::Month::February, 15)
+            .unwrap()
+            .with_hms(h, m, s)
+            .unwrap()
+            .assume_utc();
+        SystemTime::from(time)
+    };
+
+    // The filename's receipt time wins over the entry's
+    let product = find("A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT");
+    assert_eq!(product.received_at, at(11, 52, 57));
+    assert_eq!(
+        product.issued_at().map(SystemTime::from),
+        Some(at(11, 52, 0))
+    );
+
+    // Otherwise the entry's modification time is used
+    assert_eq!(find("AFDOKXNY.TXT").received_at, at(12, 1, 30));
+
+    // ...unless it wasn't recorded
+    let age = SystemTime::now()
+        .duration_since(find("ZFPOKXNY.TXT").received_at)
+        .unwrap();
+    assert!(age < Duration::from_secs(5), "{:?}", age);
+}
+
+#[tokio::test]
+async fn stored_members_share_the_archive_buffer() {
+    let stored = b"WWUS53 KMPX 202245\r\r\nSVSMPX\r\r\n".repeat(10);
+    let deflated = b"FXUS63 KDVN 151152\r\r\nAFDDVN\r\r\n".repeat(10);
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::FileOptions::default();
+    zip.start_file(
+        "SVSMPX.TXT",
+        options.compression_method(zip::CompressionMethod::Stored),
+    )
+    .unwrap();
+    std::io::Write::write_all(&mut zip, &stored).unwrap();
+    zip.start_file("AFDDVN.TXT", options).unwrap();
+    std::io::Write::write_all(&mut zip, &deflated).unwrap();
+    let archive = bytes::Bytes::from(zip.finish().unwrap().into_inner());
+
+    let source = futures::stream::iter(vec![Ok(archive.clone())]).boxed();
+    let products: Vec<_> = emwin_tg::Stream::from_source(source)
+        .map(|result| result.unwrap())
+        .collect()
+        .await;
+    assert_eq!(products.len(), 2);
+
+    let within = |contents: &[u8]| archive.as_ptr_range().contains(&contents.as_ptr());
+    let product = |name: &str| products.iter().find(|p| p.filename == name).unwrap();
+    assert_eq!(product("SVSMPX.TXT").contents, stored);
+    assert!(within(&product("SVSMPX.TXT").contents));
+    assert_eq!(product("AFDDVN.TXT").contents, deflated);
+    assert!(!within(&product("AFDDVN.TXT").contents));
+}
+
+#[tokio::test]
+async fn clones_share_contents() {
+    let contents = b"WWUS53 KMPX 202245\r\r\nSVSMPX\r\r\n".repeat(10);
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("SVSMPX.TXT", options).unwrap();
+    std::io::Write::write_all(&mut zip, &contents).unwrap();
+    let archive = bytes::Bytes::from(zip.finish().unwrap().into_inner());
+
+    let source = futures::stream::iter(vec![Ok(archive)]).boxed();
+    let mut stream = emwin_tg::Stream::from_source(source);
+    let product = stream.next().await.unwrap().unwrap();
+    let clone = product.clone();
+    assert_eq!(clone.filename, product.filename);
+    assert_eq!(clone.received_at, product.received_at);
+    assert_eq!(clone.contents.as_ptr(), product.contents.as_ptr());
+}
+
+#[tokio::test]
+async fn corrupt_members_are_skipped() {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let stored =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for (name, contents) in [("AFDOKXNY.TXT", "hello"), ("ZFPOKXNY.TXT", "world")] {
+        zip.start_file(name, stored).unwrap();
+        std::io::Write::write_all(&mut zip, contents.as_bytes()).unwrap();
+    }
+    let mut archive = zip.finish().unwrap().into_inner();
+
+    // Damage the first member so it fails its checksum
+    let at = archive.windows(5).position(|w| w == b"hello").unwrap();
+    archive[at] = b'j';
+
+    let results: Vec<_> = emwin_tg::Stream::from_source(common::source(vec![archive]))
+        .collect()
+        .await;
+    assert_eq!(results.len(), 2);
+    match &results[0] {
+        Err(emwin_tg::Error::CorruptMember { name, .. }) => assert_eq!(name, "AFDOKXNY.TXT"),
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(
+        results[0].as_ref().unwrap_err().kind(),
+        emwin_tg::ErrorKind::ArchiveFormat
+    );
+    assert_eq!(results[1].as_ref().unwrap().filename, "ZFPOKXNY.TXT");
+}
+
+#[tokio::test]
+async fn oversized_members_are_skipped() {
+    // A small archive which decompresses to just over the limit
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file("BOMB.TXT", Default::default()).unwrap();
+    let zeros = vec![0; 1 << 20];
+    for _ in 0..emwin_tg::product::MAX_MEMBER_SIZE >> 20 {
+        std::io::Write::write_all(&mut zip, &zeros).unwrap();
+    }
+    std::io::Write::write_all(&mut zip, b"!").unwrap();
+    zip.start_file("ZFPOKXNY.TXT", Default::default()).unwrap();
+    std::io::Write::write_all(&mut zip, b"world").unwrap();
+    let archive = zip.finish().unwrap().into_inner();
+    assert!(archive.len() < 1 << 20);
+
+    let results: Vec<_> = emwin_tg::Stream::from_source(common::source(vec![archive]))
+        .collect()
+        .await;
+    assert_eq!(results.len(), 2);
+    match &results[0] {
+        Err(emwin_tg::Error::MemberTooLarge { name, limit }) => {
+            assert_eq!(name, "BOMB.TXT");
+            assert_eq!(*limit, emwin_tg::product::MAX_MEMBER_SIZE);
+        }
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(results[1].as_ref().unwrap().filename, "ZFPOKXNY.TXT");
+}
+
+#[tokio::test]
+async fn truncated_archives_yield_what_they_can() {
+    let archive = common::archive(&[
+        ("AFDOKXNY.TXT", b"hello"),
+        ("ZFPOKXNY.TXT", b"world"),
+        ("SVSMPX.TXT", b"again"),
+    ]);
+
+    // Cut the archive off partway through its last member
+    let last = archive
+        .windows(4)
+        .rposition(|w| w == b"PK\x03\x04")
+        .unwrap();
+    let truncated = archive[..last + 40].to_vec();
+
+    let results: Vec<_> = emwin_tg::Stream::from_source(common::source(vec![truncated]))
+        .collect()
+        .await;
+    let mut filenames: Vec<_> = results[..2]
+        .iter()
+        .map(|result| result.as_ref().unwrap().filename.clone())
+        .collect();
+    filenames.sort();
+    assert_eq!(filenames, ["AFDOKXNY.TXT", "ZFPOKXNY.TXT"]);
+    match &results[2..] {
+        [Err(emwin_tg::Error::DamagedArchive { recovered: 2, .. })] => {}
+        other => panic!("{:?}", other),
+    }
+
+    // The intact archive brings the rest
+    let results: Vec<_> =
+        emwin_tg::Stream::from_source(common::source(vec![archive[..last + 40].to_vec(), archive]))
+            .filter_map(|result| async move { result.ok() })
+            .map(|product| product.filename)
+            .collect()
+            .await;
+    assert_eq!(results.len(), 3);
+}
+
+#[tokio::test]
+async fn salvage_stops_at_oversized_members() {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file("AFDOKXNY.TXT", Default::default()).unwrap();
+    std::io::Write::write_all(&mut zip, b"hello").unwrap();
+    zip.start_file("BOMB.TXT", Default::default()).unwrap();
+    let zeros = vec![0; 1 << 20];
+    for _ in 0..emwin_tg::product::MAX_MEMBER_SIZE >> 20 {
+        std::io::Write::write_all(&mut zip, &zeros).unwrap();
+    }
+    std::io::Write::write_all(&mut zip, b"!").unwrap();
+    zip.start_file("ZFPOKXNY.TXT", Default::default()).unwrap();
+    std::io::Write::write_all(&mut zip, b"world").unwrap();
+    let archive = zip.finish().unwrap().into_inner();
+
+    // Lose the central directory, so the members must be salvaged
+    let last = archive
+        .windows(4)
+        .rposition(|w| w == b"PK\x03\x04")
+        .unwrap();
+    let truncated = archive[..last + 40].to_vec();
+
+    let results: Vec<_> = emwin_tg::Stream::from_source(common::source(vec![truncated]))
+        .collect()
+        .await;
+    assert_eq!(results.len(), 2, "{:?}", results);
+    assert_eq!(results[0].as_ref().unwrap().filename, "AFDOKXNY.TXT");
+    match &results[1] {
+        Err(emwin_tg::Error::DamagedArchive { recovered: 1, .. }) => {}
+        other => panic!("{:?}", other),
+    }
+}
@@ -0,0 +1,195 @@
+#![cfg(feature = "blocking")]
+
+mod common;
+
+use common::MockServer;
+use std::time::Duration;
+
+#[test]
+fn blocking_streams_iterate_over_products() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = runtime.block_on(MockServer::start(|path| match path {
+        "/txtmin02.zip" => (
+            200,
+            common::archive(&[("AFDOKXNY.TXT", b"hello"), ("ZFPOKXNY.TXT", b"world")]),
+        ),
+        "/txtmin06.zip" => (
+            200,
+            common::archive(&[("AFDOKXNY.TXT", b"hello"), ("SVSMPX.TXT", b"again")]),
+        ),
+        _ => (404, Vec::new()),
+    }));
+
+    let config = emwin_tg::SourceConfig::new().mirrors(
+        emwin_tg::Mirrors::new([format!("{}missing/", server.url), server.url.clone()])
+            .failover_after(1),
+    );
+    let client = reqwest::blocking::Client::new();
+    let mut stream = emwin_tg::blocking::TextStream::with_config(client, config).realtime_only();
+
+    // Each product is yielded once, from whichever mirror has it
+    let mut filenames: Vec<String> = (&mut stream)
+        .filter_map(Result::ok)
+        .take(3)
+        .map(|product| product.filename)
+        .collect();
+    filenames.sort();
+    assert_eq!(filenames, ["AFDOKXNY.TXT", "SVSMPX.TXT", "ZFPOKXNY.TXT"]);
+
+    let mut requests = server.requests();
+    requests.sort();
+    assert_eq!(
+        requests,
+        [
+            "/missing/txtmin02.zip",
+            "/missing/txtmin06.zip",
+            "/txtmin02.zip",
+            "/txtmin06.zip"
+        ]
+    );
+}
+
+#[test]
+fn blocking_streams_yield_fetch_errors() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = runtime.block_on(MockServer::start(|_| (500, Vec::new())));
+
+    let config = emwin_tg::SourceConfig::new()
+        .mirrors(emwin_tg::Mirrors::new([server.url.clone()]))
+        .retry(emwin_tg::RetryPolicy::none());
+    let client = reqwest::blocking::Client::new();
+    let mut stream = emwin_tg::blocking::TextStream::with_config(client, config);
+
+    match stream.next() {
+        Some(Err(emwin_tg::Error::Fetch {
+            archive, attempt, ..
+        })) => {
+            assert_eq!(archive, "txtmin02.zip");
+            assert_eq!(attempt, 1);
+        }
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn blocking_streams_limit_bodies_without_a_length() {
+    use std::io::{Read, Write};
+
+    // Answer every request with a large body and no Content-Length
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for mut socket in listener.incoming().flatten() {
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf);
+            let _ = socket.write_all(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n");
+            let _ = socket.write_all(&[0; 4096]);
+        }
+    });
+
+    let config = emwin_tg::SourceConfig::new()
+        .mirrors(emwin_tg::Mirrors::new([url]).max_archive_size(1024))
+        .retry(emwin_tg::RetryPolicy::none());
+    let client = reqwest::blocking::Client::new();
+    let mut stream = emwin_tg::blocking::TextStream::with_config(client, config);
+
+    match stream.next() {
+        Some(Err(emwin_tg::Error::Fetch { source, .. })) => match *source {
+            emwin_tg::Error::ArchiveTooLarge { size, limit, .. } => {
+                assert_eq!((size, limit), (1025, 1024));
+            }
+            other => panic!("{:?}", other),
+        },
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn blocking_streams_fail_over_between_mirrors() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let first = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let backup = common::archive(&[("AFDOKXNY.TXT", b"hello"), ("ZFPOKXNY.TXT", b"world")]);
+    let served = std::sync::atomic::AtomicUsize::new(0);
+    let heads = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let server = {
+        let heads = heads.clone();
+        runtime.block_on(MockServer::start_with_headers(move |path, head| {
+            if !path.ends_with("txtmin02.zip") {
+                return (404, Vec::new(), Vec::new());
+            }
+            heads
+                .lock()
+                .unwrap()
+                .push((path.to_string(), head.to_ascii_lowercase()));
+            let etag = vec![("ETag", "\"v1\"".to_string())];
+            match path {
+                // The preferred mirror works once, then fails
+                "/txtmin02.zip" => match served.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                    0 => (200, etag, first.clone()),
+                    _ => (500, Vec::new(), Vec::new()),
+                },
+                _ => (200, etag, backup.clone()),
+            }
+        }))
+    };
+
+    let config = emwin_tg::SourceConfig::new()
+        .mirrors(
+            emwin_tg::Mirrors::new([server.url.clone(), format!("{}backup/", server.url)])
+                .failover_after(2),
+        )
+        .refetch_interval("txtmin02.zip", Duration::from_millis(10))
+        .refetch_interval("txtmin06.zip", Duration::from_secs(3600))
+        .retry(emwin_tg::RetryPolicy::none());
+    let client = reqwest::blocking::Client::new();
+    let stream = emwin_tg::blocking::TextStream::with_config(client, config).realtime_only();
+
+    let filenames: Vec<String> = stream
+        .filter_map(Result::ok)
+        .take(2)
+        .map(|product| product.filename)
+        .collect();
+    assert_eq!(filenames, ["AFDOKXNY.TXT", "ZFPOKXNY.TXT"]);
+
+    // Two failures on the preferred mirror, then one fetch from the backup, which isn't sent the
+    // preferred mirror's validators
+    let heads = heads.lock().unwrap().clone();
+    let paths: Vec<&str> = heads.iter().map(|(path, _)| path.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "/txtmin02.zip",
+            "/txtmin02.zip",
+            "/txtmin02.zip",
+            "/backup/txtmin02.zip"
+        ]
+    );
+    assert!(
+        heads[1].1.contains("if-none-match: \"v1\""),
+        "{}",
+        heads[1].1
+    );
+    assert!(!heads[3].1.contains("if-none-match"), "{}", heads[3].1);
+}
+
+#[test]
+fn blocking_streams_make_no_requests_in_dry_runs() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = runtime.block_on(MockServer::start(|_| (500, Vec::new())));
+
+    let config = emwin_tg::SourceConfig::new()
+        .mirrors(emwin_tg::Mirrors::new([server.url.clone()]).dry_run(true))
+        .refetch_interval("txtmin02.zip", Duration::from_millis(10))
+        .refetch_interval("txtmin06.zip", Duration::from_millis(10));
+    let client = reqwest::blocking::Client::new();
+    let stream = emwin_tg::blocking::TextStream::with_config(client, config).realtime_only();
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for result in stream {
+            let _ = sender.send(result);
+        }
+    });
+    assert!(receiver.recv_timeout(Duration::from_millis(300)).is_err());
+    assert!(server.requests().is_empty());
+}
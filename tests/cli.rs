@@ -0,0 +1,76 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::MockServer;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+const AFD: &str = "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT";
+const TOR: &str = "A_WFUS53KLOT202245_C_KWIN_20220220225001_061010-1-TORLOTIL.TXT";
+
+async fn server() -> MockServer {
+    MockServer::start(|_| {
+        (
+            200,
+            common::archive(&[
+                (AFD, b"Area Forecast Discussion"),
+                (TOR, b"Tornado Warning"),
+            ]),
+        )
+    })
+    .await
+}
+
+fn command(server: &MockServer) -> tokio::process::Command {
+    let mut command = tokio::process::Command::new(env!("CARGO_BIN_EXE_emwin-tg"));
+    command
+        .args(["--gateway", &server.url])
+        .stdout(std::process::Stdio::piped())
+        .kill_on_drop(true);
+    command
+}
+
+#[tokio::test]
+async fn tails_filtered_products_as_json() {
+    let server = server().await;
+    let mut child = command(&server)
+        .args(["filter", "--awips", "TOR", "--format", "json"])
+        .spawn()
+        .unwrap();
+
+    // Products are yielded in filename order, so the AFD would come first if it weren't filtered
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let line = tokio::time::timeout(Duration::from_secs(10), lines.next_line())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let product: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(product["filename"], TOR);
+    assert_eq!(product["body"], "Tornado Warning");
+}
+
+#[tokio::test]
+async fn mirrors_products_to_a_directory() {
+    let server = server().await;
+    let root = std::env::temp_dir().join(format!("emwin-tg-cli-mirror-{}", std::process::id()));
+    let _child = command(&server)
+        .arg("mirror")
+        .arg(&root)
+        .arg("--flat")
+        .spawn()
+        .unwrap();
+
+    let (afd, tor) = (root.join(AFD), root.join(TOR));
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while !(afd.exists() && tor.exists()) {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(std::fs::read(&tor).unwrap(), b"Tornado Warning");
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
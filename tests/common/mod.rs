@@ -0,0 +1,144 @@
+#![allow(dead_code)]
+
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use std::io::Write;
+
+/// Build an archive containing the given members.
+pub fn archive(members: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, contents) in members {
+        zip.start_file(*name, Default::default()).unwrap();
+        zip.write_all(contents).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+/// A source which yields each archive once, then ends.
+pub fn source(archives: Vec<Vec<u8>>) -> BoxStream<'static, Result<Bytes, emwin_tg::Error>> {
+    futures::stream::iter(archives.into_iter().map(|a| Ok(Bytes::from(a)))).boxed()
+}
+
+/// A status, headers, and body to send in response to a request.
+pub type Response = (u16, Vec<(&'static str, String)>, Vec<u8>);
+
+/// A minimal HTTP server which answers every request using a handler.
+pub struct MockServer {
+    /// The base URL of the server, ending in `/`.
+    pub url: String,
+    requests: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    /// Start a server which responds to each request path with a status and body.
+    pub async fn start<H>(handler: H) -> Self
+    where
+        H: Fn(&str) -> (u16, Vec<u8>) + Send + Sync + 'static,
+    {
+        Self::start_with_headers(move |path, _| {
+            let (status, body) = handler(path);
+            (status, Vec::new(), body)
+        })
+        .await
+    }
+
+    /// Start a server which responds to each request path and head with a status, headers, and
+    /// body.
+    pub async fn start_with_headers<H>(handler: H) -> Self
+    where
+        H: Fn(&str, &str) -> Response + Send + Sync + 'static,
+    {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let handler = std::sync::Arc::new(handler);
+
+        let log = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(_) => return,
+                };
+                let handler = handler.clone();
+                let log = log.clone();
+                tokio::spawn(async move {
+                    // Read the request head
+                    let mut head = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => head.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let head = String::from_utf8_lossy(&head);
+                    let path = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    log.lock().unwrap().push(path.clone());
+
+                    let (status, headers, body) = handler(&path, &head);
+                    let headers: String = headers
+                        .iter()
+                        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+                        .collect();
+                    let response = format!(
+                        "HTTP/1.1 {} Mock\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                        status,
+                        headers,
+                        body.len()
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    let _ = socket.write_all(&body).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// The paths requested so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+static LOGGED: std::sync::Mutex<Vec<(log::Level, String)>> = std::sync::Mutex::new(Vec::new());
+
+struct CaptureLogger;
+
+impl log::Log for CaptureLogger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        LOGGED
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+/// Start capturing log messages from this test binary, returning those captured so far.
+pub fn logged() -> Vec<String> {
+    logged_with_levels()
+        .into_iter()
+        .map(|(_, message)| message)
+        .collect()
+}
+
+/// Like [`logged`], but with the level of each message.
+pub fn logged_with_levels() -> Vec<(log::Level, String)> {
+    static INSTALL: std::sync::Once = std::sync::Once::new();
+    INSTALL.call_once(|| {
+        log::set_logger(&CaptureLogger).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+    });
+    LOGGED.lock().unwrap().clone()
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use emwin_tg::sink::DirSink;
+use emwin_tg::Product;
+
+#[tokio::test]
+async fn mirrors_products_by_date() {
+    let root = std::env::temp_dir().join(format!("emwin-tg-mirror-{}", std::process::id()));
+
+    let stream = emwin_tg::Stream::from_source(common::source(vec![
+        common::archive(&[
+            (
+                "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+                b"Area Forecast Discussion",
+            ),
+            (
+                "A_WFUS53KLOT010005_C_KWIN_20220301000501_061010-1-TORLOTIL.TXT",
+                b"Tornado Warning",
+            ),
+        ]),
+        b"not an archive".to_vec(),
+    ]));
+    let mut sink = DirSink::new(&root);
+    sink.write_stream(stream).await.unwrap();
+
+    let afd =
+        root.join("2022/02/15/A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT");
+    let tor =
+        root.join("2022/03/01/A_WFUS53KLOT010005_C_KWIN_20220301000501_061010-1-TORLOTIL.TXT");
+    assert_eq!(std::fs::read(&afd).unwrap(), b"Area Forecast Discussion");
+    assert_eq!(std::fs::read(&tor).unwrap(), b"Tornado Warning");
+
+    // Existing files are left alone, and no temporary files remain
+    let resent = Product::new(afd.file_name().unwrap().to_str().unwrap(), "changed");
+    assert_eq!(sink.path_for(&resent), afd);
+    assert!(!sink.write(&resent).unwrap());
+    assert_eq!(std::fs::read(&afd).unwrap(), b"Area Forecast Discussion");
+    assert_eq!(std::fs::read_dir(afd.parent().unwrap()).unwrap().count(), 1);
+
+    let mut flat = DirSink::new(&root).flat();
+    assert!(flat
+        .write(&Product::new("mirror/radallus.gif", "GIF89a"))
+        .unwrap());
+    assert!(root.join("RADALLUS.GIF").exists());
+    assert_eq!(
+        flat.write(&Product::new("..", "")).unwrap_err().kind(),
+        emwin_tg::ErrorKind::Io
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
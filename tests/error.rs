@@ -0,0 +1,46 @@
+use emwin_tg::{Error, ErrorKind};
+use std::error::Error as _;
+
+fn http_error() -> reqwest::Error {
+    reqwest::Client::new().get("not a url").build().unwrap_err()
+}
+
+fn zip_error() -> zip::result::ZipError {
+    zip::ZipArchive::new(std::io::Cursor::new(b"not a zip")).unwrap_err()
+}
+
+#[test]
+fn kinds_and_sources() {
+    let http = Error::from(http_error());
+    assert_eq!(http.kind(), ErrorKind::Http);
+    assert!(http.source().unwrap().is::<reqwest::Error>());
+
+    let archive = Error::from(zip_error());
+    assert_eq!(archive.kind(), ErrorKind::ArchiveFormat);
+    assert!(archive.source().unwrap().is::<zip::result::ZipError>());
+
+    let member = Error::ArchiveMember("NESTED.ZIP".into());
+    assert_eq!(member.kind(), ErrorKind::ArchiveMember);
+    assert!(member.source().is_none());
+
+    let fetch = Error::Fetch {
+        url: "https://example.com/txtmin02.zip".into(),
+        archive: "txtmin02.zip".into(),
+        cycle: 3,
+        attempt: 2,
+        source: Box::new(Error::from(http_error())),
+    };
+    assert_eq!(fetch.kind(), ErrorKind::Http);
+    assert!(fetch.to_string().contains("(attempt 2)"), "{}", fetch);
+    let source = fetch.source().unwrap();
+    assert!(source.to_string().starts_with("HTTP error"), "{}", source);
+    assert!(source.source().unwrap().is::<reqwest::Error>());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_kind_and_source() {
+    let sqlite = Error::from(rusqlite::Error::InvalidQuery);
+    assert_eq!(sqlite.kind(), ErrorKind::Sqlite);
+    assert!(sqlite.source().unwrap().is::<rusqlite::Error>());
+}
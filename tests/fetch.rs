@@ -0,0 +1,959 @@
+mod common;
+
+use common::MockServer;
+use emwin_tg::product::Feed;
+use futures::StreamExt;
+use std::time::Duration;
+
+#[tokio::test]
+async fn fails_over_to_secondary_mirror() {
+    let primary = MockServer::start(|_| (500, Vec::new())).await;
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let secondary = MockServer::start(move |_| (200, archive.clone())).await;
+
+    let mirrors =
+        emwin_tg::Mirrors::new([primary.url.clone(), secondary.url.clone()]).failover_after(1);
+    let source = emwin_tg::TextSource::with_mirrors(reqwest::Client::new(), mirrors);
+    let mut stream = emwin_tg::Stream::from_source(source);
+
+    let product = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            match stream.next().await.unwrap() {
+                Ok(product) => break product,
+                Err(e) => assert!(
+                    matches!(&e, emwin_tg::Error::Fetch { source, .. } if matches!(**source, emwin_tg::Error::Http(_))),
+                    "{}",
+                    e
+                ),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(product.filename, "AFDOKXNY.TXT");
+
+    assert!(primary.requests().contains(&"/txtmin02.zip".to_string()));
+    assert!(secondary.requests().contains(&"/txtmin02.zip".to_string()));
+}
+
+#[tokio::test]
+async fn errors_say_which_fetch_failed() {
+    let server = MockServer::start(|_| (503, Vec::new())).await;
+
+    let mirrors = emwin_tg::Mirrors::new([server.url.clone()]);
+    let source = emwin_tg::TextSource::with_mirrors(reqwest::Client::new(), mirrors);
+    let mut stream = emwin_tg::Stream::from_source(source);
+
+    let error = tokio::time::timeout(Duration::from_secs(10), stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap_err();
+    match &error {
+        emwin_tg::Error::Fetch {
+            url,
+            archive,
+            cycle,
+            attempt,
+            source,
+        } => {
+            assert!(
+                url.starts_with(&server.url) && url.ends_with(archive.as_str()),
+                "{}",
+                url
+            );
+            assert!(archive.starts_with("txt") && archive.ends_with(".zip"));
+            assert_eq!((*cycle, *attempt), (1, 1));
+            assert!(matches!(**source, emwin_tg::Error::Http(_)));
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+    assert!(error.to_string().contains(".zip"));
+}
+
+#[tokio::test]
+async fn dry_run_logs_without_fetching() {
+    common::logged();
+
+    let server = MockServer::start(|_| (500, Vec::new())).await;
+    let mirrors = emwin_tg::Mirrors::new([server.url.clone()]).dry_run(true);
+    let source = emwin_tg::TextSource::with_mirrors(reqwest::Client::new(), mirrors);
+    let mut stream = emwin_tg::Stream::from_source(source);
+
+    assert!(
+        tokio::time::timeout(Duration::from_millis(200), stream.next())
+            .await
+            .is_err(),
+        "nothing should be yielded"
+    );
+    assert!(server.requests().is_empty());
+
+    let logged = common::logged();
+    for filename in [
+        "txtmin02.zip",
+        "txtmin06.zip",
+        "txtmin20.zip",
+        "txthrs03.zip",
+    ] {
+        let expected = format!("dry run: would GET {}{}", server.url, filename);
+        assert!(logged.contains(&expected), "{:?}", logged);
+    }
+}
+
+#[tokio::test]
+async fn rejects_oversized_archives() {
+    // Incompressible contents, so the archive is about as large as the product
+    let mut x = 0x2545f491u32;
+    let contents: Vec<u8> = (0..4096)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            x as u8
+        })
+        .collect();
+    let archive = common::archive(&[("AFDOKXNY.TXT", &contents)]);
+    assert!(archive.len() > 4096, "{}", archive.len());
+    let size = archive.len() as u64;
+    let server = MockServer::start(move |_| (200, archive.clone())).await;
+
+    let mirrors = emwin_tg::Mirrors::new([server.url.clone()]).max_archive_size(1024);
+    let source = emwin_tg::TextSource::with_mirrors(reqwest::Client::new(), mirrors);
+    let mut stream = emwin_tg::Stream::from_source(source);
+
+    let error = tokio::time::timeout(Duration::from_secs(10), stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(error.kind(), emwin_tg::ErrorKind::ArchiveTooLarge);
+    match &error {
+        emwin_tg::Error::Fetch { source, .. } => match &**source {
+            emwin_tg::Error::ArchiveTooLarge {
+                url,
+                size: actual,
+                limit,
+            } => {
+                assert!(url.starts_with(&server.url), "{}", url);
+                assert_eq!((*actual, *limit), (size, 1024));
+            }
+            other => panic!("unexpected error: {}", other),
+        },
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[tokio::test]
+async fn fetches_a_specific_archive() {
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello"), ("BAD.ZIP", b"not a zip")]);
+    let server = MockServer::start(move |path| match path {
+        "/archives/txtmin02-1152.zip" => (200, archive.clone()),
+        _ => (404, Vec::new()),
+    })
+    .await;
+    let client = reqwest::Client::new();
+
+    let url = format!("{}archives/txtmin02-1152.zip", server.url);
+    let results = emwin_tg::fetch_archive(&client, &url).await.unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].as_ref().unwrap().filename, "AFDOKXNY.TXT");
+    assert_eq!(
+        results[1].as_ref().unwrap_err().kind(),
+        emwin_tg::ErrorKind::ArchiveFormat
+    );
+    assert_eq!(server.requests(), ["/archives/txtmin02-1152.zip"]);
+
+    let missing = format!("{}archives/missing.zip", server.url);
+    let error = emwin_tg::fetch_archive(&client, &missing)
+        .await
+        .unwrap_err();
+    assert_eq!(error.kind(), emwin_tg::ErrorKind::Http);
+}
+
+#[tokio::test]
+async fn archives_are_fetched_and_listed_through_any_transport() {
+    use emwin_tg::{Error, HttpRequest, HttpResponse, HttpTransport};
+    use futures::future::BoxFuture;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Canned(Arc<Mutex<Vec<HttpRequest>>>);
+
+    impl HttpTransport for Canned {
+        fn get(&self, request: HttpRequest) -> BoxFuture<'static, Result<HttpResponse, Error>> {
+            let response = if request.url.ends_with(".zip") {
+                HttpResponse::new(200, common::archive(&[("AFDOKXNY.TXT", b"hello")]))
+            } else {
+                let html =
+                    r#"<pre><a href="txtmin02.zip">txtmin02.zip</a>  15-Feb-2022 11:55  1.5K"#;
+                HttpResponse::new(200, html)
+            };
+            self.0.lock().unwrap().push(request);
+            Box::pin(async move { Ok(response) })
+        }
+    }
+
+    let transport = Canned::default();
+    let results = emwin_tg::fetch_archive(&transport, "https://transport.invalid/txtmin02.zip")
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].as_ref().unwrap().filename, "AFDOKXNY.TXT");
+
+    let listings = emwin_tg::list_archives(&transport).await.unwrap();
+    assert_eq!(listings.len(), 1);
+    assert_eq!(listings[0].filename, "txtmin02.zip");
+
+    // Both are bounded like the archives sources fetch
+    let requests = transport.0.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(requests
+        .iter()
+        .all(|request| request.max_body_size == 256 << 20));
+}
+
+#[tokio::test]
+async fn refetch_intervals_are_configurable() {
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let server = MockServer::start(move |_| (200, archive.clone())).await;
+
+    let config = emwin_tg::SourceConfig::new()
+        .mirrors(emwin_tg::Mirrors::new([server.url.clone()]))
+        .refetch_interval("txtmin02.zip", Duration::from_millis(50))
+        .refetch_interval("txtmin06.zip", Duration::from_millis(1))
+        .min_refetch_interval(Duration::from_millis(100));
+    let source = emwin_tg::TextSource::with_config(reqwest::Client::new(), config);
+    let stream = emwin_tg::Stream::from_source(source);
+
+    // Drive the stream for a while
+    let driven =
+        tokio::time::timeout(Duration::from_millis(550), stream.for_each(|_| async {})).await;
+    assert!(driven.is_err());
+
+    let requests = server.requests();
+    let count = |path: &str| requests.iter().filter(|r| *r == path).count();
+
+    // Both short intervals were raised to the minimum, and the rest kept their defaults
+    for path in ["/txtmin02.zip", "/txtmin06.zip"] {
+        assert!((4..=7).contains(&count(path)), "{}: {:?}", path, requests);
+    }
+    assert_eq!(count("/txtmin20.zip"), 1);
+    assert_eq!(count("/txthrs03.zip"), 1);
+}
+
+#[tokio::test]
+async fn redelivery_waits_for_slowly_refetched_archives() {
+    // The product is only in the 3-hour archive, which is refetched less often than the window
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let empty = common::archive(&[]);
+    let server = MockServer::start(move |path| match path {
+        "/txthrs03.zip" => (200, archive.clone()),
+        _ => (200, empty.clone()),
+    })
+    .await;
+
+    let config = emwin_tg::SourceConfig::new()
+        .mirrors(emwin_tg::Mirrors::new([server.url.clone()]))
+        .refetch_interval("txtmin02.zip", Duration::from_millis(20))
+        .refetch_interval("txtmin06.zip", Duration::from_millis(20))
+        .refetch_interval("txtmin20.zip", Duration::from_millis(20))
+        .refetch_interval("txthrs03.zip", Duration::from_millis(100));
+    let source = emwin_tg::TextSource::with_config(reqwest::Client::new(), config);
+    let mut stream =
+        emwin_tg::Stream::from_source(source).redeliver_after(Duration::from_millis(30));
+
+    let mut products = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_millis(650), async {
+        while let Some(product) = stream.next().await {
+            products.push(product.unwrap().filename);
+        }
+    })
+    .await;
+
+    // It was refetched several times, but never gone long enough to count as new
+    let requests = server.requests();
+    assert!(requests.iter().filter(|r| *r == "/txthrs03.zip").count() >= 4);
+    assert_eq!(products, ["AFDOKXNY.TXT"]);
+}
+
+#[tokio::test]
+async fn realtime_only_skips_long_archives() {
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let server = MockServer::start(move |_| (200, archive.clone())).await;
+
+    let mirrors = emwin_tg::Mirrors::new([server.url.clone()]);
+    let source =
+        emwin_tg::TextSource::with_mirrors(reqwest::Client::new(), mirrors.clone()).realtime_only();
+    let mut stream = emwin_tg::Stream::from_source(source);
+    let product = tokio::time::timeout(Duration::from_secs(10), stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(product.filename, "AFDOKXNY.TXT");
+    let _ = tokio::time::timeout(Duration::from_millis(200), stream.next()).await;
+
+    let mut requests = server.requests();
+    requests.sort();
+    assert_eq!(requests, ["/txtmin02.zip", "/txtmin06.zip"]);
+
+    let source =
+        emwin_tg::ImageSource::with_mirrors(reqwest::Client::new(), mirrors).realtime_only();
+    let mut stream = emwin_tg::Stream::from_source(source);
+    let _ = tokio::time::timeout(Duration::from_millis(300), stream.next()).await;
+    let mut requests = server.requests();
+    requests.sort();
+    assert_eq!(
+        requests,
+        ["/imgmin15.zip", "/txtmin02.zip", "/txtmin06.zip"]
+    );
+}
+
+#[tokio::test]
+async fn archives_are_yielded_as_fetched() {
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let served = archive.clone();
+    let server = MockServer::start(move |_| (200, served.clone())).await;
+
+    let mirrors = emwin_tg::Mirrors::new([server.url.clone()]);
+    let before = std::time::SystemTime::now();
+    let mut archives = emwin_tg::TextSource::with_mirrors(reqwest::Client::new(), mirrors)
+        .realtime_only()
+        .archives();
+
+    let mut names = Vec::new();
+    for _ in 0..2 {
+        let fetched = tokio::time::timeout(Duration::from_secs(10), archives.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.url, format!("{}{}", server.url, fetched.name));
+        assert_eq!(fetched.contents, archive);
+        assert!(fetched.fetched_at >= before);
+        names.push(fetched.name);
+    }
+    names.sort_unstable();
+    assert_eq!(names, ["txtmin02.zip", "txtmin06.zip"]);
+}
+
+#[tokio::test]
+async fn failed_fetches_are_retried_with_backoff() {
+    let server = MockServer::start(|_| (500, Vec::new())).await;
+
+    let retry = emwin_tg::RetryPolicy::new(Duration::from_millis(50), Duration::from_millis(100))
+        .jitter(0.0);
+    let config = emwin_tg::SourceConfig::new()
+        .mirrors(emwin_tg::Mirrors::new([server.url.clone()]))
+        .retry(retry);
+    let source = emwin_tg::TextSource::with_config(reqwest::Client::new(), config).realtime_only();
+    let stream = emwin_tg::Stream::from_source(source);
+    let attempts = std::sync::Mutex::new(Vec::new());
+    let _ = tokio::time::timeout(
+        Duration::from_millis(480),
+        stream.for_each(|result| {
+            if let Err(emwin_tg::Error::Fetch {
+                archive, attempt, ..
+            }) = result
+            {
+                if archive == "txtmin06.zip" {
+                    attempts.lock().unwrap().push(attempt);
+                }
+            }
+            async {}
+        }),
+    )
+    .await;
+
+    // Each error says how many times in a row the archive has failed
+    let attempts = attempts.into_inner().unwrap();
+    assert!(attempts.len() >= 4, "{:?}", attempts);
+    assert!(attempts.iter().copied().eq(1..=attempts.len() as u32));
+
+    // Retried after 50 ms, then every 100 ms: at about 0, 50, 150, 250, 350, and 450 ms
+    let requests = server.requests();
+    let count = requests.iter().filter(|r| *r == "/txtmin06.zip").count();
+    assert!((4..=6).contains(&count), "{:?}", requests);
+
+    // Without retries, only the scheduled fetch happens
+    let server = MockServer::start(|_| (500, Vec::new())).await;
+    let config = emwin_tg::SourceConfig::new()
+        .mirrors(emwin_tg::Mirrors::new([server.url.clone()]))
+        .retry(emwin_tg::RetryPolicy::none());
+    let source = emwin_tg::TextSource::with_config(reqwest::Client::new(), config).realtime_only();
+    let stream = emwin_tg::Stream::from_source(source);
+    let _ = tokio::time::timeout(Duration::from_millis(300), stream.for_each(|_| async {})).await;
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn retry_classifiers_choose_which_errors_to_retry() {
+    async fn requests_for(retry: emwin_tg::RetryPolicy) -> usize {
+        let server = MockServer::start(|_| (404, Vec::new())).await;
+        let config = emwin_tg::SourceConfig::new()
+            .mirrors(emwin_tg::Mirrors::new([server.url.clone()]))
+            .retry(retry.jitter(0.0));
+        let source =
+            emwin_tg::TextSource::with_config(reqwest::Client::new(), config).realtime_only();
+        let stream = emwin_tg::Stream::from_source(source);
+        let _ =
+            tokio::time::timeout(Duration::from_millis(300), stream.for_each(|_| async {})).await;
+        let requests = server.requests();
+        requests.iter().filter(|r| *r == "/txtmin06.zip").count()
+    }
+    let retry = emwin_tg::RetryPolicy::new(Duration::from_millis(50), Duration::from_millis(50));
+
+    // By default, a 404 waits for the next scheduled fetch
+    assert_eq!(requests_for(retry.clone()).await, 1);
+
+    // A classifier can decide otherwise
+    let retry = retry.retry_classifier(|error| {
+        assert!(!matches!(error, emwin_tg::Error::Fetch { .. }));
+        error.status() == Some(404)
+    });
+    assert!(requests_for(retry).await >= 3);
+}
+
+#[tokio::test]
+async fn retries_recover_the_long_archive() {
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let failed = std::sync::atomic::AtomicBool::new(false);
+    let server = MockServer::start(move |path| {
+        if path != "/txthrs03.zip" {
+            (404, Vec::new())
+        } else if failed.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            (200, archive.clone())
+        } else {
+            (500, Vec::new())
+        }
+    })
+    .await;
+
+    let retry = emwin_tg::RetryPolicy::new(Duration::from_millis(50), Duration::from_secs(1));
+    let config = emwin_tg::SourceConfig::new()
+        .mirrors(emwin_tg::Mirrors::new([server.url.clone()]))
+        .retry(retry);
+    let source = emwin_tg::TextSource::with_config(reqwest::Client::new(), config);
+    let mut stream = emwin_tg::Stream::from_source(source);
+
+    let product = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(product) = stream.next().await.unwrap() {
+                break product;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(product.filename, "AFDOKXNY.TXT");
+}
+
+/// Serve `archive` like a static file server: with an `ETag`, honoring `If-None-Match`, `Range`,
+/// and `If-Range`. Returns the response and a description of what was sent.
+fn serve_file(archive: &[u8], etag: &str, head: &str) -> (common::Response, String) {
+    let header = |name: &str| {
+        head.lines().find_map(|line| {
+            let (n, value) = line.split_once(':')?;
+            Some(value.trim().to_string()).filter(|_| n.eq_ignore_ascii_case(name))
+        })
+    };
+    let headers = vec![("ETag", etag.to_string())];
+    if header("if-none-match").as_deref() == Some(etag) {
+        return ((304, headers, Vec::new()), "not modified".into());
+    }
+
+    let len = archive.len();
+    let range = header("range")
+        .filter(|_| header("if-range").is_none_or(|v| v == etag))
+        .and_then(|range| {
+            let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+            Some(match start {
+                "" => (len.saturating_sub(end.parse().ok()?), len - 1),
+                _ => (start.parse().ok()?, end.parse().ok()?),
+            })
+        });
+    match range {
+        Some((start, end)) => {
+            let mut headers = headers;
+            headers.push(("Content-Range", format!("bytes {}-{}/{}", start, end, len)));
+            let description = format!("bytes {}-{}", start, end);
+            ((206, headers, archive[start..=end].to_vec()), description)
+        }
+        None => ((200, headers, archive.to_vec()), "whole".into()),
+    }
+}
+
+#[tokio::test]
+async fn range_requests_fetch_only_new_members() {
+    let body = |name: &str| name.repeat(100).into_bytes();
+    let v1 = common::archive(&[
+        ("AFDOKXNY.TXT", &body("first")),
+        ("ZFPOKXNY.TXT", &body("second")),
+    ]);
+    let v2 = common::archive(&[
+        ("AFDOKXNY.TXT", &body("first")),
+        ("ZFPOKXNY.TXT", &body("second")),
+        ("SVROKXNY.TXT", &body("third")),
+    ]);
+
+    let current = std::sync::Arc::new(std::sync::Mutex::new((v1, "\"v1\"")));
+    let served = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let server = {
+        let (current, served) = (current.clone(), served.clone());
+        MockServer::start_with_headers(move |path, head| {
+            if path != "/txtmin02.zip" {
+                return (404, Vec::new(), Vec::new());
+            }
+            let current = current.lock().unwrap();
+            let (response, description) = serve_file(&current.0, current.1, head);
+            served.lock().unwrap().push(description);
+            response
+        })
+        .await
+    };
+
+    let config = emwin_tg::SourceConfig::new()
+        .mirrors(emwin_tg::Mirrors::new([server.url.clone()]).range_requests(true))
+        .refetch_interval("txtmin02.zip", Duration::from_millis(100))
+        .retry(emwin_tg::RetryPolicy::none());
+    let source = emwin_tg::TextSource::with_config(reqwest::Client::new(), config).realtime_only();
+    let mut products = emwin_tg::Stream::from_source(source)
+        .filter_map(|result| async move { result.ok().map(|product| product.filename) })
+        .boxed();
+
+    let mut first = vec![
+        products.next().await.unwrap(),
+        products.next().await.unwrap(),
+    ];
+    first.sort();
+    assert_eq!(first, ["AFDOKXNY.TXT", "ZFPOKXNY.TXT"]);
+
+    *current.lock().unwrap() = (v2, "\"v2\"");
+    let next = tokio::time::timeout(Duration::from_secs(10), products.next())
+        .await
+        .unwrap();
+    assert_eq!(next.as_deref(), Some("SVROKXNY.TXT"));
+
+    // Only the first fetch downloaded the whole archive
+    let served = served.lock().unwrap().clone();
+    assert_eq!(served[0], "whole");
+    assert!(!served[1..].contains(&"whole".to_string()), "{:?}", served);
+    assert!(served.len() >= 3, "{:?}", served);
+}
+
+#[tokio::test]
+async fn range_requests_fall_back_to_whole_archives() {
+    let v1 = common::archive(&[("AFDOKXNY.TXT", b"first")]);
+    let v2 = common::archive(&[("AFDOKXNY.TXT", b"first"), ("ZFPOKXNY.TXT", b"second")]);
+    let fetches = std::sync::atomic::AtomicUsize::new(0);
+
+    // This server ignores ranges
+    let server = MockServer::start(move |path| match path {
+        "/txtmin02.zip" => match fetches.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+            0 => (200, v1.clone()),
+            _ => (200, v2.clone()),
+        },
+        _ => (404, Vec::new()),
+    })
+    .await;
+
+    let config = emwin_tg::SourceConfig::new()
+        .mirrors(emwin_tg::Mirrors::new([server.url.clone()]).range_requests(true))
+        .refetch_interval("txtmin02.zip", Duration::from_millis(100))
+        .retry(emwin_tg::RetryPolicy::none());
+    let source = emwin_tg::TextSource::with_config(reqwest::Client::new(), config).realtime_only();
+    let products: Vec<_> = emwin_tg::Stream::from_source(source)
+        .filter_map(|result| async move { result.ok().map(|product| product.filename) })
+        .take(2)
+        .collect()
+        .await;
+    assert_eq!(products, ["AFDOKXNY.TXT", "ZFPOKXNY.TXT"]);
+}
+
+#[tokio::test]
+async fn range_requests_survive_damaged_directories() {
+    let v1 = common::archive(&[("AFDOKXNY.TXT", b"first")]);
+    let mut v2 = common::archive(&[("AFDOKXNY.TXT", b"first"), ("ZFPOKXNY.TXT", b"second")]);
+    // Point the central directory far past the end of the archive
+    let offset = v2.len() - 22 + 16;
+    v2[offset..offset + 4].copy_from_slice(&0x7fff_fff0u32.to_le_bytes());
+
+    let current = std::sync::Arc::new(std::sync::Mutex::new((v1, "\"v1\"")));
+    let served = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let server = {
+        let (current, served) = (current.clone(), served.clone());
+        MockServer::start_with_headers(move |path, head| {
+            if path != "/txtmin02.zip" {
+                return (404, Vec::new(), Vec::new());
+            }
+            let current = current.lock().unwrap();
+            let (response, description) = serve_file(&current.0, current.1, head);
+            served.lock().unwrap().push(description);
+            response
+        })
+        .await
+    };
+
+    let config = emwin_tg::SourceConfig::new()
+        .mirrors(emwin_tg::Mirrors::new([server.url.clone()]).range_requests(true))
+        .refetch_interval("txtmin02.zip", Duration::from_millis(100))
+        .retry(emwin_tg::RetryPolicy::none());
+    let source = emwin_tg::TextSource::with_config(reqwest::Client::new(), config).realtime_only();
+    let mut stream = emwin_tg::Stream::from_source(source);
+    let first = stream.next().await.unwrap().unwrap();
+    assert_eq!(first.filename, "AFDOKXNY.TXT");
+
+    // The damaged tail sends the next fetch back to the whole archive
+    *current.lock().unwrap() = (v2, "\"v2\"");
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while served.lock().unwrap().len() < 3 {
+            stream.next().await;
+        }
+    })
+    .await
+    .unwrap();
+    let served = served.lock().unwrap().clone();
+    assert_eq!(served[0], "whole");
+    assert!(served[1].starts_with("bytes"), "{:?}", served);
+    assert_eq!(served[2], "whole", "{:?}", served);
+}
+
+#[tokio::test]
+async fn url_sources_poll_any_archive() {
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let server = MockServer::start(move |path| match path {
+        "/regional/feed.zip" => (200, archive.clone()),
+        _ => (404, Vec::new()),
+    })
+    .await;
+
+    let source = emwin_tg::UrlSource::with_client(
+        reqwest::Client::new(),
+        format!("{}regional/feed.zip", server.url),
+        Duration::from_secs(60),
+    );
+    let mut stream = emwin_tg::Stream::from_source(source);
+    let product = tokio::time::timeout(Duration::from_secs(10), stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(product.filename, "AFDOKXNY.TXT");
+    assert_eq!(server.requests(), ["/regional/feed.zip"]);
+
+    let mut archives = emwin_tg::UrlSource::with_client(
+        reqwest::Client::new(),
+        format!("{}regional/feed.zip", server.url),
+        Duration::from_secs(60),
+    )
+    .archives();
+    let fetched = tokio::time::timeout(Duration::from_secs(10), archives.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(fetched.name, "feed.zip");
+}
+
+#[tokio::test]
+async fn rate_limiters_are_shared_across_sources() {
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let server = MockServer::start(move |_| (200, archive.clone())).await;
+
+    let limiter = emwin_tg::RateLimiter::new(Duration::from_millis(300));
+    let config =
+        emwin_tg::SourceConfig::new().mirrors(emwin_tg::Mirrors::new([server.url.clone()]));
+    let text = emwin_tg::TextStream::builder()
+        .source_config(config.clone())
+        .rate_limiter(limiter.clone())
+        .build();
+    let images = emwin_tg::ImageStream::builder()
+        .source_config(config)
+        .rate_limiter(limiter.clone())
+        .build();
+
+    // A limiter derived from a shared one doesn't change it
+    let bursty = limiter.burst(6);
+    std::mem::drop(bursty);
+
+    // Six archives are due at once, but they're fetched one at a time
+    let consumer = tokio::spawn(
+        futures::stream::select(text.map(|_| ()), images.map(|_| ())).for_each(|_| async {}),
+    );
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(server.requests().len(), 1);
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert_eq!(server.requests().len(), 3);
+    consumer.abort();
+}
+
+#[tokio::test]
+async fn combined_streams_merge_both_feeds() {
+    let text = common::archive(&[("AFDOKXNY.TXT", b"hello"), ("SHARED.TXT", b"hello")]);
+    let image = common::archive(&[("RADALLUS.GIF", b"GIF89a"), ("SHARED.TXT", b"hello")]);
+    let server = MockServer::start(move |path| match path {
+        "/txtmin02.zip" => (200, text.clone()),
+        "/imgmin15.zip" => (200, image.clone()),
+        _ => (404, Vec::new()),
+    })
+    .await;
+
+    let config =
+        emwin_tg::SourceConfig::new().mirrors(emwin_tg::Mirrors::new([server.url.clone()]));
+    let stream = emwin_tg::CombinedStream::builder()
+        .source_config(config)
+        .build();
+    let mut products: Vec<(Option<Feed>, String)> = stream
+        .filter_map(|result| async move { result.ok() })
+        .map(|product| (product.feed(), product.filename))
+        .take(3)
+        .collect()
+        .await;
+    products.sort();
+
+    // The filename in both archives is yielded once
+    assert_eq!(
+        products,
+        [
+            (Some(Feed::Text), "AFDOKXNY.TXT".to_string()),
+            (Some(Feed::Text), "SHARED.TXT".to_string()),
+            (Some(Feed::Image), "RADALLUS.GIF".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn shutdown_drains_fetches_in_flight() {
+    let server = MockServer::start(|path| {
+        let name = path[1..].replace(".zip", ".TXT").to_uppercase();
+        (200, common::archive(&[(name.as_str(), b"hello")]))
+    })
+    .await;
+
+    // The rate limiter holds all but the first fetch in flight
+    let config =
+        emwin_tg::SourceConfig::new().mirrors(emwin_tg::Mirrors::new([server.url.clone()]));
+    let mut stream = emwin_tg::TextStream::builder()
+        .source_config(config)
+        .rate_limiter(emwin_tg::RateLimiter::new(Duration::from_millis(100)))
+        .build();
+    let first = stream.next().await.unwrap().unwrap();
+    assert_eq!(server.requests().len(), 1);
+
+    stream.shutdown();
+    let rest: Vec<String> =
+        tokio::time::timeout(Duration::from_secs(5), stream.collect::<Vec<_>>())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|result| result.unwrap().filename)
+            .collect();
+    let mut filenames: Vec<String> = std::iter::once(first.filename).chain(rest).collect();
+    filenames.sort();
+    assert_eq!(
+        filenames,
+        [
+            "TXTHRS03.TXT",
+            "TXTMIN02.TXT",
+            "TXTMIN06.TXT",
+            "TXTMIN20.TXT"
+        ]
+    );
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[tokio::test]
+async fn shutdown_signals_stop_sources_which_cannot_drain() {
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let source = futures::stream::pending().boxed();
+    let (source, state, mut buffered) = emwin_tg::Stream::from_source(source).into_inner();
+    buffered.push_back(Ok(emwin_tg::Product::new("AFDOKXNY.TXT", &b"hello"[..])));
+    let mut stream = emwin_tg::Stream::from_parts(source, state, buffered).shutdown_on(async {
+        rx.await.ok();
+    });
+
+    assert!(stream.next().await.unwrap().is_ok());
+    assert!(futures::poll!(stream.next()).is_pending());
+
+    tx.send(()).unwrap();
+    let next = tokio::time::timeout(Duration::from_secs(1), stream.next()).await;
+    assert!(next.unwrap().is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn slow_fetches_are_not_stacked() {
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let server = MockServer::start(move |_| {
+        std::thread::sleep(Duration::from_millis(200));
+        (200, archive.clone())
+    })
+    .await;
+
+    // Ticks which come due while the last fetch is in flight are skipped
+    let archives = emwin_tg::UrlSource::with_client(
+        reqwest::Client::new(),
+        format!("{}feed.zip", server.url),
+        Duration::from_millis(10),
+    )
+    .archives();
+    let driven =
+        tokio::time::timeout(Duration::from_millis(700), archives.for_each(|_| async {})).await;
+    assert!(driven.is_err());
+    assert!(
+        (2..=4).contains(&server.requests().len()),
+        "{:?}",
+        server.requests()
+    );
+}
+
+#[tokio::test]
+async fn fetches_pause_while_results_wait() {
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let server = MockServer::start(move |_| (200, archive.clone())).await;
+
+    let names = [
+        "txtmin02.zip",
+        "txtmin06.zip",
+        "txtmin20.zip",
+        "txthrs03.zip",
+    ];
+    let config = names
+        .iter()
+        .fold(emwin_tg::SourceConfig::new(), |config, name| {
+            config.refetch_interval(*name, Duration::from_millis(10))
+        })
+        .mirrors(emwin_tg::Mirrors::new([server.url.clone()]))
+        .max_buffered_archives(1);
+    let mut archives = emwin_tg::TextSource::with_config(reqwest::Client::new(), config).archives();
+
+    // A slow consumer takes one archive at a time, leaving the rest waiting in the source
+    let mut taken = 0;
+    for _ in 0..10 {
+        tokio::time::timeout(Duration::from_secs(5), archives.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        taken += 1;
+        tokio::time::sleep(Duration::from_millis(30)).await;
+    }
+
+    // Each archive has at most one download waiting and one in flight
+    let requests = server.requests().len();
+    assert!(requests <= taken + 2 * names.len(), "{}", requests);
+}
+
+#[tokio::test]
+async fn custom_transports_replace_reqwest() {
+    use emwin_tg::{Error, HttpRequest, HttpResponse, HttpTransport};
+    use futures::future::BoxFuture;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Canned(Arc<Mutex<Vec<HttpRequest>>>);
+
+    impl HttpTransport for Canned {
+        fn get(&self, request: HttpRequest) -> BoxFuture<'static, Result<HttpResponse, Error>> {
+            let response = if request.url.ends_with("/txtmin02.zip") {
+                let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+                HttpResponse::new(200, archive).with_header("ETag", "\"v1\"")
+            } else {
+                HttpResponse::new(503, Vec::new())
+            };
+            self.0.lock().unwrap().push(request);
+            Box::pin(async move { Ok(response) })
+        }
+    }
+
+    let transport = Canned::default();
+    let requests = transport.0.clone();
+    let config = emwin_tg::SourceConfig::new()
+        .mirrors(emwin_tg::Mirrors::new(["https://transport.invalid/"]))
+        .refetch_interval("txtmin02.zip", Duration::from_millis(50))
+        .retry(emwin_tg::RetryPolicy::none())
+        .transport(transport);
+    let source = emwin_tg::TextSource::with_config(reqwest::Client::new(), config).realtime_only();
+    let mut stream = emwin_tg::Stream::from_source(source);
+
+    let mut results = Vec::new();
+    for _ in 0..2 {
+        results.push(
+            tokio::time::timeout(Duration::from_secs(5), stream.next())
+                .await
+                .unwrap()
+                .unwrap(),
+        );
+    }
+    let product = results
+        .iter()
+        .find_map(|result| result.as_ref().ok())
+        .unwrap();
+    assert_eq!(product.filename, "AFDOKXNY.TXT");
+    let error = results
+        .iter()
+        .find_map(|result| result.as_ref().err())
+        .unwrap();
+    match error {
+        Error::Fetch { source, .. } => match &**source {
+            Error::Status { url, status } => {
+                assert_eq!(url, "https://transport.invalid/txtmin06.zip");
+                assert_eq!(*status, 503);
+            }
+            other => panic!("{:?}", other),
+        },
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(error.kind(), emwin_tg::ErrorKind::Http);
+
+    // Refetches are conditional on the ETag
+    let _ = tokio::time::timeout(Duration::from_millis(200), stream.next()).await;
+    let requests = requests.lock().unwrap();
+    let refetch = requests
+        .iter()
+        .filter(|request| request.url.ends_with("/txtmin02.zip"))
+        .nth(1)
+        .unwrap();
+    assert!(refetch
+        .headers
+        .contains(&("if-none-match".to_string(), "\"v1\"".to_string())));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn feeds_fetch_concurrently() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Record how many requests are being served at once
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let most_in_flight = Arc::new(AtomicUsize::new(0));
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let server = MockServer::start({
+        let (in_flight, most_in_flight) = (in_flight.clone(), most_in_flight.clone());
+        move |_| {
+            let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            most_in_flight.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(300));
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            (200, archive.clone())
+        }
+    })
+    .await;
+
+    // Every feed comes due at startup, and each fetch is slow
+    let config =
+        emwin_tg::SourceConfig::new().mirrors(emwin_tg::Mirrors::new([server.url.clone()]));
+    let mut stream = emwin_tg::TextStream::builder()
+        .source_config(config)
+        .build();
+    tokio::time::timeout(Duration::from_secs(5), stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+    assert!(
+        most_in_flight.load(Ordering::SeqCst) >= 2,
+        "{:?}",
+        server.requests()
+    );
+}
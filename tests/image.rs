@@ -0,0 +1,84 @@
+#![cfg(feature = "image")]
+
+use emwin_tg::{ErrorKind, Product};
+use image::{Delay, Frame, Rgba, RgbaImage};
+
+fn animated_gif() -> Vec<u8> {
+    let mut gif = Vec::new();
+    {
+        let mut encoder = image::codecs::gif::GifEncoder::new(&mut gif);
+        encoder
+            .set_repeat(image::codecs::gif::Repeat::Infinite)
+            .unwrap();
+        let frames = [Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255])]
+            .iter()
+            .map(|color| {
+                Frame::from_parts(
+                    RgbaImage::from_pixel(4, 3, *color),
+                    0,
+                    0,
+                    Delay::from_numer_denom_ms(500, 1),
+                )
+            });
+        encoder.encode_frames(frames).unwrap();
+    }
+    gif
+}
+
+#[test]
+fn decodes_animated_gif_frames() {
+    let product = Product::new("RADALLUS.GIF", animated_gif());
+    let frames = product.decode_gif_frames().unwrap();
+
+    assert_eq!(frames.len(), 2);
+    for (frame, color) in frames.iter().zip([[255, 0, 0, 255], [0, 0, 255, 255]]) {
+        assert_eq!(frame.buffer().dimensions(), (4, 3));
+        assert_eq!(frame.buffer().get_pixel(1, 1).0, color);
+        assert_eq!(frame.delay().numer_denom_ms(), (500, 1));
+    }
+}
+
+#[test]
+fn rejects_non_gif_products() {
+    let product = Product::new("AFDOKXNY.TXT", &b"hello"[..]);
+    match product.decode_gif_frames() {
+        Err(e) => assert_eq!(e.kind(), ErrorKind::Image),
+        Ok(frames) => panic!("decoded {} frames", frames.len()),
+    }
+}
+
+#[test]
+fn converts_png_products() {
+    let mut png = Vec::new();
+    RgbaImage::from_pixel(5, 2, Rgba([0, 128, 0, 255]))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+
+    let image = image::DynamicImage::try_from(Product::new("G16CONUS.PNG", png)).unwrap();
+    assert_eq!((image.width(), image.height()), (5, 2));
+    assert_eq!(image.to_rgba8().get_pixel(4, 1).0, [0, 128, 0, 255]);
+
+    let text = Product::new("AFDOKXNY.TXT", &b"hello"[..]);
+    match image::DynamicImage::try_from(text) {
+        Err(e) => assert_eq!(e.kind(), ErrorKind::Image),
+        Ok(_) => panic!("decoded text as an image"),
+    }
+}
+
+#[test]
+fn decodes_images_with_misleading_extensions() {
+    let gif = animated_gif();
+    let product = Product::new("G16CONUS.JPG", gif);
+    assert_eq!(product.image_format().unwrap().extension, "GIF");
+
+    let image = product.decode_image().unwrap();
+    assert_eq!((image.width(), image.height()), (4, 3));
+    assert_eq!(image.to_rgba8().get_pixel(0, 0).0, [255, 0, 0, 255]);
+
+    let text = Product::new("RADALLUS.GIF", &b"hello"[..]);
+    assert_eq!(text.image_format(), None);
+    match text.decode_image() {
+        Err(e) => assert_eq!(e.kind(), ErrorKind::Image),
+        Ok(_) => panic!("decoded text as an image"),
+    }
+}
@@ -0,0 +1,84 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use emwin_tg::sink::JsonlWriter;
+use emwin_tg::Product;
+
+fn lines(path: &std::path::Path) -> Vec<serde_json::Value> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn writes_a_line_per_product() {
+    let dir = std::env::temp_dir().join(format!("emwin-tg-jsonl-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("products.jsonl");
+
+    let stream = emwin_tg::Stream::from_source(common::source(vec![
+        common::archive(&[(
+            "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+            b"Area Forecast Discussion",
+        )]),
+        b"not an archive".to_vec(),
+        common::archive(&[("RADALLUS.GIF", b"GIF89a\xff")]),
+    ]));
+    let mut writer = JsonlWriter::open(&path).unwrap();
+    writer.write_stream(stream).await.unwrap();
+
+    let written = lines(&path);
+    assert_eq!(written.len(), 2);
+    assert_eq!(
+        written[0],
+        serde_json::json!({
+            "filename": "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+            "received_at": 1644925977,
+            "issued_at": 1644925920,
+            "wmo_heading": "FXUS63 KDVN 151152",
+            "pil": "AFDDVNIA",
+            "mime_type": "text/plain",
+            "body": "Area Forecast Discussion",
+        })
+    );
+    assert_eq!(written[1]["filename"], "RADALLUS.GIF");
+    assert_eq!(written[1]["wmo_heading"], serde_json::Value::Null);
+    assert_eq!(written[1]["body"], serde_json::Value::Null);
+    assert_eq!(written[1]["body_base64"], "R0lGODlh/w==");
+
+    // Reopening appends
+    drop(writer);
+    let mut writer = JsonlWriter::open(&path).unwrap();
+    writer
+        .write(&Product::new("AFDOKXNY.TXT", "hello"))
+        .unwrap();
+    assert_eq!(lines(&path).len(), 3);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn rotates_files_by_size() {
+    let dir = std::env::temp_dir().join(format!("emwin-tg-rotate-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("products.jsonl");
+    let rotated = |n: usize| dir.join(format!("products.jsonl.{}", n));
+
+    let mut writer = JsonlWriter::open(&path).unwrap().rotate_at(150).keep(2);
+    for name in ["A.TXT", "B.TXT", "C.TXT", "D.TXT", "E.TXT"] {
+        writer.write(&Product::new(name, "hello")).unwrap();
+    }
+
+    // Each line is over half the limit, so each file holds one product
+    let filename = |path: &std::path::Path| lines(path)[0]["filename"].clone();
+    assert_eq!(lines(&path).len(), 1);
+    assert_eq!(filename(&path), "E.TXT");
+    assert_eq!(filename(&rotated(1)), "D.TXT");
+    assert_eq!(filename(&rotated(2)), "C.TXT");
+    assert!(!rotated(3).exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "metrics")]
+
+mod common;
+
+use common::MockServer;
+use futures::StreamExt;
+use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+use std::time::Duration;
+
+#[tokio::test]
+async fn fetches_and_buffers_are_measured() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    recorder.install().unwrap();
+
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let size = archive.len();
+    let server = MockServer::start_with_headers(move |path, head| match path {
+        "/txtmin02.zip" if head.to_ascii_lowercase().contains("if-none-match") => {
+            (304, Vec::new(), Vec::new())
+        }
+        "/txtmin02.zip" => (200, vec![("ETag", "\"1\"".to_string())], archive.clone()),
+        _ => (404, Vec::new(), Vec::new()),
+    })
+    .await;
+
+    let config = emwin_tg::SourceConfig::new()
+        .mirrors(emwin_tg::Mirrors::new([server.url.clone()]))
+        .refetch_interval("txtmin02.zip", Duration::from_millis(100));
+    let source = emwin_tg::TextSource::with_config(reqwest::Client::new(), config).realtime_only();
+    let stream = emwin_tg::Stream::from_source(source);
+    let _ = tokio::time::timeout(Duration::from_millis(350), stream.for_each(|_| async {})).await;
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let value = |name: &str, archive: Option<&str>| {
+        snapshot
+            .iter()
+            .find(|(key, _, _, _)| {
+                let key = key.key();
+                key.name() == name
+                    && archive.is_none_or(|archive| {
+                        key.labels()
+                            .any(|label| label.key() == "archive" && label.value() == archive)
+                    })
+            })
+            .map(|(_, _, _, value)| value)
+            .unwrap_or_else(|| panic!("no {} for {:?}", name, archive))
+    };
+    let counter = |name, archive| match value(name, Some(archive)) {
+        DebugValue::Counter(count) => *count,
+        other => panic!("{} is {:?}", name, other),
+    };
+
+    assert!(counter("emwin_tg_fetches_total", "txtmin02.zip") >= 3);
+    assert!(counter("emwin_tg_not_modified_total", "txtmin02.zip") >= 2);
+    assert_eq!(counter("emwin_tg_fetch_errors_total", "txtmin06.zip"), 1);
+    match value("emwin_tg_archive_bytes", Some("txtmin02.zip")) {
+        DebugValue::Histogram(sizes) => assert_eq!(sizes[0].into_inner(), size as f64),
+        other => panic!("archive sizes are {:?}", other),
+    }
+    assert!(matches!(
+        value("emwin_tg_fetch_duration_seconds", Some("txtmin06.zip")),
+        DebugValue::Histogram(_)
+    ));
+    assert!(matches!(
+        value("emwin_tg_dedup_filenames", None),
+        DebugValue::Gauge(count) if count.into_inner() == 1.0
+    ));
+    assert!(matches!(
+        value("emwin_tg_buffered_products", None),
+        DebugValue::Gauge(count) if count.into_inner() == 0.0
+    ));
+}
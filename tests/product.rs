@@ -0,0 +1,902 @@
+use emwin_tg::product::wmo::WmoHeading;
+use emwin_tg::Product;
+
+#[test]
+fn wmo_data_type_designators() {
+    for (filename, t1, t2) in [
+        (
+            "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+            'F',
+            'X',
+        ),
+        (
+            "A_WUUS53KLOT202245_C_KWIN_20220220224512_061002-1-SVRLOTIL.TXT",
+            'W',
+            'U',
+        ),
+        (
+            "A_SAUS70KWBC151200_C_KWIN_20220215120104_060742-3-METAR1US.TXT",
+            'S',
+            'A',
+        ),
+        (
+            "A_WFUS53KLOT202245CCA_C_KWIN_20220220225001_061010-1-TORLOTIL.TXT",
+            'W',
+            'F',
+        ),
+    ] {
+        let product = Product::new(filename, Vec::new());
+        assert_eq!(product.wmo_t1(), Some(t1), "{}", filename);
+        assert_eq!(product.wmo_t2(), Some(t2), "{}", filename);
+    }
+
+    for filename in [
+        "RADALLUS.GIF",
+        "A_fxus63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+        "A_FX_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+        "Z_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+    ] {
+        let product = Product::new(filename, Vec::new());
+        assert_eq!(product.wmo_heading(), None, "{}", filename);
+        assert_eq!(product.wmo_t1(), None, "{}", filename);
+    }
+}
+
+#[test]
+fn wmo_heading_fields() {
+    let heading = Product::new(
+        "A_WFUS53KLOT202245CCA_C_KWIN_20220220225001_061010-1-TORLOTIL.TXT",
+        Vec::new(),
+    )
+    .wmo_heading()
+    .unwrap();
+    assert_eq!(heading.ttaaii(), "WFUS53");
+    assert_eq!(heading.cccc(), Some("KLOT"));
+    assert_eq!(heading.yygggg(), Some("202245"));
+    assert_eq!(heading.bbb(), Some("CCA"));
+    assert_eq!(heading, "WFUS53 KLOT 202245 CCA".parse().unwrap());
+
+    let heading: WmoHeading = "SAUS70 KWBC 151200".parse().unwrap();
+    assert_eq!(heading.bbb(), None);
+    assert_eq!(heading.to_string(), "SAUS70 KWBC 151200");
+
+    for bad in ["", "SAUS7 KWBC 151200", "SAUS70 KWBC 151200 CCA X"] {
+        assert!(bad.parse::<WmoHeading>().is_err(), "{:?}", bad);
+    }
+}
+
+#[test]
+fn originator_from_heading_or_filename() {
+    // The heading in the contents wins
+    let product = Product::new(
+        "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+        "\x01\r\r\n000 \r\r\nFXUS63 KDMX 151152\r\r\nAFDDMX\r\r\n",
+    );
+    assert_eq!(product.originator().as_deref(), Some("KDMX"));
+    assert_eq!(product.guess_issuing_office().as_deref(), Some("KDVN"));
+
+    // Images have no heading of their own
+    let image = Product::new(
+        "A_TIGQ53KNES151200_C_KWIN_20220215120512_060744-4-G16CIRUS.GIF",
+        &b"GIF89a\x01\x00\x01\x00"[..],
+    );
+    assert_eq!(image.originator().as_deref(), Some("KNES"));
+
+    // A garbled data designator doesn't hide the originator
+    let garbled = Product::new(
+        "A_TIGq53KNES151200_C_KWIN_20220215120512_060744-4-G16CIRUS.GIF",
+        Vec::new(),
+    );
+    assert_eq!(garbled.wmo_heading(), None);
+    assert_eq!(garbled.originator().as_deref(), Some("KNES"));
+
+    for filename in [
+        "RADALLUS.GIF",
+        "A_TIGQ53_C_KWIN.GIF",
+        "A_TIGQ53knes151200_C.GIF",
+    ] {
+        assert_eq!(
+            Product::new(filename, Vec::new()).originator(),
+            None,
+            "{}",
+            filename
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn converts_json_products() {
+    let product = Product::new("CAPALERT.JSON", &br#"{"event": "Tornado Warning"}"#[..]);
+    let value = serde_json::Value::try_from(product).unwrap();
+    assert_eq!(value["event"], "Tornado Warning");
+
+    let text = Product::new("AFDOKXNY.TXT", &b"hello"[..]);
+    assert_eq!(
+        serde_json::Value::try_from(text).unwrap_err().kind(),
+        emwin_tg::ErrorKind::Json
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn products_round_trip_through_serde() {
+    let filename = "A_WFUS53KLOT202245CCA_C_KWIN_20220220225001_061010-1-TORLOTIL.TXT";
+    let product = Product::new(filename, &b"TORNADO WARNING"[..]);
+    let json = serde_json::to_string(&product).unwrap();
+    let restored: Product = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.filename, product.filename);
+    assert_eq!(restored.contents, product.contents);
+    assert_eq!(restored.received_at, product.received_at);
+
+    let id = product.id().unwrap();
+    let json = serde_json::to_value(&id).unwrap();
+    assert_eq!(json["heading"], "WFUS53 KLOT 202245 CCA");
+    assert_eq!(json["name"], "TORLOTIL");
+    let restored: emwin_tg::product::id::ProductId = serde_json::from_value(json).unwrap();
+    assert_eq!(restored, id);
+
+    let heading: WmoHeading = serde_json::from_str(r#""FXUS63 KDVN 151152""#).unwrap();
+    assert_eq!(heading.cccc(), Some("KDVN"));
+    assert!(serde_json::from_str::<WmoHeading>(r#""""#).is_err());
+}
+
+#[test]
+fn dat_products_are_sniffed_not_text() {
+    let png = Product::new("IMGPAC.DAT", &b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"[..]);
+    assert_eq!(png.mime_type(), Some("image/png"));
+
+    let jpeg = Product::new("IMGPAC.DAT", &b"\xff\xd8\xff\xe0\0\x10JFIF"[..]);
+    assert_eq!(jpeg.mime_type(), Some("image/jpeg"));
+
+    // Even printable contents aren't assumed to be text
+    let unknown = Product::new("SATPAC.DAT", &b"ZCZC\r\r\nhello\r\r\n"[..]);
+    assert_eq!(unknown.mime_type(), Some("application/octet-stream"));
+
+    // The extension wins when it's known
+    assert_eq!(
+        Product::new("AFDOKXNY.TXT", &b"GIF89a"[..]).mime_type(),
+        Some("text/plain")
+    );
+}
+
+#[test]
+fn binary_heuristic() {
+    let text = [
+        &b""[..],
+        b"hello",
+        b"\x01\r\r\n000 \r\r\nFXUS63 KDVN 151152\r\r\nAFDDVN\r\r\n\r\r\n.SYNOPSIS...\r\r\n\x03",
+        b"TEMP 72\xb0F\tFEELS LIKE 75\xb0F\x0c",
+        "Avis de temp\u{ea}te".as_bytes(),
+    ];
+    for contents in text {
+        assert!(
+            !Product::new("A.TXT", contents).is_binary(),
+            "{:?}",
+            contents
+        );
+    }
+
+    // Binary formats without a NUL in sight still have stray control characters
+    let noisy: Vec<u8> = (0..4096u32)
+        .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8 | 1)
+        .collect();
+    assert!(!noisy.contains(&0));
+
+    let binary = [
+        &b"GIF89a\x01\x00\x01\x00\x80\x00\x00"[..],
+        b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR",
+        &noisy,
+    ];
+    for contents in binary {
+        // The extension doesn't matter
+        assert!(
+            Product::new("A.TXT", contents.to_vec()).is_binary(),
+            "{:?}",
+            contents
+        );
+    }
+}
+
+#[test]
+fn partial_wmo_headings() {
+    // (heading, T1T2, CCCC, YYGGgg, BBB)
+    let cases = [
+        (
+            "FXUS63 KDVN 151152",
+            "FX",
+            Some("KDVN"),
+            Some("151152"),
+            None,
+        ),
+        ("FXUS63 KDVN", "FX", Some("KDVN"), None, None),
+        ("FXUS63 KDVN 15#152", "FX", Some("KDVN"), None, None),
+        (
+            "WUUS53 KLOT 2022 CCA",
+            "WU",
+            Some("KLOT"),
+            None,
+            Some("CCA"),
+        ),
+        ("SAUS70 151200", "SA", None, Some("151200"), None),
+        (
+            "SAUS70 K?BC 151200 RRA",
+            "SA",
+            None,
+            Some("151200"),
+            Some("RRA"),
+        ),
+        ("WFUS53", "WF", None, None, None),
+    ];
+    for (text, t1t2, cccc, yygggg, bbb) in cases {
+        let heading: WmoHeading = text.parse().unwrap();
+        assert_eq!(
+            format!("{}{}", heading.t1(), heading.t2()),
+            t1t2,
+            "{}",
+            text
+        );
+        assert_eq!(heading.cccc(), cccc, "{}", text);
+        assert_eq!(heading.yygggg(), yygggg, "{}", text);
+        assert_eq!(heading.bbb(), bbb, "{}", text);
+    }
+
+    // The data designator is required
+    for text in [
+        "FXU563 KDVN 151152",
+        "KDVN 151152",
+        "FXUS63 KDVN 151152 CCA EXTRA",
+    ] {
+        assert!(text.parse::<WmoHeading>().is_err(), "{}", text);
+    }
+
+    // Filenames too
+    let product = Product::new(
+        "A_FXUS63KDVN15#152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+        Vec::new(),
+    );
+    let heading = product.wmo_heading().unwrap();
+    assert_eq!((heading.t1(), heading.t2()), ('F', 'X'));
+    assert_eq!(heading.cccc(), Some("KDVN"));
+    assert_eq!(heading.yygggg(), None);
+    assert_eq!(heading.to_string(), "FXUS63 KDVN");
+}
+
+#[test]
+fn raw_and_normalized_headers() {
+    let product = Product::new(
+        "A_WUUS53KLOT202245_C_KWIN_20220220224512_061002-1-SVRLOTIL.TXT",
+        &b"\x01\r\r\n123 \r\r\nWUUS53 KLOT 202245\r\r\nSVRLOT\r\r\n\r\r\nBULLETIN - IMMEDIATE BROADCAST REQUESTED\r\r\n\x03"[..],
+    );
+
+    let raw = product.raw_header_bytes();
+    assert_eq!(
+        raw,
+        b"\x01\r\r\n123 \r\r\nWUUS53 KLOT 202245\r\r\nSVRLOT\r\r\n"
+    );
+    assert!(product.contents.starts_with(raw));
+
+    let header = product.header();
+    assert_eq!(header, "123\nWUUS53 KLOT 202245\nSVRLOT");
+    assert_ne!(header.as_bytes(), raw);
+
+    // Without a blank line, the whole product is the header
+    let product = Product::new("A.TXT", &b"FXUS63 KDVN 151152\nAFDDVN"[..]);
+    assert_eq!(product.raw_header_bytes(), &product.contents[..]);
+    assert_eq!(product.header(), "FXUS63 KDVN 151152\nAFDDVN");
+
+    // A leading blank line means there's no header
+    let product = Product::new("A.TXT", &b"\r\r\nbody"[..]);
+    assert!(product.raw_header_bytes().is_empty());
+    assert_eq!(product.header(), "");
+}
+
+#[test]
+fn warning_polygons() {
+    let product = Product::new(
+        "A_WUUS53KLOT202245_C_KWIN_20220220224512_061002-1-SVRLOTIL.TXT",
+        &b"\x01\r\r\n123 \r\r\nWUUS53 KLOT 202245\r\r\nSVRLOT\r\r\n\r\r\n\
+ILC031-043-197-202330-\r\r\n\
+/O.NEW.KLOT.SV.W.0021.220220T2245Z-220220T2330Z/\r\r\n\r\r\n\
+* UNTIL 530 PM CST.\r\r\n\r\r\n\
+LAT...LON 4155 8806 4154 8783 4139 8782 4140 8791\r\r\n      4128 8792 4128 8806\r\r\n\
+TIME...MOT...LOC 2245Z 250DEG 35KT 4148 8799\r\r\n\r\r\n\
+$$\r\r\n\x03"[..],
+    );
+    assert_eq!(
+        product.polygon().unwrap(),
+        [
+            (41.55, -88.06),
+            (41.54, -87.83),
+            (41.39, -87.82),
+            (41.40, -87.91),
+            (41.28, -87.92),
+            (41.28, -88.06),
+        ]
+    );
+
+    // Five-digit longitudes, and counting west past the date line
+    let polygon =
+        emwin_tg::product::text::parse_polygon("LAT...LON 3512 10234 3498 10190 3460 10201\n")
+            .unwrap();
+    assert_eq!(polygon[0], (35.12, -102.34));
+    let guam =
+        emwin_tg::product::text::parse_polygon("LAT...LON 1365 21520 1340 21510 1330 21540\n")
+            .unwrap();
+    assert!((guam[0].1 - 144.80).abs() < 1e-9, "{:?}", guam);
+
+    for text in [
+        "no polygon here",
+        "LAT...LON 4155 8806 4154 8783 4139\n",
+        "LAT...LON 4155 8806 4154 8783\n",
+        "LAT...LON 4155 8806 4154 87X3 4139 8782\n",
+    ] {
+        assert_eq!(
+            emwin_tg::product::text::parse_polygon(text),
+            None,
+            "{}",
+            text
+        );
+    }
+}
+
+#[test]
+fn timestamps_from_heading_or_filename() {
+    let date = time::Date::from_calendar_date(2022, time::Month::February, 28).unwrap();
+    let at = |h, m, s| date.with_hms(h, m, s).unwrap().assume_utc();
+
+    // Text products prefer the heading in their contents, resolved against the reference
+    let text = Product::new(
+        "A_FXUS63KDVN280930_C_KWIN_20220228093257_060735-2-AFDDVNIA.TXT",
+        "\x01\r\r\n000 \r\r\nFXUS63 KDVN 280935\r\r\nAFDDVN\r\r\n",
+    );
+    assert_eq!(text.time_from_filename(), Some(at(9, 30, 0)));
+    assert_eq!(text.timestamp(at(12, 0, 0)), Some(at(9, 35, 0)));
+
+    // A heading from the end of last month
+    let late = Product::new("AFDDVNIA.TXT", "FXUS63 KDVN 312359\r\r\nAFDDVN\r\r\n");
+    let expected = time::Date::from_calendar_date(2022, time::Month::January, 31)
+        .unwrap()
+        .with_hms(23, 59, 0)
+        .unwrap()
+        .assume_utc();
+    assert_eq!(late.timestamp(at(0, 5, 0)), Some(expected));
+    assert_eq!(late.time_from_filename(), None);
+
+    // Images fall back to the filename
+    let image = Product::new(
+        "A_TIGQ53KNES280900_C_KWIN_20220228090512_060744-4-G16CIRUS.GIF",
+        &b"GIF89a\x01\x00\x01\x00"[..],
+    );
+    assert_eq!(image.timestamp(at(12, 0, 0)), Some(at(9, 0, 0)));
+
+    // ...even when the issuance time is damaged
+    let damaged = Product::new(
+        "A_TIGQ53KNES28#900_C_KWIN_20220228090512_060744-4-G16CIRUS.GIF",
+        &b"GIF89a\x01\x00\x01\x00"[..],
+    );
+    assert_eq!(damaged.issued_at(), None);
+    assert_eq!(damaged.timestamp(at(12, 0, 0)), Some(at(9, 5, 12)));
+
+    let legacy = Product::new("RADALLUS.GIF", &b"GIF89a"[..]);
+    assert_eq!(legacy.timestamp(at(12, 0, 0)), None);
+}
+
+#[test]
+fn format_table_is_consistent() {
+    use emwin_tg::product::{Category, FORMATS};
+
+    for format in FORMATS {
+        assert_eq!(format.extension, format.extension.to_uppercase());
+
+        // Identified by extension
+        let named = Product::new(format!("PRODUCT.{}", format.extension), Vec::new());
+        assert_eq!(named.format(), Some(format));
+        assert_eq!(named.mime_type(), Some(format.mime_type));
+        assert_eq!(named.category(), Some(format.category));
+        assert_eq!(named.extension(), Some(format.extension));
+
+        // Identified by contents
+        for magic in format.magic {
+            let sniffed = Product::new("PRODUCT.DAT", *magic);
+            assert_eq!(sniffed.format(), Some(format), "{:?}", magic);
+            assert_eq!(sniffed.mime_type(), Some(format.mime_type));
+            assert_eq!(sniffed.category(), Some(format.category));
+            assert_eq!(sniffed.extension(), Some(format.extension));
+        }
+        assert!(!format.magic.is_empty() || format.category == Category::Text);
+    }
+
+    let unknown = Product::new("PRODUCT.DAT", &b"hello"[..]);
+    assert_eq!(unknown.format(), None);
+    assert_eq!(unknown.mime_type(), Some("application/octet-stream"));
+    assert_eq!((unknown.category(), unknown.extension()), (None, None));
+}
+
+#[test]
+fn product_ids() {
+    use emwin_tg::product::id::ProductId;
+
+    let product = Product::new(
+        "A_WFUS53KLOT202245CCA_C_KWIN_20220220225001_061010-1-TORLOTIL.TXT",
+        Vec::new(),
+    );
+    let id = product.id().unwrap();
+    assert_eq!(id.heading().to_string(), "WFUS53 KLOT 202245 CCA");
+    assert_eq!(id.center(), "KWIN");
+    let date = time::Date::from_calendar_date(2022, time::Month::February, 20).unwrap();
+    assert_eq!(
+        id.received_at(),
+        date.with_hms(22, 50, 1).unwrap().assume_utc()
+    );
+    assert_eq!(
+        id.issued_at(),
+        Some(date.with_hms(22, 45, 0).unwrap().assume_utc())
+    );
+    assert_eq!((id.sequence(), id.priority()), (61010, 1));
+    assert_eq!(id.name(), "TORLOTIL");
+    assert_eq!(id.awips_id(), "TORLOT");
+    assert_eq!((id.product_class(), id.station()), ("TOR", "LOT"));
+    assert_eq!(id.extension(), "TXT");
+
+    // Images, and damaged headings
+    let id: ProductId = "A_TIGQ53KNES15#200_C_KWIN_20220215120512_060744-4-G16CIRUS.GIF"
+        .parse()
+        .unwrap();
+    assert_eq!(id.heading().cccc(), Some("KNES"));
+    assert_eq!(id.issued_at(), None);
+    assert_eq!(id.priority(), 4);
+    assert_eq!(id.extension(), "GIF");
+
+    for filename in [
+        "RADALLUS.GIF",
+        "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA",
+        "A_FXUS63KDVN151152_X_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+        "A_FXUS63KDVN151152_C_KWIN_20221315115257_060735-2-AFDDVNIA.TXT",
+        "A_FXUS63KDVN151152_C_KWIN_20220215115257_06O735-2-AFDDVNIA.TXT",
+        "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-22-AFDDVNIA.TXT",
+        "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-.TXT",
+        "A_fxus63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+    ] {
+        assert!(filename.parse::<ProductId>().is_err(), "{}", filename);
+    }
+}
+
+#[test]
+fn wmo_headings_in_contents() {
+    use emwin_tg::product::wmo::BbbKind;
+
+    // (contents, heading, BBB kind)
+    let cases: [(&[u8], &str, Option<BbbKind>); 5] = [
+        (
+            b"\x01\r\r\n000 \r\r\nFXUS63 KDVN 151152\r\r\nAFDDVN\r\r\n",
+            "FXUS63 KDVN 151152",
+            None,
+        ),
+        (
+            b"WUUS53 KLOT 202245 RRA\r\r\nSVRLOT\r\r\n",
+            "WUUS53 KLOT 202245 RRA",
+            Some(BbbKind::Delayed),
+        ),
+        (
+            b"\x01\r\r\n456 \r\r\nWFUS53 KLOT 202245 CCB\r\r\n",
+            "WFUS53 KLOT 202245 CCB",
+            Some(BbbKind::Correction),
+        ),
+        (
+            b"\x01\n789\nFPUS53 KDVN 151152 AAA\n",
+            "FPUS53 KDVN 151152 AAA",
+            Some(BbbKind::Amendment),
+        ),
+        (
+            b"SXUS99 KWBC 151200 PAA\r\r\n",
+            "SXUS99 KWBC 151200 PAA",
+            Some(BbbKind::Segment),
+        ),
+    ];
+    for (contents, expected, kind) in cases {
+        let product = Product::new("A.TXT", contents);
+        let heading = product.wmo_heading_in_contents().unwrap();
+        assert_eq!(heading.to_string(), expected);
+        assert_eq!(heading.bbb_kind(), kind, "{}", expected);
+    }
+
+    let reference = time::Date::from_calendar_date(2022, time::Month::February, 20)
+        .unwrap()
+        .with_hms(23, 0, 0)
+        .unwrap()
+        .assume_utc();
+    let heading = WmoHeading::from_contents(b"WUUS53 KLOT 202245\r\r\n").unwrap();
+    assert_eq!(
+        heading.issued_at(reference),
+        Some(reference - time::Duration::minutes(15))
+    );
+
+    // A lone word isn't a heading, and neither is a binary product
+    for contents in [&b"SVRLOT\r\r\nhello\r\r\n"[..], b"GIF89a\x01\x00\x01\x00"] {
+        assert_eq!(WmoHeading::from_contents(contents), None);
+    }
+}
+
+#[test]
+fn awips_ids_in_contents() {
+    // (contents, category, office)
+    let cases: [(&[u8], &str, &str); 3] = [
+        (
+            b"\x01\r\r\n000 \r\r\nFXUS63 KDVN 151152\r\r\nAFDDVN\r\r\n",
+            "AFD",
+            "DVN",
+        ),
+        (b"WUUS53 KLOT 202245 RRA\r\r\nSVRLOT \r\r\n", "SVR", "LOT"),
+        (b"SRUS55 KWOH 151200\nRR3K\n", "RR3", "K"),
+    ];
+    for (contents, category, office) in cases {
+        let id = Product::new("A.TXT", contents).awips_id().unwrap();
+        assert_eq!((id.category(), id.office()), (category, office));
+        assert_eq!(id.to_string(), format!("{}{}", category, office));
+    }
+
+    // The identifier must immediately follow the heading
+    for contents in [
+        &b"FXUS63 KDVN 151152\r\r\n\r\r\nAFDDVN\r\r\n"[..],
+        b"FXUS63 KDVN 151152\r\r\nAREA FORECAST DISCUSSION\r\r\n",
+        b"AFDDVN\r\r\n",
+    ] {
+        assert_eq!(Product::new("A.TXT", contents).awips_id(), None);
+    }
+}
+
+#[test]
+fn ugc_strings() {
+    use emwin_tg::product::ugc::UgcKind;
+
+    let text = "\
+WWUS53 KLOT 302245\r\r\n\
+SVSLOT\r\r\n\
+\r\r\n\
+ILC031-043-\r\r\n\
+197-302330-\r\r\n\
+\r\r\n\
+INZ001>003-ILZ ALL-\r\r\n\
+\r\r\n\
+LMZ740-INC089-010100-\r\r\n";
+    let product = Product::new(
+        "A_WWUS53KLOT302245_C_KWIN_20220130224803_061010-1-SVSLOTIL.TXT",
+        text,
+    );
+    let ugcs = product.ugc();
+    // The malformed string in between is skipped
+    assert_eq!(ugcs.len(), 2, "{:?}", ugcs);
+
+    let codes = |i: usize| -> Vec<String> { ugcs[i].codes.iter().map(|c| c.to_string()).collect() };
+    assert_eq!(codes(0), ["ILC031", "ILC043", "ILC197"]);
+    assert_eq!(codes(1), ["LMZ740", "INC089"]);
+    assert_eq!(ugcs[1].codes[0].kind(), UgcKind::Zone);
+    assert_eq!(ugcs[1].codes[1].kind(), UgcKind::County);
+
+    // The latest expiration falls in the next month
+    let expires = product.expires_at().unwrap();
+    assert_eq!(expires.month(), time::Month::February);
+    assert_eq!((expires.day(), expires.hour()), (1, 1));
+}
+
+#[test]
+fn vtec_strings() {
+    use emwin_tg::product::vtec::{Action, ProductClass, Significance};
+
+    let text = "\
+WGUS83 KILX 210300\r\r\n\
+FLSILX\r\r\n\
+\r\r\n\
+ILC019-211500-\r\r\n\
+/O.EXT.KILX.FL.W.0003.220221T0600Z-000000T0000Z/\r\r\n\
+/ILLI2.2.ER.220221T0600Z.220222T1800Z.000000T0000Z.NR/\r\r\n\
+\r\r\n\
+ILC113-210315-\r\r\n\
+/T.CAN.KILX.SV.A.0042.000000T0000Z-220221T0315Z/\r\r\n\
+/O.NEW.KILX.TO.W.12.220220T2245Z-220220T2330Z/\r\r\n";
+    let events = Product::new("A.TXT", text).vtec();
+    assert_eq!(events.len(), 2, "{:?}", events);
+
+    let flood = &events[0];
+    assert_eq!(flood.class, ProductClass::Operational);
+    assert_eq!(flood.action, Action::ExtendTime);
+    assert_eq!(
+        (flood.phenomena.as_str(), flood.significance),
+        ("FL", Significance::Warning)
+    );
+    assert_eq!(flood.etn, 3);
+    let begins = time::Date::from_calendar_date(2022, time::Month::February, 21)
+        .unwrap()
+        .with_hms(6, 0, 0)
+        .unwrap()
+        .assume_utc();
+    assert_eq!((flood.begins, flood.ends), (Some(begins), None));
+    let hydrologic = flood.hydrologic.as_ref().unwrap();
+    assert_eq!(hydrologic.nwsli, "ILLI2");
+    assert_eq!(hydrologic.immediate_cause, "ER");
+    assert_eq!(hydrologic.flood_record, "NR");
+    assert_eq!(
+        flood.to_string(),
+        "/O.EXT.KILX.FL.W.0003.220221T0600Z-000000T0000Z/"
+    );
+    assert_eq!(
+        hydrologic.to_string(),
+        "/ILLI2.2.ER.220221T0600Z.220222T1800Z.000000T0000Z.NR/"
+    );
+
+    // The malformed ETN is skipped
+    let watch = &events[1];
+    assert_eq!(watch.class, ProductClass::Test);
+    assert_eq!(watch.action, Action::Cancel);
+    assert_eq!(watch.significance, Significance::Watch);
+    assert_eq!(watch.begins, None);
+    assert_eq!(watch.hydrologic, None);
+}
+
+#[test]
+fn text_encodings() {
+    use emwin_tg::product::text::TextEncoding;
+
+    let cases: &[(&[u8], TextEncoding, &str)] = &[
+        (b"FLOOD WATCH", TextEncoding::Ascii, "FLOOD WATCH"),
+        ("Qu\u{e9}bec".as_bytes(), TextEncoding::Utf8, "Qu\u{e9}bec"),
+        (
+            b"Qu\xe9bec \xb0F",
+            TextEncoding::Latin1,
+            "Qu\u{e9}bec \u{b0}F",
+        ),
+        (
+            b"Qu\x82bec \xf8F",
+            TextEncoding::Cp437,
+            "Qu\u{e9}bec \u{b0}F",
+        ),
+    ];
+    for (contents, encoding, text) in cases {
+        let product = Product::new("FFAOKXNY.TXT", *contents);
+        assert_eq!(product.text_encoding(), *encoding);
+        assert_eq!(product.decode_text(), *text);
+    }
+
+    assert_eq!(TextEncoding::Cp437.decode(b"\xc4\xff"), "\u{2500}\u{a0}");
+}
+
+const CAP_ALERT: &[u8] = br#"<?xml version="1.0" encoding="UTF-8"?>
+<alert xmlns="urn:oasis:names:tc:emergency:cap:1.2">
+  <identifier>urn:oid:2.49.0.1.840.0.1f2e3d</identifier>
+  <sender>w-nws.webmaster@noaa.gov</sender>
+  <sent>2022-02-20T16:45:00-06:00</sent>
+  <status>Actual</status>
+  <msgType>Alert</msgType>
+  <scope>Public</scope>
+  <info>
+    <event>Severe Thunderstorm Warning</event>
+    <urgency>Immediate</urgency>
+    <severity>Severe</severity>
+    <certainty>Observed</certainty>
+    <expires>2022-02-20T17:30:00-06:00</expires>
+    <senderName>NWS Chicago IL</senderName>
+    <description><![CDATA[* UNTIL 530 PM CST & beyond]]></description>
+    <parameter>
+      <valueName>VTEC</valueName>
+      <value>/O.NEW.KLOT.SV.W.0021.220220T2245Z-220220T2330Z/</value>
+    </parameter>
+    <area>
+      <areaDesc>Cook, IL &amp; Will, IL</areaDesc>
+      <polygon>41.55,-88.06 41.54,-87.83 41.39,-87.82 41.55,-88.06</polygon>
+      <geocode>
+        <valueName>UGC</valueName>
+        <value>ILC031</value>
+      </geocode>
+      <geocode>
+        <valueName>UGC</valueName>
+        <value>ILC197</value>
+      </geocode>
+    </area>
+  </info>
+</alert>
+"#;
+
+#[test]
+fn product_kinds() {
+    use emwin_tg::product::ProductKind;
+
+    assert_eq!(
+        Product::new("SVRLOTIL.TXT", &b"WUUS53 KLOT 202245\r\r\n"[..]).kind(),
+        ProductKind::Text
+    );
+    assert_eq!(
+        Product::new("SVRLOTIL.XML", CAP_ALERT).kind(),
+        ProductKind::CapXml
+    );
+    assert_eq!(
+        Product::new("RADALLUS.GIF", &b"GIF89a"[..]).kind(),
+        ProductKind::Image {
+            format: Product::new("RADALLUS.GIF", Vec::new()).format().unwrap()
+        }
+    );
+    assert_eq!(
+        Product::new("ZIPPED.ZIP", &b"PK\x03\x04"[..]).kind(),
+        ProductKind::Unknown
+    );
+}
+
+#[cfg(feature = "cap")]
+#[test]
+fn cap_alerts() {
+    let alert = Product::new("SVRLOTIL.XML", CAP_ALERT).parse_cap().unwrap();
+    assert_eq!(alert.identifier, "urn:oid:2.49.0.1.840.0.1f2e3d");
+    assert_eq!(alert.sent, "2022-02-20T16:45:00-06:00");
+    assert_eq!(
+        (alert.status.as_str(), alert.msg_type.as_str()),
+        ("Actual", "Alert")
+    );
+    assert_eq!(alert.references, None);
+
+    let info = &alert.info[..];
+    assert_eq!(info.len(), 1);
+    assert_eq!(info[0].event, "Severe Thunderstorm Warning");
+    assert_eq!(info[0].severity, "Severe");
+    assert_eq!(info[0].sender_name.as_deref(), Some("NWS Chicago IL"));
+    assert_eq!(
+        info[0].description.as_deref(),
+        Some("* UNTIL 530 PM CST & beyond")
+    );
+    assert_eq!(info[0].parameters[0].0, "VTEC");
+
+    let area = &info[0].areas[0];
+    assert_eq!(area.area_desc, "Cook, IL & Will, IL");
+    assert_eq!(area.polygons[0].len(), 4);
+    assert_eq!(area.polygons[0][0], (41.55, -88.06));
+    assert_eq!(
+        area.geocodes,
+        [
+            ("UGC".to_string(), "ILC031".to_string()),
+            ("UGC".to_string(), "ILC197".to_string()),
+        ]
+    );
+
+    let error = Product::new("A.TXT", &b"<html></html>"[..])
+        .parse_cap()
+        .unwrap_err();
+    assert_eq!(error.kind(), emwin_tg::ErrorKind::Cap);
+    let error = Product::new("A.XML", &b"<alert><identifier>x</alert>"[..])
+        .parse_cap()
+        .unwrap_err();
+    assert_eq!(error.kind(), emwin_tg::ErrorKind::Cap);
+}
+
+#[cfg(feature = "aviation")]
+#[test]
+fn aviation_reports() {
+    use emwin_tg::product::aviation::{Change, Cover, Metar, Pressure, SigmetKind, Visibility};
+    use time::{Date, Month, OffsetDateTime};
+
+    let datetime = |month, day, hour, minute| -> OffsetDateTime {
+        Date::from_calendar_date(2022, month, day)
+            .unwrap()
+            .with_hms(hour, minute, 0)
+            .unwrap()
+            .assume_utc()
+    };
+
+    // A collective with a single METAR header, a corrected report, and a NIL report
+    let product = Product::new(
+        "SAEU31.TXT",
+        "SAEU31 EGRR 201600\r\r\nMETAR\r\r\n\
+         EGLL 201550Z 24015KT 9999 -RA SCT012 BKN020CB 09/07 Q1006=\r\r\n\
+         COR EGKK 201550Z 23012MPS 0800 +TSRA VV002 M01/M03 Q0998=\r\r\n\
+         EGSS 201550Z NIL=\r\r\n",
+    );
+    let metars = product.metars();
+    assert_eq!(metars.len(), 2);
+    assert_eq!(
+        metars[0].conditions.visibility,
+        Some(Visibility::Meters(9999))
+    );
+    assert_eq!(metars[0].conditions.clouds[1].kind.as_deref(), Some("CB"));
+    assert_eq!(metars[0].altimeter, Some(Pressure::Hectopascals(1006)));
+    assert!(metars[1].corrected);
+    assert_eq!(
+        metars[1].raw,
+        "COR EGKK 201550Z 23012MPS 0800 +TSRA VV002 M01/M03 Q0998"
+    );
+    assert_eq!(metars[1].conditions.weather, ["+TSRA"]);
+    assert_eq!(
+        metars[1].conditions.clouds[0].cover,
+        Cover::VerticalVisibility
+    );
+    assert_eq!(metars[1].conditions.clouds[0].base_ft, Some(200));
+    assert_eq!(
+        metars[0].observed_at(datetime(Month::February, 20, 16, 0)),
+        Some(datetime(Month::February, 20, 15, 50))
+    );
+
+    let metar: Metar = "SPECI KORD 201608Z M1/4SM FG VV001 01/01 A2992 RMK AO2="
+        .parse()
+        .unwrap();
+    assert_eq!(
+        metar.conditions.visibility,
+        Some(Visibility::StatuteMiles(0.25))
+    );
+    assert_eq!(metar.remarks.as_deref(), Some("AO2"));
+    assert!("hello world".parse::<Metar>().is_err());
+
+    let tafs = Product::new(
+        "TAFORD.TXT",
+        "FTUS43 KLOT 302320\r\r\nTAFORD\r\r\nTAF\r\r\n\
+         KORD 302320Z 3100/0106 18010KT P6SM SCT050\r\r\n\
+         \x20    PROB30 TEMPO 3102/3106 2SM TSRA BKN030CB\r\r\n\
+         \x20    BECMG 0100/0102 VRB03KT=\r\r\n\
+         TAF KMDW 302320Z 3100/0106 NIL=\r\r\n",
+    )
+    .tafs();
+    assert_eq!(tafs.len(), 1);
+    let taf = &tafs[0];
+    assert!(!taf.amended);
+    let changes: Vec<Change> = taf.groups.iter().map(|group| group.change).collect();
+    assert_eq!(
+        changes,
+        [
+            Change::Base,
+            Change::Probability {
+                percent: 30,
+                temporary: true
+            },
+            Change::Becoming,
+        ]
+    );
+    assert_eq!(taf.groups[1].conditions.weather, ["TSRA"]);
+    assert_eq!(taf.groups[2].conditions.wind.unwrap().speed, 3);
+    // Forecast periods cross into the next month
+    assert_eq!(
+        taf.valid_to.resolve(datetime(Month::January, 30, 23, 20)),
+        Some(datetime(Month::February, 1, 6, 0))
+    );
+
+    let sigmets = Product::new(
+        "WSUS01.TXT",
+        "WSUS01 KKCI 202300\r\r\nSIGE\r\r\n\
+         SIGMET NOVEMBER 2 VALID UNTIL 210300\r\r\n\
+         NY PA\r\r\n\
+         FROM BUF TO ALB TO HAR TO BUF\r\r\n\
+         OCNL SEV TURB BTN FL280 AND FL380.\r\r\n\
+         \r\r\n\
+         KZNY SIGMET ALFA 3 VALID 202300/210300 KKCI-\r\r\n\
+         NEW YORK OCEANIC FIR FRQ TS OBS AT 2245Z WI N3700 W06500 - N3500 W06300.\r\r\n\
+         TOP FL450. MOV E 15KT. NC.=\r\r\n",
+    )
+    .sigmets();
+    assert_eq!(sigmets.len(), 2);
+    assert_eq!(sigmets[0].kind, SigmetKind::NonConvective);
+    assert_eq!(sigmets[0].id, "NOVEMBER 2");
+    assert_eq!(sigmets[0].valid_until_day, Some(21));
+    assert_eq!(sigmets[0].text.lines().count(), 3);
+    assert_eq!(sigmets[1].kind, SigmetKind::International);
+    assert_eq!(sigmets[1].region.as_deref(), Some("KZNY"));
+    assert_eq!(sigmets[1].valid_from.unwrap().hour, 23);
+    assert!(sigmets[1].text.ends_with("NC."));
+    assert_eq!(
+        sigmets[1].valid_until(datetime(Month::February, 20, 23, 0)),
+        Some(datetime(Month::February, 21, 3, 0))
+    );
+}
+
+#[test]
+fn retransmissions() {
+    let named = |bbb: &str| {
+        Product::new(
+            format!(
+                "A_WFUS53KLOT202245{}_C_KWIN_20220220224512_061002-1-TORLOTIL.TXT",
+                bbb
+            ),
+            Vec::new(),
+        )
+    };
+    assert!(named("RRA").is_retransmission());
+    assert!(named("RRB").is_retransmission());
+    assert!(!named("").is_retransmission());
+    assert!(!named("CCA").is_retransmission());
+    assert!(!named("AAA").is_retransmission());
+    assert!(!Product::new("TORLOTIL.TXT", Vec::new()).is_retransmission());
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "sqlite")]
+
+use emwin_tg::sink::SqliteSink;
+use emwin_tg::Product;
+
+#[test]
+fn stores_and_dedups_products() {
+    let mut sink = SqliteSink::open_in_memory().unwrap();
+
+    let afd = Product::new(
+        "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+        "Area Forecast Discussion",
+    );
+    let correction = Product::new(
+        "A_FXUS63KDVN151152CCA_C_KWIN_20220215120301_060740-2-AFDDVNIA.TXT",
+        "Area Forecast Discussion, corrected",
+    );
+    let image = Product::new("RADALLUS.GIF", vec![0x47, 0x49, 0x46]);
+
+    assert!(sink.insert(&afd).unwrap());
+    assert!(sink.insert(&correction).unwrap());
+    assert!(sink.insert(&image).unwrap());
+
+    // The same bulletin arriving in a later archive under a new filename is a duplicate
+    let resent = Product::new(
+        "A_FXUS63KDVN151152_C_KWIN_20220215115901_060799-2-AFDDVNIA.TXT",
+        "Area Forecast Discussion",
+    );
+    assert!(!sink.insert(&resent).unwrap());
+
+    type Row = (String, Option<String>, Option<String>, Option<i64>, Vec<u8>);
+    let rows: Vec<Row> = sink
+        .connection()
+        .prepare(
+            "SELECT filename, wmo_heading, pil, issued_at, contents
+                FROM products ORDER BY rowid",
+        )
+        .unwrap()
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(rows.len(), 3);
+
+    assert_eq!(rows[0].0, afd.filename);
+    assert_eq!(rows[0].1.as_deref(), Some("FXUS63 KDVN 151152"));
+    assert_eq!(rows[0].2.as_deref(), Some("AFDDVNIA"));
+    assert_eq!(rows[0].3, Some(1644925920));
+    assert_eq!(rows[0].4, afd.contents);
+
+    assert_eq!(rows[1].1.as_deref(), Some("FXUS63 KDVN 151152 CCA"));
+
+    assert_eq!(rows[2].0, "RADALLUS.GIF");
+    assert_eq!(rows[2].1, None);
+    assert_eq!(rows[2].2, None);
+    assert_eq!(rows[2].3, None);
+}
+
+#[test]
+fn headings_repeat_monthly() {
+    let mut sink = SqliteSink::open_in_memory().unwrap();
+
+    // DDHHMM is the same, but these were issued a month apart
+    let february = Product::new(
+        "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+        "Area Forecast Discussion",
+    );
+    let march = Product::new(
+        "A_FXUS63KDVN151152_C_KWIN_20220315115257_061735-2-AFDDVNIA.TXT",
+        "Area Forecast Discussion",
+    );
+    assert!(sink.insert(&february).unwrap());
+    assert!(sink.insert(&march).unwrap());
+    assert!(!sink.insert(&march).unwrap());
+
+    let keys: Vec<String> = sink
+        .connection()
+        .prepare("SELECT bulletin_key FROM products ORDER BY rowid")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        keys,
+        [
+            "2022-02 FXUS63 KDVN 151152 AFDDVNIA",
+            "2022-03 FXUS63 KDVN 151152 AFDDVNIA",
+        ]
+    );
+}
@@ -0,0 +1,980 @@
+mod common;
+
+use emwin_tg::product::ugc::UgcCode;
+use emwin_tg::StreamEvent;
+use futures::{FutureExt, StreamExt};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+fn numbered_archives() -> (Vec<Vec<u8>>, Vec<String>) {
+    let names: Vec<String> = (0..20).map(|i| format!("PRODUCT{:02}.TXT", i)).collect();
+    let archives = names
+        .chunks(7)
+        .map(|chunk| {
+            let members: Vec<(&str, &[u8])> = chunk
+                .iter()
+                .map(|name| (name.as_str(), name.as_bytes()))
+                .collect();
+            common::archive(&members)
+        })
+        .collect();
+    (archives, names)
+}
+
+#[tokio::test]
+async fn dropped_next_futures_lose_nothing() {
+    let (archives, names) = numbered_archives();
+    let mut stream = emwin_tg::Stream::from_source(common::source(archives));
+
+    // Poll each `next()` future at most once, dropping it if it isn't ready, and yield to the
+    // runtime in between
+    let mut delivered = BTreeMap::new();
+    loop {
+        match stream.next().now_or_never() {
+            Some(Some(product)) => *delivered.entry(product.unwrap().filename).or_insert(0) += 1,
+            Some(None) => break,
+            None => tokio::task::yield_now().await,
+        }
+    }
+
+    assert_eq!(delivered.keys().cloned().collect::<Vec<_>>(), names);
+    assert!(delivered.values().all(|count| *count == 1));
+}
+
+#[tokio::test]
+async fn cancelled_event_polls_lose_nothing() {
+    let (archives, names) = numbered_archives();
+    let mut events = emwin_tg::Stream::from_source(common::source(archives))
+        .events()
+        .heartbeat(Duration::from_millis(1));
+
+    // Race every `next()` against an already-elapsed timer, cancelling whichever loses
+    let mut delivered = BTreeMap::new();
+    loop {
+        tokio::select! {
+            event = events.next() => match event {
+                Some(Ok(StreamEvent::Product(product))) => {
+                    *delivered.entry(product.filename).or_insert(0) += 1
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => panic!("{}", e),
+                None => break,
+            },
+            _ = tokio::time::sleep(Duration::ZERO) => {}
+        }
+    }
+
+    assert_eq!(delivered.keys().cloned().collect::<Vec<_>>(), names);
+    assert!(delivered.values().all(|count| *count == 1));
+}
+
+#[tokio::test]
+async fn redelivers_after_absence() {
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+
+    // The same product appears three times: immediately, shortly after, and after a long gap
+    let source = futures::stream::iter([0, 10, 200])
+        .then(move |delay| {
+            let archive = archive.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                Ok(bytes::Bytes::from(archive))
+            }
+        })
+        .boxed();
+
+    let products: Vec<_> = emwin_tg::Stream::from_source(source)
+        .redeliver_after(Duration::from_millis(100))
+        .map(|result| result.unwrap().filename)
+        .collect()
+        .await;
+    assert_eq!(products, ["AFDOKXNY.TXT", "AFDOKXNY.TXT"]);
+}
+
+#[tokio::test]
+async fn dedup_windows_forget_old_filenames() {
+    let first = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let other = common::archive(&[("ZFPOKXNY.TXT", b"hello")]);
+
+    // The other archive arrives after the window, culling the first product, which reappears
+    let archives = [(first.clone(), 0), (other, 200), (first, 10)];
+    let source = futures::stream::iter(archives)
+        .then(|(archive, delay)| async move {
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            Ok(bytes::Bytes::from(archive))
+        })
+        .boxed();
+
+    let products: Vec<_> = emwin_tg::Stream::from_source(source)
+        .dedup_window(Duration::from_millis(100))
+        .map(|result| result.unwrap().filename)
+        .collect()
+        .await;
+    assert_eq!(products, ["AFDOKXNY.TXT", "ZFPOKXNY.TXT", "AFDOKXNY.TXT"]);
+}
+
+#[tokio::test]
+async fn dedup_windows_follow_enabled_archives() {
+    use emwin_tg::Source;
+
+    let client = reqwest::Client::new();
+    let hours = |hours: u64| Some(Duration::from_secs(hours * 3600));
+    assert_eq!(
+        emwin_tg::TextSource::from(client.clone()).dedup_window(),
+        hours(6)
+    );
+    assert_eq!(
+        emwin_tg::CombinedSource::from(client.clone()).dedup_window(),
+        hours(6)
+    );
+    assert_eq!(
+        emwin_tg::TextSource::from(client.clone())
+            .realtime_only()
+            .dedup_window(),
+        Some(Duration::from_secs(12 * 60))
+    );
+    assert_eq!(
+        emwin_tg::ImageSource::from(client)
+            .realtime_only()
+            .dedup_window(),
+        Some(Duration::from_secs(30 * 60))
+    );
+    assert_eq!(common::source(Vec::new()).dedup_window(), None);
+}
+
+#[tokio::test]
+async fn suppresses_duplicates_by_default() {
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello")]);
+    let archives = vec![archive.clone(), archive.clone(), archive];
+
+    let products: Vec<_> = emwin_tg::Stream::from_source(common::source(archives))
+        .map(|result| result.unwrap().filename)
+        .collect()
+        .await;
+    assert_eq!(products, ["AFDOKXNY.TXT"]);
+}
+
+#[tokio::test]
+async fn dedup_capacity_evicts_least_recently_seen() {
+    let archives = ["A.TXT", "B.TXT", "C.TXT", "B.TXT", "A.TXT"]
+        .iter()
+        .map(|name| common::archive(&[(name, b"hello")]))
+        .collect();
+
+    let mut stream = emwin_tg::Stream::from_source(common::source(archives)).dedup_capacity(2);
+    let mut products = Vec::new();
+    while let Some(result) = stream.next().await {
+        products.push(result.unwrap().filename);
+        assert!(stream.recently_seen_filenames().len() <= 2);
+    }
+
+    // A was evicted when C arrived, so it's new again; B was retained
+    assert_eq!(products, ["A.TXT", "B.TXT", "C.TXT", "A.TXT"]);
+
+    let mut remembered: Vec<String> = stream
+        .recently_seen_filenames()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    remembered.sort();
+    assert_eq!(remembered, ["A.TXT", "B.TXT"]);
+}
+
+#[tokio::test]
+async fn filter_map_product_drops_unmapped_products() {
+    let archives = vec![
+        common::archive(&[("ONE.TXT", b"1"), ("SKIP.TXT", b"x"), ("TWO.TXT", b"2")]),
+        b"not a zip".to_vec(),
+        common::archive(&[("THREE.TXT", b"3")]),
+    ];
+
+    let results: Vec<_> = emwin_tg::Stream::from_source(common::source(archives))
+        .filter_map_product(|product| {
+            std::str::from_utf8(&product.contents)
+                .ok()?
+                .parse::<u32>()
+                .ok()
+        })
+        .collect()
+        .await;
+
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0].as_ref().unwrap(), &1);
+    assert_eq!(results[1].as_ref().unwrap(), &2);
+    assert!(matches!(results[2], Err(emwin_tg::Error::ArchiveFormat(_))));
+    assert_eq!(results[3].as_ref().unwrap(), &3);
+}
+
+#[tokio::test]
+async fn sorted_within_orders_by_issuance() {
+    fn name(ddhhmm: &str, seq: u32) -> String {
+        format!(
+            "A_FXUS63KDVN{}_C_KWIN_20220215{}00_{:06}-2-AFDDVNIA.TXT",
+            ddhhmm,
+            &ddhhmm[2..],
+            seq
+        )
+    }
+
+    let batches = [
+        vec![name("151205", 1), name("151150", 2)],
+        vec![name("151210", 3)],
+        vec![name("151140", 4), name("151200", 5)],
+    ];
+    let archives = batches
+        .iter()
+        .map(|batch| {
+            let members: Vec<(&str, &[u8])> =
+                batch.iter().map(|name| (name.as_str(), &b""[..])).collect();
+            common::archive(&members)
+        })
+        .collect();
+
+    // Keep the source open, so products are released by the delay rather than by the end of input
+    let source = common::source(archives)
+        .chain(futures::stream::pending())
+        .boxed();
+    let issued: Vec<_> = emwin_tg::Stream::from_source(source)
+        .sorted_within(Duration::from_millis(50))
+        .take(5)
+        .map(|result| result.unwrap().issued_at().unwrap())
+        .collect()
+        .await;
+
+    assert_eq!(issued.len(), 5);
+    assert!(
+        issued.windows(2).all(|pair| pair[0] <= pair[1]),
+        "{:?}",
+        issued
+    );
+}
+
+#[tokio::test]
+async fn buffered_prefetches_while_consumer_is_busy() {
+    const ARCHIVES: usize = 8;
+    const DELAY: Duration = Duration::from_millis(50);
+
+    // A source which takes a while to produce each archive, sometimes a few at once
+    fn bursty_source() -> futures::stream::BoxStream<'static, Result<bytes::Bytes, emwin_tg::Error>>
+    {
+        futures::stream::iter(0..ARCHIVES)
+            .then(|i| async move {
+                if i % 2 == 0 {
+                    tokio::time::sleep(DELAY * 2).await;
+                }
+                let name = format!("PRODUCT{}.TXT", i);
+                Ok(bytes::Bytes::from(common::archive(&[(&name, b"hello")])))
+            })
+            .boxed()
+    }
+
+    async fn consume<St: futures::Stream<Item = Result<emwin_tg::Product, emwin_tg::Error>>>(
+        stream: St,
+    ) -> Duration {
+        let start = tokio::time::Instant::now();
+        futures::pin_mut!(stream);
+        let mut count = 0;
+        while let Some(result) = stream.next().await {
+            result.unwrap();
+            count += 1;
+            // Spend a while on each product
+            tokio::time::sleep(DELAY).await;
+        }
+        assert_eq!(count, ARCHIVES);
+        start.elapsed()
+    }
+
+    let unbuffered = consume(emwin_tg::Stream::from_source(bursty_source())).await;
+    let buffered = consume(emwin_tg::Stream::from_source(bursty_source()).buffered(4)).await;
+
+    // Unbuffered, production and consumption alternate; buffered, they overlap
+    assert!(
+        unbuffered >= DELAY * ARCHIVES as u32 * 2,
+        "{:?}",
+        unbuffered
+    );
+    assert!(
+        buffered < unbuffered * 3 / 4,
+        "{:?} vs {:?}",
+        buffered,
+        unbuffered
+    );
+}
+
+#[tokio::test]
+async fn filters_duplicates_across_satellites() {
+    let east = common::archive(&[
+        (
+            "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+            b"discussion",
+        ),
+        ("RADALLUS.GIF", b"GIF89a"),
+    ]);
+    let west = common::archive(&[
+        (
+            "A_FXUS63KDVN151152_C_KWIN_20220215115301_120112-2-AFDDVNIA.TXT",
+            b"discussion",
+        ),
+        (
+            "A_FXUS63KDVN151152CCA_C_KWIN_20220215120004_120130-2-AFDDVNIA.TXT",
+            b"corrected discussion",
+        ),
+        ("RADALLUS.GIF", b"GIF89a"),
+    ]);
+
+    let products: Vec<_> = emwin_tg::Stream::from_source(common::source(vec![east, west]))
+        .filter_duplicates_across_satellites()
+        .map(|result| result.unwrap().filename)
+        .collect()
+        .await;
+    assert_eq!(
+        products,
+        [
+            "A_FXUS63KDVN151152_C_KWIN_20220215115257_060735-2-AFDDVNIA.TXT",
+            "RADALLUS.GIF",
+            "A_FXUS63KDVN151152CCA_C_KWIN_20220215120004_120130-2-AFDDVNIA.TXT",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn reports_archive_churn() {
+    let names: Vec<String> = (0..12).map(|i| format!("PRODUCT{:02}.TXT", i)).collect();
+    let archive = |range: std::ops::Range<usize>| {
+        let members: Vec<(&str, &[u8])> = names[range]
+            .iter()
+            .map(|name| (name.as_str(), &b"hello"[..]))
+            .collect();
+        common::archive(&members)
+    };
+
+    // The archive is regenerated with two members aged out and three added
+    let mut stream =
+        emwin_tg::Stream::from_source(common::source(vec![archive(0..9), archive(2..12)]));
+    for _ in 0..9 {
+        stream.next().await.unwrap().unwrap();
+    }
+    let first = stream.last_archive_stats().unwrap();
+    assert_eq!((first.new_members, first.total_members), (9, 9));
+    assert_eq!(first.churn_ratio(), 1.0);
+
+    for _ in 0..3 {
+        stream.next().await.unwrap().unwrap();
+    }
+    let second = stream.last_archive_stats().unwrap();
+    assert_eq!((second.new_members, second.total_members), (3, 10));
+    assert!((second.churn_ratio() - 0.3).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn quiet_cycles_log_at_debug() {
+    common::logged();
+
+    let names: Vec<String> = (0..13).map(|i| format!("QUIET{:02}.TXT", i)).collect();
+    let members: Vec<(&str, &[u8])> = names
+        .iter()
+        .map(|name| (name.as_str(), &b"hello"[..]))
+        .collect();
+    let archive = common::archive(&members);
+    let last = common::archive(&[("QUIET13.TXT", b"hello")]);
+
+    // The repeated archive has nothing new
+    let mut stream =
+        emwin_tg::Stream::from_source(common::source(vec![archive.clone(), archive, last]));
+    for _ in 0..14 {
+        stream.next().await.unwrap().unwrap();
+    }
+
+    let logged = common::logged_with_levels();
+    let level_of = |message: &str| {
+        logged
+            .iter()
+            .find(|(_, m)| m.starts_with(message))
+            .map(|(level, _)| *level)
+    };
+    assert_eq!(
+        level_of("13 of 13 products are new"),
+        Some(log::Level::Info)
+    );
+    assert_eq!(
+        level_of("0 of 13 products are new"),
+        Some(log::Level::Debug)
+    );
+}
+
+#[tokio::test]
+async fn idle_timeout_reports_stalls_and_continues() {
+    // One archive, then a long silence, then another archive
+    let first = common::archive(&[("FIRST.TXT", b"hello")]);
+    let second = common::archive(&[("SECOND.TXT", b"hello")]);
+    let source = futures::stream::iter(vec![Ok(bytes::Bytes::from(first))])
+        .chain(futures::stream::once(async move {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            Ok(bytes::Bytes::from(second))
+        }))
+        .boxed();
+
+    let results: Vec<_> = emwin_tg::Stream::from_source(source)
+        .with_idle_timeout(Duration::from_millis(100))
+        .collect()
+        .await;
+
+    let summary: Vec<String> = results
+        .iter()
+        .map(|result| match result {
+            Ok(product) => product.filename.clone(),
+            Err(e) => {
+                assert_eq!(e.kind(), emwin_tg::ErrorKind::Stalled);
+                "stalled".to_string()
+            }
+        })
+        .collect();
+    assert_eq!(summary, ["FIRST.TXT", "stalled", "stalled", "SECOND.TXT"]);
+}
+
+#[tokio::test]
+async fn into_inner_and_from_parts_round_trip() {
+    let archives = vec![
+        common::archive(&[("ONE.TXT", b"1"), ("TWO.TXT", b"2"), ("THREE.TXT", b"3")]),
+        common::archive(&[("TWO.TXT", b"2"), ("FOUR.TXT", b"4")]),
+    ];
+
+    // Take one product, leaving two buffered and the second archive unread
+    let mut stream = emwin_tg::Stream::from_source(common::source(archives));
+    assert_eq!(stream.next().await.unwrap().unwrap().filename, "ONE.TXT");
+
+    let (source, state, buffered) = stream.into_inner();
+    let names: Vec<_> = buffered
+        .iter()
+        .map(|result| result.as_ref().unwrap().filename.clone())
+        .collect();
+    assert_eq!(names, ["THREE.TXT", "TWO.TXT"]);
+
+    // The reassembled stream yields the buffered products, and still knows what it's seen
+    let rest: Vec<_> = emwin_tg::Stream::from_parts(source, state, buffered)
+        .map(|result| result.unwrap().filename)
+        .collect()
+        .await;
+    assert_eq!(rest, ["THREE.TXT", "TWO.TXT", "FOUR.TXT"]);
+}
+
+#[tokio::test]
+async fn filter_new_or_corrected_drops_retransmissions() {
+    let svr = |heading: &str, body: &str| {
+        format!(
+            "\x01\r\r\n123 \r\r\n{}\r\r\nSVRLOT\r\r\n\r\r\n{}\r\r\n\x03",
+            heading, body
+        )
+        .into_bytes()
+    };
+
+    let original = svr(
+        "WUUS53 KLOT 202245",
+        "SEVERE THUNDERSTORM WARNING UNTIL 530 PM",
+    );
+    let resent = original.clone();
+    let delayed = svr(
+        "WUUS53 KLOT 202245 RRA",
+        "SEVERE THUNDERSTORM WARNING UNTIL 530 PM",
+    );
+    let corrected = svr(
+        "WUUS53 KLOT 202245 CCA",
+        "SEVERE THUNDERSTORM WARNING UNTIL 545 PM",
+    );
+    let corrected_again = corrected.clone();
+
+    let archives = vec![
+        common::archive(&[(
+            "A_WUUS53KLOT202245_C_KWIN_20220220224512_061002-1-SVRLOTIL.TXT",
+            &original,
+        )]),
+        // The same bulletin, from the other satellite
+        common::archive(&[(
+            "A_WUUS53KLOT202245_C_KWIN_20220220224519_120440-1-SVRLOTIL.TXT",
+            &resent,
+        )]),
+        common::archive(&[(
+            "A_WUUS53KLOT202245RRA_C_KWIN_20220220225102_061020-1-SVRLOTIL.TXT",
+            &delayed,
+        )]),
+        common::archive(&[(
+            "A_WUUS53KLOT202245CCA_C_KWIN_20220220225530_061031-1-SVRLOTIL.TXT",
+            &corrected,
+        )]),
+        common::archive(&[(
+            "A_WUUS53KLOT202245CCA_C_KWIN_20220220225537_120461-1-SVRLOTIL.TXT",
+            &corrected_again,
+        )]),
+    ];
+
+    let products: Vec<_> = emwin_tg::Stream::from_source(common::source(archives))
+        .filter_new_or_corrected()
+        .map(|result| result.unwrap().filename)
+        .collect()
+        .await;
+    assert_eq!(
+        products,
+        [
+            "A_WUUS53KLOT202245_C_KWIN_20220220224512_061002-1-SVRLOTIL.TXT",
+            "A_WUUS53KLOT202245CCA_C_KWIN_20220220225530_061031-1-SVRLOTIL.TXT",
+        ]
+    );
+}
+
+#[derive(Default)]
+struct Recorder(std::sync::Mutex<Vec<String>>);
+
+impl emwin_tg::Observer for Recorder {
+    fn on_fetch(&self, archive: &bytes::Bytes) {
+        let event = format!("fetch {}", !archive.is_empty());
+        self.0.lock().unwrap().push(event);
+    }
+
+    fn on_cycle(&self, stats: &emwin_tg::ArchiveStats) {
+        let event = format!("cycle {}/{}", stats.new_members, stats.total_members);
+        self.0.lock().unwrap().push(event);
+    }
+
+    fn on_product(&self, product: &emwin_tg::Product) {
+        let event = format!("product {}", product.filename);
+        self.0.lock().unwrap().push(event);
+    }
+
+    fn on_error(&self, error: &emwin_tg::Error) {
+        let event = format!("error {:?}", error.kind());
+        self.0.lock().unwrap().push(event);
+    }
+}
+
+#[tokio::test]
+async fn observers_see_fetches_cycles_products_and_errors() {
+    let first = common::archive(&[("FIRST.TXT", b"hello"), ("SECOND.TXT", b"hello")]);
+    let second = common::archive(&[("SECOND.TXT", b"hello"), ("THIRD.TXT", b"hello")]);
+    let source = futures::stream::iter(vec![
+        Ok(bytes::Bytes::from(first)),
+        Err(emwin_tg::Error::ArchiveMember("BAD.ZIP".into())),
+        Ok(bytes::Bytes::from(second)),
+    ])
+    .boxed();
+
+    let recorder = std::sync::Arc::new(Recorder::default());
+    let stream = emwin_tg::Stream::from_source(source).observe(recorder.clone());
+    let results: Vec<_> = stream.collect().await;
+    assert_eq!(results.len(), 4);
+
+    assert_eq!(
+        *recorder.0.lock().unwrap(),
+        [
+            "fetch true",
+            "cycle 2/2",
+            "product FIRST.TXT",
+            "product SECOND.TXT",
+            "error ArchiveMember",
+            "fetch true",
+            "cycle 1/2",
+            "product THIRD.TXT",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn replayed_archives_are_not_yielded() {
+    let captured = vec![
+        bytes::Bytes::from(common::archive(&[("FIRST.TXT", b"hello")])),
+        bytes::Bytes::from_static(b"not a zip"),
+        bytes::Bytes::from(common::archive(&[
+            ("FIRST.TXT", b"hello"),
+            ("SECOND.TXT", b"hello"),
+        ])),
+    ];
+    let live = common::archive(&[
+        ("FIRST.TXT", b"hello"),
+        ("SECOND.TXT", b"hello"),
+        ("THIRD.TXT", b"hello"),
+    ]);
+
+    let stream = emwin_tg::Stream::from_source(common::source(vec![live])).replay_from(captured);
+    let filenames: Vec<String> = stream
+        .map(|result| result.unwrap().filename)
+        .collect()
+        .await;
+    assert_eq!(filenames, ["THIRD.TXT"]);
+}
+
+#[tokio::test]
+async fn replay_seeds_bulletin_dedup() {
+    let heading = "\x01\r\r\n123 \r\r\nWUUS53 KLOT 202245\r\r\nSVRLOT\r\r\n\r\r\nWARNING\r\r\n";
+    let captured = common::archive(&[(
+        "A_WUUS53KLOT202245_C_KWIN_20220220224512_061002-1-SVRLOTIL.TXT",
+        heading.as_bytes(),
+    )]);
+    let live = common::archive(&[(
+        "A_WUUS53KLOT202245_C_KWIN_20220220224712_061003-1-SVRLOTIL.TXT",
+        heading.as_bytes(),
+    )]);
+
+    let stream = emwin_tg::Stream::from_source(common::source(vec![live]))
+        .filter_new_or_corrected()
+        .replay_from(vec![bytes::Bytes::from(captured)]);
+    let results: Vec<_> = stream.collect().await;
+    assert!(results.is_empty(), "{:?}", results);
+}
+
+#[tokio::test]
+async fn filters_by_awips_office_heading_and_filename() {
+    let names = [
+        "A_WFUS53KLOT202245_C_KWIN_20220220225001_061010-1-TORLOTIL.TXT",
+        "A_WUUS53KLOT202245_C_KWIN_20220220224512_061002-1-SVRLOTIL.TXT",
+        "A_WUUS51KOKX202245_C_KWIN_20220220224512_061003-1-SVROKXNY.TXT",
+        "A_FXUS61KOKX151152_C_KWIN_20220215115257_060735-2-AFDOKXNY.TXT",
+        "SVRDVNIA.TXT",
+        "RADALLUS.GIF",
+    ];
+    let members: Vec<(&str, &[u8])> = names.iter().map(|name| (*name, &b"hello"[..])).collect();
+    let archive = common::archive(&members);
+
+    // Products arrive in no particular order
+    let filtered = |configure: fn(emwin_tg::Stream<_>) -> emwin_tg::Stream<_>| {
+        let stream = configure(emwin_tg::Stream::from_source(common::source(vec![
+            archive.clone()
+        ])));
+        async move {
+            let mut indices: Vec<usize> = stream
+                .map(|result| {
+                    let filename = result.unwrap().filename;
+                    names.iter().position(|name| *name == filename).unwrap()
+                })
+                .collect()
+                .await;
+            indices.sort_unstable();
+            indices
+        }
+    };
+
+    assert_eq!(
+        filtered(|s| s.filter_awips(["TOR", "SVR"])).await,
+        [0, 1, 2, 4]
+    );
+    assert_eq!(filtered(|s| s.filter_awips(["tor"])).await, [0]);
+    assert_eq!(filtered(|s| s.filter_office("KOKX")).await, [2, 3]);
+    assert_eq!(filtered(|s| s.filter_heading("WUUS5")).await, [1, 2]);
+    assert_eq!(
+        filtered(|s| s.filter_filename("a_*-?-*il.txt")).await,
+        [0, 1]
+    );
+
+    // Filters combine
+    let both = filtered(|s| s.filter_awips(["SVR"]).filter_office("KLOT")).await;
+    assert_eq!(both, [1]);
+
+    assert!(filtered(|s| s.filter_filename("*.JPG")).await.is_empty());
+}
+
+#[tokio::test]
+async fn filters_by_state_wfo_and_zone() {
+    let products: [(&str, &[u8]); 4] = [
+        (
+            "SVRMPXMN.TXT",
+            b"WUUS53 KMPX 202245\r\r\nSVRMPX\r\r\n\r\r\nMNC053-123-210300-\r\r\n",
+        ),
+        (
+            "SVSARXWI.TXT",
+            b"WWUS53 KARX 202245\r\r\nSVSARX\r\r\n\r\r\nMNZ096-WIZ032-210300-\r\r\n",
+        ),
+        (
+            "WSWMPXMN.TXT",
+            b"WWUS43 KMPX 202245\r\r\nWSWMPX\r\r\n\r\r\nMNZALL-210300-\r\r\n",
+        ),
+        ("AFDOKXNY.TXT", b"FXUS61 KOKX 151152\r\r\nAFDOKX\r\r\n"),
+    ];
+    let archive = common::archive(&products);
+
+    let filtered = |configure: Box<dyn FnOnce(emwin_tg::Stream<_>) -> emwin_tg::Stream<_>>| {
+        let stream = configure(emwin_tg::Stream::from_source(common::source(vec![
+            archive.clone()
+        ])));
+        async move {
+            let mut indices: Vec<usize> = stream
+                .map(|result| {
+                    let filename = result.unwrap().filename;
+                    products
+                        .iter()
+                        .position(|(name, _)| *name == filename)
+                        .unwrap()
+                })
+                .collect()
+                .await;
+            indices.sort_unstable();
+            indices
+        }
+    };
+
+    assert_eq!(filtered(Box::new(|s| s.filter_wfo("MPX"))).await, [0, 2]);
+    assert_eq!(filtered(Box::new(|s| s.filter_wfo("KOKX"))).await, [3]);
+    assert_eq!(
+        filtered(Box::new(|s| s.filter_states(["MN", "WI"]))).await,
+        [0, 1, 2]
+    );
+    assert_eq!(filtered(Box::new(|s| s.filter_states(["wi"]))).await, [1]);
+
+    let zone: UgcCode = "MNZ096".parse().unwrap();
+    assert_eq!(
+        filtered(Box::new(move |s| s.filter_zones([zone]))).await,
+        [1, 2]
+    );
+    let county: UgcCode = "MNC123".parse().unwrap();
+    assert_eq!(
+        filtered(Box::new(move |s| s.filter_zones([county]))).await,
+        [0]
+    );
+}
+
+#[tokio::test]
+async fn state_snapshots_survive_restarts() {
+    let archive = common::archive(&[("FIRST.TXT", b"hello"), ("SECOND.TXT", b"hello")]);
+    let mut stream = emwin_tg::Stream::from_source(common::source(vec![archive.clone()]));
+    while stream.next().await.is_some() {}
+
+    let snapshot = stream.snapshot_state();
+    let filenames: Vec<&String> = snapshot.filenames.keys().collect();
+    assert_eq!(filenames, ["FIRST.TXT", "SECOND.TXT"]);
+    let now = std::time::SystemTime::now();
+    for seen_at in snapshot.filenames.values() {
+        assert!(now.duration_since(*seen_at).unwrap() < Duration::from_secs(5));
+    }
+
+    #[cfg(feature = "serde")]
+    let snapshot = {
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let restored: emwin_tg::StateSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, snapshot);
+        restored
+    };
+
+    // Entries too old to remember are dropped on restore
+    let mut snapshot = snapshot;
+    snapshot
+        .filenames
+        .insert("STALE.TXT".into(), now - Duration::from_secs(7 * 60 * 60));
+
+    let restarted = common::archive(&[
+        ("FIRST.TXT", b"hello"),
+        ("SECOND.TXT", b"hello"),
+        ("STALE.TXT", b"hello"),
+        ("THIRD.TXT", b"hello"),
+    ]);
+    let stream =
+        emwin_tg::Stream::from_source(common::source(vec![restarted])).with_state(snapshot);
+    let mut filenames: Vec<String> = stream
+        .map(|result| result.unwrap().filename)
+        .collect()
+        .await;
+    filenames.sort();
+    assert_eq!(filenames, ["STALE.TXT", "THIRD.TXT"]);
+}
+
+#[tokio::test]
+async fn overflow_policies_bound_the_buffer() {
+    use emwin_tg::OverflowPolicy;
+
+    for (policy, expected) in [
+        (OverflowPolicy::DropOldest, 15),
+        (OverflowPolicy::DropNewest, 15),
+        (OverflowPolicy::Backpressure, 20),
+    ] {
+        // Archives of 7, 7, and 6 products, each trimmed to 5
+        let (archives, names) = numbered_archives();
+        let products: Vec<_> = emwin_tg::Stream::from_source(common::source(archives))
+            .max_buffered(5, policy)
+            .map(|result| result.unwrap().filename)
+            .collect()
+            .await;
+        assert_eq!(products.len(), expected, "{:?}", policy);
+        assert!(products.iter().all(|name| names.contains(name)));
+    }
+}
+
+#[tokio::test]
+async fn overflow_policies_count_filtered_products() {
+    use emwin_tg::OverflowPolicy;
+
+    // Two tornado warnings among many other products
+    let mut members: Vec<(String, &[u8])> = (0..20)
+        .map(|i| (format!("ZFP{:02}.TXT", i), &b"forecast"[..]))
+        .collect();
+    members.push(("TORDMX.TXT".into(), b"tornado"));
+    members.push(("TOROAX.TXT".into(), b"tornado"));
+    let members: Vec<(&str, &[u8])> = members
+        .iter()
+        .map(|(name, contents)| (name.as_str(), *contents))
+        .collect();
+
+    let products: Vec<_> =
+        emwin_tg::Stream::from_source(common::source(vec![common::archive(&members)]))
+            .filter_awips(["TOR"])
+            .max_buffered(2, OverflowPolicy::DropOldest)
+            .map(|result| result.unwrap().filename)
+            .collect()
+            .await;
+    assert_eq!(products, ["TORDMX.TXT", "TOROAX.TXT"]);
+}
+
+#[tokio::test]
+async fn into_inner_keeps_products_not_yet_decompressed() {
+    let (archives, names) = numbered_archives();
+    let mut stream = emwin_tg::Stream::from_source(common::source(archives));
+    let first = stream.next().await.unwrap().unwrap().filename;
+
+    // The rest of the first archive comes back out, ready to resume
+    let (source, state, buffered) = stream.into_inner();
+    assert_eq!(buffered.len(), 6);
+    let mut delivered: Vec<String> = buffered
+        .into_iter()
+        .map(|result| result.unwrap().filename)
+        .chain(std::iter::once(first))
+        .collect();
+
+    let resumed = emwin_tg::Stream::from_parts(source, state, Default::default());
+    delivered.extend(
+        resumed
+            .map(|result| result.unwrap().filename)
+            .collect::<Vec<_>>()
+            .await,
+    );
+    delivered.sort();
+    assert_eq!(delivered, names);
+}
+
+#[tokio::test]
+async fn filter_duplicate_contents_ignores_filenames() {
+    let first = common::archive(&[
+        (
+            "A_WUUS53KLOT202245_C_KWIN_20220220224512_061002-1-SVRLOTIL.TXT",
+            b"SVR",
+        ),
+        ("RADALLUS.GIF", b"GIF89a radar"),
+    ]);
+    let second = common::archive(&[
+        // The same bulletin, received again
+        (
+            "A_WUUS53KLOT202245_C_KWIN_20220220224733_061010-1-SVRLOTIL.TXT",
+            b"SVR",
+        ),
+        (
+            "A_WUUS53KLOT202245CCA_C_KWIN_20220220225012_061020-1-SVRLOTIL.TXT",
+            b"SVR CCA",
+        ),
+        ("RADALLUS_1.GIF", b"GIF89a radar"),
+    ]);
+
+    let mut products: Vec<_> = emwin_tg::Stream::from_source(common::source(vec![first, second]))
+        .filter_duplicate_contents()
+        .map(|result| result.unwrap().filename)
+        .collect()
+        .await;
+    products.sort();
+    assert_eq!(
+        products,
+        [
+            "A_WUUS53KLOT202245CCA_C_KWIN_20220220225012_061020-1-SVRLOTIL.TXT",
+            "A_WUUS53KLOT202245_C_KWIN_20220220224512_061002-1-SVRLOTIL.TXT",
+            "RADALLUS.GIF",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn events_report_gaps_in_coverage() {
+    let name = |hhmmss: &str| {
+        format!(
+            "A_NOUS43KLOT202245_C_KWIN_20220220{}_061002-4-PNSLOTIL.TXT",
+            hhmmss
+        )
+    };
+    let archive = |times: &[&str]| {
+        let names: Vec<String> = times.iter().map(|time| name(time)).collect();
+        let members: Vec<(&str, &[u8])> = names
+            .iter()
+            .map(|name| (name.as_str(), name.as_bytes()))
+            .collect();
+        common::archive(&members)
+    };
+    let archives = vec![
+        archive(&["224512", "224600"]),
+        // Overlaps the last archive
+        archive(&["224600", "224700"]),
+        // Doesn't
+        archive(&["231000", "231100"]),
+    ];
+
+    let events: Vec<_> = emwin_tg::Stream::from_source(common::source(archives))
+        .events()
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    let gaps: Vec<(usize, emwin_tg::Gap)> = events
+        .iter()
+        .enumerate()
+        .filter_map(|(i, event)| match event {
+            StreamEvent::Gap(gap) => Some((i, *gap)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(gaps.len(), 1, "{:?}", events);
+
+    // Reported just before the products which revealed it
+    let (index, gap) = gaps[0];
+    assert_eq!(index, 3);
+    assert!(events[index + 1..].iter().all(
+        |event| matches!(event, StreamEvent::Product(p) if p.filename.contains("_2022022023"))
+    ));
+    assert_eq!(gap.duration(), Duration::from_secs(23 * 60));
+    let received = |hhmmss| emwin_tg::Product::new(name(hhmmss), Vec::new()).received_at;
+    assert_eq!(
+        (gap.after, gap.before),
+        (received("224700"), received("231000"))
+    );
+}
+
+#[tokio::test]
+async fn directory_sources_replay_archives() {
+    let dir = std::env::temp_dir().join(format!("emwin-tg-replay-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let epoch = std::time::SystemTime::UNIX_EPOCH;
+    for (name, product, modified) in [
+        ("a.zip", "AFDOKXNY.TXT", 200),
+        ("b.zip", "ZFPOKXNY.TXT", 100),
+    ] {
+        let path = dir.join(name);
+        std::fs::write(&path, common::archive(&[(product, b"hello")])).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(epoch + Duration::from_secs(modified))
+            .unwrap();
+    }
+    std::fs::write(dir.join("README.txt"), b"not an archive").unwrap();
+
+    let filenames = |source| async move {
+        emwin_tg::Stream::from_source(source)
+            .map(|product| product.unwrap().filename)
+            .collect::<Vec<_>>()
+            .await
+    };
+
+    let source = emwin_tg::DirectorySource::new(&dir).unwrap();
+    assert_eq!(filenames(source).await, ["AFDOKXNY.TXT", "ZFPOKXNY.TXT"]);
+
+    let source = emwin_tg::DirectorySource::new(&dir)
+        .unwrap()
+        .in_timestamp_order()
+        .delay(Duration::from_millis(50));
+    let started = std::time::Instant::now();
+    assert_eq!(filenames(source).await, ["ZFPOKXNY.TXT", "AFDOKXNY.TXT"]);
+    assert!(started.elapsed() >= Duration::from_millis(50));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    let error = emwin_tg::DirectorySource::new(&dir).unwrap_err();
+    assert_eq!(error.kind(), emwin_tg::ErrorKind::Io);
+}
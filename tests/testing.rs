@@ -0,0 +1,61 @@
+#![cfg(feature = "testing")]
+
+use emwin_tg::testing::{ArchiveBuilder, MockSource};
+use emwin_tg::Product;
+use futures::StreamExt;
+
+#[tokio::test]
+async fn mock_sources_yield_products_and_errors() {
+    let products = [
+        Product::new("AFDOKXNY.TXT", b"hello".to_vec()),
+        Product::new("ZFPOKXNY.TXT", b"world".to_vec()),
+    ];
+    let source = MockSource::new()
+        .products(&products)
+        .error(emwin_tg::Error::Status {
+            url: "http://example.com/txtmin02.zip".into(),
+            status: 503,
+        })
+        .archive(
+            ArchiveBuilder::new()
+                .product(&products[0])
+                .member("SVSMPX.TXT", "again")
+                .build(),
+        );
+
+    let results: Vec<_> = emwin_tg::Stream::from_source(source).collect().await;
+    assert_eq!(results.len(), 4);
+    for (result, product) in results.iter().zip(&products) {
+        let result = result.as_ref().unwrap();
+        assert_eq!(result.filename, product.filename);
+        assert_eq!(result.contents, product.contents);
+    }
+    assert!(matches!(
+        results[2],
+        Err(emwin_tg::Error::Status { status: 503, .. })
+    ));
+    assert_eq!(results[3].as_ref().unwrap().filename, "SVSMPX.TXT");
+}
+
+#[tokio::test]
+async fn kept_open_mock_sources_end_on_shutdown() {
+    let mut stream = emwin_tg::Stream::from_source(
+        MockSource::new()
+            .archive(
+                ArchiveBuilder::new()
+                    .member("AFDOKXNY.TXT", "hello")
+                    .build(),
+            )
+            .keep_open(),
+    );
+    assert_eq!(
+        stream.next().await.unwrap().unwrap().filename,
+        "AFDOKXNY.TXT"
+    );
+
+    let pending = tokio::time::timeout(std::time::Duration::from_millis(50), stream.next()).await;
+    assert!(pending.is_err());
+
+    stream.shutdown();
+    assert!(stream.next().await.is_none());
+}
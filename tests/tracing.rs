@@ -0,0 +1,141 @@
+#![cfg(feature = "tracing")]
+
+mod common;
+
+use common::MockServer;
+use futures::StreamExt;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+use tracing_core::span::Current;
+
+/// The fields recorded on each span, and the messages of each event.
+#[derive(Debug, Default)]
+struct Recorded {
+    spans: Vec<(&'static Metadata<'static>, BTreeMap<String, String>)>,
+    events: Vec<BTreeMap<String, String>>,
+    entered: Vec<Id>,
+}
+
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Recorded>>);
+
+struct Fields<'a>(&'a mut BTreeMap<String, String>);
+
+impl Visit for Fields<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut recorded = self.0.lock().unwrap();
+        let mut fields = BTreeMap::new();
+        span.record(&mut Fields(&mut fields));
+        recorded.spans.push((span.metadata(), fields));
+        Id::from_u64(recorded.spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut recorded = self.0.lock().unwrap();
+        let (_, fields) = &mut recorded.spans[span.into_u64() as usize - 1];
+        values.record(&mut Fields(fields));
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = BTreeMap::new();
+        event.record(&mut Fields(&mut fields));
+        self.0.lock().unwrap().events.push(fields);
+    }
+
+    fn enter(&self, span: &Id) {
+        self.0.lock().unwrap().entered.push(span.clone());
+    }
+
+    fn exit(&self, _: &Id) {
+        self.0.lock().unwrap().entered.pop();
+    }
+
+    fn current_span(&self) -> Current {
+        let recorded = self.0.lock().unwrap();
+        match recorded.entered.last() {
+            Some(span) => {
+                Current::new(span.clone(), recorded.spans[span.into_u64() as usize - 1].0)
+            }
+            None => Current::none(),
+        }
+    }
+}
+
+#[tokio::test]
+async fn spans_cover_fetches_archives_and_products() {
+    let recorder = Recorder::default();
+    let _guard = tracing::subscriber::set_default(recorder.clone());
+
+    let archive = common::archive(&[("AFDOKXNY.TXT", b"hello"), ("ZFPOKXNY.TXT", b"world")]);
+    let server = MockServer::start(move |path| match path {
+        "/txtmin02.zip" => (200, archive.clone()),
+        _ => (404, Vec::new()),
+    })
+    .await;
+
+    let mirrors = emwin_tg::Mirrors::new([server.url.clone()]);
+    let source = emwin_tg::TextSource::with_mirrors(reqwest::Client::new(), mirrors);
+    let products: Vec<_> = emwin_tg::Stream::from_source(source)
+        .filter_map(|result| async move { result.ok() })
+        .take(2)
+        .collect()
+        .await;
+    assert_eq!(products.len(), 2);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let recorded = recorder.0.lock().unwrap();
+    let spans = |name| {
+        recorded
+            .spans
+            .iter()
+            .filter(move |(span, _)| span.name() == name)
+            .map(|(_, fields)| fields)
+    };
+
+    let fetch = spans("fetch")
+        .find(|fields| fields["archive"] == "\"txtmin02.zip\"")
+        .unwrap();
+    assert_eq!(
+        fetch["url"],
+        format!("{:?}", format!("{}txtmin02.zip", server.url))
+    );
+    assert_eq!(fetch["status"], "200");
+    assert!(fetch.contains_key("bytes"));
+    assert!(fetch.contains_key("latency_ms"));
+    assert!(spans("fetch").any(|fields| fields.get("status").map(String::as_str) == Some("404")));
+
+    let archive = spans("archive").next().unwrap();
+    assert_eq!(archive["members"], "2");
+    assert_eq!(archive["new_members"], "2");
+    // One member at a time, or all at once with the `parallel` feature
+    let decoded: usize = spans("decode")
+        .map(|fields| fields["products"].parse::<usize>().unwrap())
+        .sum();
+    assert_eq!(decoded, 2);
+
+    let yielded: Vec<&String> = recorded
+        .events
+        .iter()
+        .filter(|fields| fields.get("message").map(String::as_str) == Some("yielded product"))
+        .map(|fields| &fields["filename"])
+        .collect();
+    assert_eq!(yielded.len(), 2);
+}